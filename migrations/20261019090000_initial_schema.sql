@@ -0,0 +1,27 @@
+-- Initial schema
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY NOT NULL,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS categories (
+    id TEXT PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS subcategories (
+    id TEXT PRIMARY KEY NOT NULL,
+    category_id TEXT NOT NULL REFERENCES categories(id),
+    title TEXT NOT NULL,
+    description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS questions (
+    id TEXT PRIMARY KEY NOT NULL,
+    category_id TEXT NOT NULL REFERENCES categories(id),
+    subcategory_id TEXT REFERENCES subcategories(id),
+    question_text TEXT NOT NULL,
+    options TEXT NOT NULL, -- JSON array of strings
+    correct_answer_idx INTEGER NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Roles for content management and soft-delete for content tables
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'student';
+
+ALTER TABLE categories ADD COLUMN deleted_at TEXT;
+ALTER TABLE subcategories ADD COLUMN deleted_at TEXT;
+ALTER TABLE questions ADD COLUMN deleted_at TEXT;
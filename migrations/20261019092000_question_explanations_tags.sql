@@ -0,0 +1,10 @@
+-- Explanations and tags for questions (used by content import/export)
+ALTER TABLE questions ADD COLUMN explanation TEXT;
+
+CREATE TABLE IF NOT EXISTS question_tags (
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (question_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_question_tags_tag ON question_tags (tag);
@@ -0,0 +1,7 @@
+-- Question kinds beyond single-answer multiple choice.
+--   choice            options + correct_answer_idx
+--   multiple_response options + correct_answers (JSON array of indices)
+--   text_entry        options are the accepted answers
+--   order             options are stored in the correct order
+ALTER TABLE questions ADD COLUMN kind TEXT NOT NULL DEFAULT 'choice';
+ALTER TABLE questions ADD COLUMN correct_answers TEXT;
@@ -0,0 +1,8 @@
+-- Entries synced from the content directory, with the file each came from,
+-- so entries whose files are removed can be retired on the next sync
+CREATE TABLE content_pack_entries (
+    entity TEXT NOT NULL,    -- category, subcategory or question
+    id TEXT NOT NULL,
+    path TEXT NOT NULL,      -- relative to the content directory
+    PRIMARY KEY (entity, id)
+);
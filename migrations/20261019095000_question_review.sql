@@ -0,0 +1,22 @@
+-- Editorial workflow for questions: draft -> in_review -> published -> retired.
+-- A question nobody has reviewed is a draft; writers that publish directly
+-- (imports, content packs) say so.
+ALTER TABLE questions ADD COLUMN status TEXT NOT NULL DEFAULT 'draft';
+ALTER TABLE questions ADD COLUMN author_id TEXT REFERENCES users(id);
+ALTER TABLE questions ADD COLUMN reviewer_id TEXT REFERENCES users(id);
+
+-- Every workflow step and review comment on a question
+CREATE TABLE IF NOT EXISTS question_reviews (
+    id TEXT PRIMARY KEY NOT NULL,
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    user_id TEXT NOT NULL REFERENCES users(id),
+    action TEXT NOT NULL, -- submitted, approved, changes_requested, commented, retired, reopened
+    comment TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Existing questions are already live
+UPDATE questions SET status = 'published';
+
+CREATE INDEX IF NOT EXISTS idx_question_reviews_question ON question_reviews (question_id);
+CREATE INDEX IF NOT EXISTS idx_questions_status ON questions (status);
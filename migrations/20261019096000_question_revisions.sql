@@ -0,0 +1,62 @@
+-- Immutable snapshots of question content. `questions` holds the current
+-- content and the number of its latest revision; every change adds a row.
+ALTER TABLE questions ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+
+CREATE TABLE IF NOT EXISTS question_revisions (
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    revision INTEGER NOT NULL,
+    category_id TEXT NOT NULL,
+    subcategory_id TEXT,
+    kind TEXT NOT NULL,
+    question_text TEXT NOT NULL,
+    options TEXT NOT NULL, -- JSON array of strings
+    correct_answer_idx INTEGER NOT NULL,
+    correct_answers TEXT,
+    explanation TEXT,
+    tags TEXT NOT NULL DEFAULT '[]', -- JSON array of strings
+    author_id TEXT REFERENCES users(id), -- NULL for imports
+    note TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (question_id, revision)
+);
+
+CREATE TRIGGER IF NOT EXISTS question_revisions_no_update BEFORE UPDATE ON question_revisions
+BEGIN
+    SELECT RAISE(ABORT, 'question revisions are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_revisions_no_delete BEFORE DELETE ON question_revisions
+BEGIN
+    SELECT RAISE(ABORT, 'question revisions are immutable');
+END;
+
+-- Existing questions become revision 1
+INSERT INTO question_revisions (question_id, revision, category_id, subcategory_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation, tags, author_id, note)
+SELECT id, 1, category_id, subcategory_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation,
+    (SELECT json_group_array(tag) FROM (SELECT tag FROM question_tags WHERE question_id = questions.id ORDER BY tag)),
+    author_id, 'initial'
+FROM questions;
+
+-- Submitted quizzes. Each answer records the revision of the question that
+-- was served, so later edits don't change what the learner was graded on.
+CREATE TABLE IF NOT EXISTS quiz_attempts (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users(id), -- NULL when not signed in
+    category_id TEXT REFERENCES categories(id),
+    subcategory_id TEXT REFERENCES subcategories(id),
+    score INTEGER NOT NULL,
+    total INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS attempt_answers (
+    attempt_id TEXT NOT NULL REFERENCES quiz_attempts(id),
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    revision INTEGER NOT NULL,
+    response TEXT NOT NULL, -- JSON array of the submitted values
+    correct INTEGER NOT NULL,
+    PRIMARY KEY (attempt_id, question_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_quiz_attempts_user ON quiz_attempts (user_id);
+CREATE INDEX IF NOT EXISTS idx_attempt_answers_question ON attempt_answers (question_id, revision);
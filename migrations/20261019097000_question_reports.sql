@@ -0,0 +1,30 @@
+-- Problems learners report with a question, triaged by staff:
+-- open -> confirmed -> resolved, or open -> dismissed.
+CREATE TABLE IF NOT EXISTS question_reports (
+    id TEXT PRIMARY KEY NOT NULL,
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    revision INTEGER NOT NULL, -- the revision the learner saw
+    attempt_id TEXT REFERENCES quiz_attempts(id), -- NULL when reported from the quiz page
+    user_id TEXT REFERENCES users(id), -- NULL when not signed in
+    reason TEXT NOT NULL, -- wrong_answer, unclear, typo, other
+    comment TEXT,
+    status TEXT NOT NULL DEFAULT 'open',
+    triaged_by TEXT REFERENCES users(id),
+    resolution TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_question_reports_status ON question_reports (status, created_at);
+CREATE INDEX IF NOT EXISTS idx_question_reports_question ON question_reports (question_id);
+
+-- Question revisions that no longer count towards attempt scores, e.g. once
+-- a report confirms the revision is broken
+CREATE TABLE IF NOT EXISTS scoring_exclusions (
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    revision INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    created_by TEXT REFERENCES users(id),
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (question_id, revision)
+);
@@ -0,0 +1,25 @@
+-- Extra options accepted as correct for a revision of a choice question,
+-- added by a regrade when the answer key turns out to be too strict
+CREATE TABLE IF NOT EXISTS accepted_answers (
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    revision INTEGER NOT NULL,
+    option_idx INTEGER NOT NULL,
+    PRIMARY KEY (question_id, revision, option_idx)
+);
+
+-- Audit trail of regrades: what was done to which revisions, by whom, and
+-- how many stored answers and attempts it changed
+CREATE TABLE IF NOT EXISTS regrades (
+    id TEXT PRIMARY KEY NOT NULL,
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    action TEXT NOT NULL, -- regrade, accept or void
+    revisions TEXT NOT NULL, -- JSON array of revision numbers
+    accepted TEXT, -- JSON array of option indices, for accept
+    note TEXT,
+    user_id TEXT NOT NULL REFERENCES users(id),
+    answers_changed INTEGER NOT NULL,
+    attempts_rescored INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_regrades_question ON regrades (question_id, created_at);
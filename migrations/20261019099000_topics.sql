@@ -0,0 +1,87 @@
+-- Topics form a tree of any depth (an adjacency list; subtrees are walked
+-- with recursive queries). Categories are the root topics and subcategories
+-- their children, under the same ids, so everything that files questions by
+-- category keeps working; deeper topics exist only here.
+CREATE TABLE IF NOT EXISTS topics (
+    id TEXT PRIMARY KEY NOT NULL,
+    parent_id TEXT REFERENCES topics(id), -- NULL for root topics
+    title TEXT NOT NULL,
+    description TEXT,
+    position INTEGER NOT NULL DEFAULT 0, -- order among siblings, then title
+    deleted_at TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_topics_parent ON topics (parent_id);
+
+INSERT INTO topics (id, parent_id, title, deleted_at)
+SELECT id, NULL, title, deleted_at FROM categories;
+
+-- A subcategory that shares its id with a category can't get a topic of its
+-- own; its questions stay filed under the category's topic
+INSERT OR IGNORE INTO topics (id, parent_id, title, description, deleted_at)
+SELECT id, category_id, title, description, deleted_at FROM subcategories;
+
+CREATE TRIGGER IF NOT EXISTS categories_topic_insert AFTER INSERT ON categories
+BEGIN
+    INSERT INTO topics (id, parent_id, title, deleted_at) VALUES (NEW.id, NULL, NEW.title, NEW.deleted_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS categories_topic_update AFTER UPDATE ON categories
+BEGIN
+    UPDATE topics SET title = NEW.title, deleted_at = NEW.deleted_at WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS subcategories_topic_insert AFTER INSERT ON subcategories
+BEGIN
+    INSERT INTO topics (id, parent_id, title, description, deleted_at)
+    VALUES (NEW.id, NEW.category_id, NEW.title, NEW.description, NEW.deleted_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS subcategories_topic_update AFTER UPDATE ON subcategories
+BEGIN
+    UPDATE topics SET parent_id = NEW.category_id, title = NEW.title, description = NEW.description, deleted_at = NEW.deleted_at
+    WHERE id = NEW.id;
+END;
+
+-- The most specific topic a question is filed under. category_id and
+-- subcategory_id stay as its first two levels.
+ALTER TABLE questions ADD COLUMN topic_id TEXT REFERENCES topics(id);
+
+UPDATE questions SET topic_id = COALESCE(
+    (SELECT id FROM topics WHERE id = questions.subcategory_id AND parent_id = questions.category_id),
+    category_id
+);
+
+-- Writers that only know categories file new questions at the deepest of
+-- the two levels they gave
+CREATE TRIGGER IF NOT EXISTS questions_default_topic AFTER INSERT ON questions
+WHEN NEW.topic_id IS NULL
+BEGIN
+    UPDATE questions SET topic_id = COALESCE(
+        (SELECT id FROM topics WHERE id = NEW.subcategory_id AND parent_id = NEW.category_id),
+        NEW.category_id
+    )
+    WHERE id = NEW.id;
+END;
+
+CREATE INDEX IF NOT EXISTS idx_questions_topic ON questions (topic_id);
+
+-- Revisions record the topic too. They are immutable, so the guard is lifted
+-- just long enough to fill in the existing ones.
+DROP TRIGGER IF EXISTS question_revisions_no_update;
+
+ALTER TABLE question_revisions ADD COLUMN topic_id TEXT;
+
+UPDATE question_revisions SET topic_id = COALESCE(
+    (SELECT id FROM topics WHERE id = question_revisions.subcategory_id AND parent_id = question_revisions.category_id),
+    category_id
+);
+
+CREATE TRIGGER IF NOT EXISTS question_revisions_no_update BEFORE UPDATE ON question_revisions
+BEGIN
+    SELECT RAISE(ABORT, 'question revisions are immutable');
+END;
+
+-- Which topic a quiz was drawn from, when it was drawn from a subtree
+ALTER TABLE quiz_attempts ADD COLUMN topic_id TEXT REFERENCES topics(id);
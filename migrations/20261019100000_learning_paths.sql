@@ -0,0 +1,44 @@
+-- Curated learning paths: ordered steps, each a lesson, a quiz or a
+-- challenge, unlocked once the steps it requires are completed.
+CREATE TABLE IF NOT EXISTS learning_paths (
+    id TEXT PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    deleted_at TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS path_steps (
+    id TEXT PRIMARY KEY NOT NULL,
+    path_id TEXT NOT NULL REFERENCES learning_paths(id),
+    position INTEGER NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('lesson', 'quiz', 'challenge')),
+    title TEXT NOT NULL,
+    body TEXT, -- instructions shown on the step page
+    url TEXT, -- lesson: where to read it
+    topic_id TEXT REFERENCES topics(id), -- quiz: the topic whose quiz to pass
+    pass_pct INTEGER NOT NULL DEFAULT 80, -- quiz: score needed, in percent
+    flag_hash TEXT, -- challenge: SHA-256 of the flag, hex
+    deleted_at TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- A step stays locked until every step it requires is completed.
+-- Requirements always point at earlier steps of the same path.
+CREATE TABLE IF NOT EXISTS path_step_prereqs (
+    step_id TEXT NOT NULL REFERENCES path_steps(id),
+    requires_id TEXT NOT NULL REFERENCES path_steps(id),
+    PRIMARY KEY (step_id, requires_id)
+);
+
+CREATE TABLE IF NOT EXISTS path_step_completions (
+    user_id TEXT NOT NULL REFERENCES users(id),
+    step_id TEXT NOT NULL REFERENCES path_steps(id),
+    score_pct INTEGER, -- quiz steps: the best passing score
+    attempt_id TEXT REFERENCES quiz_attempts(id),
+    completed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, step_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_path_steps_path ON path_steps (path_id, position);
+CREATE INDEX IF NOT EXISTS idx_path_steps_topic ON path_steps (topic_id);
@@ -0,0 +1,24 @@
+-- Markdown lessons attached to subcategories. A lesson may embed questions
+-- by id ([[question:ID]] on a line of its own), graded on the page.
+CREATE TABLE IF NOT EXISTS lessons (
+    id TEXT PRIMARY KEY NOT NULL,
+    subcategory_id TEXT NOT NULL REFERENCES subcategories(id),
+    title TEXT NOT NULL,
+    body TEXT NOT NULL, -- markdown
+    position INTEGER NOT NULL DEFAULT 0,
+    deleted_at TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- How far each learner has read, in percent; it only goes up
+CREATE TABLE IF NOT EXISTS lesson_progress (
+    user_id TEXT NOT NULL REFERENCES users(id),
+    lesson_id TEXT NOT NULL REFERENCES lessons(id),
+    progress_pct INTEGER NOT NULL DEFAULT 0,
+    completed_at TEXT, -- when it reached 100
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, lesson_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_lessons_subcategory ON lessons (subcategory_id, position);
@@ -0,0 +1,15 @@
+-- Per-user spaced review of questions. Flashcard self-ratings and quiz
+-- answers both move a question's next review date.
+CREATE TABLE IF NOT EXISTS review_schedule (
+    user_id TEXT NOT NULL REFERENCES users(id),
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    ease REAL NOT NULL DEFAULT 2.5, -- how fast the interval grows
+    interval_days INTEGER NOT NULL DEFAULT 0,
+    repetitions INTEGER NOT NULL DEFAULT 0, -- successful reviews in a row
+    last_rating TEXT NOT NULL CHECK (last_rating IN ('again', 'hard', 'good', 'easy')),
+    due_at TEXT NOT NULL,
+    reviewed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, question_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_review_schedule_due ON review_schedule (user_id, due_at);
@@ -0,0 +1,75 @@
+-- Full-text indexes for search. Each is an external-content FTS5 table over
+-- its source table, kept in sync by the triggers below; search ranks with
+-- bm25() and marks matches with highlight() and snippet().
+CREATE VIRTUAL TABLE IF NOT EXISTS categories_fts USING fts5(
+    title,
+    content = 'categories', content_rowid = 'rowid', tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS subcategories_fts USING fts5(
+    title, description,
+    content = 'subcategories', content_rowid = 'rowid', tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS questions_fts USING fts5(
+    question_text, explanation,
+    content = 'questions', content_rowid = 'rowid', tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS lessons_fts USING fts5(
+    title, body,
+    content = 'lessons', content_rowid = 'rowid', tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+INSERT INTO categories_fts (categories_fts) VALUES ('rebuild');
+INSERT INTO subcategories_fts (subcategories_fts) VALUES ('rebuild');
+INSERT INTO questions_fts (questions_fts) VALUES ('rebuild');
+INSERT INTO lessons_fts (lessons_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS categories_fts_insert AFTER INSERT ON categories BEGIN
+    INSERT INTO categories_fts (rowid, title) VALUES (new.rowid, new.title);
+END;
+CREATE TRIGGER IF NOT EXISTS categories_fts_delete AFTER DELETE ON categories BEGIN
+    INSERT INTO categories_fts (categories_fts, rowid, title) VALUES ('delete', old.rowid, old.title);
+END;
+CREATE TRIGGER IF NOT EXISTS categories_fts_update AFTER UPDATE OF title ON categories BEGIN
+    INSERT INTO categories_fts (categories_fts, rowid, title) VALUES ('delete', old.rowid, old.title);
+    INSERT INTO categories_fts (rowid, title) VALUES (new.rowid, new.title);
+END;
+
+CREATE TRIGGER IF NOT EXISTS subcategories_fts_insert AFTER INSERT ON subcategories BEGIN
+    INSERT INTO subcategories_fts (rowid, title, description) VALUES (new.rowid, new.title, new.description);
+END;
+CREATE TRIGGER IF NOT EXISTS subcategories_fts_delete AFTER DELETE ON subcategories BEGIN
+    INSERT INTO subcategories_fts (subcategories_fts, rowid, title, description)
+        VALUES ('delete', old.rowid, old.title, old.description);
+END;
+CREATE TRIGGER IF NOT EXISTS subcategories_fts_update AFTER UPDATE OF title, description ON subcategories BEGIN
+    INSERT INTO subcategories_fts (subcategories_fts, rowid, title, description)
+        VALUES ('delete', old.rowid, old.title, old.description);
+    INSERT INTO subcategories_fts (rowid, title, description) VALUES (new.rowid, new.title, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS questions_fts_insert AFTER INSERT ON questions BEGIN
+    INSERT INTO questions_fts (rowid, question_text, explanation) VALUES (new.rowid, new.question_text, new.explanation);
+END;
+CREATE TRIGGER IF NOT EXISTS questions_fts_delete AFTER DELETE ON questions BEGIN
+    INSERT INTO questions_fts (questions_fts, rowid, question_text, explanation)
+        VALUES ('delete', old.rowid, old.question_text, old.explanation);
+END;
+CREATE TRIGGER IF NOT EXISTS questions_fts_update AFTER UPDATE OF question_text, explanation ON questions BEGIN
+    INSERT INTO questions_fts (questions_fts, rowid, question_text, explanation)
+        VALUES ('delete', old.rowid, old.question_text, old.explanation);
+    INSERT INTO questions_fts (rowid, question_text, explanation) VALUES (new.rowid, new.question_text, new.explanation);
+END;
+
+CREATE TRIGGER IF NOT EXISTS lessons_fts_insert AFTER INSERT ON lessons BEGIN
+    INSERT INTO lessons_fts (rowid, title, body) VALUES (new.rowid, new.title, new.body);
+END;
+CREATE TRIGGER IF NOT EXISTS lessons_fts_delete AFTER DELETE ON lessons BEGIN
+    INSERT INTO lessons_fts (lessons_fts, rowid, title, body) VALUES ('delete', old.rowid, old.title, old.body);
+END;
+CREATE TRIGGER IF NOT EXISTS lessons_fts_update AFTER UPDATE OF title, body ON lessons BEGIN
+    INSERT INTO lessons_fts (lessons_fts, rowid, title, body) VALUES ('delete', old.rowid, old.title, old.body);
+    INSERT INTO lessons_fts (rowid, title, body) VALUES (new.rowid, new.title, new.body);
+END;
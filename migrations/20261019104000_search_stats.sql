@@ -0,0 +1,12 @@
+-- Search statistics for instructors, counted per day and query. Nothing is
+-- kept about who searched or when during the day, so single searches cannot
+-- be told apart or traced back to a learner.
+CREATE TABLE IF NOT EXISTS search_stats (
+    day TEXT NOT NULL, -- YYYY-MM-DD
+    query TEXT NOT NULL, -- as normalized by content::search_stats
+    searches INTEGER NOT NULL DEFAULT 0,
+    zero_result_searches INTEGER NOT NULL DEFAULT 0,
+    results INTEGER NOT NULL DEFAULT 0, -- results of the day's latest search
+    clicks INTEGER NOT NULL DEFAULT 0, -- results opened from the results page
+    PRIMARY KEY (day, query)
+);
@@ -0,0 +1,49 @@
+-- The questions and lessons most similar to each question and lesson, by
+-- TF-IDF similarity of their words (see content::similarity).
+CREATE TABLE IF NOT EXISTS related_content (
+    kind TEXT NOT NULL, -- question or lesson
+    id TEXT NOT NULL,
+    related_kind TEXT NOT NULL,
+    related_id TEXT NOT NULL,
+    score REAL NOT NULL, -- cosine similarity, 0 to 1
+    PRIMARY KEY (kind, id, related_kind, related_id)
+);
+
+-- Triggers bump content_version whenever what is compared changes; the
+-- table above is rebuilt when built_version falls behind
+CREATE TABLE IF NOT EXISTS related_content_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    content_version INTEGER NOT NULL DEFAULT 1,
+    built_version INTEGER NOT NULL DEFAULT 0
+);
+INSERT OR IGNORE INTO related_content_state (id) VALUES (1);
+
+CREATE TRIGGER IF NOT EXISTS related_questions_insert AFTER INSERT ON questions BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+CREATE TRIGGER IF NOT EXISTS related_questions_update
+AFTER UPDATE OF question_text, options, explanation, status, deleted_at, category_id, subcategory_id ON questions BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+CREATE TRIGGER IF NOT EXISTS related_questions_delete AFTER DELETE ON questions BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS related_lessons_insert AFTER INSERT ON lessons BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+CREATE TRIGGER IF NOT EXISTS related_lessons_update
+AFTER UPDATE OF title, body, deleted_at, subcategory_id ON lessons BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+CREATE TRIGGER IF NOT EXISTS related_lessons_delete AFTER DELETE ON lessons BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+
+-- Lessons are only compared while their subcategory and category are live
+CREATE TRIGGER IF NOT EXISTS related_subcategories_update AFTER UPDATE OF deleted_at ON subcategories BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
+CREATE TRIGGER IF NOT EXISTS related_categories_update AFTER UPDATE OF deleted_at ON categories BEGIN
+    UPDATE related_content_state SET content_version = content_version + 1;
+END;
@@ -0,0 +1,17 @@
+-- Personal API tokens, sent as `Authorization: Bearer <token>`. Only the
+-- SHA-256 hash of a token is kept; its first characters are kept too, so the
+-- owner can tell their tokens apart.
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id),
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    token_prefix TEXT NOT NULL,
+    scopes TEXT NOT NULL, -- space-separated: read, submit, admin
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TEXT, -- NULL when it never expires
+    last_used_at TEXT,
+    revoked_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user ON api_tokens (user_id);
@@ -0,0 +1,22 @@
+-- Edits to published questions. The published content stays live while an
+-- edit goes through draft -> in_review; approving it copies it into
+-- questions as a new revision. A question has one edit at most.
+CREATE TABLE IF NOT EXISTS question_edits (
+    question_id TEXT PRIMARY KEY NOT NULL REFERENCES questions(id),
+    category_id TEXT NOT NULL,
+    subcategory_id TEXT,
+    topic_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    question_text TEXT NOT NULL,
+    options TEXT NOT NULL, -- JSON array
+    correct_answer_idx INTEGER NOT NULL,
+    correct_answers TEXT,
+    explanation TEXT,
+    tags TEXT NOT NULL DEFAULT '[]', -- JSON array
+    status TEXT NOT NULL DEFAULT 'draft', -- draft or in_review
+    author_id TEXT REFERENCES users(id),
+    reviewer_id TEXT REFERENCES users(id),
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_question_edits_reviewer ON question_edits (reviewer_id, status);
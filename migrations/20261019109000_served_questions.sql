@@ -0,0 +1,13 @@
+-- The revision of each question a quiz or lesson page showed, under an id
+-- the page sends back with its answers. Answers are graded against these
+-- revisions rather than whatever revision the form claims. Rows go once the
+-- answers are in, or after a day.
+CREATE TABLE IF NOT EXISTS served_questions (
+    served_id TEXT NOT NULL,
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    revision INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (served_id, question_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_served_questions_created ON served_questions (created_at);
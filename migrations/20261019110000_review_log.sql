@@ -0,0 +1,34 @@
+-- Every rating that moved a review schedule, oldest first, so a schedule can
+-- be rebuilt by replaying them, e.g. once a regrade changes which quiz
+-- answers were right. Quiz answers point at their attempt and are rated by
+-- how the answer is graded at replay time.
+CREATE TABLE IF NOT EXISTS review_log (
+    id INTEGER PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id),
+    question_id TEXT NOT NULL REFERENCES questions(id),
+    rating TEXT NOT NULL CHECK (rating IN ('again', 'hard', 'good', 'easy')),
+    attempt_id TEXT REFERENCES quiz_attempts(id), -- NULL for flashcards
+    reviewed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_review_log_question ON review_log (question_id, user_id);
+
+-- Past quiz answers of signed-in users, and the latest flashcard rating
+-- where it came after the last quiz answer (earlier ones weren't kept), in
+-- the order they were given
+INSERT INTO review_log (user_id, question_id, rating, attempt_id, reviewed_at)
+SELECT user_id, question_id, rating, attempt_id, reviewed_at FROM (
+    SELECT t.user_id, a.question_id, CASE WHEN a.correct THEN 'good' ELSE 'again' END AS rating,
+        t.id AS attempt_id, t.created_at AS reviewed_at, 0 AS flashcard, t.rowid AS seq
+    FROM attempt_answers a JOIN quiz_attempts t ON t.id = a.attempt_id
+    WHERE t.user_id IS NOT NULL
+        AND NOT EXISTS (SELECT 1 FROM scoring_exclusions x WHERE x.question_id = a.question_id AND x.revision = a.revision)
+    UNION ALL
+    SELECT s.user_id, s.question_id, s.last_rating, NULL, s.reviewed_at, 1, 0
+    FROM review_schedule s
+    WHERE NOT EXISTS (
+        SELECT 1 FROM attempt_answers a JOIN quiz_attempts t ON t.id = a.attempt_id
+        WHERE t.user_id = s.user_id AND a.question_id = s.question_id AND t.created_at >= s.reviewed_at
+    )
+)
+ORDER BY reviewed_at, flashcard, seq;
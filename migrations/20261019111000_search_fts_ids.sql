@@ -0,0 +1,87 @@
+-- Key the full-text indexes by their source rows' TEXT ids. They pointed at
+-- the rowids of tables without an INTEGER PRIMARY KEY, which VACUUM may
+-- renumber, leaving search joined to the wrong rows. Each index is now a
+-- regular FTS5 table with the id stored (not indexed) as its last column,
+-- so highlight() and snippet() column numbers are unchanged.
+DROP TRIGGER IF EXISTS categories_fts_insert;
+DROP TRIGGER IF EXISTS categories_fts_delete;
+DROP TRIGGER IF EXISTS categories_fts_update;
+DROP TRIGGER IF EXISTS subcategories_fts_insert;
+DROP TRIGGER IF EXISTS subcategories_fts_delete;
+DROP TRIGGER IF EXISTS subcategories_fts_update;
+DROP TRIGGER IF EXISTS questions_fts_insert;
+DROP TRIGGER IF EXISTS questions_fts_delete;
+DROP TRIGGER IF EXISTS questions_fts_update;
+DROP TRIGGER IF EXISTS lessons_fts_insert;
+DROP TRIGGER IF EXISTS lessons_fts_delete;
+DROP TRIGGER IF EXISTS lessons_fts_update;
+
+DROP TABLE IF EXISTS categories_fts;
+DROP TABLE IF EXISTS subcategories_fts;
+DROP TABLE IF EXISTS questions_fts;
+DROP TABLE IF EXISTS lessons_fts;
+
+CREATE VIRTUAL TABLE categories_fts USING fts5(
+    title, id UNINDEXED,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE subcategories_fts USING fts5(
+    title, description, id UNINDEXED,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE questions_fts USING fts5(
+    question_text, explanation, id UNINDEXED,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE lessons_fts USING fts5(
+    title, body, id UNINDEXED,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+INSERT INTO categories_fts (title, id) SELECT title, id FROM categories;
+INSERT INTO subcategories_fts (title, description, id) SELECT title, description, id FROM subcategories;
+INSERT INTO questions_fts (question_text, explanation, id) SELECT question_text, explanation, id FROM questions;
+INSERT INTO lessons_fts (title, body, id) SELECT title, body, id FROM lessons;
+
+CREATE TRIGGER categories_fts_insert AFTER INSERT ON categories BEGIN
+    INSERT INTO categories_fts (title, id) VALUES (new.title, new.id);
+END;
+CREATE TRIGGER categories_fts_delete AFTER DELETE ON categories BEGIN
+    DELETE FROM categories_fts WHERE id = old.id;
+END;
+CREATE TRIGGER categories_fts_update AFTER UPDATE OF id, title ON categories BEGIN
+    UPDATE categories_fts SET title = new.title, id = new.id WHERE id = old.id;
+END;
+
+CREATE TRIGGER subcategories_fts_insert AFTER INSERT ON subcategories BEGIN
+    INSERT INTO subcategories_fts (title, description, id) VALUES (new.title, new.description, new.id);
+END;
+CREATE TRIGGER subcategories_fts_delete AFTER DELETE ON subcategories BEGIN
+    DELETE FROM subcategories_fts WHERE id = old.id;
+END;
+CREATE TRIGGER subcategories_fts_update AFTER UPDATE OF id, title, description ON subcategories BEGIN
+    UPDATE subcategories_fts SET title = new.title, description = new.description, id = new.id WHERE id = old.id;
+END;
+
+CREATE TRIGGER questions_fts_insert AFTER INSERT ON questions BEGIN
+    INSERT INTO questions_fts (question_text, explanation, id) VALUES (new.question_text, new.explanation, new.id);
+END;
+CREATE TRIGGER questions_fts_delete AFTER DELETE ON questions BEGIN
+    DELETE FROM questions_fts WHERE id = old.id;
+END;
+CREATE TRIGGER questions_fts_update AFTER UPDATE OF id, question_text, explanation ON questions BEGIN
+    UPDATE questions_fts SET question_text = new.question_text, explanation = new.explanation, id = new.id WHERE id = old.id;
+END;
+
+CREATE TRIGGER lessons_fts_insert AFTER INSERT ON lessons BEGIN
+    INSERT INTO lessons_fts (title, body, id) VALUES (new.title, new.body, new.id);
+END;
+CREATE TRIGGER lessons_fts_delete AFTER DELETE ON lessons BEGIN
+    DELETE FROM lessons_fts WHERE id = old.id;
+END;
+CREATE TRIGGER lessons_fts_update AFTER UPDATE OF id, title, body ON lessons BEGIN
+    UPDATE lessons_fts SET title = new.title, body = new.body, id = new.id WHERE id = old.id;
+END;
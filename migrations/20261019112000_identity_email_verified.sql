@@ -0,0 +1,7 @@
+-- Whether the provider vouched for an identity's email. A first sign-in with
+-- a verified email joins the user who already has that email verified.
+ALTER TABLE user_identities ADD COLUMN email_verified INTEGER NOT NULL DEFAULT 0;
+
+-- Directory mail attributes are kept by the directory's admins
+UPDATE user_identities SET email_verified = 1
+WHERE email IS NOT NULL AND (issuer LIKE 'ldap://%' OR issuer LIKE 'ldaps://%');
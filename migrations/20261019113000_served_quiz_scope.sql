@@ -0,0 +1,8 @@
+-- What a quiz page drew its questions from, kept with each question it
+-- served, so the attempt and any path step it passes are credited to the
+-- quiz that was shown rather than whatever the answers claim. NULL on
+-- lesson pages.
+ALTER TABLE served_questions ADD COLUMN category_id TEXT;
+ALTER TABLE served_questions ADD COLUMN subcategory_id TEXT;
+ALTER TABLE served_questions ADD COLUMN topic_id TEXT;
+ALTER TABLE served_questions ADD COLUMN tags TEXT; -- comma-separated, as parsed
@@ -0,0 +1,139 @@
+-- Key the full-text indexes by INTEGER rowids again, each mapped to its
+-- source row's TEXT id by a <table>_fts_ids table. The id column they had
+-- was UNINDEXED, so every trigger's DELETE or UPDATE ... WHERE id = old.id
+-- scanned the whole index. The mapping's fts_rowid is an INTEGER PRIMARY
+-- KEY, which VACUUM leaves alone, so the triggers find index rows by rowid
+-- and search joins through the mapping.
+DROP TRIGGER IF EXISTS categories_fts_insert;
+DROP TRIGGER IF EXISTS categories_fts_delete;
+DROP TRIGGER IF EXISTS categories_fts_update;
+DROP TRIGGER IF EXISTS subcategories_fts_insert;
+DROP TRIGGER IF EXISTS subcategories_fts_delete;
+DROP TRIGGER IF EXISTS subcategories_fts_update;
+DROP TRIGGER IF EXISTS questions_fts_insert;
+DROP TRIGGER IF EXISTS questions_fts_delete;
+DROP TRIGGER IF EXISTS questions_fts_update;
+DROP TRIGGER IF EXISTS lessons_fts_insert;
+DROP TRIGGER IF EXISTS lessons_fts_delete;
+DROP TRIGGER IF EXISTS lessons_fts_update;
+
+DROP TABLE IF EXISTS categories_fts;
+DROP TABLE IF EXISTS subcategories_fts;
+DROP TABLE IF EXISTS questions_fts;
+DROP TABLE IF EXISTS lessons_fts;
+
+CREATE TABLE IF NOT EXISTS categories_fts_ids (
+    fts_rowid INTEGER PRIMARY KEY,
+    id TEXT NOT NULL UNIQUE
+);
+
+CREATE VIRTUAL TABLE categories_fts USING fts5(
+    title,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+INSERT INTO categories_fts_ids (id) SELECT id FROM categories ORDER BY rowid;
+INSERT INTO categories_fts (rowid, title)
+SELECT m.fts_rowid, t.title FROM categories t JOIN categories_fts_ids m ON m.id = t.id;
+
+CREATE TRIGGER categories_fts_insert AFTER INSERT ON categories BEGIN
+    INSERT INTO categories_fts_ids (id) VALUES (new.id);
+    INSERT INTO categories_fts (rowid, title)
+    VALUES ((SELECT fts_rowid FROM categories_fts_ids WHERE id = new.id), new.title);
+END;
+CREATE TRIGGER categories_fts_delete AFTER DELETE ON categories BEGIN
+    DELETE FROM categories_fts WHERE rowid = (SELECT fts_rowid FROM categories_fts_ids WHERE id = old.id);
+    DELETE FROM categories_fts_ids WHERE id = old.id;
+END;
+CREATE TRIGGER categories_fts_update AFTER UPDATE OF id, title ON categories BEGIN
+    UPDATE categories_fts_ids SET id = new.id WHERE id = old.id;
+    UPDATE categories_fts SET title = new.title
+    WHERE rowid = (SELECT fts_rowid FROM categories_fts_ids WHERE id = new.id);
+END;
+
+CREATE TABLE IF NOT EXISTS subcategories_fts_ids (
+    fts_rowid INTEGER PRIMARY KEY,
+    id TEXT NOT NULL UNIQUE
+);
+
+CREATE VIRTUAL TABLE subcategories_fts USING fts5(
+    title, description,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+INSERT INTO subcategories_fts_ids (id) SELECT id FROM subcategories ORDER BY rowid;
+INSERT INTO subcategories_fts (rowid, title, description)
+SELECT m.fts_rowid, t.title, t.description FROM subcategories t JOIN subcategories_fts_ids m ON m.id = t.id;
+
+CREATE TRIGGER subcategories_fts_insert AFTER INSERT ON subcategories BEGIN
+    INSERT INTO subcategories_fts_ids (id) VALUES (new.id);
+    INSERT INTO subcategories_fts (rowid, title, description)
+    VALUES ((SELECT fts_rowid FROM subcategories_fts_ids WHERE id = new.id), new.title, new.description);
+END;
+CREATE TRIGGER subcategories_fts_delete AFTER DELETE ON subcategories BEGIN
+    DELETE FROM subcategories_fts WHERE rowid = (SELECT fts_rowid FROM subcategories_fts_ids WHERE id = old.id);
+    DELETE FROM subcategories_fts_ids WHERE id = old.id;
+END;
+CREATE TRIGGER subcategories_fts_update AFTER UPDATE OF id, title, description ON subcategories BEGIN
+    UPDATE subcategories_fts_ids SET id = new.id WHERE id = old.id;
+    UPDATE subcategories_fts SET title = new.title, description = new.description
+    WHERE rowid = (SELECT fts_rowid FROM subcategories_fts_ids WHERE id = new.id);
+END;
+
+CREATE TABLE IF NOT EXISTS questions_fts_ids (
+    fts_rowid INTEGER PRIMARY KEY,
+    id TEXT NOT NULL UNIQUE
+);
+
+CREATE VIRTUAL TABLE questions_fts USING fts5(
+    question_text, explanation,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+INSERT INTO questions_fts_ids (id) SELECT id FROM questions ORDER BY rowid;
+INSERT INTO questions_fts (rowid, question_text, explanation)
+SELECT m.fts_rowid, t.question_text, t.explanation FROM questions t JOIN questions_fts_ids m ON m.id = t.id;
+
+CREATE TRIGGER questions_fts_insert AFTER INSERT ON questions BEGIN
+    INSERT INTO questions_fts_ids (id) VALUES (new.id);
+    INSERT INTO questions_fts (rowid, question_text, explanation)
+    VALUES ((SELECT fts_rowid FROM questions_fts_ids WHERE id = new.id), new.question_text, new.explanation);
+END;
+CREATE TRIGGER questions_fts_delete AFTER DELETE ON questions BEGIN
+    DELETE FROM questions_fts WHERE rowid = (SELECT fts_rowid FROM questions_fts_ids WHERE id = old.id);
+    DELETE FROM questions_fts_ids WHERE id = old.id;
+END;
+CREATE TRIGGER questions_fts_update AFTER UPDATE OF id, question_text, explanation ON questions BEGIN
+    UPDATE questions_fts_ids SET id = new.id WHERE id = old.id;
+    UPDATE questions_fts SET question_text = new.question_text, explanation = new.explanation
+    WHERE rowid = (SELECT fts_rowid FROM questions_fts_ids WHERE id = new.id);
+END;
+
+CREATE TABLE IF NOT EXISTS lessons_fts_ids (
+    fts_rowid INTEGER PRIMARY KEY,
+    id TEXT NOT NULL UNIQUE
+);
+
+CREATE VIRTUAL TABLE lessons_fts USING fts5(
+    title, body,
+    tokenize = 'porter unicode61', prefix = '2 3'
+);
+
+INSERT INTO lessons_fts_ids (id) SELECT id FROM lessons ORDER BY rowid;
+INSERT INTO lessons_fts (rowid, title, body)
+SELECT m.fts_rowid, t.title, t.body FROM lessons t JOIN lessons_fts_ids m ON m.id = t.id;
+
+CREATE TRIGGER lessons_fts_insert AFTER INSERT ON lessons BEGIN
+    INSERT INTO lessons_fts_ids (id) VALUES (new.id);
+    INSERT INTO lessons_fts (rowid, title, body)
+    VALUES ((SELECT fts_rowid FROM lessons_fts_ids WHERE id = new.id), new.title, new.body);
+END;
+CREATE TRIGGER lessons_fts_delete AFTER DELETE ON lessons BEGIN
+    DELETE FROM lessons_fts WHERE rowid = (SELECT fts_rowid FROM lessons_fts_ids WHERE id = old.id);
+    DELETE FROM lessons_fts_ids WHERE id = old.id;
+END;
+CREATE TRIGGER lessons_fts_update AFTER UPDATE OF id, title, body ON lessons BEGIN
+    UPDATE lessons_fts_ids SET id = new.id WHERE id = old.id;
+    UPDATE lessons_fts SET title = new.title, body = new.body
+    WHERE rowid = (SELECT fts_rowid FROM lessons_fts_ids WHERE id = new.id);
+END;
@@ -0,0 +1,5 @@
+-- Directory mail attributes only count as verified with LDAP_TRUST_MAIL set,
+-- since users can edit their own in many directories. Where it is set, each
+-- identity is marked verified again at its next sign-in.
+UPDATE user_identities SET email_verified = 0
+WHERE issuer LIKE 'ldap://%' OR issuer LIKE 'ldaps://%';
@@ -0,0 +1,119 @@
+use poem::web::Data;
+use poem_openapi::{param::{Path, Query}, payload::Json, Object, OpenApi};
+use sqlx::{Pool, Sqlite};
+
+use super::{ApiError, ApiResult, ApiTags, Page, Paging};
+
+// Like the HTML lists, only categories and subcategories with published
+// questions are listed
+const HAS_CATEGORY_QUESTIONS: &str = "EXISTS (SELECT 1 FROM questions q
+     WHERE q.category_id = c.id AND q.deleted_at IS NULL AND q.status = 'published')";
+const HAS_SUBCATEGORY_QUESTIONS: &str = "EXISTS (SELECT 1 FROM questions q
+     WHERE q.subcategory_id = s.id AND q.deleted_at IS NULL AND q.status = 'published')";
+
+#[derive(Debug, Object, sqlx::FromRow)]
+pub struct CategoryItem {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Object, sqlx::FromRow)]
+pub struct SubcategoryItem {
+    pub id: String,
+    pub category_id: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+async fn find_category(db: &Pool<Sqlite>, id: &str) -> Option<CategoryItem> {
+    sqlx::query_as::<_, CategoryItem>("SELECT c.id, c.title FROM categories c WHERE c.id = ? AND c.deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+pub struct CatalogApi;
+
+#[OpenApi(tag = "ApiTags::Catalog")]
+impl CatalogApi {
+    /// List categories
+    #[oai(path = "/categories", method = "get")]
+    async fn categories(
+        &self,
+        db: Data<&Pool<Sqlite>>,
+        page: Query<Option<i64>>,
+        per_page: Query<Option<i64>>,
+    ) -> ApiResult<Page<CategoryItem>> {
+        let paging = Paging::new(page.0, per_page.0);
+        let filter = format!("c.deleted_at IS NULL AND {}", HAS_CATEGORY_QUESTIONS);
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM categories c WHERE {}", filter))
+            .fetch_one(&**db)
+            .await
+            .unwrap();
+        let items = sqlx::query_as::<_, CategoryItem>(&format!(
+            "SELECT c.id, c.title FROM categories c WHERE {} ORDER BY c.title, c.id LIMIT ? OFFSET ?",
+            filter
+        ))
+        .bind(paging.per_page)
+        .bind(paging.offset())
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+        Ok(Json(paging.page(items, total)))
+    }
+
+    /// Get a category
+    #[oai(path = "/categories/:id", method = "get")]
+    async fn category(&self, db: Data<&Pool<Sqlite>>, id: Path<String>) -> ApiResult<CategoryItem> {
+        find_category(&db, &id).await.map(Json).ok_or_else(|| ApiError::not_found("Category"))
+    }
+
+    /// List the subcategories of a category
+    #[oai(path = "/categories/:id/subcategories", method = "get")]
+    async fn category_subcategories(
+        &self,
+        db: Data<&Pool<Sqlite>>,
+        id: Path<String>,
+        page: Query<Option<i64>>,
+        per_page: Query<Option<i64>>,
+    ) -> ApiResult<Page<SubcategoryItem>> {
+        if find_category(&db, &id).await.is_none() {
+            return Err(ApiError::not_found("Category"));
+        }
+        let paging = Paging::new(page.0, per_page.0);
+        let filter = format!("s.category_id = ? AND s.deleted_at IS NULL AND {}", HAS_SUBCATEGORY_QUESTIONS);
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM subcategories s WHERE {}", filter))
+            .bind(&id.0)
+            .fetch_one(&**db)
+            .await
+            .unwrap();
+        let items = sqlx::query_as::<_, SubcategoryItem>(&format!(
+            "SELECT s.id, s.category_id, s.title, s.description FROM subcategories s
+             WHERE {} ORDER BY s.title, s.id LIMIT ? OFFSET ?",
+            filter
+        ))
+        .bind(&id.0)
+        .bind(paging.per_page)
+        .bind(paging.offset())
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+        Ok(Json(paging.page(items, total)))
+    }
+
+    /// Get a subcategory
+    #[oai(path = "/subcategories/:id", method = "get")]
+    async fn subcategory(&self, db: Data<&Pool<Sqlite>>, id: Path<String>) -> ApiResult<SubcategoryItem> {
+        let subcategory = sqlx::query_as::<_, SubcategoryItem>(
+            "SELECT s.id, s.category_id, s.title, s.description FROM subcategories s
+             JOIN categories c ON c.id = s.category_id
+             WHERE s.id = ? AND s.deleted_at IS NULL AND c.deleted_at IS NULL"
+        )
+        .bind(&id.0)
+        .fetch_optional(&**db)
+        .await
+        .unwrap();
+        subcategory.map(Json).ok_or_else(|| ApiError::not_found("Subcategory"))
+    }
+}
@@ -0,0 +1,132 @@
+use poem::web::Data;
+use poem_openapi::{param::{Path, Query}, payload::Json, Object, OpenApi};
+use sqlx::{Pool, Sqlite};
+
+use super::{ApiAuth, ApiError, ApiResult, ApiTags, Page, Paging};
+use crate::content::paths::path_steps;
+use crate::routes::paths::find_path;
+
+#[derive(Debug, Object, sqlx::FromRow)]
+pub struct GradedAttempt {
+    pub id: String,
+    pub user_id: String,
+    pub username: String,
+    pub category_id: Option<String>,
+    pub subcategory_id: Option<String>,
+    pub topic_id: Option<String>,
+    pub score: i64,
+    pub total: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Object)]
+pub struct GradedStep {
+    pub id: String,
+    /// `lesson`, `quiz` or `challenge`
+    pub kind: String,
+    pub title: String,
+}
+
+#[derive(Debug, Object, sqlx::FromRow)]
+pub struct StepCompletion {
+    pub user_id: String,
+    pub username: String,
+    pub step_id: String,
+    /// Quiz steps: the best passing score, in percent
+    pub score_pct: Option<i64>,
+    /// The attempt that got that score
+    pub attempt_id: Option<String>,
+    pub completed_at: String,
+}
+
+#[derive(Debug, Object)]
+pub struct PathGrades {
+    pub id: String,
+    pub title: String,
+    pub steps: Vec<GradedStep>,
+    /// Every learner's completed steps, by learner then step order
+    pub completions: Vec<StepCompletion>,
+}
+
+pub struct GradeApi;
+
+#[OpenApi(tag = "ApiTags::Grades")]
+impl GradeApi {
+    /// List learners' quiz attempts, newest first
+    ///
+    /// Staff only. Attempts made signed out belong to nobody and aren't
+    /// listed.
+    #[oai(path = "/grades/attempts", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn attempts(
+        &self,
+        auth: ApiAuth,
+        db: Data<&Pool<Sqlite>>,
+        user_id: Query<Option<String>>,
+        category_id: Query<Option<String>>,
+        topic_id: Query<Option<String>>,
+        /// Only attempts from then on, e.g. `2026-09-01`
+        since: Query<Option<String>>,
+        page: Query<Option<i64>>,
+        per_page: Query<Option<i64>>,
+    ) -> ApiResult<Page<GradedAttempt>> {
+        auth.require_staff()?;
+        let paging = Paging::new(page.0, per_page.0);
+        let filter = "(?1 IS NULL OR a.user_id = ?1) AND (?2 IS NULL OR a.category_id = ?2)
+             AND (?3 IS NULL OR a.topic_id = ?3) AND (?4 IS NULL OR a.created_at >= ?4)";
+        let total: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM quiz_attempts a JOIN users u ON u.id = a.user_id WHERE {}",
+            filter
+        ))
+        .bind(&user_id.0)
+        .bind(&category_id.0)
+        .bind(&topic_id.0)
+        .bind(&since.0)
+        .fetch_one(&**db)
+        .await
+        .unwrap();
+        let items = sqlx::query_as::<_, GradedAttempt>(&format!(
+            "SELECT a.id, a.user_id, u.username, a.category_id, a.subcategory_id, a.topic_id, a.score, a.total, a.created_at
+             FROM quiz_attempts a JOIN users u ON u.id = a.user_id
+             WHERE {} ORDER BY a.created_at DESC, a.rowid DESC LIMIT ?5 OFFSET ?6",
+            filter
+        ))
+        .bind(&user_id.0)
+        .bind(&category_id.0)
+        .bind(&topic_id.0)
+        .bind(&since.0)
+        .bind(paging.per_page)
+        .bind(paging.offset())
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+        Ok(Json(paging.page(items, total)))
+    }
+
+    /// Get every learner's progress through a learning path
+    ///
+    /// Staff only.
+    #[oai(path = "/grades/paths/:id", method = "get")]
+    async fn path(&self, auth: ApiAuth, db: Data<&Pool<Sqlite>>, id: Path<String>) -> ApiResult<PathGrades> {
+        auth.require_staff()?;
+        let path = find_path(&db, &id).await.ok_or_else(|| ApiError::not_found("Learning path"))?;
+        let mut conn = db.acquire().await.unwrap();
+        let steps = path_steps(&mut conn, &path.id).await.unwrap();
+        let completions = sqlx::query_as::<_, StepCompletion>(
+            "SELECT c.user_id, u.username, c.step_id, c.score_pct, c.attempt_id, c.completed_at
+             FROM path_step_completions c JOIN path_steps s ON s.id = c.step_id JOIN users u ON u.id = c.user_id
+             WHERE s.path_id = ? AND s.deleted_at IS NULL
+             ORDER BY u.username, c.user_id, s.position, s.created_at"
+        )
+        .bind(&path.id)
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+        Ok(Json(PathGrades {
+            id: path.id,
+            title: path.title,
+            steps: steps.into_iter().map(|s| GradedStep { id: s.id, kind: s.kind, title: s.title }).collect(),
+            completions,
+        }))
+    }
+}
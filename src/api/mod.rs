@@ -0,0 +1,389 @@
+// The JSON API, served under /api/v1. Handlers reuse the queries of the HTML
+// routes; the types here describe requests and responses, and the OpenAPI
+// document at /api/v1/openapi.json is generated from them, so it always
+// matches what is served.
+//
+// Every error has the body `{"code": ..., "message": ...}`, including those
+// raised before a handler runs (bad parameters, unknown paths, missing
+// sign-in).
+//
+// Callers are signed in with the session cookie or send a personal API token
+// (see routes::tokens); tokens are limited to their scopes.
+
+mod catalog;
+mod grades;
+mod paths;
+mod profile;
+mod quizzes;
+mod search;
+
+use poem::{http::StatusCode, IntoResponse, Request, RequestBody, Response};
+use poem_openapi::{
+    payload::Json,
+    registry::{MetaSecurityScheme, Registry},
+    types::{ParseFromJSON, ToJSON},
+    ApiExtractor, ApiExtractorType, ApiResponse, ExtractParamOptions, Object, OpenApiService, Tags,
+};
+use sqlx::{Pool, Sqlite};
+
+use crate::models::User;
+use crate::routes::auth::{current_user, SessionStore};
+use crate::routes::tokens::{authenticate, bearer_token, ApiToken, SCOPE_ADMIN, SCOPE_READ};
+
+pub const PREFIX: &str = "/api/v1";
+const SECURITY_SCHEME: &str = "ApiToken";
+
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Categories and subcategories with questions to quiz on
+    Catalog,
+    /// Taking quizzes and reviewing past attempts
+    Quizzes,
+    /// Learning paths and their challenges
+    Paths,
+    Search,
+    /// The signed-in user
+    Profile,
+    /// Learners' results, for staff
+    Grades,
+}
+
+#[derive(Debug, Object)]
+pub struct ErrorBody {
+    /// Stable, machine-readable, e.g. `not_found`
+    pub code: String,
+    /// For people
+    pub message: String,
+}
+
+#[derive(ApiResponse)]
+pub enum ApiError {
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 403)]
+    Forbidden(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+}
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::BadRequest(Json(ErrorBody { code: "bad_request".to_string(), message: message.into() }))
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        ApiError::Unauthorized(Json(ErrorBody { code: "unauthorized".to_string(), message: message.into() }))
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        ApiError::Forbidden(Json(ErrorBody { code: "forbidden".to_string(), message: message.into() }))
+    }
+
+    pub fn not_found(what: &str) -> Self {
+        ApiError::NotFound(Json(ErrorBody { code: "not_found".to_string(), message: format!("{} not found", what) }))
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        ApiError::Conflict(Json(ErrorBody { code: "conflict".to_string(), message: message.into() }))
+    }
+}
+
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+// Errors from outside the handlers (parameters that don't parse, unknown
+// paths, failed sign-in) get the same body as those from inside them
+pub async fn error_body(err: poem::Error) -> Response {
+    if err.is_from_response() {
+        return err.into_response();
+    }
+    let status = err.status();
+    let code = match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        _ => "error",
+    };
+    Json(ErrorBody { code: code.to_string(), message: err.to_string() }).with_status(status).into_response()
+}
+
+/// One page of a list, and how many items there are in all
+#[derive(Debug, Object)]
+pub struct Page<T: ParseFromJSON + ToJSON> {
+    pub items: Vec<T>,
+    pub total: i64,
+    /// From 1
+    pub page: i64,
+    pub per_page: i64,
+}
+
+// The page asked for by `page` and `per_page` query parameters
+#[derive(Debug, Clone, Copy)]
+pub struct Paging {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Paging {
+    pub fn new(page: Option<i64>, per_page: Option<i64>) -> Self {
+        Paging {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+
+    pub fn page<T: ParseFromJSON + ToJSON>(&self, items: Vec<T>, total: i64) -> Page<T> {
+        Page { items, total, page: self.page, per_page: self.per_page }
+    }
+}
+
+// Who is calling: a user signed in with the session cookie, or a personal
+// API token
+pub struct ApiAuth {
+    pub user: User,
+    // None for a session
+    token: Option<ApiToken>,
+}
+
+impl ApiAuth {
+    // A session may do whatever its user can; a token only what its scopes
+    // allow
+    pub fn require(&self, scope: &str) -> Result<(), ApiError> {
+        match &self.token {
+            Some(token) if !token.has_scope(scope) => {
+                Err(ApiError::forbidden(format!("This token lacks the {} scope", scope)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Staff only; a token needs the read or admin scope
+    pub fn require_staff(&self) -> Result<(), ApiError> {
+        if !self.user.is_staff() {
+            return Err(ApiError::forbidden("Only staff can do this"));
+        }
+        match &self.token {
+            Some(token) if !token.has_scope(SCOPE_READ) && !token.has_scope(SCOPE_ADMIN) => {
+                Err(ApiError::forbidden("This token lacks the read or admin scope"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// The caller of a request, None without credentials. A bearer token is
+// tried first, and one that is unknown, revoked or expired is refused rather
+// than falling back to the cookie.
+pub async fn caller(req: &Request) -> Result<Option<ApiAuth>, ApiError> {
+    let db = req.data::<Pool<Sqlite>>().unwrap();
+    if let Some(token) = bearer_token(req) {
+        let (user, token) = authenticate(db, token)
+            .await
+            .ok_or_else(|| ApiError::unauthorized("Unknown, revoked or expired API token"))?;
+        return Ok(Some(ApiAuth { user, token: Some(token) }));
+    }
+    let session_store = req.data::<SessionStore>().unwrap();
+    Ok(current_user(req, db, session_store).await.map(|user| ApiAuth { user, token: None }))
+}
+
+#[poem::async_trait]
+impl<'a> ApiExtractor<'a> for ApiAuth {
+    const TYPE: ApiExtractorType = ApiExtractorType::SecurityScheme;
+
+    type ParamType = ();
+    type ParamRawType = ();
+
+    fn register(registry: &mut Registry) {
+        registry.create_security_scheme(
+            SECURITY_SCHEME,
+            MetaSecurityScheme {
+                ty: "http",
+                description: Some(
+                    "A personal API token from the profile page, with the read, submit or admin scope. \
+                     The hackademy_session_id cookie set by /auth/login is accepted too.",
+                ),
+                name: None,
+                key_in: None,
+                scheme: Some("bearer"),
+                bearer_format: None,
+                flows: None,
+                openid_connect_url: None,
+            },
+        );
+    }
+
+    fn security_scheme() -> Option<&'static str> {
+        Some(SECURITY_SCHEME)
+    }
+
+    async fn from_request(
+        req: &'a Request,
+        _body: &mut RequestBody,
+        _param_opts: ExtractParamOptions<Self::ParamType>,
+    ) -> poem::Result<Self> {
+        Ok(caller(req).await?.ok_or_else(|| ApiError::unauthorized("Sign in or send an API token"))?)
+    }
+}
+
+pub type Api =
+    (catalog::CatalogApi, quizzes::QuizApi, paths::PathApi, search::SearchApi, profile::ProfileApi, grades::GradeApi);
+
+pub fn service() -> OpenApiService<Api, ()> {
+    OpenApiService::new(
+        (catalog::CatalogApi, quizzes::QuizApi, paths::PathApi, search::SearchApi, profile::ProfileApi, grades::GradeApi),
+        "Hackademy",
+        "1.0",
+    )
+    .server(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{http::Method, Endpoint, EndpointExt, IntoEndpoint, Route};
+
+    use super::*;
+    use crate::content::paths::hash_flag;
+    use crate::db::test_db;
+    use crate::routes::tokens::hash_token;
+
+    // Sam the student and Ivy the instructor, with a token per scope, and a
+    // path whose challenge has the flag "flag{xss}"
+    async fn lab() -> Pool<Sqlite> {
+        let db = test_db().await;
+        for sql in [
+            "INSERT INTO users (id, username, password_hash, role) VALUES
+                ('u-sam', 'sam', '', 'student'), ('u-ivy', 'ivy', '', 'instructor')",
+            "INSERT INTO learning_paths (id, title) VALUES ('p', 'Web Pentesting 101')",
+        ] {
+            sqlx::query(sql).execute(&db).await.unwrap();
+        }
+        sqlx::query("INSERT INTO path_steps (id, path_id, position, kind, title, flag_hash) VALUES ('xss', 'p', 1, 'challenge', 'XSS', ?)")
+            .bind(hash_flag("flag{xss}"))
+            .execute(&db)
+            .await
+            .unwrap();
+        for (user, scope) in [("sam", "read"), ("sam", "submit"), ("ivy", "read"), ("ivy", "submit"), ("ivy", "admin")] {
+            let token = format!("hk_{}_{}", user, scope);
+            sqlx::query(
+                "INSERT INTO api_tokens (id, user_id, name, token_hash, token_prefix, scopes) VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&token)
+            .bind(format!("u-{}", user))
+            .bind(scope)
+            .bind(hash_token(&token))
+            .bind(&token[..6])
+            .bind(scope)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    // The status and body of a request with a token, if any
+    async fn call(db: &Pool<Sqlite>, method: Method, uri: &str, token: Option<&str>, body: &str) -> (StatusCode, String) {
+        let app = Route::new()
+            .nest(PREFIX, service().into_endpoint().catch_all_error(error_body))
+            .data(db.clone())
+            .data(SessionStore::new());
+        let mut request = Request::builder().method(method).uri_str(format!("{}{}", PREFIX, uri));
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let request = request.content_type("application/json").body(body.to_string());
+        let response = app.call(request).await.unwrap();
+        (response.status(), response.into_body().into_string().await.unwrap())
+    }
+
+    async fn flag(db: &Pool<Sqlite>, token: Option<&str>, flag: &str) -> (StatusCode, String) {
+        call(db, Method::POST, "/paths/p/steps/xss/flag", token, &format!(r#"{{"flag": "{}"}}"#, flag)).await
+    }
+
+    async fn completed(db: &Pool<Sqlite>) -> bool {
+        let n: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM path_step_completions WHERE user_id = 'u-sam'")
+            .fetch_one(db)
+            .await
+            .unwrap();
+        n > 0
+    }
+
+    #[tokio::test]
+    async fn flags_need_the_submit_scope() {
+        let db = lab().await;
+        assert_eq!(flag(&db, None, "flag{xss}").await.0, StatusCode::UNAUTHORIZED);
+        let (status, body) = flag(&db, Some("hk_sam_read"), "flag{xss}").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("lacks the submit scope"), "{}", body);
+        assert!(!completed(&db).await);
+
+        assert_eq!(flag(&db, Some("hk_sam_submit"), "flag{sqli}").await, (StatusCode::OK, r#"{"correct":false}"#.to_string()));
+        assert!(!completed(&db).await);
+        assert_eq!(flag(&db, Some("hk_sam_submit"), "flag{xss}").await, (StatusCode::OK, r#"{"correct":true}"#.to_string()));
+        assert!(completed(&db).await);
+
+        let (status, body) = call(&db, Method::GET, "/paths/p", Some("hk_sam_read"), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""state":"completed""#), "{}", body);
+        assert_eq!(call(&db, Method::GET, "/paths/p", Some("hk_sam_submit"), "").await.0, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn refuses_flags_for_locked_steps_and_other_kinds() {
+        let db = lab().await;
+        sqlx::query(
+            "INSERT INTO path_steps (id, path_id, position, kind, title, flag_hash) VALUES
+                ('sqli', 'p', 2, 'challenge', 'SQLi', 'x'), ('read', 'p', 3, 'lesson', 'Read up', NULL)"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO path_step_prereqs (step_id, requires_id) VALUES ('sqli', 'xss')").execute(&db).await.unwrap();
+        let uri = |step: &str| format!("/paths/p/steps/{}/flag", step);
+        let body = r#"{"flag": "flag{xss}"}"#;
+        assert_eq!(call(&db, Method::POST, &uri("sqli"), Some("hk_sam_submit"), body).await.0, StatusCode::CONFLICT);
+        assert_eq!(call(&db, Method::POST, &uri("read"), Some("hk_sam_submit"), body).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(call(&db, Method::POST, &uri("nope"), Some("hk_sam_submit"), body).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn grades_are_for_staff_with_the_read_or_admin_scope() {
+        let db = lab().await;
+        sqlx::query("INSERT INTO quiz_attempts (id, user_id, score, total) VALUES ('a1', 'u-sam', 4, 5), ('a2', NULL, 1, 5)")
+            .execute(&db)
+            .await
+            .unwrap();
+        flag(&db, Some("hk_sam_submit"), "flag{xss}").await;
+
+        for uri in ["/grades/attempts", "/grades/paths/p"] {
+            assert_eq!(call(&db, Method::GET, uri, None, "").await.0, StatusCode::UNAUTHORIZED);
+            let (status, body) = call(&db, Method::GET, uri, Some("hk_sam_read"), "").await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+            assert!(body.contains("Only staff"), "{}", body);
+            let (status, body) = call(&db, Method::GET, uri, Some("hk_ivy_submit"), "").await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+            assert!(body.contains("lacks the read or admin scope"), "{}", body);
+            assert_eq!(call(&db, Method::GET, uri, Some("hk_ivy_read"), "").await.0, StatusCode::OK, "{}", uri);
+            assert_eq!(call(&db, Method::GET, uri, Some("hk_ivy_admin"), "").await.0, StatusCode::OK, "{}", uri);
+        }
+
+        let (_, body) = call(&db, Method::GET, "/grades/attempts", Some("hk_ivy_read"), "").await;
+        // The signed-out attempt belongs to nobody
+        assert!(body.contains(r#""username":"sam""#) && body.ends_with(r#""total":1}"#), "{}", body);
+        let (_, body) = call(&db, Method::GET, "/grades/paths/p", Some("hk_ivy_read"), "").await;
+        assert!(body.contains(r#""step_id":"xss""#), "{}", body);
+    }
+}
@@ -0,0 +1,97 @@
+use poem::web::Data;
+use poem_openapi::{param::Path, payload::Json, Object, OpenApi};
+use sqlx::{Pool, Sqlite};
+
+use super::{ApiAuth, ApiError, ApiResult, ApiTags};
+use crate::content::paths::{path_progress, step_prereqs};
+use crate::models::STEP_CHALLENGE;
+use crate::routes::paths::{capture_flag, find_path, step_to_complete, StepUnavailable};
+use crate::routes::tokens::{SCOPE_READ, SCOPE_SUBMIT};
+
+#[derive(Debug, Object)]
+pub struct StepItem {
+    pub id: String,
+    /// `lesson`, `quiz` or `challenge`
+    pub kind: String,
+    pub title: String,
+    /// `locked`, `unlocked` or `completed`
+    pub state: String,
+    /// Quiz steps: the best passing score, in percent
+    pub score_pct: Option<i64>,
+    /// The ids of the steps it requires
+    pub requires: Vec<String>,
+}
+
+#[derive(Debug, Object)]
+pub struct PathDetail {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// In order, as the caller sees them
+    pub steps: Vec<StepItem>,
+}
+
+#[derive(Debug, Object)]
+pub struct FlagRequest {
+    pub flag: String,
+}
+
+#[derive(Debug, Object)]
+pub struct FlagResult {
+    /// Whether it was the flag; the step is completed if so
+    pub correct: bool,
+}
+
+fn unavailable(e: StepUnavailable) -> ApiError {
+    match e {
+        StepUnavailable::NotFound => ApiError::not_found("Step"),
+        StepUnavailable::WrongKind(_) => ApiError::bad_request(e.message()),
+        StepUnavailable::Locked => ApiError::conflict(e.message()),
+    }
+}
+
+pub struct PathApi;
+
+#[OpenApi(tag = "ApiTags::Paths")]
+impl PathApi {
+    /// Get a learning path with your progress through it
+    #[oai(path = "/paths/:id", method = "get")]
+    async fn path(&self, auth: ApiAuth, db: Data<&Pool<Sqlite>>, id: Path<String>) -> ApiResult<PathDetail> {
+        auth.require(SCOPE_READ)?;
+        let path = find_path(&db, &id).await.ok_or_else(|| ApiError::not_found("Learning path"))?;
+        let mut conn = db.acquire().await.unwrap();
+        let progress = path_progress(&mut conn, &path.id, Some(&auth.user.id)).await.unwrap();
+        let mut steps = Vec::new();
+        for p in progress {
+            let requires = step_prereqs(&mut conn, &p.step.id).await.unwrap();
+            steps.push(StepItem {
+                id: p.step.id,
+                kind: p.step.kind,
+                title: p.step.title,
+                state: p.state.to_string(),
+                score_pct: p.score_pct,
+                requires,
+            });
+        }
+        Ok(Json(PathDetail { id: path.id, title: path.title, description: path.description, steps }))
+    }
+
+    /// Submit the flag of a challenge step
+    ///
+    /// The step must be unlocked. A right flag completes it; submitting a
+    /// flag again once it is completed does no harm.
+    #[oai(path = "/paths/:id/steps/:step_id/flag", method = "post")]
+    async fn submit_flag(
+        &self,
+        auth: ApiAuth,
+        db: Data<&Pool<Sqlite>>,
+        id: Path<String>,
+        step_id: Path<String>,
+        body: Json<FlagRequest>,
+    ) -> ApiResult<FlagResult> {
+        auth.require(SCOPE_SUBMIT)?;
+        let (_, step) = step_to_complete(&db, &auth.user, &id, &step_id, STEP_CHALLENGE).await.map_err(unavailable)?;
+        let correct = capture_flag(&db, &auth.user, &step, &body.flag).await;
+        Ok(Json(FlagResult { correct }))
+    }
+}
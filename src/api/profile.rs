@@ -0,0 +1,44 @@
+use poem::web::Data;
+use poem_openapi::{payload::Json, Object, OpenApi};
+use sqlx::{Pool, Sqlite};
+
+use super::{ApiAuth, ApiResult, ApiTags};
+use crate::routes::tokens::SCOPE_READ;
+
+#[derive(Debug, Object)]
+pub struct Profile {
+    pub id: String,
+    pub username: String,
+    /// `student`, `instructor` or `admin`
+    pub role: String,
+    /// Quizzes submitted while signed in
+    pub attempts: i64,
+    /// Questions due for spaced-repetition review
+    pub reviews_due: i64,
+}
+
+pub struct ProfileApi;
+
+#[OpenApi(tag = "ApiTags::Profile")]
+impl ProfileApi {
+    /// Get the signed-in user
+    #[oai(path = "/profile", method = "get")]
+    async fn profile(&self, auth: ApiAuth, db: Data<&Pool<Sqlite>>) -> ApiResult<Profile> {
+        auth.require(SCOPE_READ)?;
+        let user = auth.user;
+        let attempts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM quiz_attempts WHERE user_id = ?")
+            .bind(&user.id)
+            .fetch_one(&**db)
+            .await
+            .unwrap();
+        let reviews_due: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM review_schedule r JOIN questions q ON q.id = r.question_id
+             WHERE r.user_id = ? AND r.due_at <= datetime('now') AND q.deleted_at IS NULL AND q.status = 'published'"
+        )
+        .bind(&user.id)
+        .fetch_one(&**db)
+        .await
+        .unwrap();
+        Ok(Json(Profile { id: user.id, username: user.username, role: user.role, attempts, reviews_due }))
+    }
+}
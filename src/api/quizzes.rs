@@ -0,0 +1,281 @@
+use poem::{web::Data, Request};
+use poem_openapi::{param::{Path, Query}, payload::Json, Object, OpenApi};
+use sqlx::{Pool, Sqlite};
+
+use super::{caller, ApiAuth, ApiError, ApiResult, ApiTags, Page, Paging};
+use crate::routes::tokens::{SCOPE_READ, SCOPE_SUBMIT};
+use crate::routes::quiz::{
+    grade_quiz, load_quiz, quiz_question, record_served, QuestionFeedback, QuizParams, QuizQuestion, QuizUnavailable,
+    SubmittedAnswer,
+};
+
+#[derive(Debug, Object)]
+pub struct TopicRef {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Object)]
+pub struct Choice {
+    /// What to send back in a response
+    pub index: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Object)]
+pub struct QuizQuestionItem {
+    pub id: String,
+    /// Answers are graded against this revision, even if the question is
+    /// edited before they are submitted
+    pub revision: i64,
+    /// `choice`, `multiple_response`, `text_entry` or `order`
+    pub kind: String,
+    pub question_text: String,
+    /// In display order; empty for text entry
+    pub choices: Vec<Choice>,
+}
+
+#[derive(Debug, Object)]
+pub struct Quiz {
+    /// Send back with the answers; a quiz can be submitted once, within a
+    /// day
+    pub served_id: String,
+    /// The quiz's topic, from its root category down
+    pub breadcrumbs: Vec<TopicRef>,
+    pub questions: Vec<QuizQuestionItem>,
+}
+
+#[derive(Debug, Object)]
+pub struct AnswerItem {
+    pub question_id: String,
+    /// Optional; if given, it must be the revision the quiz showed
+    pub revision: Option<i64>,
+    /// Choice indices (several for multiple response, all of them in order
+    /// for order questions), or the typed answer for text entry
+    pub response: Vec<String>,
+}
+
+#[derive(Debug, Object)]
+pub struct AttemptRequest {
+    /// From the quiz the answers are to
+    pub served_id: String,
+    pub answers: Vec<AnswerItem>,
+}
+
+#[derive(Debug, Object)]
+pub struct Feedback {
+    pub question_id: String,
+    pub revision: i64,
+    pub question_text: String,
+    pub your_answer: String,
+    pub correct_answer: String,
+    pub correct: bool,
+    /// False once the revision has been taken out of scoring
+    pub scored: bool,
+}
+
+impl From<QuestionFeedback> for Feedback {
+    fn from(f: QuestionFeedback) -> Self {
+        Feedback {
+            question_id: f.question_id,
+            revision: f.revision,
+            question_text: f.question_text,
+            your_answer: f.selected_option,
+            correct_answer: f.correct_option,
+            correct: f.is_correct,
+            scored: f.scored,
+        }
+    }
+}
+
+#[derive(Debug, Object)]
+pub struct AttemptResult {
+    /// Absent if none of the questions could be graded
+    pub attempt_id: Option<String>,
+    pub score: i64,
+    pub total: i64,
+    pub feedback: Vec<Feedback>,
+    /// Learning path steps this attempt completed
+    pub completed_steps: Vec<String>,
+}
+
+#[derive(Debug, Object, sqlx::FromRow)]
+pub struct AttemptSummary {
+    pub id: String,
+    pub category_id: Option<String>,
+    pub subcategory_id: Option<String>,
+    pub topic_id: Option<String>,
+    pub score: i64,
+    pub total: i64,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub created_at: String,
+}
+
+#[derive(Debug, Object)]
+pub struct AttemptAnswer {
+    pub question_id: String,
+    pub revision: i64,
+    pub response: Vec<String>,
+    pub correct: bool,
+}
+
+#[derive(Debug, Object)]
+pub struct AttemptDetail {
+    pub attempt: AttemptSummary,
+    pub answers: Vec<AttemptAnswer>,
+}
+
+pub struct QuizApi;
+
+#[OpenApi(tag = "ApiTags::Quizzes")]
+impl QuizApi {
+    /// Get the questions of a quiz
+    ///
+    /// A quiz draws from a category (optionally narrowed to a subcategory),
+    /// a topic and everything below it, or questions carrying some tags.
+    #[oai(path = "/quizzes", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn quiz(
+        &self,
+        db: Data<&Pool<Sqlite>>,
+        category_id: Query<Option<String>>,
+        subcategory_id: Query<Option<String>>,
+        topic_id: Query<Option<String>>,
+        /// Comma-separated
+        tags: Query<Option<String>>,
+        /// `all` (the default) or `any` of the tags
+        tag_match: Query<Option<String>>,
+    ) -> ApiResult<Quiz> {
+        let params = QuizParams {
+            category_id: category_id.0,
+            subcategory_id: subcategory_id.0,
+            topic_id: topic_id.0,
+            tags: tags.0,
+            tag_match: tag_match.0,
+        };
+        let mut conn = db.acquire().await.unwrap();
+        let (breadcrumbs, questions) = load_quiz(&mut conn, &params).await.map_err(unavailable)?;
+        let questions: Vec<QuizQuestion> = questions.into_iter().map(quiz_question).collect();
+        let served_id = record_served(&mut conn, &questions.iter().collect::<Vec<_>>(), Some(&params)).await.unwrap();
+        Ok(Json(Quiz {
+            served_id,
+            breadcrumbs: breadcrumbs.into_iter().map(|t| TopicRef { id: t.id, title: t.title }).collect(),
+            questions: questions
+                .into_iter()
+                .map(|q| QuizQuestionItem {
+                    id: q.id,
+                    revision: q.revision,
+                    kind: q.kind,
+                    question_text: q.question_text,
+                    choices: q.choices.into_iter().map(|(index, text)| Choice { index: index as i64, text }).collect(),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Submit answers to a quiz
+    ///
+    /// Works signed out too. Signed in, or with a token with the submit
+    /// scope, the attempt is saved to the user's history and schedules their
+    /// reviews.
+    #[oai(path = "/attempts", method = "post")]
+    async fn submit(
+        &self,
+        req: &Request,
+        db: Data<&Pool<Sqlite>>,
+        body: Json<AttemptRequest>,
+    ) -> ApiResult<AttemptResult> {
+        let body = body.0;
+        if body.answers.is_empty() {
+            return Err(ApiError::bad_request("Answer at least one question"));
+        }
+        let answers: Vec<SubmittedAnswer> = body
+            .answers
+            .into_iter()
+            .map(|a| SubmittedAnswer { question_id: a.question_id, revision: a.revision, response: a.response })
+            .collect();
+        let auth = caller(req).await?;
+        if let Some(auth) = &auth {
+            auth.require(SCOPE_SUBMIT)?;
+        }
+        let result = grade_quiz(&db, auth.as_ref().map(|a| &a.user), &body.served_id, &answers)
+            .await
+            .map_err(ApiError::bad_request)?;
+        Ok(Json(AttemptResult {
+            attempt_id: result.attempt_id,
+            score: result.correct_count as i64,
+            total: result.total_questions as i64,
+            feedback: result.feedback.into_iter().map(Feedback::from).collect(),
+            completed_steps: result.completed_steps,
+        }))
+    }
+
+    /// List your quiz attempts, newest first
+    #[oai(path = "/attempts", method = "get")]
+    async fn attempts(
+        &self,
+        auth: ApiAuth,
+        db: Data<&Pool<Sqlite>>,
+        page: Query<Option<i64>>,
+        per_page: Query<Option<i64>>,
+    ) -> ApiResult<Page<AttemptSummary>> {
+        auth.require(SCOPE_READ)?;
+        let paging = Paging::new(page.0, per_page.0);
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM quiz_attempts WHERE user_id = ?")
+            .bind(&auth.user.id)
+            .fetch_one(&**db)
+            .await
+            .unwrap();
+        let items = sqlx::query_as::<_, AttemptSummary>(
+            "SELECT id, category_id, subcategory_id, topic_id, score, total, created_at FROM quiz_attempts
+             WHERE user_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ? OFFSET ?"
+        )
+        .bind(&auth.user.id)
+        .bind(paging.per_page)
+        .bind(paging.offset())
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+        Ok(Json(paging.page(items, total)))
+    }
+
+    /// Get one of your quiz attempts, with its answers
+    #[oai(path = "/attempts/:id", method = "get")]
+    async fn attempt(&self, auth: ApiAuth, db: Data<&Pool<Sqlite>>, id: Path<String>) -> ApiResult<AttemptDetail> {
+        auth.require(SCOPE_READ)?;
+        let attempt = sqlx::query_as::<_, AttemptSummary>(
+            "SELECT id, category_id, subcategory_id, topic_id, score, total, created_at FROM quiz_attempts
+             WHERE id = ? AND user_id = ?"
+        )
+        .bind(&id.0)
+        .bind(&auth.user.id)
+        .fetch_optional(&**db)
+        .await
+        .unwrap()
+        .ok_or_else(|| ApiError::not_found("Attempt"))?;
+        let rows: Vec<(String, i64, String, bool)> = sqlx::query_as(
+            "SELECT question_id, revision, response, correct FROM attempt_answers WHERE attempt_id = ? ORDER BY rowid"
+        )
+        .bind(&attempt.id)
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+        let answers = rows
+            .into_iter()
+            .map(|(question_id, revision, response, correct)| AttemptAnswer {
+                question_id,
+                revision,
+                response: serde_json::from_str(&response).unwrap_or_default(),
+                correct,
+            })
+            .collect();
+        Ok(Json(AttemptDetail { attempt, answers }))
+    }
+}
+
+fn unavailable(e: QuizUnavailable) -> ApiError {
+    match e {
+        QuizUnavailable::NothingChosen => ApiError::bad_request(e.message()),
+        QuizUnavailable::TopicNotFound => ApiError::not_found("Topic"),
+    }
+}
@@ -0,0 +1,166 @@
+use poem::web::Data;
+use poem_openapi::{
+    param::Query,
+    payload::Json,
+    types::{ParseFromJSON, ToJSON},
+    Object, OpenApi,
+};
+use sqlx::{Pool, Sqlite};
+
+use super::{ApiError, ApiResult, ApiTags, Page};
+use crate::content::search::Synonyms;
+use crate::content::search_stats::record_search;
+use crate::routes::search::{run_search, SearchParams, SearchResults, Section, PER_PAGE, PREVIEW};
+
+// Fields ending in _html hold escaped text with the matches in <mark>
+
+#[derive(Debug, Object)]
+pub struct CategoryHit {
+    pub id: String,
+    pub title_html: String,
+    pub url: String,
+}
+
+#[derive(Debug, Object)]
+pub struct SubcategoryHit {
+    pub id: String,
+    pub category_id: String,
+    pub title_html: String,
+    pub description_html: Option<String>,
+    pub category_title: String,
+    pub url: String,
+}
+
+#[derive(Debug, Object)]
+pub struct QuestionHit {
+    pub id: String,
+    pub category_id: String,
+    pub subcategory_id: Option<String>,
+    pub question_html: String,
+    /// Only when the explanation matched
+    pub explanation_html: Option<String>,
+    pub url: String,
+}
+
+#[derive(Debug, Object)]
+pub struct LessonHit {
+    pub id: String,
+    pub title_html: String,
+    pub subcategory_title: String,
+    pub snippet_html: String,
+    pub url: String,
+}
+
+#[derive(Debug, Object)]
+pub struct Correction {
+    pub typed: String,
+    pub searched_for: String,
+}
+
+#[derive(Debug, Object)]
+pub struct SearchResponse {
+    pub query: String,
+    /// Words taken as misspelt
+    pub corrections: Vec<Correction>,
+    pub categories: Page<CategoryHit>,
+    pub subcategories: Page<SubcategoryHit>,
+    pub questions: Page<QuestionHit>,
+    pub lessons: Page<LessonHit>,
+}
+
+pub struct SearchApi;
+
+#[OpenApi(tag = "ApiTags::Search")]
+impl SearchApi {
+    /// Search categories, subcategories, questions and lessons
+    ///
+    /// Every type is counted in full. With `type=all` (the default) each
+    /// lists its first few results; otherwise the chosen type lists a page
+    /// and the others their first few.
+    #[oai(path = "/search", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn search(
+        &self,
+        db: Data<&Pool<Sqlite>>,
+        synonyms: Data<&Synonyms>,
+        q: Query<String>,
+        /// `all`, `categories`, `subcategories`, `questions` or `lessons`
+        #[oai(name = "type")]
+        result_type: Query<Option<String>>,
+        category_id: Query<Option<String>>,
+        tag: Query<Option<String>>,
+        /// `beginner`, `intermediate` or `advanced`
+        difficulty: Query<Option<String>>,
+        /// `relevance` (the default), `title` or `recent`
+        sort: Query<Option<String>>,
+        page: Query<Option<i64>>,
+    ) -> ApiResult<SearchResponse> {
+        let params = SearchParams {
+            q: Some(q.0),
+            result_type: result_type.0,
+            category_id: category_id.0,
+            tag: tag.0,
+            difficulty: difficulty.0,
+            sort: sort.0,
+            page: page.0,
+        };
+        if params.query().is_empty() {
+            return Err(ApiError::bad_request("Enter something to search for"));
+        }
+
+        let SearchResults { categories, subcategories, questions, lessons, corrections } =
+            run_search(&db, &params, &synonyms).await;
+        // Counted like a search from the search page
+        if params.result_type() == "all" && params.page() == 1 {
+            let total = categories.total + subcategories.total + questions.total + lessons.total;
+            let mut conn = db.acquire().await.unwrap();
+            record_search(&mut conn, params.query(), total).await.unwrap();
+        }
+
+        Ok(Json(SearchResponse {
+            query: params.query().to_string(),
+            corrections: corrections.into_iter().map(|(typed, searched_for)| Correction { typed, searched_for }).collect(),
+            categories: page_of_hits(&params, "categories", categories, |c| CategoryHit {
+                url: c.url(),
+                id: c.id,
+                title_html: c.title_html,
+            }),
+            subcategories: page_of_hits(&params, "subcategories", subcategories, |s| SubcategoryHit {
+                url: s.url(),
+                id: s.id,
+                category_id: s.category_id,
+                title_html: s.title_html,
+                description_html: s.description_html,
+                category_title: s.parent_category_title,
+            }),
+            questions: page_of_hits(&params, "questions", questions, |q| QuestionHit {
+                url: q.url(),
+                id: q.id,
+                category_id: q.category_id,
+                subcategory_id: q.subcategory_id,
+                question_html: q.question_html,
+                explanation_html: q.explanation_html,
+            }),
+            lessons: page_of_hits(&params, "lessons", lessons, |l| LessonHit {
+                url: l.url(),
+                id: l.id,
+                title_html: l.title_html,
+                subcategory_title: l.subcategory_title,
+                snippet_html: l.snippet_html,
+            }),
+        }))
+    }
+}
+
+// A type of result as a page: the page asked for of the chosen type, the
+// first few of the others
+fn page_of_hits<T, H>(params: &SearchParams, result_type: &str, section: Section<T>, hit: impl Fn(T) -> H) -> Page<H>
+where
+    H: ParseFromJSON + ToJSON,
+{
+    let (page, per_page) = match params.result_type() == result_type {
+        true => (params.page(), PER_PAGE),
+        false => (1, PREVIEW),
+    };
+    Page { items: section.items.into_iter().map(hit).collect(), total: section.total, page, per_page }
+}
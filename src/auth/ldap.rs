@@ -0,0 +1,554 @@
+// Passwords checked by binding to an LDAP directory as the user. The user's
+// entry is found either from a DN template, or by searching for it, bound as
+// a service account if the directory needs one. Their groups come from the
+// entry's memberOf values and, optionally, a search for groups listing them.
+// Groups are matched to roles by their cn, ignoring case.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use ldap3::{dn_escape, ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+
+use super::{map_role, parse_role_map, Identity};
+use crate::utils::config::AppConfig;
+
+// Result code of a bind with the wrong DN or password
+const INVALID_CREDENTIALS: u32 = 49;
+
+pub struct LdapSettings {
+    // e.g. ldap://ldap.lab.local or ldaps://ldap.lab.local:636
+    pub url: String,
+    pub starttls: bool,
+    pub timeout: Duration,
+    // e.g. "uid={username},ou=people,dc=lab,dc=local"; when set, users are
+    // bound to directly without a search
+    pub user_dn: Option<String>,
+    // Where to search for users, and how
+    pub base_dn: String,
+    pub user_filter: String,
+    // The service account to search as; anonymous when unset
+    pub bind_dn: Option<String>,
+    pub bind_password: String,
+    // The attribute holding the username, e.g. "uid" or "sAMAccountName"
+    pub username_attr: String,
+    // Where to search for groups naming the user ({dn} and {username}); no
+    // search when unset
+    pub group_base_dn: Option<String>,
+    pub group_filter: String,
+    // (group cn in lowercase, role); empty leaves roles alone
+    pub role_map: Vec<(String, String)>,
+    // Whether entries' mail values count as verified, so a sign-in can join
+    // the user who has that email from another provider. Only where users
+    // can't edit their own mail attribute.
+    pub trust_mail: bool,
+}
+
+impl LdapSettings {
+    // None unless a URL is configured
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>> {
+        let Some(url) = &config.ldap_url else {
+            return Ok(None);
+        };
+        if config.ldap_user_dn.is_none() && config.ldap_base_dn.is_none() {
+            return Err(anyhow!("LDAP_URL is set, but neither LDAP_USER_DN nor LDAP_BASE_DN"));
+        }
+        let role_map = parse_role_map(config.ldap_role_map.as_deref().unwrap_or(""))
+            .map_err(|e| anyhow!("LDAP_ROLE_MAP: {}", e))?;
+        Ok(Some(LdapSettings {
+            url: url.clone(),
+            starttls: config.ldap_starttls,
+            timeout: Duration::from_secs(config.ldap_timeout_secs),
+            user_dn: config.ldap_user_dn.clone(),
+            base_dn: config.ldap_base_dn.clone().unwrap_or_default(),
+            user_filter: config.ldap_user_filter.clone(),
+            bind_dn: config.ldap_bind_dn.clone(),
+            bind_password: config.ldap_bind_password.clone().unwrap_or_default(),
+            username_attr: config.ldap_username_attr.clone(),
+            group_base_dn: config.ldap_group_base_dn.clone(),
+            group_filter: config.ldap_group_filter.clone(),
+            role_map: role_map.into_iter().map(|(group, role)| (group.to_lowercase(), role)).collect(),
+            trust_mail: config.ldap_trust_mail,
+        }))
+    }
+}
+
+pub struct Ldap {
+    settings: LdapSettings,
+    // Stands in for the server in tests
+    #[cfg(test)]
+    fake: Option<std::sync::Arc<fake::Directory>>,
+}
+
+// A connection to the directory
+enum Connection {
+    Ldap(ldap3::Ldap),
+    #[cfg(test)]
+    Fake(std::sync::Arc<fake::Directory>),
+}
+
+impl Connection {
+    // The result code of a simple bind
+    async fn simple_bind(&mut self, dn: &str, password: &str) -> Result<u32> {
+        match self {
+            Connection::Ldap(ldap) => Ok(ldap.simple_bind(dn, password).await?.rc),
+            #[cfg(test)]
+            Connection::Fake(directory) => Ok(directory.bind(dn, password)),
+        }
+    }
+
+    // The entries found; None when the directory refuses the search
+    async fn search(&mut self, base: &str, scope: Scope, filter: &str, attrs: &[&str]) -> Result<Option<Vec<SearchEntry>>> {
+        match self {
+            Connection::Ldap(ldap) => {
+                let result = ldap.search(base, scope, filter, attrs.to_vec()).await?;
+                Ok(result.success().ok().map(|(entries, _)| entries.into_iter().map(SearchEntry::construct).collect()))
+            }
+            #[cfg(test)]
+            Connection::Fake(directory) => Ok(directory.search(base, scope, filter)),
+        }
+    }
+
+    async fn unbind(&mut self) {
+        match self {
+            // The answer stands whether or not the goodbye gets through
+            Connection::Ldap(ldap) => {
+                let _ = ldap.unbind().await;
+            }
+            #[cfg(test)]
+            Connection::Fake(_) => {}
+        }
+    }
+}
+
+// The cn of a DN such as "cn=lab-admins,ou=groups,dc=lab,dc=local"
+fn dn_cn(dn: &str) -> Option<&str> {
+    let (attr, value) = dn.split(',').next()?.split_once('=')?;
+    Some(value.trim()).filter(|_| attr.trim().eq_ignore_ascii_case("cn"))
+}
+
+fn first<'a>(entry: &'a SearchEntry, attr: &str) -> Option<&'a str> {
+    entry.attrs.iter().find(|(name, _)| name.eq_ignore_ascii_case(attr))?.1.first().map(String::as_str)
+}
+
+fn all<'a>(entry: &'a SearchEntry, attr: &str) -> Vec<&'a str> {
+    entry
+        .attrs
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(attr))
+        .flat_map(|(_, values)| values.iter().map(String::as_str))
+        .collect()
+}
+
+impl Ldap {
+    pub fn new(settings: LdapSettings) -> Self {
+        Ldap {
+            settings,
+            #[cfg(test)]
+            fake: None,
+        }
+    }
+
+    async fn connect(&self) -> Result<Connection> {
+        #[cfg(test)]
+        if let Some(directory) = &self.fake {
+            return Ok(Connection::Fake(directory.clone()));
+        }
+        let settings = &self.settings;
+        let conn_settings = LdapConnSettings::new().set_conn_timeout(settings.timeout).set_starttls(settings.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(conn_settings, &settings.url).await?;
+        ldap3::drive!(conn);
+        ldap.with_timeout(settings.timeout);
+        Ok(Connection::Ldap(ldap))
+    }
+
+    // Who the directory says the username and password belong to; None when
+    // it doesn't know them. Err when it can't be asked.
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<Option<Identity>> {
+        // An empty password would be an unauthenticated bind, which succeeds
+        if username.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+        let mut ldap = self.connect().await?;
+        let identity = self.find_and_bind(&mut ldap, username, password).await;
+        ldap.unbind().await;
+        identity
+    }
+
+    async fn find_and_bind(&self, ldap: &mut Connection, username: &str, password: &str) -> Result<Option<Identity>> {
+        let settings = &self.settings;
+        let attrs = [settings.username_attr.as_str(), "mail", "memberOf"];
+        let entry = if let Some(template) = &settings.user_dn {
+            let dn = template.replace("{username}", &dn_escape(username));
+            if !self.bind(ldap, &dn, password).await? {
+                return Ok(None);
+            }
+            // Users may not be allowed to read their own entry
+            let entries = ldap.search(&dn, Scope::Base, "(objectClass=*)", &attrs).await?.unwrap_or_default();
+            entries.into_iter().next().unwrap_or(SearchEntry {
+                dn,
+                attrs: HashMap::new(),
+                bin_attrs: HashMap::new(),
+            })
+        } else {
+            if let Some(bind_dn) = &settings.bind_dn
+                && !self.bind(ldap, bind_dn, &settings.bind_password).await?
+            {
+                return Err(anyhow!("The directory refused the LDAP_BIND_DN credentials"));
+            }
+            let filter = settings.user_filter.replace("{username}", &ldap_escape(username));
+            let entries = ldap
+                .search(&settings.base_dn, Scope::Subtree, &filter, &attrs)
+                .await?
+                .ok_or_else(|| anyhow!("The directory refused the search for users"))?;
+            // Ambiguous usernames sign in as nobody
+            let Ok([entry]) = <[SearchEntry; 1]>::try_from(entries) else {
+                return Ok(None);
+            };
+            if !self.bind(ldap, &entry.dn, password).await? {
+                return Ok(None);
+            }
+            entry
+        };
+
+        let mut groups: Vec<String> = all(&entry, "memberOf").into_iter().filter_map(dn_cn).map(String::from).collect();
+        if let Some(group_base_dn) = &settings.group_base_dn {
+            let filter =
+                settings.group_filter.replace("{dn}", &ldap_escape(&entry.dn)).replace("{username}", &ldap_escape(username));
+            let entries = ldap
+                .search(group_base_dn, Scope::Subtree, &filter, &["cn"])
+                .await?
+                .ok_or_else(|| anyhow!("The directory refused the search for groups"))?;
+            for group in &entries {
+                groups.extend(all(group, "cn").into_iter().map(String::from));
+            }
+        }
+        let groups: Vec<String> = groups.iter().map(|g| g.to_lowercase()).collect();
+        let groups: Vec<&str> = groups.iter().map(String::as_str).collect();
+
+        Ok(Some(Identity {
+            issuer: settings.url.clone(),
+            username: Some(first(&entry, &settings.username_attr).unwrap_or(username).to_string()),
+            email: first(&entry, "mail").map(String::from),
+            email_verified: settings.trust_mail,
+            role: map_role(&settings.role_map, &groups),
+            // DNs don't care about case, so "uid=Alice" and "uid=alice" are one user
+            subject: entry.dn.to_lowercase(),
+            link_user_id: None,
+        }))
+    }
+
+    // Whether the directory accepts the DN and password
+    async fn bind(&self, ldap: &mut Connection, dn: &str, password: &str) -> Result<bool> {
+        match ldap.simple_bind(dn, password).await? {
+            0 => Ok(true),
+            INVALID_CREDENTIALS => Ok(false),
+            rc => Err(anyhow!("The directory refused to bind as {} (result code {})", dn, rc)),
+        }
+    }
+}
+
+// An in-memory directory standing in for the server in tests. It knows
+// entries and their passwords, answers searches with simple filters (&, |,
+// !, presence and equality ignoring case) and logs every bind and search.
+#[cfg(test)]
+pub(crate) mod fake {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use ldap3::{Scope, SearchEntry};
+
+    use super::{all, Ldap, LdapSettings, INVALID_CREDENTIALS};
+
+    pub const BASE_DN: &str = "dc=lab,dc=local";
+
+    #[derive(Default)]
+    pub struct Directory {
+        entries: Vec<SearchEntry>,
+        // By DN in lowercase
+        passwords: HashMap<String, String>,
+        // "bind DN" and "search base filter", in order
+        log: Mutex<Vec<String>>,
+    }
+
+    impl Directory {
+        // Add an entry; with a password, it can be bound to
+        pub fn entry(mut self, dn: &str, password: Option<&str>, attrs: &[(&str, &str)]) -> Self {
+            let mut values: HashMap<String, Vec<String>> = HashMap::new();
+            for (name, value) in attrs {
+                values.entry(name.to_string()).or_default().push(value.to_string());
+            }
+            if let Some(password) = password {
+                self.passwords.insert(dn.to_lowercase(), password.to_string());
+            }
+            self.entries.push(SearchEntry { dn: dn.to_string(), attrs: values, bin_attrs: HashMap::new() });
+            self
+        }
+
+        // A person under ou=people
+        pub fn user(self, uid: &str, password: &str, attrs: &[(&str, &str)]) -> Self {
+            let dn = format!("uid={},ou=people,{}", uid, BASE_DN);
+            let attrs: Vec<(&str, &str)> = [("objectClass", "inetOrgPerson"), ("uid", uid)].into_iter().chain(attrs.iter().copied()).collect();
+            self.entry(&dn, Some(password), &attrs)
+        }
+
+        // A client of this directory
+        pub fn client(self, settings: LdapSettings) -> (Ldap, Arc<Directory>) {
+            let directory = Arc::new(self);
+            (Ldap { settings, fake: Some(directory.clone()) }, directory)
+        }
+
+        pub fn log(&self) -> Vec<String> {
+            self.log.lock().unwrap().clone()
+        }
+
+        pub(super) fn bind(&self, dn: &str, password: &str) -> u32 {
+            self.log.lock().unwrap().push(format!("bind {}", dn));
+            match self.passwords.get(&dn.to_lowercase()) {
+                Some(p) if p == password => 0,
+                _ => INVALID_CREDENTIALS,
+            }
+        }
+
+        pub(super) fn search(&self, base: &str, scope: Scope, filter: &str) -> Option<Vec<SearchEntry>> {
+            self.log.lock().unwrap().push(format!("search {} {}", base, filter));
+            let base = base.to_lowercase();
+            let found = self.entries.iter().filter(|entry| {
+                let dn = entry.dn.to_lowercase();
+                let in_scope = dn == base || (scope != Scope::Base && dn.ends_with(&format!(",{}", base)));
+                in_scope && matches(filter, entry)
+            });
+            Some(found.cloned().collect())
+        }
+    }
+
+    fn matches(filter: &str, entry: &SearchEntry) -> bool {
+        let inner = filter.strip_prefix('(').and_then(|f| f.strip_suffix(')')).expect("a filter in parentheses");
+        if let Some(rest) = inner.strip_prefix('&') {
+            return parts(rest).iter().all(|f| matches(f, entry));
+        }
+        if let Some(rest) = inner.strip_prefix('|') {
+            return parts(rest).iter().any(|f| matches(f, entry));
+        }
+        if let Some(rest) = inner.strip_prefix('!') {
+            return !matches(rest, entry);
+        }
+        let (attr, value) = inner.split_once('=').expect("attr=value");
+        assert!(!value.contains(['(', ')']), "unescaped parenthesis in {}", filter);
+        let values = if attr.eq_ignore_ascii_case("objectClass") || !attr.eq_ignore_ascii_case("dn") {
+            all(entry, attr)
+        } else {
+            vec![entry.dn.as_str()]
+        };
+        if value == "*" {
+            return !values.is_empty();
+        }
+        assert!(!value.contains('*'), "substring filters aren't supported: {}", filter);
+        let value = unescape(value);
+        values.iter().any(|v| v.eq_ignore_ascii_case(&value))
+    }
+
+    // The filters in "(a)(b)(c)"
+    fn parts(list: &str) -> Vec<&str> {
+        let (mut parts, mut depth, mut start) = (Vec::new(), 0, 0);
+        for (i, c) in list.char_indices() {
+            match c {
+                '(' => {
+                    if depth == 0 {
+                        start = i;
+                    }
+                    depth += 1;
+                }
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        parts.push(&list[start..=i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        parts
+    }
+
+    // A filter value with its \xx escapes decoded
+    fn unescape(value: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut rest = value.as_bytes();
+        while let Some((&b, tail)) = rest.split_first() {
+            if b == b'\\' && tail.len() >= 2 {
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&tail[..2]).unwrap(), 16).unwrap());
+                rest = &tail[2..];
+            } else {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+        String::from_utf8(bytes).unwrap()
+    }
+
+    // Settings for searching the directory as a service account
+    pub fn search_settings() -> LdapSettings {
+        LdapSettings {
+            url: "ldap://ldap.lab.local".to_string(),
+            starttls: false,
+            timeout: Duration::from_secs(1),
+            user_dn: None,
+            base_dn: BASE_DN.to_string(),
+            user_filter: "(uid={username})".to_string(),
+            bind_dn: Some(format!("cn=hackademy,ou=services,{}", BASE_DN)),
+            bind_password: "service-secret".to_string(),
+            username_attr: "uid".to_string(),
+            group_base_dn: Some(format!("ou=groups,{}", BASE_DN)),
+            group_filter: "(|(member={dn})(uniqueMember={dn})(memberUid={username}))".to_string(),
+            role_map: vec![("lab-admins".to_string(), "admin".to_string()), ("tas".to_string(), "instructor".to_string())],
+            trust_mail: false,
+        }
+    }
+
+    // The lab directory: the service account, alice (a TA by memberOf), bob
+    // (an admin by group membership) and two carols
+    pub fn lab() -> Directory {
+        Directory::default()
+            .entry(&format!("cn=hackademy,ou=services,{}", BASE_DN), Some("service-secret"), &[("cn", "hackademy")])
+            .user("alice", "alice-pw", &[("mail", "alice@lab.local"), ("memberOf", "cn=TAs,ou=groups,dc=lab,dc=local")])
+            .user("bob", "bob-pw", &[("mail", "bob@lab.local")])
+            .entry("uid=carol,ou=people,dc=lab,dc=local", Some("carol-pw"), &[("objectClass", "inetOrgPerson"), ("uid", "carol")])
+            .entry("uid=carol,ou=staff,dc=lab,dc=local", Some("carol-pw"), &[("objectClass", "inetOrgPerson"), ("uid", "carol")])
+            .entry(
+                &format!("cn=lab-admins,ou=groups,{}", BASE_DN),
+                None,
+                &[("objectClass", "groupOfNames"), ("cn", "lab-admins"), ("member", "uid=bob,ou=people,dc=lab,dc=local")],
+            )
+            .entry(
+                &format!("cn=readers,ou=groups,{}", BASE_DN),
+                None,
+                &[("objectClass", "posixGroup"), ("cn", "readers"), ("memberUid", "alice"), ("memberUid", "bob")],
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::{lab, search_settings, BASE_DN};
+    use super::*;
+
+    #[tokio::test]
+    async fn binds_as_a_user_found_by_search() {
+        let (ldap, directory) = lab().client(search_settings());
+        let identity = ldap.authenticate("alice", "alice-pw").await.unwrap().unwrap();
+        assert_eq!(identity.issuer, "ldap://ldap.lab.local");
+        assert_eq!(identity.subject, "uid=alice,ou=people,dc=lab,dc=local");
+        assert_eq!(identity.username.as_deref(), Some("alice"));
+        assert_eq!(identity.email.as_deref(), Some("alice@lab.local"));
+        assert!(!identity.email_verified);
+        assert_eq!(
+            directory.log(),
+            [
+                "bind cn=hackademy,ou=services,dc=lab,dc=local".to_string(),
+                format!("search {} (uid=alice)", BASE_DN),
+                "bind uid=alice,ou=people,dc=lab,dc=local".to_string(),
+                "search ou=groups,dc=lab,dc=local (|(member=uid=alice,ou=people,dc=lab,dc=local)(uniqueMember=uid=alice,ou=people,dc=lab,dc=local)(memberUid=alice))".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn vouches_for_mail_only_when_told_to() {
+        let (ldap, _) = lab().client(search_settings());
+        assert!(!ldap.authenticate("alice", "alice-pw").await.unwrap().unwrap().email_verified);
+
+        let (ldap, _) = lab().client(LdapSettings { trust_mail: true, ..search_settings() });
+        let identity = ldap.authenticate("alice", "alice-pw").await.unwrap().unwrap();
+        assert!(identity.email_verified);
+    }
+
+    #[tokio::test]
+    async fn binds_through_a_dn_template() {
+        let settings = LdapSettings { user_dn: Some(format!("uid={{username}},ou=people,{}", BASE_DN)), ..search_settings() };
+        let (ldap, directory) = lab().client(settings);
+        let identity = ldap.authenticate("Alice", "alice-pw").await.unwrap().unwrap();
+        // The entry's spelling of the username, and the DN in lowercase
+        assert_eq!(identity.username.as_deref(), Some("alice"));
+        assert_eq!(identity.subject, "uid=alice,ou=people,dc=lab,dc=local");
+        assert_eq!(directory.log()[0], "bind uid=Alice,ou=people,dc=lab,dc=local");
+    }
+
+    #[tokio::test]
+    async fn refuses_invalid_credentials() {
+        let (ldap, _) = lab().client(search_settings());
+        assert!(ldap.authenticate("alice", "wrong").await.unwrap().is_none());
+        assert!(ldap.authenticate("nobody", "alice-pw").await.unwrap().is_none());
+
+        let settings = LdapSettings { user_dn: Some(format!("uid={{username}},ou=people,{}", BASE_DN)), ..search_settings() };
+        let (ldap, _) = lab().client(settings);
+        assert!(ldap.authenticate("alice", "wrong").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn never_binds_with_an_empty_password() {
+        let (ldap, directory) = lab().client(search_settings());
+        assert!(ldap.authenticate("alice", "").await.unwrap().is_none());
+        assert!(ldap.authenticate("", "alice-pw").await.unwrap().is_none());
+        assert!(directory.log().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_username_without_exactly_one_entry() {
+        let (ldap, directory) = lab().client(search_settings());
+        assert!(ldap.authenticate("dave", "dave-pw").await.unwrap().is_none());
+        assert!(ldap.authenticate("carol", "carol-pw").await.unwrap().is_none());
+        // Neither carol was bound to
+        assert!(!directory.log().iter().any(|l| l.starts_with("bind uid=carol")), "{:?}", directory.log());
+    }
+
+    #[tokio::test]
+    async fn escapes_the_username_in_filters_and_dns() {
+        let (ldap, directory) = lab().client(search_settings());
+        assert!(ldap.authenticate("*", "alice-pw").await.unwrap().is_none());
+        assert!(ldap.authenticate("alice)(uid=*", "alice-pw").await.unwrap().is_none());
+        assert!(ldap.authenticate("al\\ice", "alice-pw").await.unwrap().is_none());
+        let searches: Vec<String> = directory.log().into_iter().filter(|l| l.starts_with("search")).collect();
+        assert_eq!(
+            searches,
+            [
+                format!("search {} (uid=\\2a)", BASE_DN),
+                format!("search {} (uid=alice\\29\\28uid=\\2a)", BASE_DN),
+                format!("search {} (uid=al\\5cice)", BASE_DN),
+            ]
+        );
+
+        let settings = LdapSettings { user_dn: Some(format!("uid={{username}},ou=people,{}", BASE_DN)), ..search_settings() };
+        let (ldap, directory) = lab().client(settings);
+        assert!(ldap.authenticate("bob,ou=people", "bob-pw").await.unwrap().is_none());
+        assert_eq!(directory.log(), ["bind uid=bob\\2cou\\3dpeople,ou=people,dc=lab,dc=local"]);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_search_when_the_service_account_is_refused() {
+        let settings = LdapSettings { bind_password: "wrong".to_string(), ..search_settings() };
+        let (ldap, _) = lab().client(settings);
+        let error = ldap.authenticate("alice", "alice-pw").await.unwrap_err();
+        assert!(error.to_string().contains("LDAP_BIND_DN"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn maps_groups_to_roles() {
+        let (ldap, _) = lab().client(search_settings());
+        // memberOf, matched by cn ignoring case
+        assert_eq!(ldap.authenticate("alice", "alice-pw").await.unwrap().unwrap().role, Some("instructor"));
+        // A group search listing the user's DN
+        assert_eq!(ldap.authenticate("bob", "bob-pw").await.unwrap().unwrap().role, Some("admin"));
+
+        // Groups that aren't mapped make a student
+        let settings = LdapSettings { role_map: vec![("other".to_string(), "admin".to_string())], ..search_settings() };
+        let (ldap, _) = lab().client(settings);
+        assert_eq!(ldap.authenticate("bob", "bob-pw").await.unwrap().unwrap().role, Some("student"));
+
+        // Without a map, roles are left alone
+        let settings = LdapSettings { role_map: Vec::new(), ..search_settings() };
+        let (ldap, _) = lab().client(settings);
+        assert_eq!(ldap.authenticate("bob", "bob-pw").await.unwrap().unwrap().role, None);
+    }
+}
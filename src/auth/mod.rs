@@ -0,0 +1,193 @@
+// How users prove who they are. Usernames and passwords from the login form
+// go to the configured providers in turn (see `providers`); single sign-on
+// goes through an OpenID Connect provider (see `oidc`). Accounts from outside
+// (an OpenID Connect provider, an LDAP directory) are linked to local users
+// in user_identities, and a local user is created the first time one signs in.
+
+pub mod ldap;
+pub mod oidc;
+pub mod providers;
+
+use anyhow::{bail, Result};
+use nanoid::nanoid;
+use sqlx::{Pool, Sqlite};
+
+use crate::models::{User, ROLE_ADMIN, ROLE_INSTRUCTOR, ROLE_STUDENT};
+
+const MAX_USERNAME_CHARS: usize = 32;
+
+// Someone an outside provider vouches for
+#[derive(Debug)]
+pub struct Identity {
+    // The provider: an OpenID Connect issuer or an LDAP URL
+    pub issuer: String,
+    // Their ID at the provider: a subject or a DN
+    pub subject: String,
+    // A username to offer a new account
+    pub username: Option<String>,
+    pub email: Option<String>,
+    // Whether the provider checked the email belongs to them
+    pub email_verified: bool,
+    // From the role mapping, if one is configured
+    pub role: Option<&'static str>,
+    // The signed-in user linking this account to theirs, if any
+    pub link_user_id: Option<String>,
+}
+
+// The strongest of some roles (admin, then instructor), student if none
+pub fn strongest_role<'a>(roles: impl IntoIterator<Item = &'a str>) -> &'static str {
+    let roles: Vec<&str> = roles.into_iter().collect();
+    if roles.contains(&ROLE_ADMIN) {
+        ROLE_ADMIN
+    } else if roles.contains(&ROLE_INSTRUCTOR) {
+        ROLE_INSTRUCTOR
+    } else {
+        ROLE_STUDENT
+    }
+}
+
+// "value=role, value=role", e.g. "hackademy-admins=admin, lecturers=instructor"
+pub fn parse_role_map(text: &str) -> Result<Vec<(String, String)>> {
+    let mut map = Vec::new();
+    for entry in text.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((value, role)) = entry.rsplit_once('=') else {
+            bail!("\"{}\" should be value=role", entry);
+        };
+        let role = role.trim();
+        if ![ROLE_STUDENT, ROLE_INSTRUCTOR, ROLE_ADMIN].contains(&role) {
+            bail!("unknown role \"{}\"", role);
+        }
+        map.push((value.trim().to_string(), role.to_string()));
+    }
+    Ok(map)
+}
+
+// The strongest role mapped from any of the values (groups, say), and
+// student when none match. None when the map is empty.
+pub fn map_role(map: &[(String, String)], values: &[&str]) -> Option<&'static str> {
+    if map.is_empty() {
+        return None;
+    }
+    let roles = map.iter().filter(|(value, _)| values.contains(&value.as_str())).map(|(_, role)| role.as_str());
+    Some(strongest_role(roles))
+}
+
+// The local user an outside account signs in as: the user it is linked to,
+// the user linking it, the one user with the same verified email, or a new
+// user. With a role mapping, the user's role is set from it. Err says why
+// the sign-in is refused.
+pub async fn sign_in_identity(db: &Pool<Sqlite>, identity: &Identity) -> Result<String, &'static str> {
+    let linked_user_id: Option<String> =
+        sqlx::query_scalar("SELECT user_id FROM user_identities WHERE issuer = ? AND subject = ?")
+            .bind(&identity.issuer)
+            .bind(&identity.subject)
+            .fetch_optional(db)
+            .await
+            .unwrap();
+    let user_id = match (linked_user_id, &identity.link_user_id) {
+        (Some(linked), Some(linking)) if linked != *linking => {
+            return Err("That account is already linked to another user");
+        }
+        (Some(linked), _) => linked,
+        (None, Some(linking)) => {
+            link_identity(db, identity, linking).await;
+            linking.clone()
+        }
+        (None, None) => match verified_email_user(db, identity).await {
+            Some(user_id) => {
+                link_identity(db, identity, &user_id).await;
+                user_id
+            }
+            None => provision_user(db, identity).await,
+        },
+    };
+
+    sqlx::query(
+        "UPDATE user_identities SET last_login_at = CURRENT_TIMESTAMP, email = ?, email_verified = ?
+         WHERE issuer = ? AND subject = ?"
+    )
+        .bind(&identity.email)
+        .bind(identity.email_verified)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .execute(db)
+        .await
+        .unwrap();
+    if let Some(role) = identity.role {
+        sqlx::query("UPDATE users SET role = ? WHERE id = ?")
+            .bind(role)
+            .bind(&user_id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+    Ok(user_id)
+}
+
+// The user whose other accounts have the identity's email, both verified.
+// None when the email isn't verified, or more than one user has it.
+async fn verified_email_user(db: &Pool<Sqlite>, identity: &Identity) -> Option<String> {
+    let email = identity.email.as_deref().filter(|_| identity.email_verified)?;
+    let users: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT user_id FROM user_identities WHERE email_verified = 1 AND lower(email) = lower(?)"
+    )
+    .bind(email)
+    .fetch_all(db)
+    .await
+    .unwrap();
+    let [user_id] = &users[..] else {
+        return None;
+    };
+    Some(user_id.clone())
+}
+
+async fn link_identity(db: &Pool<Sqlite>, identity: &Identity, user_id: &str) {
+    sqlx::query("INSERT INTO user_identities (issuer, subject, user_id, email, email_verified) VALUES (?, ?, ?, ?, ?)")
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(user_id)
+        .bind(&identity.email)
+        .bind(identity.email_verified)
+        .execute(db)
+        .await
+        .unwrap();
+}
+
+// Create a user for an outside account signing in for the first time. A
+// taken username gets a number added rather than signing in as its owner.
+async fn provision_user(db: &Pool<Sqlite>, identity: &Identity) -> String {
+    let base = identity.username.as_deref().map(clean_username).filter(|u| !u.is_empty());
+    let base = base.unwrap_or_else(|| "user".to_string());
+    let mut username = base.clone();
+    let mut n = 1;
+    while sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
+        .bind(&username)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+        .is_some()
+    {
+        n += 1;
+        username = format!("{}-{}", base, n);
+    }
+
+    let user_id = nanoid!();
+    // No password hash: the user can only sign in through the provider
+    sqlx::query("INSERT INTO users (id, username, password_hash, role) VALUES (?, ?, '', ?)")
+        .bind(&user_id)
+        .bind(&username)
+        .bind(identity.role.unwrap_or(ROLE_STUDENT))
+        .execute(db)
+        .await
+        .unwrap();
+    link_identity(db, identity, &user_id).await;
+    user_id
+}
+
+// Letters, digits, '.', '_' and '-' of a provider's username
+fn clean_username(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .take(MAX_USERNAME_CHARS)
+        .collect()
+}
@@ -0,0 +1,706 @@
+// OpenID Connect sign-in, with the authorization code flow and PKCE. The
+// provider's endpoints come from its discovery document, fetched on first
+// use so the server starts even when the provider is down.
+//
+// The ID token is taken straight from the token endpoint, so its signature
+// isn't checked (OpenID Connect Core 3.1.3.7 allows this); its issuer,
+// audience, expiry and nonce are. That relies on TLS, so the issuer and
+// every endpoint it names must be https.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use nanoid::nanoid;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::{map_role, parse_role_map, Identity};
+use crate::utils::config::AppConfig;
+
+// How long a sign-in may take at the provider
+const PENDING_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+// Clock difference tolerated when checking expiry
+const CLOCK_SKEW_SECS: u64 = 60;
+
+pub struct OidcSettings {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    // Shown on the sign-in button, e.g. "University login"
+    pub name: String,
+    // The claim holding groups or roles, e.g. "groups"
+    pub role_claim: String,
+    // (claim value, role); empty leaves roles alone
+    pub role_map: Vec<(String, String)>,
+}
+
+impl OidcSettings {
+    // None unless an issuer and a client ID are configured
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>> {
+        let (Some(issuer), Some(client_id)) = (&config.oidc_issuer, &config.oidc_client_id) else {
+            return Ok(None);
+        };
+        require_https("OIDC_ISSUER", issuer)?;
+        Ok(Some(OidcSettings {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            redirect_url: config.oidc_redirect_url.clone(),
+            scopes: config.oidc_scopes.clone(),
+            name: config.oidc_name.clone(),
+            role_claim: config.oidc_role_claim.clone(),
+            role_map: parse_role_map(config.oidc_role_map.as_deref().unwrap_or(""))
+                .map_err(|e| anyhow!("OIDC_ROLE_MAP: {}", e))?,
+        }))
+    }
+}
+
+// The role the claims map to, if a mapping is configured
+pub fn mapped_role(settings: &OidcSettings, claims: &Map<String, Value>) -> Option<&'static str> {
+    let values: Vec<&str> = match claims.get(&settings.role_claim) {
+        Some(Value::String(s)) => vec![s.as_str()],
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    map_role(&settings.role_map, &values)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TokenResponse {
+    id_token: String,
+    access_token: Option<String>,
+}
+
+// A sign-in sent to the provider and not yet back
+struct Pending {
+    verifier: String,
+    nonce: String,
+    // The signed-in user linking their account, if any
+    link_user_id: Option<String>,
+    started: Instant,
+}
+
+// Sign-in through the provider; disabled when not configured
+#[derive(Clone)]
+pub struct Oidc {
+    settings: Option<Arc<OidcSettings>>,
+    discovery: Arc<Mutex<Option<Arc<Discovery>>>>,
+    // By state
+    pending: Arc<Mutex<HashMap<String, Pending>>>,
+    http: reqwest::Client,
+    // Only the tests' local provider is plain http
+    allow_http: bool,
+}
+
+// Refuses a provider URL the ID token could be tampered with on the way from
+fn require_https(what: &str, url: &str) -> Result<()> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if url.scheme() == "https" => Ok(()),
+        _ => bail!("The {} must be an https URL, not {}", what, url),
+    }
+}
+
+// The S256 code challenge of a PKCE code verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+// The claims of a JWT, unverified
+fn jwt_claims(token: &str) -> Result<Map<String, Value>> {
+    let payload = token.split('.').nth(1).ok_or_else(|| anyhow!("The ID token is not a JWT"))?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('='))?;
+    match serde_json::from_slice(&bytes)? {
+        Value::Object(claims) => Ok(claims),
+        _ => bail!("The ID token's claims are not an object"),
+    }
+}
+
+fn claim<'a>(claims: &'a Map<String, Value>, name: &str) -> Option<&'a str> {
+    claims.get(name).and_then(Value::as_str)
+}
+
+impl Oidc {
+    pub fn new(settings: Option<OidcSettings>) -> Self {
+        Oidc {
+            settings: settings.map(Arc::new),
+            discovery: Arc::new(Mutex::new(None)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            http: reqwest::Client::new(),
+            allow_http: false,
+        }
+    }
+
+    #[cfg(test)]
+    fn allowing_http(mut self) -> Self {
+        self.allow_http = true;
+        self
+    }
+
+    // The provider's name for the sign-in button, None when disabled
+    pub fn name(&self) -> Option<&str> {
+        self.settings.as_ref().map(|s| s.name.as_str())
+    }
+
+    fn settings(&self) -> Result<&OidcSettings> {
+        self.settings.as_deref().ok_or_else(|| anyhow!("Single sign-on is not configured"))
+    }
+
+    async fn discovery(&self) -> Result<Arc<Discovery>> {
+        if let Some(discovery) = self.discovery.lock().unwrap().as_ref() {
+            return Ok(discovery.clone());
+        }
+        let settings = self.settings()?;
+        if !self.allow_http {
+            require_https("issuer", &settings.issuer)?;
+        }
+        let url = format!("{}/.well-known/openid-configuration", settings.issuer);
+        let discovery: Discovery = self.http.get(&url).send().await?.error_for_status()?.json().await?;
+        if discovery.issuer.trim_end_matches('/') != settings.issuer {
+            bail!("The provider says its issuer is {}, not {}", discovery.issuer, settings.issuer);
+        }
+        if !self.allow_http {
+            require_https("authorization endpoint", &discovery.authorization_endpoint)?;
+            require_https("token endpoint", &discovery.token_endpoint)?;
+            if let Some(endpoint) = &discovery.userinfo_endpoint {
+                require_https("userinfo endpoint", endpoint)?;
+            }
+        }
+        let discovery = Arc::new(discovery);
+        *self.discovery.lock().unwrap() = Some(discovery.clone());
+        Ok(discovery)
+    }
+
+    // Where to send the browser to sign in, and the state the provider will
+    // send back. The nonce and PKCE verifier are kept for the callback.
+    pub async fn authorization_url(&self, link_user_id: Option<String>) -> Result<(String, String)> {
+        let settings = self.settings()?;
+        let discovery = self.discovery().await?;
+        let (state, nonce, verifier) = (nanoid!(32), nanoid!(32), nanoid!(64));
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", settings.client_id.as_str()),
+            ("redirect_uri", settings.redirect_url.as_str()),
+            ("scope", settings.scopes.as_str()),
+            ("state", state.as_str()),
+            ("nonce", nonce.as_str()),
+            ("code_challenge", pkce_challenge(&verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ])?;
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.started.elapsed() < PENDING_MAX_AGE);
+        pending.insert(state.clone(), Pending { verifier, nonce, link_user_id, started: Instant::now() });
+        let separator = if discovery.authorization_endpoint.contains('?') { '&' } else { '?' };
+        Ok((format!("{}{}{}", discovery.authorization_endpoint, separator, query), state))
+    }
+
+    // Redeem the code the provider sent back and check the ID token
+    pub async fn finish(&self, code: &str, state: &str) -> Result<Identity> {
+        let settings = self.settings()?;
+        let pending = self.pending.lock().unwrap().remove(state);
+        let pending = pending
+            .filter(|p| p.started.elapsed() < PENDING_MAX_AGE)
+            .ok_or_else(|| anyhow!("This sign-in has expired or was already used; please try again"))?;
+        let discovery = self.discovery().await?;
+
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", settings.redirect_url.as_str()),
+            ("client_id", settings.client_id.as_str()),
+            ("code_verifier", pending.verifier.as_str()),
+        ];
+        let mut request = self.http.post(&discovery.token_endpoint).form(&form);
+        if let Some(secret) = &settings.client_secret {
+            request = request.basic_auth(&settings.client_id, Some(secret));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            bail!("The provider refused the code: {}", response.text().await.unwrap_or_default());
+        }
+        let tokens: TokenResponse = response.json().await?;
+
+        let mut claims = jwt_claims(&tokens.id_token)?;
+        check_id_token(&claims, &discovery.issuer, &settings.client_id, &pending.nonce)?;
+        // Groups are often only in the userinfo response
+        if let (Some(endpoint), Some(access_token)) = (&discovery.userinfo_endpoint, &tokens.access_token) {
+            let userinfo: Map<String, Value> =
+                self.http.get(endpoint).bearer_auth(access_token).send().await?.error_for_status()?.json().await?;
+            if claim(&userinfo, "sub") == claim(&claims, "sub") {
+                for (name, value) in userinfo {
+                    claims.entry(name).or_insert(value);
+                }
+            }
+        }
+
+        let subject = claim(&claims, "sub").ok_or_else(|| anyhow!("The ID token has no subject"))?.to_string();
+        let email = claim(&claims, "email").map(String::from);
+        // Some providers send "true" as a string
+        let email_verified = matches!(claims.get("email_verified"), Some(Value::Bool(true)))
+            || claim(&claims, "email_verified") == Some("true");
+        let username = claim(&claims, "preferred_username")
+            .or_else(|| email.as_deref().and_then(|e| e.split('@').next()))
+            .map(String::from);
+        Ok(Identity {
+            issuer: settings.issuer.clone(),
+            subject,
+            username,
+            email,
+            email_verified,
+            role: mapped_role(settings, &claims),
+            link_user_id: pending.link_user_id,
+        })
+    }
+}
+
+fn check_id_token(claims: &Map<String, Value>, issuer: &str, client_id: &str, nonce: &str) -> Result<()> {
+    if claim(claims, "iss").map(|i| i.trim_end_matches('/')) != Some(issuer.trim_end_matches('/')) {
+        bail!("The ID token is from another issuer");
+    }
+    let audience: Vec<&str> = match claims.get("aud") {
+        Some(Value::String(aud)) => vec![aud.as_str()],
+        Some(Value::Array(auds)) => auds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !audience.contains(&client_id) || (audience.len() > 1 && claim(claims, "azp") != Some(client_id)) {
+        bail!("The ID token is for another client");
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    match claims.get("exp").and_then(Value::as_u64) {
+        Some(exp) if exp + CLOCK_SKEW_SECS > now => {}
+        _ => bail!("The ID token has expired"),
+    }
+    if claim(claims, "nonce") != Some(nonce) {
+        bail!("The ID token's nonce doesn't match");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::sign_in_identity;
+    use crate::db::test_db;
+    use poem::http::StatusCode;
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::web::{Data, Form, Json};
+    use poem::{get, handler, post, EndpointExt, IntoResponse, Request, Response, Route, Server};
+    use serde_json::json;
+    use sqlx::{Pool, Sqlite};
+
+    const CLIENT_ID: &str = "hackademy";
+    const CODE: &str = "the-code";
+    const ACCESS_TOKEN: &str = "the-access-token";
+
+    // What the mock provider hands out; each test sets the claims
+    #[derive(Default)]
+    struct Provider {
+        issuer: String,
+        // The code challenge of the sign-in under way
+        challenge: Option<String>,
+        claims: Map<String, Value>,
+        userinfo: Map<String, Value>,
+    }
+
+    type Shared = Arc<Mutex<Provider>>;
+
+    #[handler]
+    fn discovery(provider: Data<&Shared>) -> Json<Value> {
+        let issuer = provider.lock().unwrap().issuer.clone();
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "userinfo_endpoint": format!("{}/userinfo", issuer),
+        }))
+    }
+
+    // Redeems the code only with the verifier of the challenge it was given
+    #[handler]
+    fn token(provider: Data<&Shared>, Form(form): Form<HashMap<String, String>>) -> Response {
+        let provider = provider.lock().unwrap();
+        let field = |name: &str| form.get(name).map(String::as_str);
+        let verified = field("code_verifier").map(pkce_challenge) == provider.challenge;
+        if field("grant_type") != Some("authorization_code") || field("code") != Some(CODE) || !verified {
+            return Response::builder().status(StatusCode::BAD_REQUEST).body(r#"{"error":"invalid_grant"}"#);
+        }
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(Value::Object(provider.claims.clone()).to_string());
+        Json(json!({
+            "id_token": format!("{}.{}.", header, payload),
+            "access_token": ACCESS_TOKEN,
+            "token_type": "Bearer",
+        }))
+        .into_response()
+    }
+
+    #[handler]
+    fn userinfo(req: &Request, provider: Data<&Shared>) -> Response {
+        let expected = format!("Bearer {}", ACCESS_TOKEN);
+        if req.header("Authorization") != Some(expected.as_str()) {
+            return Response::builder().status(StatusCode::UNAUTHORIZED).finish();
+        }
+        Json(Value::Object(provider.lock().unwrap().userinfo.clone())).into_response()
+    }
+
+    // A provider listening on a free local port
+    async fn start_provider() -> Shared {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        let provider = Arc::new(Mutex::new(Provider { issuer: format!("http://{}", addr), ..Default::default() }));
+        let app = Route::new()
+            .at("/.well-known/openid-configuration", get(discovery))
+            .at("/token", post(token))
+            .at("/userinfo", get(userinfo))
+            .data(provider.clone());
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        provider
+    }
+
+    fn settings(issuer: &str) -> OidcSettings {
+        OidcSettings {
+            issuer: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost:3000/auth/oidc/callback".to_string(),
+            scopes: "openid profile email".to_string(),
+            name: "University login".to_string(),
+            role_claim: "groups".to_string(),
+            role_map: parse_role_map("hackademy-admins=admin, lecturers=instructor").unwrap(),
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    // An ID token the client should accept
+    fn good_claims(issuer: &str, nonce: &str) -> Map<String, Value> {
+        let Value::Object(claims) = json!({
+            "iss": issuer,
+            "sub": "u-1001",
+            "aud": CLIENT_ID,
+            "exp": now() + 300,
+            "iat": now(),
+            "nonce": nonce,
+            "email": "alice@uni.example",
+            "email_verified": true,
+            "preferred_username": "alice",
+        }) else {
+            unreachable!()
+        };
+        claims
+    }
+
+    // Start a sign-in, have the provider issue the claims `edit` makes of
+    // good ones, and finish it. The client's code challenge is checked on
+    // the way.
+    async fn sign_in(edit: impl FnOnce(&mut Map<String, Value>, &mut Provider)) -> Result<Identity> {
+        let provider = start_provider().await;
+        let issuer = provider.lock().unwrap().issuer.clone();
+        let oidc = Oidc::new(Some(settings(&issuer))).allowing_http();
+        let (url, state) = oidc.authorization_url(None).await?;
+
+        let (endpoint, query) = url.split_once('?').unwrap();
+        assert_eq!(endpoint, format!("{}/authorize", issuer));
+        let query: HashMap<String, String> = serde_urlencoded::from_str(query)?;
+        assert_eq!(query["response_type"], "code");
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert_eq!(query["state"], state);
+        assert_eq!(query["code_challenge_method"], "S256");
+        {
+            let mut provider = provider.lock().unwrap();
+            let mut claims = good_claims(&issuer, &query["nonce"]);
+            provider.challenge = Some(query["code_challenge"].clone());
+            edit(&mut claims, &mut provider);
+            provider.claims = claims;
+        }
+        oidc.finish(CODE, &state).await
+    }
+
+    #[test]
+    fn pkce_challenge_is_s256() {
+        // RFC 7636, appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_an_http_issuer() {
+        let provider = start_provider().await;
+        let issuer = provider.lock().unwrap().issuer.clone();
+        let error = Oidc::new(Some(settings(&issuer))).authorization_url(None).await.unwrap_err();
+        assert!(error.to_string().contains("issuer must be an https URL"), "{}", error);
+
+        let config = AppConfig {
+            oidc_issuer: Some(issuer),
+            oidc_client_id: Some(CLIENT_ID.to_string()),
+            ..AppConfig::from_env()
+        };
+        let error = OidcSettings::from_config(&config).err().unwrap();
+        assert!(error.to_string().contains("OIDC_ISSUER must be an https URL"), "{}", error);
+    }
+
+    #[test]
+    fn refuses_http_endpoints() {
+        assert!(require_https("token endpoint", "https://idp.uni.example/token").is_ok());
+        let error = require_https("token endpoint", "http://idp.uni.example/token").unwrap_err();
+        assert!(error.to_string().contains("token endpoint must be an https URL"), "{}", error);
+        let error = require_https("userinfo endpoint", "HTTP://idp.uni.example/userinfo").unwrap_err();
+        assert!(error.to_string().contains("userinfo endpoint must be an https URL"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn signs_in_with_pkce() {
+        let identity = sign_in(|_, _| {}).await.unwrap();
+        assert_eq!(identity.subject, "u-1001");
+        assert_eq!(identity.username.as_deref(), Some("alice"));
+        assert_eq!(identity.email.as_deref(), Some("alice@uni.example"));
+        assert!(identity.email_verified);
+        assert_eq!(identity.role, Some("student"));
+        assert_eq!(identity.link_user_id, None);
+    }
+
+    #[tokio::test]
+    async fn refuses_a_code_without_the_right_verifier() {
+        let error = sign_in(|_, provider| provider.challenge = Some(pkce_challenge("another verifier")))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("refused the code"), "{}", error);
+    }
+
+    async fn refused(edit: impl FnOnce(&mut Map<String, Value>)) -> String {
+        sign_in(|claims, _| edit(claims)).await.unwrap_err().to_string()
+    }
+
+    #[tokio::test]
+    async fn refuses_another_issuer() {
+        let error = refused(|c| {
+            c.insert("iss".into(), json!("https://evil.example"));
+        })
+        .await;
+        assert!(error.contains("another issuer"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn refuses_another_audience() {
+        let error = refused(|c| {
+            c.insert("aud".into(), json!("someone-else"));
+        })
+        .await;
+        assert!(error.contains("another client"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn refuses_several_audiences_with_another_azp() {
+        let error = refused(|c| {
+            c.insert("aud".into(), json!([CLIENT_ID, "someone-else"]));
+            c.insert("azp".into(), json!("someone-else"));
+        })
+        .await;
+        assert!(error.contains("another client"), "{}", error);
+
+        let identity = sign_in(|c, _| {
+            c.insert("aud".into(), json!([CLIENT_ID, "someone-else"]));
+            c.insert("azp".into(), json!(CLIENT_ID));
+        })
+        .await;
+        assert!(identity.is_ok());
+    }
+
+    #[tokio::test]
+    async fn refuses_another_nonce() {
+        let error = refused(|c| {
+            c.insert("nonce".into(), json!("replayed"));
+        })
+        .await;
+        assert!(error.contains("nonce"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn refuses_an_expired_token() {
+        let error = refused(|c| {
+            c.insert("exp".into(), json!(now() - CLOCK_SKEW_SECS - 1));
+        })
+        .await;
+        assert!(error.contains("expired"), "{}", error);
+
+        let error = refused(|c| {
+            c.remove("exp");
+        })
+        .await;
+        assert!(error.contains("expired"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn refuses_an_unknown_or_reused_state() {
+        let provider = start_provider().await;
+        let issuer = provider.lock().unwrap().issuer.clone();
+        let oidc = Oidc::new(Some(settings(&issuer))).allowing_http();
+        let (url, state) = oidc.authorization_url(None).await.unwrap();
+        let query: HashMap<String, String> = serde_urlencoded::from_str(url.split_once('?').unwrap().1).unwrap();
+        {
+            let mut provider = provider.lock().unwrap();
+            provider.challenge = Some(query["code_challenge"].clone());
+            provider.claims = good_claims(&issuer, &query["nonce"]);
+        }
+
+        let error = oidc.finish(CODE, "made-up").await.unwrap_err();
+        assert!(error.to_string().contains("expired or was already used"), "{}", error);
+        assert!(oidc.finish(CODE, &state).await.is_ok());
+        let error = oidc.finish(CODE, &state).await.unwrap_err();
+        assert!(error.to_string().contains("expired or was already used"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn maps_claims_to_roles() {
+        let identity = sign_in(|c, _| {
+            c.insert("groups".into(), json!(["staff", "lecturers"]));
+        })
+        .await
+        .unwrap();
+        assert_eq!(identity.role, Some("instructor"));
+
+        // The strongest mapped role wins, and a single value is a list of one
+        let identity = sign_in(|c, _| {
+            c.insert("groups".into(), json!(["lecturers", "hackademy-admins"]));
+        })
+        .await
+        .unwrap();
+        assert_eq!(identity.role, Some("admin"));
+        let identity = sign_in(|c, _| {
+            c.insert("groups".into(), json!("lecturers"));
+        })
+        .await
+        .unwrap();
+        assert_eq!(identity.role, Some("instructor"));
+    }
+
+    #[tokio::test]
+    async fn reads_roles_from_userinfo_for_the_same_subject() {
+        let identity = sign_in(|_, provider| {
+            provider.userinfo = good_claims("", "");
+            provider.userinfo.insert("groups".into(), json!(["hackademy-admins"]));
+        })
+        .await
+        .unwrap();
+        assert_eq!(identity.role, Some("admin"));
+
+        let identity = sign_in(|_, provider| {
+            provider.userinfo.insert("sub".into(), json!("someone-else"));
+            provider.userinfo.insert("groups".into(), json!(["hackademy-admins"]));
+        })
+        .await
+        .unwrap();
+        assert_eq!(identity.role, Some("student"));
+    }
+
+    #[tokio::test]
+    async fn sets_the_mapped_role_at_each_sign_in() {
+        let db = test_db().await;
+        let identity = sign_in(|c, _| {
+            c.insert("groups".into(), json!(["lecturers"]));
+        })
+        .await
+        .unwrap();
+        let user_id = sign_in_identity(&db, &identity).await.unwrap();
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(&user_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(role, "instructor");
+
+        let identity = sign_in(|_, _| {}).await.unwrap();
+        assert_eq!(sign_in_identity(&db, &identity).await.unwrap(), user_id);
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(&user_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(role, "student");
+    }
+
+    // A local user whose directory account has alice's email
+    async fn user_with_email(db: &Pool<Sqlite>, verified: bool) -> String {
+        sqlx::query("INSERT INTO users (id, username, password_hash, role) VALUES ('u-alice', 'alice', 'hash', 'student')")
+            .execute(db)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO user_identities (issuer, subject, user_id, email, email_verified)
+             VALUES ('ldap://ldap.lab.local', 'uid=alice,ou=people,dc=lab,dc=local', 'u-alice', 'Alice@Uni.example', ?)"
+        )
+        .bind(verified)
+        .execute(db)
+        .await
+        .unwrap();
+        "u-alice".to_string()
+    }
+
+    #[tokio::test]
+    async fn links_to_the_user_with_the_same_verified_email() {
+        let db = test_db().await;
+        let alice = user_with_email(&db, true).await;
+        let identity = sign_in(|_, _| {}).await.unwrap();
+        assert_eq!(sign_in_identity(&db, &identity).await.unwrap(), alice);
+        let linked: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_identities WHERE user_id = ?")
+            .bind(&alice)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(linked, 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_link_by_unverified_email() {
+        let db = test_db().await;
+        let alice = user_with_email(&db, true).await;
+        let identity = sign_in(|c, _| {
+            c.insert("email_verified".into(), json!(false));
+        })
+        .await
+        .unwrap();
+        let user_id = sign_in_identity(&db, &identity).await.unwrap();
+        assert_ne!(user_id, alice);
+        // Named after the account, without taking alice's username
+        let username: String = sqlx::query_scalar("SELECT username FROM users WHERE id = ?")
+            .bind(&user_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(username, "alice-2");
+
+        // Nor to a user whose own email wasn't verified
+        let db = test_db().await;
+        let alice = user_with_email(&db, false).await;
+        let identity = sign_in(|_, _| {}).await.unwrap();
+        assert_ne!(sign_in_identity(&db, &identity).await.unwrap(), alice);
+    }
+
+    #[tokio::test]
+    async fn accepts_email_verified_as_a_string() {
+        let identity = sign_in(|c, _| {
+            c.insert("email_verified".into(), json!("true"));
+        })
+        .await
+        .unwrap();
+        assert!(identity.email_verified);
+    }
+}
@@ -0,0 +1,225 @@
+// The providers that check usernames and passwords from the login form,
+// tried in the order AUTH_PROVIDERS lists them:
+//  - local: the password hashes in the users table
+//  - ldap: a bind to an LDAP directory (see `ldap`)
+// The first provider that knows the username and password signs the user in.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use sqlx::{Pool, Sqlite};
+
+use super::ldap::{Ldap, LdapSettings};
+use super::sign_in_identity;
+use crate::models::User;
+use crate::utils::config::AppConfig;
+use crate::utils::security::verify_password;
+
+pub enum AuthProvider {
+    Local,
+    Ldap(Box<Ldap>),
+}
+
+impl AuthProvider {
+    // The user the username and password sign in as; None when this provider
+    // doesn't know them, Err when it can't be asked or refuses the sign-in
+    async fn authenticate(&self, db: &Pool<Sqlite>, username: &str, password: &str) -> Result<Option<String>> {
+        match self {
+            AuthProvider::Local => {
+                let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
+                    .bind(username)
+                    .fetch_optional(db)
+                    .await
+                    .unwrap();
+                Ok(user.filter(|u| verify_password(password, &u.password_hash)).map(|u| u.id))
+            }
+            AuthProvider::Ldap(ldap) => {
+                let Some(identity) = ldap.authenticate(username, password).await? else {
+                    return Ok(None);
+                };
+                sign_in_identity(db, &identity).await.map(Some).map_err(|e| anyhow!(e))
+            }
+        }
+    }
+}
+
+// Why a login failed
+pub enum LoginError {
+    Invalid,
+    // A provider couldn't be asked; the details go to the log
+    Unavailable,
+}
+
+impl LoginError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            LoginError::Invalid => "Invalid username or password",
+            LoginError::Unavailable => "Signing in isn't working right now; please try again later",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthProviders {
+    providers: Arc<Vec<AuthProvider>>,
+}
+
+impl AuthProviders {
+    // AUTH_PROVIDERS, e.g. "local,ldap"; by default local, then LDAP when
+    // LDAP_URL is set
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        let mut ldap = LdapSettings::from_config(config)?;
+        let names = match &config.auth_providers {
+            Some(names) => names.clone(),
+            None if ldap.is_some() => "local,ldap".to_string(),
+            None => "local".to_string(),
+        };
+        let mut providers = Vec::new();
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match name {
+                "local" => providers.push(AuthProvider::Local),
+                "ldap" => match ldap.take() {
+                    Some(settings) => providers.push(AuthProvider::Ldap(Box::new(Ldap::new(settings)))),
+                    None => bail!("AUTH_PROVIDERS: ldap is listed once at most, and needs LDAP_URL"),
+                },
+                _ => bail!("AUTH_PROVIDERS: unknown provider \"{}\"", name),
+            }
+        }
+        Ok(AuthProviders { providers: Arc::new(providers) })
+    }
+
+    // The user the username and password sign in as
+    pub async fn login(&self, db: &Pool<Sqlite>, username: &str, password: &str) -> Result<String, LoginError> {
+        let mut error = LoginError::Invalid;
+        for provider in self.providers.iter() {
+            match provider.authenticate(db, username, password).await {
+                Ok(Some(user_id)) => return Ok(user_id),
+                Ok(None) => {}
+                Err(e) => {
+                    println!("Login for {} failed: {:#}", username, e);
+                    error = LoginError::Unavailable;
+                }
+            }
+        }
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::auth::ldap::fake::{lab, search_settings};
+    use crate::db::test_db;
+    use crate::utils::security::hash_password;
+
+    // A local alice whose password differs from the directory's
+    async fn local_alice(db: &Pool<Sqlite>) {
+        sqlx::query("INSERT INTO users (id, username, password_hash, role) VALUES ('u-alice', 'alice', ?, 'student')")
+            .bind(hash_password("local-pw").unwrap())
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    fn providers(providers: Vec<AuthProvider>) -> AuthProviders {
+        AuthProviders { providers: Arc::new(providers) }
+    }
+
+    fn directory() -> AuthProvider {
+        AuthProvider::Ldap(Box::new(lab().client(search_settings()).0))
+    }
+
+    // A directory nothing listens for
+    fn unreachable() -> AuthProvider {
+        let settings = LdapSettings { url: "ldap://127.0.0.1:1".to_string(), timeout: Duration::from_secs(2), ..search_settings() };
+        AuthProvider::Ldap(Box::new(Ldap::new(settings)))
+    }
+
+    async fn username(db: &Pool<Sqlite>, user_id: &str) -> String {
+        sqlx::query_scalar("SELECT username FROM users WHERE id = ?").bind(user_id).fetch_one(db).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn tries_local_passwords_then_the_directory() {
+        let db = test_db().await;
+        local_alice(&db).await;
+        let auth = providers(vec![AuthProvider::Local, directory()]);
+
+        assert_eq!(auth.login(&db, "alice", "local-pw").await.ok().as_deref(), Some("u-alice"));
+
+        // The directory's alice isn't the local one, so she gets a username of her own
+        let user_id = auth.login(&db, "alice", "alice-pw").await.ok().unwrap();
+        assert_ne!(user_id, "u-alice");
+        assert_eq!(username(&db, &user_id).await, "alice-2");
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?").bind(&user_id).fetch_one(&db).await.unwrap();
+        assert_eq!(role, "instructor");
+
+        // Signing in again finds the same user by the identity
+        assert_eq!(auth.login(&db, "alice", "alice-pw").await.ok(), Some(user_id));
+
+        let user_id = auth.login(&db, "bob", "bob-pw").await.ok().unwrap();
+        assert_eq!(username(&db, &user_id).await, "bob");
+
+        assert!(matches!(auth.login(&db, "alice", "wrong").await, Err(LoginError::Invalid)));
+        assert!(matches!(auth.login(&db, "nobody", "local-pw").await, Err(LoginError::Invalid)));
+    }
+
+    // Alice's account was made through another provider that verified her
+    // email
+    async fn alice_verified_elsewhere(db: &Pool<Sqlite>) {
+        local_alice(db).await;
+        sqlx::query(
+            "INSERT INTO user_identities (issuer, subject, user_id, email, email_verified)
+             VALUES ('https://idp.lab.local', 'alice-sub', 'u-alice', 'alice@lab.local', 1)"
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn links_by_directory_mail_only_when_trusted() {
+        // Anyone who can set their own mail attribute could claim alice's
+        // account, so by default the directory's alice is someone else
+        let db = test_db().await;
+        alice_verified_elsewhere(&db).await;
+        let auth = providers(vec![directory()]);
+        let user_id = auth.login(&db, "alice", "alice-pw").await.ok().unwrap();
+        assert_ne!(user_id, "u-alice");
+        assert_eq!(username(&db, &user_id).await, "alice-2");
+
+        let db = test_db().await;
+        alice_verified_elsewhere(&db).await;
+        let trusted = lab().client(LdapSettings { trust_mail: true, ..search_settings() }).0;
+        let auth = providers(vec![AuthProvider::Ldap(Box::new(trusted))]);
+        assert_eq!(auth.login(&db, "alice", "alice-pw").await.ok().as_deref(), Some("u-alice"));
+    }
+
+    #[tokio::test]
+    async fn tries_the_directory_first_when_listed_first() {
+        let db = test_db().await;
+        local_alice(&db).await;
+        let auth = providers(vec![directory(), AuthProvider::Local]);
+
+        let user_id = auth.login(&db, "alice", "alice-pw").await.ok().unwrap();
+        assert_eq!(username(&db, &user_id).await, "alice-2");
+        assert_eq!(auth.login(&db, "alice", "local-pw").await.ok().as_deref(), Some("u-alice"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_the_directory_is_unavailable() {
+        let db = test_db().await;
+        local_alice(&db).await;
+
+        let auth = providers(vec![unreachable(), AuthProvider::Local]);
+        assert_eq!(auth.login(&db, "alice", "local-pw").await.ok().as_deref(), Some("u-alice"));
+        // A password nothing accepted can't be called wrong while the directory is down
+        assert!(matches!(auth.login(&db, "alice", "alice-pw").await, Err(LoginError::Unavailable)));
+
+        let auth = providers(vec![AuthProvider::Local, unreachable()]);
+        assert_eq!(auth.login(&db, "alice", "local-pw").await.ok().as_deref(), Some("u-alice"));
+        assert!(matches!(auth.login(&db, "alice", "wrong").await, Err(LoginError::Unavailable)));
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use sqlx::{Pool, Sqlite};
+
+use crate::content::{
+    export::export_bank,
+    import::import_bank,
+    lint::{lint_path, Severity},
+    parse_bank, render_bank, Format,
+};
+
+const USAGE: &str = "usage:
+  hackademy                              start the web server
+  hackademy import <file> [--dry-run]    import a question bank
+  hackademy export <file>                export all content
+  hackademy lint <file|dir> [--json] [--strict]
+                                         check a bank or content directory
+
+The file format follows the extension: .json, .yaml/.yml, .gift/.txt
+(Moodle GIFT), .xml (Moodle XML), .csv or .zip (IMS QTI 2.1 package; 3.0
+packages are read too). Pass --format <name> to override it, e.g.
+--format qti3 to export QTI 3.0.
+
+lint exits non-zero when it finds errors, or any warning with --strict,
+and prints its findings as JSON with --json.";
+
+pub async fn run(args: &[String], db: &Pool<Sqlite>) -> Result<()> {
+    match args[0].as_str() {
+        "import" => {
+            let path = args.get(1).ok_or_else(|| anyhow!(USAGE))?;
+            let dry_run = args.iter().any(|a| a == "--dry-run");
+            let input = std::fs::read(path)?;
+            let parsed = parse_bank(&input, format_for(args, path)?);
+
+            let report = import_bank(db, &parsed, dry_run).await?;
+            println!("{}", report);
+            if !report.errors.is_empty() {
+                bail!("{} problem(s) found in {}, nothing imported", report.errors.len(), path);
+            }
+            Ok(())
+        }
+        "export" => {
+            let path = args.get(1).ok_or_else(|| anyhow!(USAGE))?;
+            let bank = export_bank(db).await?;
+            std::fs::write(path, render_bank(&bank, format_for(args, path)?)?)?;
+            println!("Exported {} categories to {}", bank.categories.len(), path);
+            Ok(())
+        }
+        _ => bail!(USAGE),
+    }
+}
+
+// --format <name> if given, else the file's extension
+fn format_for(args: &[String], path: &str) -> Result<Format> {
+    match args.iter().position(|a| a == "--format") {
+        Some(i) => Format::from_name(args.get(i + 1).ok_or_else(|| anyhow!(USAGE))?),
+        None => Format::from_path(path),
+    }
+}
+
+// Works on files alone, so it runs without a database
+pub fn lint(args: &[String]) -> Result<()> {
+    let path = args.get(1).filter(|a| !a.starts_with("--")).ok_or_else(|| anyhow!(USAGE))?;
+    let format = match args.iter().position(|a| a == "--format") {
+        Some(i) => Some(Format::from_name(args.get(i + 1).ok_or_else(|| anyhow!(USAGE))?)?),
+        None => None,
+    };
+    let report = lint_path(Path::new(path), format)?;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", report);
+    }
+
+    let strict = args.iter().any(|a| a == "--strict");
+    if report.count(Severity::Error) > 0 || (strict && report.count(Severity::Warning) > 0) {
+        bail!("{} did not pass lint", path);
+    }
+    Ok(())
+}
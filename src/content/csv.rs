@@ -0,0 +1,186 @@
+// Spreadsheet layout, one question per row:
+//
+//   id,category,subcategory,kind,question_text,option_a,option_b,...,answer,explanation,tags
+//
+// `category`, `question_text`, `answer` and at least two option columns are
+// required; `answer` is the letter of the correct option column, or several
+// letters separated by semicolons for a multiple_response question. For
+// text_entry (options are the accepted answers) and order (options in the
+// right order) questions `answer` is left blank. `kind` defaults to choice,
+// `id` may be left blank (one is derived from the question text), and `tags`
+// is a semicolon-separated list. Column order does not matter.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::schema::{ContentBank, QuestionDoc};
+use super::{question_id_for, ParsedBank};
+use crate::models::{KIND_CHOICE, KIND_MULTIPLE_RESPONSE};
+
+const REQUIRED: [&str; 3] = ["category", "question_text", "answer"];
+
+pub fn parse(input: &str) -> ParsedBank {
+    let mut parsed = ParsedBank::default();
+    let mut reader = ::csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(::csv::Trim::All)
+        .from_reader(input.as_bytes());
+
+    let headers: Vec<String> = match reader.headers() {
+        Ok(h) => h.iter().map(|h| h.to_ascii_lowercase()).collect(),
+        Err(e) => {
+            parsed.errors.push(format!("line 1: {}", e));
+            return parsed;
+        }
+    };
+    let columns: HashMap<&str, usize> = headers.iter().enumerate().map(|(i, h)| (h.as_str(), i)).collect();
+    for name in REQUIRED {
+        if !columns.contains_key(name) {
+            parsed.errors.push(format!("line 1: missing required column '{}'", name));
+        }
+    }
+    // option_a, option_b, ... in letter order
+    let mut option_columns: Vec<(char, usize)> = headers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| {
+            let letter = h.strip_prefix("option_")?;
+            let mut chars = letter.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_lowercase() => Some((c, i)),
+                _ => None,
+            }
+        })
+        .collect();
+    option_columns.sort();
+    if option_columns.len() < 2 {
+        parsed.errors.push("line 1: need at least two option columns (option_a, option_b, ...)".to_string());
+    }
+    if !parsed.errors.is_empty() {
+        return parsed;
+    }
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                parsed.errors.push(e.to_string());
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let field = |name: &str| columns.get(name).and_then(|i| record.get(*i)).unwrap_or("");
+
+        if record.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+
+        // Keep the letters of non-blank options so the answer can be mapped
+        let options: Vec<(char, String)> = option_columns
+            .iter()
+            .filter_map(|(letter, i)| {
+                let text = record.get(*i).unwrap_or("");
+                (!text.is_empty()).then(|| (*letter, text.to_string()))
+            })
+            .collect();
+
+        let kind = match field("kind") {
+            "" => KIND_CHOICE,
+            kind => kind,
+        };
+        let answer = field("answer").to_ascii_lowercase();
+        let letters: Vec<&str> = match kind {
+            KIND_CHOICE => vec![answer.as_str()],
+            KIND_MULTIPLE_RESPONSE => answer.split(';').map(str::trim).collect(),
+            _ => Vec::new(),
+        };
+        let correct: Option<Vec<i64>> = letters
+            .iter()
+            .map(|letter| {
+                let mut chars = letter.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => options.iter().position(|(l, _)| *l == c).map(|i| i as i64),
+                    _ => None,
+                }
+            })
+            .collect();
+        let Some(correct) = correct else {
+            parsed.errors.push(format!(
+                "line {}: answer '{}' does not name a filled-in option column",
+                line,
+                field("answer")
+            ));
+            continue;
+        };
+
+        let question_text = field("question_text").to_string();
+        if question_text.is_empty() || field("category").is_empty() {
+            parsed.errors.push(format!("line {}: category and question_text are required", line));
+            continue;
+        }
+        let id = match field("id") {
+            "" => question_id_for(&question_text),
+            id => id.to_string(),
+        };
+        let subcategory = Some(field("subcategory")).filter(|s| !s.is_empty());
+
+        parsed.bank.push_question(
+            field("category"),
+            subcategory,
+            QuestionDoc {
+                id,
+                kind: kind.to_string(),
+                question_text,
+                options: options.into_iter().map(|(_, text)| text).collect(),
+                correct_answer_idx: correct.first().copied().unwrap_or(0),
+                correct_answers: if kind == KIND_MULTIPLE_RESPONSE { correct } else { Vec::new() },
+                explanation: Some(field("explanation").to_string()).filter(|s| !s.is_empty()),
+                tags: field("tags")
+                    .split(';')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect(),
+            },
+        );
+    }
+    parsed
+}
+
+pub fn render(bank: &ContentBank) -> Result<String> {
+    let questions = bank.questions();
+    let option_count = questions.iter().map(|(_, _, q)| q.options.len()).max().unwrap_or(2).clamp(2, 26);
+
+    let mut writer = ::csv::Writer::from_writer(Vec::new());
+    let mut header = vec![
+        "id".to_string(),
+        "category".to_string(),
+        "subcategory".to_string(),
+        "kind".to_string(),
+        "question_text".to_string(),
+    ];
+    header.extend((0..option_count).map(|i| format!("option_{}", (b'a' + i as u8) as char)));
+    header.extend(["answer".to_string(), "explanation".to_string(), "tags".to_string()]);
+    writer.write_record(&header)?;
+
+    for (cat, sub, q) in questions {
+        let mut row = vec![
+            q.id.clone(),
+            cat.title.clone(),
+            sub.map(|s| s.title.clone()).unwrap_or_default(),
+            q.kind.clone(),
+            q.question_text.clone(),
+        ];
+        row.extend((0..option_count).map(|i| q.options.get(i).cloned().unwrap_or_default()));
+        let letter = |idx: &i64| ((b'A' + *idx as u8) as char).to_string();
+        row.push(match q.kind.as_str() {
+            KIND_CHOICE => letter(&q.correct_answer_idx),
+            KIND_MULTIPLE_RESPONSE => q.correct_answers.iter().map(letter).collect::<Vec<_>>().join(";"),
+            _ => String::new(),
+        });
+        row.push(q.explanation.clone().unwrap_or_default());
+        row.push(q.tags.join(";"));
+        writer.write_record(&row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use sqlx::{Pool, Sqlite};
+
+use super::schema::{CategoryDoc, ContentBank, QuestionDoc, SubcategoryDoc};
+use crate::models::{Category, Question, Subcategory};
+
+// Dump all live (not soft-deleted) content as a bank that `import_bank`
+// accepts back unchanged.
+pub async fn export_bank(db: &Pool<Sqlite>) -> Result<ContentBank> {
+    let categories = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE deleted_at IS NULL ORDER BY id")
+        .fetch_all(db)
+        .await?;
+    let subcategories = sqlx::query_as::<_, Subcategory>("SELECT * FROM subcategories WHERE deleted_at IS NULL ORDER BY id")
+        .fetch_all(db)
+        .await?;
+    let questions = sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE deleted_at IS NULL ORDER BY id")
+        .fetch_all(db)
+        .await?;
+    let tag_rows: Vec<(String, String)> = sqlx::query_as("SELECT question_id, tag FROM question_tags ORDER BY tag")
+        .fetch_all(db)
+        .await?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for (question_id, tag) in tag_rows {
+        tags.entry(question_id).or_default().push(tag);
+    }
+
+    // Group questions by their direct parent
+    let mut by_category: HashMap<String, Vec<QuestionDoc>> = HashMap::new();
+    let mut by_subcategory: HashMap<String, Vec<QuestionDoc>> = HashMap::new();
+    for q in questions {
+        let doc = QuestionDoc {
+            options: q.get_options_vec(),
+            correct_answers: q.get_correct_answers(),
+            tags: tags.remove(&q.id).unwrap_or_default(),
+            id: q.id,
+            kind: q.kind,
+            question_text: q.question_text,
+            correct_answer_idx: q.correct_answer_idx,
+            explanation: q.explanation,
+        };
+        match q.subcategory_id {
+            Some(sub_id) => by_subcategory.entry(sub_id).or_default().push(doc),
+            None => by_category.entry(q.category_id).or_default().push(doc),
+        }
+    }
+
+    let mut subs_by_category: HashMap<String, Vec<SubcategoryDoc>> = HashMap::new();
+    for sub in subcategories {
+        subs_by_category.entry(sub.category_id).or_default().push(SubcategoryDoc {
+            questions: by_subcategory.remove(&sub.id).unwrap_or_default(),
+            id: sub.id,
+            title: sub.title,
+            description: sub.description,
+        });
+    }
+
+    let categories = categories
+        .into_iter()
+        .map(|cat| CategoryDoc {
+            subcategories: subs_by_category.remove(&cat.id).unwrap_or_default(),
+            questions: by_category.remove(&cat.id).unwrap_or_default(),
+            id: cat.id,
+            title: cat.title,
+        })
+        .collect();
+
+    Ok(ContentBank { categories })
+}
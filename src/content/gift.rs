@@ -0,0 +1,393 @@
+// Moodle GIFT format (https://docs.moodle.org/en/GIFT_format).
+//
+// Multiple choice (single and multiple answer), true/false and short answer
+// questions map onto Hackademy's question kinds; other GIFT types are
+// reported as skipped, and order questions are left out of exports.
+
+use super::schema::{ContentBank, QuestionDoc};
+use super::{question_id_for, slugify, ParsedBank, BLANK};
+use crate::models::{KIND_CHOICE, KIND_MULTIPLE_RESPONSE, KIND_ORDER, KIND_TEXT_ENTRY};
+
+const DEFAULT_CATEGORY: &str = "Imported";
+
+enum Item {
+    Question(QuestionDoc),
+    Unsupported(&'static str),
+}
+
+pub fn parse(input: &str) -> ParsedBank {
+    let mut parsed = ParsedBank::default();
+    let mut category = DEFAULT_CATEGORY.to_string();
+    let mut subcategory: Option<String> = None;
+
+    for (mut line, mut block) in blocks(input) {
+        if let Some(path) = block.trim_start().strip_prefix("$CATEGORY:") {
+            let (path, rest) = path.split_once('\n').unwrap_or((path, ""));
+            (category, subcategory) = split_category_path(path);
+            if rest.trim().is_empty() {
+                continue;
+            }
+            // A question may follow the category line without a blank line
+            block = rest.to_string();
+            line += 1;
+        }
+        match parse_item(&block) {
+            Ok(Item::Question(q)) => parsed.bank.push_question(&category, subcategory.as_deref(), q),
+            Ok(Item::Unsupported(kind)) => parsed
+                .warnings
+                .push(format!("line {}: skipped {} question", line, kind)),
+            Err(e) => parsed.errors.push(format!("line {}: {}", line, e)),
+        }
+    }
+    parsed
+}
+
+// Split the input into blank-line separated items, dropping comment lines,
+// and remember the line each item starts on.
+fn blocks(input: &str) -> Vec<(usize, String)> {
+    let mut out = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (idx, line) in input.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") {
+            continue;
+        }
+        if trimmed.is_empty() {
+            out.extend(current.take());
+            continue;
+        }
+        match &mut current {
+            Some((_, text)) => {
+                text.push('\n');
+                text.push_str(line);
+            }
+            None => current = Some((idx + 1, line.to_string())),
+        }
+    }
+    out.extend(current);
+    out
+}
+
+// "$course$/top/Web Security/XSS/Reflected" -> ("Web Security", Some("XSS / Reflected")).
+// Moodle writes a literal slash in a name as "//".
+pub fn split_category_path(path: &str) -> (String, Option<String>) {
+    let mut segments: Vec<String> = path
+        .trim()
+        .replace("//", "\u{0}")
+        .split('/')
+        .map(|s| s.replace('\u{0}', "/").trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    while segments
+        .first()
+        .is_some_and(|s| s.starts_with('$') || s == "top" || s == "Default for top")
+    {
+        segments.remove(0);
+    }
+    match segments.len() {
+        0 => (DEFAULT_CATEGORY.to_string(), None),
+        1 => (segments.remove(0), None),
+        _ => {
+            let category = segments.remove(0);
+            (category, Some(segments.join(" / ")))
+        }
+    }
+}
+
+fn parse_item(block: &str) -> Result<Item, String> {
+    let mut rest = block.trim();
+
+    // Optional "::title::"
+    let mut title = None;
+    if let Some(after) = rest.strip_prefix("::") {
+        let end = find_unescaped(after, "::").ok_or("unterminated ::title::")?;
+        title = Some(unescape(&after[..end]));
+        rest = after[end + 2..].trim_start();
+    }
+
+    let Some(open) = find_unescaped(rest, "{") else {
+        // A "description" item has no answers at all
+        return Ok(Item::Unsupported("description"));
+    };
+    let close = find_unescaped(&rest[open..], "}")
+        .map(|i| open + i)
+        .ok_or("missing closing } for the answer block")?;
+
+    let before = strip_text_format(rest[..open].trim());
+    let after = rest[close + 1..].trim();
+    let mut question_text = unescape(before);
+    if !after.is_empty() {
+        // "Missing word" style: the blank sits inside the sentence
+        question_text = format!("{} {} {}", question_text, BLANK, unescape(after));
+    }
+    if question_text.trim().is_empty() {
+        return Err("question text is empty".to_string());
+    }
+
+    let id = match title.as_deref().map(str::trim) {
+        // Titles written by `render` are ids already
+        Some(t) if !t.is_empty() && t.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => t.to_string(),
+        Some(t) if !slugify(t).is_empty() => slugify(t),
+        _ => question_id_for(&question_text),
+    };
+
+    let body = rest[open + 1..close].trim();
+    let (body, explanation) = match find_unescaped(body, "####") {
+        Some(i) => (body[..i].trim(), Some(unescape(body[i + 4..].trim())).filter(|s| !s.is_empty())),
+        None => (body, None),
+    };
+
+    if body.is_empty() {
+        return Ok(Item::Unsupported("essay"));
+    }
+    if body.starts_with('#') {
+        return Ok(Item::Unsupported("numerical"));
+    }
+
+    // True/false: {T}, {TRUE}, {F}, {FALSE}, optionally with #feedback
+    let tf_value = match find_unescaped(body, "#") {
+        Some(i) => body[..i].trim(),
+        None => body,
+    };
+    let is_true = match tf_value.to_ascii_uppercase().as_str() {
+        "T" | "TRUE" => Some(true),
+        "F" | "FALSE" => Some(false),
+        _ => None,
+    };
+    if let Some(is_true) = is_true {
+        return Ok(Item::Question(QuestionDoc {
+            id,
+            kind: KIND_CHOICE.to_string(),
+            question_text,
+            options: vec!["True".to_string(), "False".to_string()],
+            correct_answer_idx: if is_true { 0 } else { 1 },
+            correct_answers: Vec::new(),
+            explanation,
+            tags: Vec::new(),
+        }));
+    }
+
+    let answers = split_answers(body)?;
+    if answers.iter().any(|a| find_unescaped(&a.text, "->").is_some()) {
+        return Ok(Item::Unsupported("matching"));
+    }
+    if answers.iter().all(|a| a.marker == '=') {
+        // Short answer: every "=" answer is accepted
+        return Ok(Item::Question(QuestionDoc {
+            id,
+            kind: KIND_TEXT_ENTRY.to_string(),
+            question_text,
+            options: answers.iter().filter(|a| a.is_correct()).map(|a| unescape(&a.text)).collect(),
+            correct_answer_idx: 0,
+            correct_answers: Vec::new(),
+            explanation,
+            tags: Vec::new(),
+        }));
+    }
+
+    let correct: Vec<i64> = answers
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| a.is_correct())
+        .map(|(i, _)| i as i64)
+        .collect();
+    let options = answers.iter().map(|a| unescape(&a.text)).collect();
+    let question = match correct.len() {
+        0 => return Err("no correct answer (mark one with =)".to_string()),
+        1 => QuestionDoc {
+            id,
+            kind: KIND_CHOICE.to_string(),
+            question_text,
+            options,
+            correct_answer_idx: correct[0],
+            correct_answers: Vec::new(),
+            explanation,
+            tags: Vec::new(),
+        },
+        _ => QuestionDoc {
+            id,
+            kind: KIND_MULTIPLE_RESPONSE.to_string(),
+            question_text,
+            options,
+            correct_answer_idx: correct[0],
+            correct_answers: correct,
+            explanation,
+            tags: Vec::new(),
+        },
+    };
+    Ok(Item::Question(question))
+}
+
+struct Answer {
+    marker: char,
+    weight: Option<f64>,
+    text: String,
+}
+
+impl Answer {
+    fn is_correct(&self) -> bool {
+        match self.marker {
+            '=' => self.weight.is_none_or(|w| w > 0.0),
+            _ => self.weight.is_some_and(|w| w > 0.0),
+        }
+    }
+}
+
+// Split "=right ~wrong ~%50%partial#feedback" into answers
+fn split_answers(body: &str) -> Result<Vec<Answer>, String> {
+    let mut raw: Vec<(char, String)> = Vec::new();
+    let mut escaped = false;
+    for c in body.chars() {
+        if escaped {
+            if let Some((_, text)) = raw.last_mut() {
+                text.push('\\');
+                text.push(c);
+            }
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '=' | '~' => raw.push((c, String::new())),
+            _ => match raw.last_mut() {
+                Some((_, text)) => text.push(c),
+                None if c.is_whitespace() => {}
+                None => return Err("answers must start with = or ~".to_string()),
+            },
+        }
+    }
+
+    let mut answers = Vec::new();
+    for (marker, mut text) in raw {
+        // Per-answer feedback is not kept
+        if let Some(i) = find_unescaped(&text, "#") {
+            text.truncate(i);
+        }
+        let mut text = text.trim().to_string();
+        let mut weight = None;
+        if let Some(after) = text.strip_prefix('%') {
+            let end = after.find('%').ok_or("unterminated %weight%")?;
+            let value: f64 = after[..end]
+                .parse()
+                .map_err(|_| format!("invalid answer weight %{}%", &after[..end]))?;
+            weight = Some(value);
+            text = after[end + 1..].trim().to_string();
+        }
+        if text.is_empty() {
+            return Err("empty answer".to_string());
+        }
+        answers.push(Answer { marker, weight, text });
+    }
+    Ok(answers)
+}
+
+fn strip_text_format(text: &str) -> &str {
+    for marker in ["[html]", "[moodle]", "[plain]", "[markdown]"] {
+        if let Some(rest) = text.strip_prefix(marker) {
+            return rest.trim_start();
+        }
+    }
+    text
+}
+
+// Byte offset of the first occurrence of `pat` not preceded by a backslash
+fn find_unescaped(s: &str, pat: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if c == '\\' {
+            escaped = true;
+        } else if s[i..].starts_with(pat) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some(next) => out.push(next),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '~' | '=' | '#' | '{' | '}' | ':' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// Weight of each correct answer when `count` answers share full credit,
+// e.g. "33.33333". Moodle only accepts weights from a fixed list, which
+// these fall into for up to ten answers.
+pub fn partial_credit(count: usize) -> String {
+    let weight = 100.0 / count.max(1) as f64;
+    format!("{}", (weight * 100000.0).round() / 100000.0)
+}
+
+fn category_path(title: &str) -> String {
+    title.replace('/', "//")
+}
+
+pub fn render(bank: &ContentBank) -> String {
+    let mut out = String::from("// Exported from Hackademy\n");
+    for cat in &bank.categories {
+        let sections = std::iter::once((category_path(&cat.title), &cat.questions)).chain(
+            cat.subcategories
+                .iter()
+                .map(|sub| (format!("{}/{}", category_path(&cat.title), category_path(&sub.title)), &sub.questions)),
+        );
+        for (path, questions) in sections {
+            if questions.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("\n$CATEGORY: {}\n", path));
+            for q in questions {
+                if q.kind == KIND_ORDER {
+                    out.push_str(&format!("\n// skipped order question {}: GIFT has no ordering type\n", q.id));
+                    continue;
+                }
+                out.push_str(&format!("\n::{}:: {} {{\n", escape(&q.id), escape(&q.question_text)));
+                for (i, option) in q.options.iter().enumerate() {
+                    let answer = match q.kind.as_str() {
+                        KIND_TEXT_ENTRY => format!("={}", escape(option)),
+                        KIND_MULTIPLE_RESPONSE if q.correct_answers.contains(&(i as i64)) => {
+                            format!("~%{}%{}", partial_credit(q.correct_answers.len()), escape(option))
+                        }
+                        KIND_MULTIPLE_RESPONSE => format!("~%-100%{}", escape(option)),
+                        _ if i as i64 == q.correct_answer_idx => format!("={}", escape(option)),
+                        _ => format!("~{}", escape(option)),
+                    };
+                    out.push_str(&format!("\t{}\n", answer));
+                }
+                if let Some(explanation) = &q.explanation {
+                    out.push_str(&format!("\t####{}\n", escape(explanation)));
+                }
+                out.push_str("}\n");
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+
+use anyhow::Result;
+use sqlx::{Pool, Sqlite, Transaction};
+
+use super::revisions::record_revision;
+use super::similarity::refresh_related;
+use super::tags::{question_tags, set_question_tags};
+use super::topics::file_under;
+use super::schema::QuestionDoc;
+use super::{correct_answers_json, normalize_tag, ParsedBank};
+use crate::models::{Category, Question, Subcategory, STATUS_PUBLISHED};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Update,
+    Unchanged,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Create => write!(f, "create"),
+            ChangeKind::Update => write!(f, "update"),
+            ChangeKind::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+// One row touched (or left alone) by an import
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub entity: &'static str,
+    pub id: String,
+    pub details: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub changes: Vec<Change>,
+    // Parse and validation problems; when non-empty nothing was written
+    pub errors: Vec<String>,
+    // Entries from the source file that were skipped
+    pub warnings: Vec<String>,
+    pub applied: bool,
+}
+
+impl ImportReport {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} created, {} updated, {} unchanged",
+            self.count(ChangeKind::Create),
+            self.count(ChangeKind::Update),
+            self.count(ChangeKind::Unchanged)
+        )
+    }
+
+    fn record(&mut self, entity: &'static str, id: &str, exists: bool, details: Vec<String>) -> ChangeKind {
+        let kind = if !exists {
+            ChangeKind::Create
+        } else if details.is_empty() {
+            ChangeKind::Unchanged
+        } else {
+            ChangeKind::Update
+        };
+        self.changes.push(Change {
+            kind,
+            entity,
+            id: id.to_string(),
+            details,
+        });
+        kind
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        for change in self.changes.iter().filter(|c| c.kind != ChangeKind::Unchanged) {
+            writeln!(f, "{} {} {}", change.kind, change.entity, change.id)?;
+            for detail in &change.details {
+                writeln!(f, "    {}", detail)?;
+            }
+        }
+        write!(f, "{}", self.summary())?;
+        if !self.applied {
+            write!(f, " (not applied)")?;
+        }
+        Ok(())
+    }
+}
+
+fn diff<T: PartialEq + fmt::Debug>(details: &mut Vec<String>, field: &str, old: &T, new: &T) {
+    if old != new {
+        details.push(format!("{}: {:?} -> {:?}", field, old, new));
+    }
+}
+
+// Upsert a question bank by id inside a single transaction. With `dry_run`
+// the transaction is rolled back, so the report shows what would change
+// without touching the database. Banks with parse or validation errors are
+// rejected up front.
+pub async fn import_bank(db: &Pool<Sqlite>, parsed: &ParsedBank, dry_run: bool) -> Result<ImportReport> {
+    let bank = &parsed.bank;
+    let mut report = ImportReport {
+        errors: parsed.errors.clone(),
+        warnings: parsed.warnings.clone(),
+        ..Default::default()
+    };
+    if report.errors.is_empty() {
+        report.errors = bank.validate();
+    }
+    if !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    let mut tx = db.begin().await?;
+    for cat in &bank.categories {
+        let existing: Option<Category> = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id = ?")
+            .bind(&cat.id)
+            .fetch_optional(&mut tx)
+            .await?;
+
+        let mut details = Vec::new();
+        if let Some(old) = &existing {
+            diff(&mut details, "title", &old.title, &cat.title);
+            if old.deleted_at.is_some() {
+                details.push("restored".to_string());
+            }
+        }
+        match report.record("category", &cat.id, existing.is_some(), details) {
+            ChangeKind::Create => {
+                sqlx::query("INSERT INTO categories (id, title) VALUES (?, ?)")
+                    .bind(&cat.id)
+                    .bind(&cat.title)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            ChangeKind::Update => {
+                sqlx::query("UPDATE categories SET title = ?, deleted_at = NULL WHERE id = ?")
+                    .bind(&cat.title)
+                    .bind(&cat.id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            ChangeKind::Unchanged => {}
+        }
+
+        for q in &cat.questions {
+            upsert_question(&mut tx, &mut report, q, &cat.id, None).await?;
+        }
+
+        for sub in &cat.subcategories {
+            let existing: Option<Subcategory> = sqlx::query_as::<_, Subcategory>("SELECT * FROM subcategories WHERE id = ?")
+                .bind(&sub.id)
+                .fetch_optional(&mut tx)
+                .await?;
+
+            let mut details = Vec::new();
+            if let Some(old) = &existing {
+                diff(&mut details, "category_id", &old.category_id, &cat.id);
+                diff(&mut details, "title", &old.title, &sub.title);
+                diff(&mut details, "description", &old.description, &sub.description);
+                if old.deleted_at.is_some() {
+                    details.push("restored".to_string());
+                }
+            }
+            match report.record("subcategory", &sub.id, existing.is_some(), details) {
+                ChangeKind::Create => {
+                    sqlx::query("INSERT INTO subcategories (id, category_id, title, description) VALUES (?, ?, ?, ?)")
+                        .bind(&sub.id)
+                        .bind(&cat.id)
+                        .bind(&sub.title)
+                        .bind(&sub.description)
+                        .execute(&mut tx)
+                        .await?;
+                }
+                ChangeKind::Update => {
+                    sqlx::query(
+                        "UPDATE subcategories SET category_id = ?, title = ?, description = ?, deleted_at = NULL WHERE id = ?"
+                    )
+                    .bind(&cat.id)
+                    .bind(&sub.title)
+                    .bind(&sub.description)
+                    .bind(&sub.id)
+                    .execute(&mut tx)
+                    .await?;
+                }
+                ChangeKind::Unchanged => {}
+            }
+
+            for q in &sub.questions {
+                upsert_question(&mut tx, &mut report, q, &cat.id, Some(&sub.id)).await?;
+            }
+        }
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+        report.applied = true;
+        refresh_related(&mut *db.acquire().await?).await?;
+    }
+    Ok(report)
+}
+
+async fn upsert_question(
+    tx: &mut Transaction<'_, Sqlite>,
+    report: &mut ImportReport,
+    q: &QuestionDoc,
+    category_id: &str,
+    subcategory_id: Option<&str>,
+) -> Result<()> {
+    let existing: Option<Question> = sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ?")
+        .bind(&q.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let old_tags = question_tags(&mut *tx, &q.id).await?;
+
+    let mut tags: Vec<String> = q.tags.iter().map(|t| normalize_tag(t)).collect();
+    tags.sort();
+    tags.dedup();
+
+    let correct_answer_idx = q.stored_answer_idx();
+    let mut details = Vec::new();
+    if let Some(old) = &existing {
+        diff(&mut details, "category_id", &old.category_id.as_str(), &category_id);
+        diff(&mut details, "subcategory_id", &old.subcategory_id.as_deref(), &subcategory_id);
+        diff(&mut details, "kind", &old.kind, &q.kind);
+        diff(&mut details, "question_text", &old.question_text, &q.question_text);
+        diff(&mut details, "options", &old.get_options_vec(), &q.options);
+        diff(&mut details, "correct_answer_idx", &old.correct_answer_idx, &correct_answer_idx);
+        diff(&mut details, "correct_answers", &old.get_correct_answers(), &q.correct_answers);
+        diff(&mut details, "explanation", &old.explanation, &q.explanation);
+        diff(&mut details, "tags", &old_tags, &tags);
+        if old.deleted_at.is_some() {
+            details.push("restored".to_string());
+        }
+    }
+
+    let options = serde_json::to_string(&q.options)?;
+    let correct_answers = correct_answers_json(&q.correct_answers);
+    let topic_id = file_under(&mut *tx, existing.as_ref().map(|q| q.topic_id.as_str()), category_id, subcategory_id).await?;
+    match report.record("question", &q.id, existing.is_some(), details) {
+        // Imported and synced content was reviewed where it was written, so it
+        // goes live; updates leave the status alone
+        ChangeKind::Create => {
+            sqlx::query(
+                "INSERT INTO questions (id, category_id, subcategory_id, topic_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&q.id)
+            .bind(category_id)
+            .bind(subcategory_id)
+            .bind(&topic_id)
+            .bind(&q.kind)
+            .bind(&q.question_text)
+            .bind(&options)
+            .bind(correct_answer_idx)
+            .bind(&correct_answers)
+            .bind(&q.explanation)
+            .bind(STATUS_PUBLISHED)
+            .execute(&mut *tx)
+            .await?;
+        }
+        ChangeKind::Update => {
+            sqlx::query(
+                "UPDATE questions SET category_id = ?, subcategory_id = ?, topic_id = ?, kind = ?, question_text = ?, options = ?, correct_answer_idx = ?, correct_answers = ?, explanation = ?, deleted_at = NULL WHERE id = ?"
+            )
+            .bind(category_id)
+            .bind(subcategory_id)
+            .bind(&topic_id)
+            .bind(&q.kind)
+            .bind(&q.question_text)
+            .bind(&options)
+            .bind(correct_answer_idx)
+            .bind(&correct_answers)
+            .bind(&q.explanation)
+            .bind(&q.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        ChangeKind::Unchanged => return Ok(()),
+    }
+
+    if old_tags != tags {
+        set_question_tags(&mut *tx, &q.id, &tags).await?;
+    }
+    record_revision(&mut *tx, &q.id, None, "imported").await?;
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// Lessons: markdown pages attached to subcategories. A line holding only a
+// shortcode such as [[question:xss-csp]] embeds that question as a knowledge
+// check, graded on the page.
+
+use anyhow::Result;
+use pulldown_cmark::{html, Event, Options, Parser};
+use sqlx::SqliteConnection;
+
+use super::paths::{complete_step, is_unlocked};
+
+// A piece of a lesson, in page order
+#[derive(Debug, PartialEq)]
+pub enum LessonPart {
+    Html(String),
+    // Id of an embedded question
+    Question(String),
+}
+
+// The question id if the line is an embed shortcode
+fn shortcode(line: &str) -> Option<&str> {
+    let id = line.trim().strip_prefix("[[question:")?.strip_suffix("]]")?.trim();
+    Some(id).filter(|id| !id.is_empty() && !id.contains(char::is_whitespace))
+}
+
+// Markdown to HTML. Raw HTML in the source is shown as text, not passed
+// through.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+        Event::Html(raw) => Event::Text(raw),
+        other => other,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
+
+// Split a lesson at its shortcodes, rendering the markdown between them.
+// Shortcodes inside fenced code blocks are left alone.
+pub fn render_lesson(markdown: &str) -> Vec<LessonPart> {
+    let mut parts = Vec::new();
+    let mut pending = String::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        match shortcode(line).filter(|_| !in_fence) {
+            Some(id) => {
+                if !pending.trim().is_empty() {
+                    parts.push(LessonPart::Html(markdown_to_html(&pending)));
+                }
+                pending.clear();
+                parts.push(LessonPart::Question(id.to_string()));
+            }
+            None => {
+                pending.push_str(line);
+                pending.push('\n');
+            }
+        }
+    }
+    if !pending.trim().is_empty() {
+        parts.push(LessonPart::Html(markdown_to_html(&pending)));
+    }
+    parts
+}
+
+// Ids of the questions a lesson embeds, in order
+pub fn embedded_questions(markdown: &str) -> Vec<String> {
+    render_lesson(markdown)
+        .into_iter()
+        .filter_map(|part| match part {
+            LessonPart::Question(id) => Some(id),
+            LessonPart::Html(_) => None,
+        })
+        .collect()
+}
+
+// The text of a lesson without markup or shortcodes, e.g. for search
+// snippets
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for line in markdown.lines().filter(|l| shortcode(l).is_none()) {
+        for event in Parser::new(line) {
+            if let Event::Text(t) | Event::Code(t) = event {
+                text.push_str(&t);
+            }
+        }
+        text.push(' ');
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Record how far a learner has read, in percent; progress only goes up.
+// Reaching the end completes path steps that link to the lesson. Returns
+// the progress now stored.
+pub async fn record_progress(conn: &mut SqliteConnection, user_id: &str, lesson_id: &str, pct: i64) -> Result<i64> {
+    let pct = pct.clamp(0, 100);
+    sqlx::query(
+        "INSERT INTO lesson_progress (user_id, lesson_id, progress_pct, completed_at) VALUES (?, ?, ?, CASE WHEN ? >= 100 THEN CURRENT_TIMESTAMP END)
+         ON CONFLICT (user_id, lesson_id) DO UPDATE SET
+             progress_pct = MAX(progress_pct, excluded.progress_pct),
+             completed_at = COALESCE(completed_at, excluded.completed_at),
+             updated_at = CURRENT_TIMESTAMP"
+    )
+    .bind(user_id)
+    .bind(lesson_id)
+    .bind(pct)
+    .bind(pct)
+    .execute(&mut *conn)
+    .await?;
+    let stored: i64 = sqlx::query_scalar("SELECT progress_pct FROM lesson_progress WHERE user_id = ? AND lesson_id = ?")
+        .bind(user_id)
+        .bind(lesson_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    if stored >= 100 {
+        let steps: Vec<String> = sqlx::query_scalar(
+            "SELECT s.id FROM path_steps s JOIN learning_paths p ON p.id = s.path_id
+             WHERE s.kind = 'lesson' AND s.url = ? AND s.deleted_at IS NULL AND p.deleted_at IS NULL"
+        )
+        .bind(format!("/lessons/{}", lesson_id))
+        .fetch_all(&mut *conn)
+        .await?;
+        for step in steps {
+            if is_unlocked(conn, user_id, &step).await? {
+                complete_step(conn, user_id, &step, None, None).await?;
+            }
+        }
+    }
+    Ok(stored)
+}
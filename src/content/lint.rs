@@ -0,0 +1,414 @@
+// Offline checks on question banks and content packs, for `hackademy lint`.
+//
+// Errors are the problems that would make an import or sync reject an
+// entry. Warnings point at content that is valid but probably unintended:
+// duplicate questions, options that only differ in case, and links that
+// lead nowhere.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::schema::{CategoryDoc, ContentBank, QuestionDoc, SubcategoryDoc};
+use super::similarity::{question_terms, TfIdf};
+use super::{pack, parse_bank, Format};
+use crate::models::{KIND_CHOICE, KIND_TEXT_ENTRY};
+
+// Questions at least this similar (TF-IDF cosine similarity of their text,
+// options and explanation) are near-duplicates
+const NEAR_DUPLICATE_SIMILARITY: f64 = 0.8;
+// Answer-position bias needs this many choice questions to mean anything
+const BIAS_MIN_QUESTIONS: usize = 20;
+// A position is biased when it is correct this many times more (or less)
+// often than chance predicts
+const BIAS_RATIO: f64 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // Short name of the check, e.g. "broken-link"
+    pub check: &'static str,
+    // File the problem is in
+    pub location: String,
+    pub message: String,
+}
+
+// How often each option position holds the answer of a choice question,
+// against how often it would by chance
+#[derive(Debug, Serialize)]
+pub struct PositionStat {
+    pub position: usize,
+    pub correct: usize,
+    pub expected: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LintReport {
+    pub files: usize,
+    pub questions: usize,
+    pub diagnostics: Vec<Diagnostic>,
+    pub answer_positions: Vec<PositionStat>,
+}
+
+impl LintReport {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == severity).count()
+    }
+
+    fn push(&mut self, severity: Severity, check: &'static str, location: &str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            check,
+            location: location.to_string(),
+            message,
+        });
+    }
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for d in &self.diagnostics {
+            writeln!(f, "{}: {} [{}] {}", d.location, d.severity, d.check, d.message)?;
+        }
+        if !self.answer_positions.is_empty() {
+            let stats: Vec<String> = self
+                .answer_positions
+                .iter()
+                .map(|p| format!("{} {} ({:.1} expected)", position_label(p.position), p.correct, p.expected))
+                .collect();
+            writeln!(f, "answer positions: {}", stats.join(", "))?;
+        }
+        write!(
+            f,
+            "{} file(s), {} question(s): {} error(s), {} warning(s)",
+            self.files,
+            self.questions,
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+fn position_label(position: usize) -> String {
+    match u8::try_from(position) {
+        Ok(p) if p < 26 => ((b'A' + p) as char).to_string(),
+        _ => position.to_string(),
+    }
+}
+
+// Where an entry lives: the file to report and the directory its relative
+// links are resolved against. Entries of a single-file bank also get their
+// id named in messages.
+struct Source {
+    location: String,
+    base_dir: PathBuf,
+    label: Option<String>,
+}
+
+impl Source {
+    fn message(&self, message: String) -> String {
+        match &self.label {
+            Some(label) => format!("{}: {}", label, message),
+            None => message,
+        }
+    }
+}
+
+// Lint a bank file, or a content pack when `path` is a directory. `format`
+// overrides the file's extension.
+pub fn lint_path(path: &Path, format: Option<Format>) -> Result<LintReport> {
+    let mut report = LintReport::default();
+    let root = path.display().to_string();
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut question_sources = HashMap::new();
+    let mut subcategory_sources = HashMap::new();
+
+    let bank = if path.is_dir() {
+        let pack = pack::load(path)?;
+        report.files = pack.files;
+        for error in &pack.errors {
+            let (rel, message) = error.split_once(": ").unwrap_or(("", error));
+            report.push(Severity::Error, classify(message), &path.join(rel).display().to_string(), message.to_string());
+        }
+        for (entity, id, rel) in &pack.sources {
+            let file = path.join(rel);
+            match *entity {
+                "question" => {
+                    let base_dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+                    question_sources.insert(id.clone(), Source { location: file.display().to_string(), base_dir, label: None });
+                }
+                "subcategory" => {
+                    let location = file.join(pack::SUBCATEGORY_FILE).display().to_string();
+                    subcategory_sources.insert(id.clone(), Source { location, base_dir: file, label: None });
+                }
+                _ => {}
+            }
+        }
+        pack.parsed.bank
+    } else {
+        let format = match format {
+            Some(format) => format,
+            None => Format::from_path(&root)?,
+        };
+        let parsed = parse_bank(&std::fs::read(path)?, format);
+        report.files = 1;
+        for error in &parsed.errors {
+            report.push(Severity::Error, classify(error), &root, error.clone());
+        }
+        for warning in &parsed.warnings {
+            report.push(Severity::Warning, "skipped", &root, warning.clone());
+        }
+        parsed.bank
+    };
+
+    // Checks that a pack already made per file; for a bank they cover everything
+    for error in bank.validate() {
+        report.push(Severity::Error, classify(&error), &root, error);
+    }
+
+    let source_for = |sources: &HashMap<String, Source>, entity: &str, id: &str| match sources.get(id) {
+        Some(source) => Source {
+            location: source.location.clone(),
+            base_dir: source.base_dir.clone(),
+            label: None,
+        },
+        None => Source {
+            location: root.clone(),
+            base_dir: base_dir.clone(),
+            label: Some(format!("{} {}", entity, id)),
+        },
+    };
+
+    let categories: HashSet<&str> = bank.categories.iter().map(|c| c.id.as_str()).collect();
+    let subcategories: HashSet<&str> = bank
+        .categories
+        .iter()
+        .flat_map(|c| c.subcategories.iter().map(|s| s.id.as_str()))
+        .collect();
+    let link_targets = LinkTargets { categories, subcategories };
+
+    for cat in &bank.categories {
+        for sub in &cat.subcategories {
+            let source = source_for(&subcategory_sources, "subcategory", &sub.id);
+            if let Some(description) = &sub.description {
+                check_links(&mut report, &source, description, &link_targets);
+            }
+        }
+    }
+
+    let questions = bank.questions();
+    report.questions = questions.len();
+    let sources: Vec<Source> = questions
+        .iter()
+        .map(|(_, _, q)| source_for(&question_sources, "question", &q.id))
+        .collect();
+
+    for ((_, _, q), source) in questions.iter().zip(&sources) {
+        check_options(&mut report, source, q);
+        check_links(&mut report, source, &q.question_text, &link_targets);
+        if let Some(explanation) = &q.explanation {
+            check_links(&mut report, source, explanation, &link_targets);
+        }
+    }
+    check_duplicates(&mut report, &questions, &sources);
+    check_answer_positions(&mut report, &bank, &root);
+    Ok(report)
+}
+
+// Name the check behind one of the import's validation messages
+fn classify(message: &str) -> &'static str {
+    if message.starts_with("Duplicate option") {
+        "duplicate-option"
+    } else if message.contains("out of range") {
+        "answer-index"
+    } else if message.contains("used by more than one file") || message.contains("duplicate question id") {
+        "duplicate-id"
+    } else {
+        "schema"
+    }
+}
+
+fn normalize_text(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Options that only differ in case or spacing. Exact duplicates are already
+// a validation error; accepted text-entry answers are compared the way
+// answers are graded, so a near-duplicate there is simply redundant.
+fn check_options(report: &mut LintReport, source: &Source, q: &QuestionDoc) {
+    let normalized: Vec<String> = q.options.iter().map(|o| normalize_text(o)).collect();
+    for (i, option) in q.options.iter().enumerate() {
+        let Some(j) = (0..i).find(|&j| normalized[j] == normalized[i] && q.options[j] != *option) else {
+            continue;
+        };
+        let message = if q.kind == KIND_TEXT_ENTRY {
+            format!("accepted answers '{}' and '{}' are the same when graded", q.options[j], option)
+        } else {
+            format!("options '{}' and '{}' differ only in case or spacing", q.options[j], option)
+        };
+        report.push(Severity::Warning, "duplicate-option", &source.location, source.message(message));
+    }
+}
+
+struct LinkTargets<'a> {
+    categories: HashSet<&'a str>,
+    subcategories: HashSet<&'a str>,
+}
+
+// Inline markdown links and images: the target of every `](target)`
+fn markdown_links(text: &str) -> Vec<&str> {
+    let mut links = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find(')') else {
+            break;
+        };
+        // Drop an optional title: [text](target "title")
+        let target = after[..end].split_whitespace().next().unwrap_or("");
+        links.push(target.trim_start_matches('<').trim_end_matches('>'));
+        rest = &after[end + 1..];
+    }
+    links
+}
+
+fn check_links(report: &mut LintReport, source: &Source, text: &str, targets: &LinkTargets) {
+    for link in markdown_links(text) {
+        if let Some((check, message)) = check_link(link, &source.base_dir, targets) {
+            report.push(Severity::Error, check, &source.location, source.message(message));
+        }
+    }
+}
+
+// External links are not fetched; links into the app must name content in
+// the bank, and relative links must point at an existing file.
+fn check_link(link: &str, base_dir: &Path, targets: &LinkTargets) -> Option<(&'static str, String)> {
+    if link.is_empty() {
+        return Some(("broken-link", "link has no target".to_string()));
+    }
+    if link.contains("://") || link.starts_with("mailto:") || link.starts_with('#') {
+        return None;
+    }
+    if let Some(rest) = link.strip_prefix("/category/") {
+        let id = rest.split(['/', '?', '#']).next().unwrap_or("");
+        if !targets.categories.contains(id) {
+            return Some(("dangling-ref", format!("link {} refers to unknown category '{}'", link, id)));
+        }
+        return None;
+    }
+    if let Some(query) = link.strip_prefix("/quiz?") {
+        for pair in query.split('#').next().unwrap_or("").split('&') {
+            let dangling = match pair.split_once('=') {
+                Some(("category_id", id)) if !targets.categories.contains(id) => Some(("category", id)),
+                Some(("subcategory_id", id)) if !targets.subcategories.contains(id) => Some(("subcategory", id)),
+                _ => None,
+            };
+            if let Some((entity, id)) = dangling {
+                return Some(("dangling-ref", format!("link {} refers to unknown {} '{}'", link, entity, id)));
+            }
+        }
+        return None;
+    }
+    if link.starts_with('/') {
+        return None;
+    }
+    let file = link.split(['#', '?']).next().unwrap_or("");
+    if !file.is_empty() && !base_dir.join(file).exists() {
+        return Some(("broken-link", format!("link target {} does not exist", file)));
+    }
+    None
+}
+
+// Each pair is reported once, on the later question
+fn check_duplicates(
+    report: &mut LintReport,
+    questions: &[(&CategoryDoc, Option<&SubcategoryDoc>, &QuestionDoc)],
+    sources: &[Source],
+) {
+    let texts: Vec<String> = questions.iter().map(|(_, _, q)| normalize_text(&q.question_text)).collect();
+    let docs: Vec<Vec<String>> = questions
+        .iter()
+        .map(|(_, _, q)| question_terms(&q.question_text, &q.options, q.explanation.as_deref()))
+        .collect();
+    let model = TfIdf::new(&docs);
+
+    for i in 0..questions.len() {
+        let (cat, _, q) = questions[i];
+        for j in 0..i {
+            let (other_cat, _, other) = questions[j];
+            let (check, what) = if texts[i] == texts[j] {
+                ("duplicate-question", "has the same text as")
+            } else if model.similarity(i, j) >= NEAR_DUPLICATE_SIMILARITY {
+                ("near-duplicate", "is very similar to")
+            } else {
+                continue;
+            };
+            let mut message = format!("question {} {} question {}", q.id, what, other.id);
+            if sources[j].location != sources[i].location {
+                message.push_str(&format!(" ({})", sources[j].location));
+            }
+            if cat.id != other_cat.id {
+                message.push_str(&format!(" in category {}", other_cat.id));
+            }
+            report.push(Severity::Warning, check, &sources[i].location, message);
+            break;
+        }
+    }
+}
+
+fn check_answer_positions(report: &mut LintReport, bank: &ContentBank, location: &str) {
+    let choice: Vec<&QuestionDoc> = bank
+        .questions()
+        .into_iter()
+        .map(|(_, _, q)| q)
+        .filter(|q| q.kind == KIND_CHOICE && !q.options.is_empty())
+        .collect();
+
+    let mut positions: BTreeMap<usize, PositionStat> = BTreeMap::new();
+    for q in &choice {
+        let chance = 1.0 / q.options.len() as f64;
+        for position in 0..q.options.len() {
+            let stat = positions.entry(position).or_insert(PositionStat { position, correct: 0, expected: 0.0 });
+            stat.expected += chance;
+            if q.correct_answer_idx == position as i64 {
+                stat.correct += 1;
+            }
+        }
+    }
+
+    if choice.len() >= BIAS_MIN_QUESTIONS {
+        for stat in positions.values().filter(|s| s.expected >= 5.0) {
+            let ratio = stat.correct as f64 / stat.expected;
+            if !(1.0 / BIAS_RATIO..=BIAS_RATIO).contains(&ratio) {
+                let message = format!(
+                    "option {} is the answer to {} of {} choice questions, {:.1} would be expected by chance",
+                    position_label(stat.position),
+                    stat.correct,
+                    choice.len(),
+                    stat.expected
+                );
+                report.push(Severity::Warning, "answer-bias", location, message);
+            }
+        }
+    }
+    report.answer_positions = positions.into_values().collect();
+}
@@ -0,0 +1,311 @@
+pub mod schema;
+pub mod import;
+pub mod export;
+pub mod gift;
+pub mod moodle_xml;
+pub mod csv;
+pub mod qti;
+pub mod pack;
+pub mod lint;
+pub mod revisions;
+pub mod scoring;
+pub mod tags;
+pub mod topics;
+pub mod paths;
+pub mod lessons;
+pub mod review;
+pub mod search;
+pub mod suggest;
+pub mod search_stats;
+pub mod similarity;
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+
+use schema::ContentBank;
+use crate::models::{KIND_CHOICE, KIND_MULTIPLE_RESPONSE, KIND_ORDER, KIND_TEXT_ENTRY};
+
+// How a text-entry blank is written inside question text
+pub const BLANK: &str = "_____";
+
+// Serialization formats supported for question banks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Gift,
+    MoodleXml,
+    Csv,
+    // IMS QTI content package; both versions are read on import
+    Qti,
+    Qti3,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "gift" | "txt" => Ok(Format::Gift),
+            "moodle-xml" | "xml" => Ok(Format::MoodleXml),
+            "csv" => Ok(Format::Csv),
+            "qti" | "qti2" | "zip" => Ok(Format::Qti),
+            "qti3" => Ok(Format::Qti3),
+            other => Err(anyhow!("Unsupported content format: {}", other)),
+        }
+    }
+
+    // Pick the format from a file name's extension
+    pub fn from_path(path: &str) -> Result<Self> {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| anyhow!("Cannot tell the format of {} from its extension", path))?;
+        Self::from_name(ext)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Gift => "gift",
+            Format::MoodleXml => "moodle-xml",
+            Format::Csv => "csv",
+            Format::Qti => "qti",
+            Format::Qti3 => "qti3",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::MoodleXml => "xml",
+            Format::Qti | Format::Qti3 => "zip",
+            other => other.name(),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Yaml => "application/yaml",
+            Format::Gift => "text/plain; charset=utf-8",
+            Format::MoodleXml => "application/xml",
+            Format::Csv => "text/csv",
+            Format::Qti | Format::Qti3 => "application/zip",
+        }
+    }
+}
+
+// A bank read from a file, along with anything that went wrong. Errors
+// (prefixed with their line where known) block the import; warnings are
+// entries that were skipped, e.g. question types Hackademy can't represent.
+#[derive(Debug, Default)]
+pub struct ParsedBank {
+    pub bank: ContentBank,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+pub fn parse_bank(input: &[u8], format: Format) -> ParsedBank {
+    if let Format::Qti | Format::Qti3 = format {
+        return qti::parse(input);
+    }
+    let input = match std::str::from_utf8(input) {
+        Ok(s) => s,
+        Err(e) => return ParsedBank { errors: vec![format!("input is not valid UTF-8 ({})", e)], ..Default::default() },
+    };
+    match format {
+        Format::Json => match serde_json::from_str(input) {
+            Ok(bank) => ParsedBank { bank, ..Default::default() },
+            Err(e) => ParsedBank { errors: vec![e.to_string()], ..Default::default() },
+        },
+        Format::Yaml => match serde_yaml::from_str(input) {
+            Ok(bank) => ParsedBank { bank, ..Default::default() },
+            Err(e) => ParsedBank { errors: vec![e.to_string()], ..Default::default() },
+        },
+        Format::Gift => gift::parse(input),
+        Format::MoodleXml => moodle_xml::parse(input),
+        Format::Csv => csv::parse(input),
+        Format::Qti | Format::Qti3 => unreachable!(),
+    }
+}
+
+pub fn render_bank(bank: &ContentBank, format: Format) -> Result<Vec<u8>> {
+    let out = match format {
+        Format::Json => serde_json::to_string_pretty(bank)?,
+        Format::Yaml => serde_yaml::to_string(bank)?,
+        Format::Gift => gift::render(bank),
+        Format::MoodleXml => moodle_xml::render(bank),
+        Format::Csv => csv::render(bank)?,
+        Format::Qti => return qti::render(bank, qti::Version::V2p1),
+        Format::Qti3 => return qti::render(bank, qti::Version::V3p0),
+    };
+    Ok(out.into_bytes())
+}
+
+// Lowercase ASCII slug used to derive stable ids from titles
+pub fn slugify(s: &str) -> String {
+    let mut slug = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+// Stable id for a question that arrives without one (e.g. an untitled GIFT
+// question), derived from its text so re-imports hit the same row
+pub fn question_id_for(question_text: &str) -> String {
+    let digest = Sha256::digest(question_text.trim().as_bytes());
+    let hex: String = digest.iter().take(6).map(|b| format!("{:02x}", b)).collect();
+    format!("q-{}", hex)
+}
+
+// Tags are compared case-insensitively and stored lowercase
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+pub fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Parse the answer fields of the question form for a question of the given
+// kind and check them. Returns the options, the answer index to store and
+// the correct answers of a multiple-response question.
+pub fn validate_answers(
+    kind: &str,
+    options_json: &str,
+    correct_answer_idx: &str,
+    correct_answers: &str,
+) -> Result<(Vec<String>, i64, Vec<i64>), Vec<String>> {
+    let mut errors = Vec::new();
+
+    let options: Vec<String> = match serde_json::from_str(options_json) {
+        Ok(opts) => opts,
+        Err(e) => {
+            errors.push(format!("Options must be a JSON array of strings ({})", e));
+            Vec::new()
+        }
+    };
+
+    let mut idx = 0;
+    if kind == KIND_CHOICE {
+        match correct_answer_idx.trim().parse::<i64>() {
+            Ok(i) => idx = i,
+            Err(_) => errors.push("Correct answer index must be a number".to_string()),
+        }
+    }
+    let mut answers = Vec::new();
+    if kind == KIND_MULTIPLE_RESPONSE {
+        match correct_answers
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(parsed) => answers = parsed,
+            Err(_) => errors.push("Correct answers must be a comma-separated list of option indices".to_string()),
+        }
+    }
+
+    if errors.is_empty() {
+        errors.extend(check_question(kind, &options, idx, &answers));
+    }
+    if errors.is_empty() {
+        Ok((options, stored_answer_idx(kind, idx, &answers), answers))
+    } else {
+        Err(errors)
+    }
+}
+
+// Checks on a question's answers that depend on its kind
+pub fn check_question(kind: &str, options: &[String], correct_answer_idx: i64, correct_answers: &[i64]) -> Vec<String> {
+    let mut errors = Vec::new();
+    match kind {
+        KIND_CHOICE => {
+            errors.extend(check_options(options));
+            if !options.is_empty() {
+                errors.extend(check_answer_idx(options, correct_answer_idx));
+            }
+        }
+        KIND_MULTIPLE_RESPONSE => {
+            errors.extend(check_options(options));
+            if correct_answers.is_empty() {
+                errors.push("A multiple-response question needs at least one correct answer".to_string());
+            }
+            for (i, idx) in correct_answers.iter().enumerate() {
+                if correct_answers[..i].contains(idx) {
+                    errors.push(format!("Correct answer {} is listed twice", idx));
+                } else if !options.is_empty() {
+                    errors.extend(check_answer_idx(options, *idx));
+                }
+            }
+        }
+        KIND_TEXT_ENTRY => {
+            if options.is_empty() {
+                errors.push("A text-entry question needs at least one accepted answer".to_string());
+            }
+            if options.iter().any(|o| o.trim().is_empty()) {
+                errors.push("Accepted answers must not be empty".to_string());
+            }
+        }
+        KIND_ORDER => errors.extend(check_options(options)),
+        other => errors.push(format!("Unknown question kind: {}", other)),
+    }
+    errors
+}
+
+// `correct_answer_idx` is only meaningful for choice questions; the other
+// kinds store the first correct answer (multiple response) or 0.
+pub fn stored_answer_idx(kind: &str, correct_answer_idx: i64, correct_answers: &[i64]) -> i64 {
+    match kind {
+        KIND_CHOICE => correct_answer_idx,
+        KIND_MULTIPLE_RESPONSE => correct_answers.first().copied().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+// The `correct_answers` column: NULL unless the question has any
+pub fn correct_answers_json(correct_answers: &[i64]) -> Option<String> {
+    (!correct_answers.is_empty()).then(|| serde_json::to_string(correct_answers).unwrap())
+}
+
+// Structural checks on a list of answer options
+pub fn check_options(options: &[String]) -> Vec<String> {
+    let mut errors = Vec::new();
+    if options.len() < 2 {
+        errors.push("A question needs at least two options".to_string());
+    }
+    if options.iter().any(|o| o.trim().is_empty()) {
+        errors.push("Options must not be empty".to_string());
+    }
+    for (i, opt) in options.iter().enumerate() {
+        if options[..i].contains(opt) {
+            errors.push(format!("Duplicate option: {}", opt));
+        }
+    }
+    errors
+}
+
+pub fn check_answer_idx(options: &[String], idx: i64) -> Option<String> {
+    if idx < 0 || idx as usize >= options.len() {
+        Some(format!(
+            "Correct answer index {} is out of range (0 to {})",
+            idx,
+            options.len().saturating_sub(1)
+        ))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,295 @@
+// Moodle XML question format (https://docs.moodle.org/en/Moodle_XML_format).
+//
+// Maps `multichoice`, `truefalse` and `shortanswer` questions; anything else
+// is reported as skipped. Categories come from the preceding
+// `<question type="category">` entry, as in Moodle's own exports.
+
+use roxmltree::{Document, Node};
+
+use super::gift::{partial_credit, split_category_path};
+use super::schema::{ContentBank, QuestionDoc};
+use super::{question_id_for, slugify, xml_escape as escape, ParsedBank};
+use crate::models::{KIND_CHOICE, KIND_MULTIPLE_RESPONSE, KIND_ORDER, KIND_TEXT_ENTRY};
+
+const DEFAULT_CATEGORY: &str = "Imported";
+
+pub fn parse(input: &str) -> ParsedBank {
+    let mut parsed = ParsedBank::default();
+    let doc = match Document::parse(input) {
+        Ok(doc) => doc,
+        Err(e) => {
+            parsed.errors.push(e.to_string());
+            return parsed;
+        }
+    };
+    if doc.root_element().tag_name().name() != "quiz" {
+        parsed.errors.push("root element must be <quiz>".to_string());
+        return parsed;
+    }
+
+    let mut category = DEFAULT_CATEGORY.to_string();
+    let mut subcategory: Option<String> = None;
+    for node in doc.root_element().children().filter(|n| n.has_tag_name("question")) {
+        let line = doc.text_pos_at(node.range().start).row;
+        let kind = node.attribute("type").unwrap_or("");
+        let result = match kind {
+            "category" => {
+                let path = child_text(node, "category").unwrap_or_default();
+                (category, subcategory) = split_category_path(&path);
+                continue;
+            }
+            "multichoice" => parse_multichoice(node),
+            "truefalse" => parse_truefalse(node),
+            "shortanswer" => parse_shortanswer(node),
+            other => {
+                parsed.warnings.push(format!("line {}: skipped {} question", line, other));
+                continue;
+            }
+        };
+        match result {
+            Ok(q) => parsed.bank.push_question(&category, subcategory.as_deref(), q),
+            Err(e) => parsed.errors.push(format!("line {}: {}", line, e)),
+        }
+    }
+    parsed
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+// Text of <name><text>...</text></name>, honouring the element's format
+fn child_text(node: Node, name: &str) -> Option<String> {
+    let el = child(node, name)?;
+    formatted_text(el)
+}
+
+fn formatted_text(el: Node) -> Option<String> {
+    let text = child(el, "text")?.text().unwrap_or("");
+    let text = match el.attribute("format") {
+        Some("html") => strip_html(text),
+        _ => text.trim().to_string(),
+    };
+    Some(text)
+}
+
+// Minimal HTML to text: drop tags and decode the common entities
+fn strip_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn question_common(node: Node) -> Result<(String, String, Option<String>, Vec<String>), String> {
+    let question_text = child_text(node, "questiontext").unwrap_or_default();
+    if question_text.is_empty() {
+        return Err("question text is empty".to_string());
+    }
+    let id = match child_text(node, "name").map(|n| n.trim().to_string()) {
+        Some(n) if !n.is_empty() && n.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => n,
+        Some(n) if !slugify(&n).is_empty() => slugify(&n),
+        _ => question_id_for(&question_text),
+    };
+    let explanation = child_text(node, "generalfeedback").filter(|s| !s.is_empty());
+    let tags = child(node, "tags")
+        .map(|tags| {
+            tags.children()
+                .filter(|n| n.has_tag_name("tag"))
+                .filter_map(|t| child(t, "text").and_then(|t| t.text()))
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((id, question_text, explanation, tags))
+}
+
+fn answers(node: Node) -> Result<Vec<(f64, String)>, String> {
+    node.children()
+        .filter(|n| n.has_tag_name("answer"))
+        .map(|a| {
+            let fraction: f64 = a
+                .attribute("fraction")
+                .unwrap_or("0")
+                .parse()
+                .map_err(|_| "invalid answer fraction".to_string())?;
+            Ok((fraction, formatted_text(a).unwrap_or_default()))
+        })
+        .collect()
+}
+
+fn parse_multichoice(node: Node) -> Result<QuestionDoc, String> {
+    let (id, question_text, explanation, tags) = question_common(node)?;
+    let answers = answers(node)?;
+    let single = child(node, "single")
+        .and_then(|s| s.text())
+        .is_none_or(|s| s.trim() != "false");
+
+    let correct: Vec<usize> = answers
+        .iter()
+        .enumerate()
+        .filter(|(_, (fraction, _))| *fraction > 0.0)
+        .map(|(i, _)| i)
+        .collect();
+    let (kind, correct_idx, correct_answers) = match correct.len() {
+        0 => return Err("no answer has a positive fraction".to_string()),
+        1 => (KIND_CHOICE, correct[0], Vec::new()),
+        _ if !single => (
+            KIND_MULTIPLE_RESPONSE,
+            correct[0],
+            correct.iter().map(|i| *i as i64).collect(),
+        ),
+        // Single-answer with partial credit: keep the best answer
+        _ => (
+            KIND_CHOICE,
+            correct
+                .iter()
+                .copied()
+                .max_by(|a, b| answers[*a].0.total_cmp(&answers[*b].0))
+                .unwrap_or(correct[0]),
+            Vec::new(),
+        ),
+    };
+
+    Ok(QuestionDoc {
+        id,
+        kind: kind.to_string(),
+        question_text,
+        options: answers.into_iter().map(|(_, text)| text).collect(),
+        correct_answer_idx: correct_idx as i64,
+        correct_answers,
+        explanation,
+        tags,
+    })
+}
+
+// Every answer with a positive fraction is accepted
+fn parse_shortanswer(node: Node) -> Result<QuestionDoc, String> {
+    let (id, question_text, explanation, tags) = question_common(node)?;
+    let accepted: Vec<String> = answers(node)?
+        .into_iter()
+        .filter(|(fraction, _)| *fraction > 0.0)
+        .map(|(_, text)| text)
+        .collect();
+    if accepted.is_empty() {
+        return Err("no answer has a positive fraction".to_string());
+    }
+
+    Ok(QuestionDoc {
+        id,
+        kind: KIND_TEXT_ENTRY.to_string(),
+        question_text,
+        options: accepted,
+        correct_answer_idx: 0,
+        correct_answers: Vec::new(),
+        explanation,
+        tags,
+    })
+}
+
+fn parse_truefalse(node: Node) -> Result<QuestionDoc, String> {
+    let (id, question_text, explanation, tags) = question_common(node)?;
+    let answers = answers(node)?;
+    let is_true = answers
+        .iter()
+        .find(|(fraction, _)| *fraction > 0.0)
+        .map(|(_, text)| text.eq_ignore_ascii_case("true"))
+        .ok_or("no answer has a positive fraction")?;
+
+    Ok(QuestionDoc {
+        id,
+        kind: KIND_CHOICE.to_string(),
+        question_text,
+        options: vec!["True".to_string(), "False".to_string()],
+        correct_answer_idx: if is_true { 0 } else { 1 },
+        correct_answers: Vec::new(),
+        explanation,
+        tags,
+    })
+}
+
+fn text_element(name: &str, text: &str) -> String {
+    format!("<{0} format=\"plain_text\"><text>{1}</text></{0}>", name, escape(text))
+}
+
+pub fn render(bank: &ContentBank) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n");
+    for cat in &bank.categories {
+        let sections = std::iter::once((cat.title.replace('/', "//"), &cat.questions)).chain(
+            cat.subcategories.iter().map(|sub| {
+                (format!("{}/{}", cat.title.replace('/', "//"), sub.title.replace('/', "//")), &sub.questions)
+            }),
+        );
+        for (path, questions) in sections {
+            if questions.is_empty() {
+                continue;
+            }
+            out.push_str(&format!(
+                "  <question type=\"category\">\n    <category><text>$course$/top/{}</text></category>\n  </question>\n",
+                escape(&path)
+            ));
+            for q in questions {
+                if q.kind == KIND_ORDER {
+                    out.push_str(&format!("  <!-- skipped order question {} -->\n", escape(&q.id)));
+                    continue;
+                }
+                let qtype = if q.kind == KIND_TEXT_ENTRY { "shortanswer" } else { "multichoice" };
+                out.push_str(&format!("  <question type=\"{}\">\n", qtype));
+                out.push_str(&format!("    <name><text>{}</text></name>\n", escape(&q.id)));
+                out.push_str(&format!("    {}\n", text_element("questiontext", &q.question_text)));
+                if let Some(explanation) = &q.explanation {
+                    out.push_str(&format!("    {}\n", text_element("generalfeedback", explanation)));
+                }
+                match q.kind.as_str() {
+                    KIND_TEXT_ENTRY => out.push_str("    <usecase>0</usecase>\n"),
+                    KIND_MULTIPLE_RESPONSE => {
+                        out.push_str("    <single>false</single>\n    <shuffleanswers>false</shuffleanswers>\n")
+                    }
+                    _ => out.push_str("    <single>true</single>\n    <shuffleanswers>false</shuffleanswers>\n"),
+                }
+                for (i, option) in q.options.iter().enumerate() {
+                    let fraction = match q.kind.as_str() {
+                        KIND_TEXT_ENTRY => "100".to_string(),
+                        KIND_MULTIPLE_RESPONSE if q.correct_answers.contains(&(i as i64)) => {
+                            partial_credit(q.correct_answers.len())
+                        }
+                        KIND_MULTIPLE_RESPONSE => "-100".to_string(),
+                        _ if i as i64 == q.correct_answer_idx => "100".to_string(),
+                        _ => "0".to_string(),
+                    };
+                    out.push_str(&format!(
+                        "    <answer fraction=\"{}\" format=\"plain_text\"><text>{}</text></answer>\n",
+                        fraction,
+                        escape(option)
+                    ));
+                }
+                if !q.tags.is_empty() {
+                    out.push_str("    <tags>\n");
+                    for tag in &q.tags {
+                        out.push_str(&format!("      <tag><text>{}</text></tag>\n", escape(tag)));
+                    }
+                    out.push_str("    </tags>\n");
+                }
+                out.push_str("  </question>\n");
+            }
+        }
+    }
+    out.push_str("</quiz>\n");
+    out
+}
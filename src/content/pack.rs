@@ -0,0 +1,464 @@
+// Content packs: a directory of markdown files, usually a git checkout,
+// that is the source of truth for the question bank.
+//
+//   content/
+//     web/                     category "web"
+//       _category.md           optional, front matter `title` (and `id`)
+//       csrf-tokens.md         question directly under the category
+//       xss/                   subcategory "web-xss"
+//         _subcategory.md      optional, front matter `title` (and `id`); body is the description
+//         xss-csp.md           question; its id defaults to the file stem
+//
+// A question file is YAML front matter with the same fields as a bank
+// question, followed by the question text. A heading named "Explanation"
+// starts the explanation:
+//
+//   ---
+//   options: [Content-Security-Policy, X-Powered-By, Server]
+//   correct_answer_idx: 0
+//   tags: [xss]
+//   ---
+//   Which response header mitigates reflected XSS?
+//
+//   ## Explanation
+//
+//   CSP restricts where scripts may load from.
+//
+// Syncing upserts everything through `import_bank` and soft-deletes entries
+// whose files were removed since the last sync. A file that fails to parse
+// is reported and whatever it held before is left alone.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+
+use super::import::{import_bank, ImportReport};
+use super::schema::QuestionDoc;
+use super::similarity::refresh_related;
+use super::ParsedBank;
+use crate::models::KIND_CHOICE;
+
+pub const CATEGORY_FILE: &str = "_category.md";
+pub const SUBCATEGORY_FILE: &str = "_subcategory.md";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SectionMeta {
+    id: Option<String>,
+    title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuestionMeta {
+    id: Option<String>,
+    kind: Option<String>,
+    #[serde(default)]
+    options: Vec<String>,
+    #[serde(default)]
+    correct_answer_idx: i64,
+    #[serde(default)]
+    correct_answers: Vec<i64>,
+    explanation: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// A content directory read into a bank
+#[derive(Debug, Default)]
+pub struct Pack {
+    pub parsed: ParsedBank,
+    // Files that could not be used, prefixed with their path
+    pub errors: Vec<String>,
+    // (entity, id, path) for every entry, path relative to the pack root
+    pub sources: Vec<(&'static str, String, String)>,
+    // Paths of files listed in `errors`
+    pub failed: HashSet<String>,
+    pub files: usize,
+}
+
+impl Pack {
+    fn fail(&mut self, path: &str, error: impl std::fmt::Display) {
+        self.errors.push(format!("{}: {}", path, error));
+        self.failed.insert(path.to_string());
+    }
+}
+
+// Read a content directory. Only I/O errors on the directory itself are
+// returned as Err; problems with individual files end up in `Pack::errors`.
+pub fn load(dir: &Path) -> Result<Pack> {
+    if !dir.is_dir() {
+        return Err(anyhow!("content directory {} does not exist", dir.display()));
+    }
+    let mut pack = Pack::default();
+    // Questions are added once all files are read, so that duplicate ids
+    // can be rejected in every file that uses them
+    let mut questions: Vec<(String, Option<String>, String, QuestionDoc)> = Vec::new();
+
+    for cat_dir in entries(dir)?.into_iter().filter(|p| p.is_dir()) {
+        let cat_name = file_name(&cat_dir);
+        let meta = read_section(&mut pack, &cat_dir.join(CATEGORY_FILE), &format!("{}/{}", cat_name, CATEGORY_FILE))
+            .map(|(meta, _)| meta)
+            .unwrap_or_default();
+        let cat_id = meta.id.unwrap_or_else(|| cat_name.clone());
+        let cat_title = meta.title.unwrap_or_else(|| cat_name.clone());
+        pack.parsed.bank.category_entry(&cat_id, &cat_title);
+        pack.sources.push(("category", cat_id.clone(), cat_name.clone()));
+
+        for path in entries(&cat_dir)? {
+            let name = file_name(&path);
+            let rel = format!("{}/{}", cat_name, name);
+            if path.is_dir() {
+                let meta = read_section(&mut pack, &path.join(SUBCATEGORY_FILE), &format!("{}/{}", rel, SUBCATEGORY_FILE));
+                let (meta, description) = meta.unwrap_or_default();
+                let sub_id = meta.id.unwrap_or_else(|| format!("{}-{}", cat_id, name));
+                let sub_title = meta.title.unwrap_or_else(|| name.clone());
+                let sub = pack.parsed.bank.category_entry(&cat_id, &cat_title).subcategory_entry(&sub_id, &sub_title);
+                sub.description = Some(description.trim().to_string()).filter(|d| !d.is_empty());
+                pack.sources.push(("subcategory", sub_id.clone(), rel.clone()));
+
+                for path in entries(&path)? {
+                    let q_rel = format!("{}/{}", rel, file_name(&path));
+                    if path.is_dir() {
+                        pack.fail(&q_rel, "directories nest at most two levels (category/subcategory)");
+                    } else if let Some(q) = read_question(&mut pack, &path, &q_rel) {
+                        questions.push((cat_id.clone(), Some(sub_id.clone()), q_rel, q));
+                    }
+                }
+            } else if let Some(q) = read_question(&mut pack, &path, &rel) {
+                questions.push((cat_id.clone(), None, rel, q));
+            }
+        }
+    }
+
+    let mut paths_by_id: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (_, _, rel, q) in &questions {
+        paths_by_id.entry(q.id.as_str()).or_default().push(rel);
+    }
+    let duplicates: Vec<(String, String)> = paths_by_id
+        .values()
+        .filter(|paths| paths.len() > 1)
+        .flat_map(|paths| paths.iter().map(|p| (p.to_string(), paths.join(", "))))
+        .collect();
+    for (rel, paths) in duplicates {
+        pack.fail(&rel, format!("question id is used by more than one file ({})", paths));
+    }
+
+    for (cat_id, sub_id, rel, q) in questions {
+        if pack.failed.contains(&rel) {
+            continue;
+        }
+        pack.sources.push(("question", q.id.clone(), rel));
+        let cat = pack.parsed.bank.categories.iter_mut().find(|c| c.id == cat_id).unwrap();
+        match sub_id {
+            Some(sub_id) => cat.subcategories.iter_mut().find(|s| s.id == sub_id).unwrap().questions.push(q),
+            None => cat.questions.push(q),
+        }
+    }
+    Ok(pack)
+}
+
+// Markdown files and directories, sorted, without hidden entries or the
+// `_category.md` / `_subcategory.md` metadata files
+fn entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = file_name(&path);
+        if name.starts_with('.') || name.starts_with('_') {
+            continue;
+        }
+        if path.is_dir() || path.extension().is_some_and(|e| e == "md") {
+            out.push(path);
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn read_section(pack: &mut Pack, path: &Path, rel: &str) -> Option<(SectionMeta, String)> {
+    if !path.is_file() {
+        return None;
+    }
+    pack.files += 1;
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            pack.fail(rel, e);
+            return None;
+        }
+    };
+    let (front, body) = split_front_matter(&text).unwrap_or(("", text.as_str()));
+    match parse_front_matter::<SectionMeta>(front) {
+        Ok(meta) => Some((meta.unwrap_or_default(), body.to_string())),
+        Err(e) => {
+            pack.fail(rel, e);
+            None
+        }
+    }
+}
+
+fn read_question(pack: &mut Pack, path: &Path, rel: &str) -> Option<QuestionDoc> {
+    pack.files += 1;
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            pack.fail(rel, e);
+            return None;
+        }
+    };
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match parse_question(&text, &stem) {
+        Ok(q) => Some(q),
+        Err(errors) => {
+            for e in errors {
+                pack.fail(rel, e);
+            }
+            None
+        }
+    }
+}
+
+// Parse one question file; `stem` is the id used when the front matter has none
+pub fn parse_question(text: &str, stem: &str) -> Result<QuestionDoc, Vec<String>> {
+    let Some((front, body)) = split_front_matter(text) else {
+        return Err(vec!["missing front matter (the file must start with a line of ---)".to_string()]);
+    };
+    let meta = match parse_front_matter::<QuestionMeta>(front) {
+        Ok(Some(meta)) => meta,
+        Ok(None) => return Err(vec!["front matter is empty".to_string()]),
+        Err(e) => return Err(vec![e]),
+    };
+
+    let (question_text, body_explanation) = split_explanation(body);
+    let explanation = match (meta.explanation, body_explanation) {
+        (Some(_), Some(_)) => {
+            return Err(vec!["explanation is given both in front matter and as a section".to_string()]);
+        }
+        (front, body) => front.as_deref().or(body).map(|e| e.trim().to_string()).filter(|e| !e.is_empty()),
+    };
+
+    let q = QuestionDoc {
+        id: meta.id.unwrap_or_else(|| stem.to_string()),
+        kind: meta.kind.unwrap_or_else(|| KIND_CHOICE.to_string()),
+        question_text: question_text.trim().to_string(),
+        options: meta.options,
+        correct_answer_idx: meta.correct_answer_idx,
+        correct_answers: meta.correct_answers,
+        explanation,
+        tags: meta.tags,
+    };
+    let errors = q.validate();
+    if errors.is_empty() { Ok(q) } else { Err(errors) }
+}
+
+// Split "---\n<yaml>\n---\n<body>" into its two parts
+fn split_front_matter(text: &str) -> Option<(&str, &str)> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let rest = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n"))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn parse_front_matter<T: serde::de::DeserializeOwned>(front: &str) -> Result<Option<T>, String> {
+    if front.trim().is_empty() {
+        return Ok(None);
+    }
+    // Front matter starts on the file's second line
+    serde_yaml::from_str(front).map(Some).map_err(|e| match e.location() {
+        Some(loc) => format!("line {}: {}", loc.line() + 1, e),
+        None => e.to_string(),
+    })
+}
+
+// The text before an "Explanation" heading, and the text after it
+fn split_explanation(body: &str) -> (&str, Option<&str>) {
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') && trimmed.trim_start_matches('#').trim().eq_ignore_ascii_case("explanation") {
+            return (&body[..offset], Some(&body[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    (body, None)
+}
+
+// What the last sync did
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub finished_at: String,
+    pub files: usize,
+    // Files that were skipped, and anything that stopped the sync
+    pub errors: Vec<String>,
+    pub import: ImportReport,
+    // "entity id" of entries soft-deleted because their files are gone
+    pub retired: Vec<String>,
+}
+
+pub async fn sync_dir(db: &Pool<Sqlite>, dir: &Path) -> Result<SyncReport> {
+    let pack = load(dir)?;
+    let import = import_bank(db, &pack.parsed, false).await?;
+    let mut report = SyncReport {
+        files: pack.files,
+        errors: pack.errors.clone(),
+        ..Default::default()
+    };
+    if import.applied {
+        report.retired = record_sources(db, &pack).await?;
+        refresh_related(&mut *db.acquire().await?).await?;
+    }
+    report.import = import;
+    Ok(report)
+}
+
+// Remember which entries came from the pack and retire the ones whose
+// files have gone. Entries from files that failed to parse are kept.
+async fn record_sources(db: &Pool<Sqlite>, pack: &Pack) -> Result<Vec<String>> {
+    let current: HashSet<(&str, &str)> = pack.sources.iter().map(|(e, id, _)| (*e, id.as_str())).collect();
+
+    let mut tx = db.begin().await?;
+    let now: String = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP").fetch_one(&mut tx).await?;
+    let previous: Vec<(String, String, String)> = sqlx::query_as("SELECT entity, id, path FROM content_pack_entries")
+        .fetch_all(&mut tx)
+        .await?;
+
+    let mut retired = Vec::new();
+    for (entity, id, path) in previous {
+        if current.contains(&(entity.as_str(), id.as_str())) || pack.failed.contains(&path) {
+            continue;
+        }
+        let sql = match entity.as_str() {
+            "category" => "UPDATE categories SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+            "subcategory" => "UPDATE subcategories SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+            _ => "UPDATE questions SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+        };
+        sqlx::query(sql).bind(&now).bind(&id).execute(&mut tx).await?;
+        sqlx::query("DELETE FROM content_pack_entries WHERE entity = ? AND id = ?")
+            .bind(&entity)
+            .bind(&id)
+            .execute(&mut tx)
+            .await?;
+        retired.push(format!("{} {}", entity, id));
+    }
+
+    for (entity, id, path) in &pack.sources {
+        sqlx::query(
+            "INSERT INTO content_pack_entries (entity, id, path) VALUES (?, ?, ?)
+             ON CONFLICT (entity, id) DO UPDATE SET path = excluded.path"
+        )
+        .bind(entity)
+        .bind(id)
+        .bind(path)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(retired)
+}
+
+// The configured content directory, shared between the watcher and the
+// admin page
+#[derive(Debug, Clone, Default)]
+pub struct ContentPack {
+    pub dir: Option<PathBuf>,
+    last: Arc<Mutex<Option<SyncReport>>>,
+    // Keeps a sync from the admin page from overlapping the watcher's
+    running: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl ContentPack {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir, ..Default::default() }
+    }
+
+    pub fn last_report(&self) -> MutexGuard<'_, Option<SyncReport>> {
+        self.last.lock().unwrap()
+    }
+
+    // Sync the directory, if one is configured, and keep the report
+    pub async fn sync(&self, db: &Pool<Sqlite>) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let _running = self.running.lock().await;
+        let mut report = sync_dir(db, dir).await.unwrap_or_else(|e| SyncReport {
+            errors: vec![e.to_string()],
+            ..Default::default()
+        });
+        report.finished_at = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP")
+            .fetch_one(db)
+            .await
+            .unwrap_or_default();
+
+        println!(
+            "Content pack {}: {} files, {}, {} retired, {} error(s)",
+            dir.display(),
+            report.files,
+            report.import.summary(),
+            report.retired.len(),
+            report.errors.len() + report.import.errors.len()
+        );
+        *self.last.lock().unwrap() = Some(report);
+    }
+
+    // Poll the directory and sync whenever a file is added, removed or
+    // modified. Polling keeps this working on bind mounts and network
+    // filesystems where change notifications are unreliable.
+    pub fn watch(&self, db: Pool<Sqlite>, interval: Duration) {
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        let pack = self.clone();
+        tokio::spawn(async move {
+            let mut seen = fingerprint(&dir);
+            loop {
+                tokio::time::sleep(interval).await;
+                let current = fingerprint(&dir);
+                if current != seen {
+                    seen = current;
+                    pack.sync(&db).await;
+                }
+            }
+        });
+    }
+}
+
+// Path, size and modification time of every file under `dir`
+fn fingerprint(dir: &Path) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    let mut out = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(read) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read.flatten() {
+            let path = entry.path();
+            if file_name(&path).starts_with('.') {
+                continue;
+            }
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => stack.push(path),
+                Ok(meta) => out.push((path, meta.len(), meta.modified().ok())),
+                Err(_) => {}
+            }
+        }
+    }
+    out.sort();
+    out
+}
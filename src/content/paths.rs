@@ -0,0 +1,177 @@
+// Learning paths: ordered lessons, quizzes and challenges. A step unlocks
+// once every step it requires is completed; completions are recorded per
+// user as they happen, so a step completed while locked never counts.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
+
+use crate::models::PathStep;
+
+pub const STATE_LOCKED: &str = "locked";
+pub const STATE_UNLOCKED: &str = "unlocked";
+pub const STATE_COMPLETED: &str = "completed";
+
+// (value, label) of the step kinds
+pub const STEP_KINDS: &[(&str, &str)] = &[("lesson", "Lesson"), ("quiz", "Quiz"), ("challenge", "Challenge")];
+
+// SQL condition that step `s` has no unmet requirements for user ?
+const UNLOCKED_SQL: &str = "NOT EXISTS (
+        SELECT 1 FROM path_step_prereqs r JOIN path_steps req ON req.id = r.requires_id
+        WHERE r.step_id = s.id AND req.deleted_at IS NULL
+            AND NOT EXISTS (SELECT 1 FROM path_step_completions c WHERE c.step_id = r.requires_id AND c.user_id = ?)
+    )";
+
+// A step as one learner sees it
+#[derive(Debug)]
+pub struct StepProgress {
+    pub step: PathStep,
+    // STATE_LOCKED, STATE_UNLOCKED or STATE_COMPLETED
+    pub state: &'static str,
+    // Titles of the steps it requires
+    pub requires: Vec<String>,
+    pub score_pct: Option<i64>,
+}
+
+impl StepProgress {
+    pub fn is_locked(&self) -> bool {
+        self.state == STATE_LOCKED
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state == STATE_COMPLETED
+    }
+}
+
+pub fn hash_flag(flag: &str) -> String {
+    Sha256::digest(flag.trim().as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub async fn path_steps(conn: &mut SqliteConnection, path_id: &str) -> Result<Vec<PathStep>> {
+    let steps = sqlx::query_as::<_, PathStep>(
+        "SELECT * FROM path_steps WHERE path_id = ? AND deleted_at IS NULL ORDER BY position, created_at"
+    )
+    .bind(path_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(steps)
+}
+
+// The ids of the live steps a step requires
+pub async fn step_prereqs(conn: &mut SqliteConnection, step_id: &str) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar(
+        "SELECT r.requires_id FROM path_step_prereqs r JOIN path_steps s ON s.id = r.requires_id
+         WHERE r.step_id = ? AND s.deleted_at IS NULL ORDER BY s.position"
+    )
+    .bind(step_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(ids)
+}
+
+// Every step of a path with its state for the user; without a user nothing
+// is completed
+pub async fn path_progress(conn: &mut SqliteConnection, path_id: &str, user_id: Option<&str>) -> Result<Vec<StepProgress>> {
+    let steps = path_steps(conn, path_id).await?;
+    let completions: HashMap<String, Option<i64>> = match user_id {
+        Some(user_id) => sqlx::query_as::<_, (String, Option<i64>)>(
+            "SELECT c.step_id, c.score_pct FROM path_step_completions c JOIN path_steps s ON s.id = c.step_id
+             WHERE c.user_id = ? AND s.path_id = ?"
+        )
+        .bind(user_id)
+        .bind(path_id)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect(),
+        None => HashMap::new(),
+    };
+    let titles: HashMap<String, String> = steps.iter().map(|s| (s.id.clone(), s.title.clone())).collect();
+
+    let mut progress = Vec::new();
+    for step in steps {
+        let prereqs = step_prereqs(conn, &step.id).await?;
+        let state = if completions.contains_key(&step.id) {
+            STATE_COMPLETED
+        } else if prereqs.iter().all(|id| completions.contains_key(id)) {
+            STATE_UNLOCKED
+        } else {
+            STATE_LOCKED
+        };
+        progress.push(StepProgress {
+            score_pct: completions.get(&step.id).copied().flatten(),
+            requires: prereqs.iter().filter_map(|id| titles.get(id).cloned()).collect(),
+            state,
+            step,
+        });
+    }
+    Ok(progress)
+}
+
+pub async fn is_unlocked(conn: &mut SqliteConnection, user_id: &str, step_id: &str) -> Result<bool> {
+    let unlocked: Option<String> = sqlx::query_scalar(&format!("SELECT s.id FROM path_steps s WHERE s.id = ? AND {}", UNLOCKED_SQL))
+        .bind(step_id)
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(unlocked.is_some())
+}
+
+// Mark a step done, keeping the best score and the attempt that got it
+pub async fn complete_step(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    step_id: &str,
+    score_pct: Option<i64>,
+    attempt_id: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO path_step_completions (user_id, step_id, score_pct, attempt_id) VALUES (?, ?, ?, ?)
+         ON CONFLICT (user_id, step_id) DO UPDATE SET
+             attempt_id = CASE WHEN excluded.score_pct > COALESCE(score_pct, -1) THEN excluded.attempt_id ELSE attempt_id END,
+             score_pct = MAX(COALESCE(score_pct, 0), COALESCE(excluded.score_pct, 0))"
+    )
+    .bind(user_id)
+    .bind(step_id)
+    .bind(score_pct)
+    .bind(attempt_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Credit a submitted quiz on `topic_id` to the unlocked quiz steps on that
+// topic it passes. Returns the titles of the steps it counted for.
+pub async fn record_quiz_pass(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    topic_id: &str,
+    correct: i64,
+    total: i64,
+    attempt_id: &str,
+) -> Result<Vec<String>> {
+    if total == 0 {
+        return Ok(Vec::new());
+    }
+    let steps = sqlx::query_as::<_, PathStep>(&format!(
+        "SELECT s.* FROM path_steps s JOIN learning_paths p ON p.id = s.path_id
+         WHERE s.kind = 'quiz' AND s.topic_id = ? AND s.deleted_at IS NULL AND p.deleted_at IS NULL
+             AND ? * 100 >= s.pass_pct * ? AND {}
+         ORDER BY p.title, s.position",
+        UNLOCKED_SQL
+    ))
+    .bind(topic_id)
+    .bind(correct)
+    .bind(total)
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let score_pct = correct * 100 / total;
+    for step in &steps {
+        complete_step(conn, user_id, &step.id, Some(score_pct), Some(attempt_id)).await?;
+    }
+    Ok(steps.into_iter().map(|s| s.title).collect())
+}
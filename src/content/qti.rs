@@ -0,0 +1,887 @@
+// IMS QTI content packages (https://www.1edtech.org/standards/qti).
+//
+// A package is a zip with an `imsmanifest.xml` listing item and test files.
+// Both QTI 2.1 and 3.0 are read: element and attribute names are compared
+// after dropping the 3.0 `qti-` prefix and dashes, so `choiceInteraction`
+// and `qti-choice-interaction` look the same. Items with one choice,
+// text-entry or order interaction become questions; anything else is
+// reported as skipped.
+//
+// Each assessment test maps to a category and each of its sections to a
+// subcategory, except a section titled like its test, which holds the
+// category's own questions. Items no test refers to land in "Imported".
+// Tags travel as LOM keywords on the item's manifest entry.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+use anyhow::Result;
+use roxmltree::{Document, Node};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::schema::{CategoryDoc, ContentBank, QuestionDoc};
+use super::{question_id_for, xml_escape as escape, ParsedBank, BLANK};
+use crate::models::{KIND_CHOICE, KIND_MULTIPLE_RESPONSE, KIND_ORDER, KIND_TEXT_ENTRY};
+
+const DEFAULT_CATEGORY: &str = "Imported";
+const MANIFEST: &str = "imsmanifest.xml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V2p1,
+    V3p0,
+}
+
+enum Item {
+    Question(QuestionDoc),
+    Unsupported(String),
+}
+
+// ----- Import -----
+
+pub fn parse(input: &[u8]) -> ParsedBank {
+    let mut parsed = ParsedBank::default();
+    let files = match read_package(input) {
+        Ok(files) => files,
+        Err(e) => {
+            parsed.errors.push(format!("not a readable zip package ({})", e));
+            return parsed;
+        }
+    };
+    let Some(manifest) = files.get(MANIFEST) else {
+        parsed.errors.push(format!("{} not found in the package", MANIFEST));
+        return parsed;
+    };
+    let manifest = match Document::parse(manifest) {
+        Ok(doc) => doc,
+        Err(e) => {
+            parsed.errors.push(format!("{}: {}", MANIFEST, e));
+            return parsed;
+        }
+    };
+
+    let mut item_paths: Vec<String> = Vec::new();
+    let mut test_paths: Vec<String> = Vec::new();
+    let mut keywords: HashMap<String, Vec<String>> = HashMap::new();
+    for resource in manifest.descendants().filter(|n| is(*n, "resource")) {
+        let href = attr(resource, "href")
+            .or_else(|| resource.children().find(|n| is(*n, "file")).and_then(|f| attr(f, "href")));
+        let (Some(kind), Some(href)) = (attr(resource, "type"), href) else {
+            continue;
+        };
+        let path = resolve("", href);
+        if kind.starts_with("imsqti_item") {
+            let tags: Vec<String> = resource
+                .descendants()
+                .filter(|n| is(*n, "keyword"))
+                .flat_map(|k| k.descendants().filter(|n| is(*n, "string")))
+                .filter_map(|s| s.text())
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect();
+            keywords.insert(path.clone(), tags);
+            item_paths.push(path);
+        } else if kind.starts_with("imsqti_test") {
+            test_paths.push(path);
+        }
+    }
+
+    // Categories and subcategories come from the tests, in order, so that
+    // empty ones survive a round trip too
+    let mut placements: Vec<(String, String, Option<String>)> = Vec::new();
+    for path in &test_paths {
+        match files.get(path).map(|xml| read_test(xml, path, &mut parsed.bank)) {
+            Some(Ok(refs)) => placements.extend(refs),
+            Some(Err(e)) => parsed.errors.push(format!("{}: {}", path, e)),
+            None => parsed.errors.push(format!("{}: listed in the manifest but missing", path)),
+        }
+    }
+    for (item_path, _, _) in &placements {
+        if !item_paths.contains(item_path) {
+            item_paths.push(item_path.clone());
+        }
+    }
+
+    let mut questions: HashMap<String, QuestionDoc> = HashMap::new();
+    for path in &item_paths {
+        let Some(xml) = files.get(path) else {
+            parsed.errors.push(format!("{}: referenced but missing from the package", path));
+            continue;
+        };
+        match parse_item(xml) {
+            Ok(Item::Question(mut q)) => {
+                q.tags = keywords.remove(path).unwrap_or_default();
+                questions.insert(path.clone(), q);
+            }
+            Ok(Item::Unsupported(what)) => parsed.warnings.push(format!("{}: skipped {} item", path, what)),
+            Err(e) => parsed.errors.push(format!("{}: {}", path, e)),
+        }
+    }
+
+    for (item_path, cat_id, sub_id) in placements {
+        let Some(q) = questions.remove(&item_path) else {
+            continue;
+        };
+        let Some(cat) = parsed.bank.categories.iter_mut().find(|c| c.id == cat_id) else {
+            continue;
+        };
+        match sub_id.and_then(|id| cat.subcategories.iter_mut().find(|s| s.id == id)) {
+            Some(sub) => sub.questions.push(q),
+            None => cat.questions.push(q),
+        }
+    }
+    for path in &item_paths {
+        if let Some(q) = questions.remove(path) {
+            parsed.bank.push_question(DEFAULT_CATEGORY, None, q);
+        }
+    }
+    parsed
+}
+
+// Every XML file in the zip, keyed by its path inside the package
+fn read_package(input: &[u8]) -> Result<HashMap<String, String>> {
+    let mut archive = ZipArchive::new(Cursor::new(input))?;
+    let mut files = HashMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() || !file.name().to_ascii_lowercase().ends_with(".xml") {
+            continue;
+        }
+        let name = resolve("", file.name());
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        files.insert(name, text);
+    }
+    Ok(files)
+}
+
+// Resolve `href` against the directory `base`, dropping "." and ".."
+fn resolve(base: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+fn dir_of(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+// Add the test's category and subcategories to the bank and return where
+// each referenced item belongs: (item path, category id, subcategory id)
+fn read_test(xml: &str, path: &str, bank: &mut ContentBank) -> Result<Vec<(String, String, Option<String>)>, String> {
+    let doc = Document::parse(xml).map_err(|e| e.to_string())?;
+    let root = doc.root_element();
+    if !is(root, "assessmenttest") {
+        return Err("root element must be assessmentTest".to_string());
+    }
+    let cat_id = attr(root, "identifier").ok_or("assessmentTest has no identifier")?.to_string();
+    let cat_title = attr(root, "title").unwrap_or(&cat_id).trim().to_string();
+    let cat = bank.category_entry(&cat_id, &cat_title);
+
+    let mut refs = Vec::new();
+    let sections = root.descendants().filter(|n| is(*n, "assessmentsection")).filter(|n| {
+        n.children().any(|c| is(c, "assessmentitemref")) || !n.children().any(|c| is(c, "assessmentsection"))
+    });
+    for section in sections {
+        // Nested sections are flattened, their titles joined into one
+        let titles: Vec<&str> = section
+            .ancestors()
+            .filter(|n| is(*n, "assessmentsection"))
+            .filter_map(|n| attr(n, "title"))
+            .map(str::trim)
+            .collect();
+        let title = titles.into_iter().rev().collect::<Vec<_>>().join(" / ");
+
+        let sub_id = if title.is_empty() || title == cat_title {
+            None
+        } else {
+            let id = attr(section, "identifier").ok_or("assessmentSection has no identifier")?;
+            let sub = cat.subcategory_entry(id, &title);
+            if sub.description.is_none() {
+                sub.description = section
+                    .children()
+                    .find(|n| is(*n, "rubricblock"))
+                    .map(text_of)
+                    .filter(|d| !d.is_empty());
+            }
+            Some(id.to_string())
+        };
+        for item_ref in section.children().filter(|n| is(*n, "assessmentitemref")) {
+            let href = attr(item_ref, "href").ok_or("assessmentItemRef has no href")?;
+            refs.push((resolve(dir_of(path), href), cat_id.clone(), sub_id.clone()));
+        }
+    }
+    Ok(refs)
+}
+
+fn parse_item(xml: &str) -> Result<Item, String> {
+    let doc = Document::parse(xml).map_err(|e| e.to_string())?;
+    let root = doc.root_element();
+    if !is(root, "assessmentitem") {
+        return Err("root element must be assessmentItem".to_string());
+    }
+    let body = root.children().find(|n| is(*n, "itembody")).ok_or("missing itemBody")?;
+    let interactions: Vec<Node> = body
+        .descendants()
+        .filter(|n| n.is_element() && local_name(*n).ends_with("interaction"))
+        .collect();
+    let interaction = match interactions.as_slice() {
+        [one] => *one,
+        [] => return Ok(Item::Unsupported("informational".to_string())),
+        _ => return Ok(Item::Unsupported("multi-interaction".to_string())),
+    };
+    let interaction_name = local_name(interaction);
+    if !matches!(
+        interaction_name.as_str(),
+        "choiceinteraction" | "orderinteraction" | "textentryinteraction"
+    ) {
+        return Ok(Item::Unsupported(interaction.tag_name().name().to_string()));
+    }
+
+    let response_id = attr(interaction, "responseidentifier").unwrap_or("RESPONSE");
+    let declaration = root
+        .children()
+        .find(|n| is(*n, "responsedeclaration") && attr(*n, "identifier") == Some(response_id));
+    let correct: Vec<String> = declaration
+        .and_then(|d| d.children().find(|n| is(*n, "correctresponse")))
+        .map(|c| {
+            c.children()
+                .filter(|n| is(*n, "value"))
+                .map(|v| v.text().unwrap_or("").trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut question_text = text_of(body);
+    // A blank on a line of its own adds nothing to the question
+    if let Some(text) = question_text.strip_suffix(BLANK)
+        && (text.is_empty() || text.ends_with('\n'))
+    {
+        question_text = text.trim_end().to_string();
+    }
+    if question_text.is_empty() {
+        return Err("question text is empty".to_string());
+    }
+    let id = match attr(root, "identifier").map(str::trim) {
+        Some(id) if !id.is_empty() => id.to_string(),
+        _ => question_id_for(&question_text),
+    };
+    let explanation = root
+        .children()
+        .find(|n| is(*n, "modalfeedback"))
+        .map(text_of)
+        .filter(|s| !s.is_empty());
+
+    let choices: Vec<(&str, String)> = interaction
+        .children()
+        .filter(|n| is(*n, "simplechoice"))
+        .map(|c| (attr(c, "identifier").unwrap_or(""), text_of(c)))
+        .collect();
+    let choice_idx = |value: &String| {
+        choices
+            .iter()
+            .position(|(id, _)| id == value)
+            .ok_or_else(|| format!("correct response '{}' is not one of the choices", value))
+    };
+
+    let mut q = QuestionDoc {
+        id,
+        kind: KIND_CHOICE.to_string(),
+        question_text,
+        options: Vec::new(),
+        correct_answer_idx: 0,
+        correct_answers: Vec::new(),
+        explanation,
+        tags: Vec::new(),
+    };
+    match interaction_name.as_str() {
+        "choiceinteraction" => {
+            let correct = correct.iter().map(choice_idx).collect::<Result<Vec<_>, _>>()?;
+            let max_choices: usize = attr(interaction, "maxchoices").and_then(|m| m.parse().ok()).unwrap_or(1);
+            match correct.as_slice() {
+                [] => return Err("no correct response".to_string()),
+                [idx] if max_choices == 1 => q.correct_answer_idx = *idx as i64,
+                _ => {
+                    q.kind = KIND_MULTIPLE_RESPONSE.to_string();
+                    q.correct_answers = correct.iter().map(|i| *i as i64).collect();
+                    q.correct_answer_idx = q.correct_answers[0];
+                }
+            }
+            q.options = choices.into_iter().map(|(_, text)| text).collect();
+        }
+        "orderinteraction" => {
+            let order = correct.iter().map(choice_idx).collect::<Result<Vec<_>, _>>()?;
+            if order.len() != choices.len() {
+                return Err("the correct order must list every choice once".to_string());
+            }
+            q.kind = KIND_ORDER.to_string();
+            q.options = order.into_iter().map(|i| choices[i].1.clone()).collect();
+        }
+        _ => {
+            // Text entry: the correct response plus every mapped answer worth points
+            let mapped = declaration
+                .and_then(|d| d.children().find(|n| is(*n, "mapping")))
+                .map(|m| {
+                    m.children()
+                        .filter(|n| is(*n, "mapentry"))
+                        .filter(|e| attr(*e, "mappedvalue").and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0) > 0.0)
+                        .filter_map(|e| attr(e, "mapkey"))
+                        .map(|k| k.trim().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            for answer in correct.into_iter().chain(mapped) {
+                if !answer.is_empty() && !q.options.iter().any(|o| o.eq_ignore_ascii_case(&answer)) {
+                    q.options.push(answer);
+                }
+            }
+            if q.options.is_empty() {
+                return Err("no correct response".to_string());
+            }
+            q.kind = KIND_TEXT_ENTRY.to_string();
+        }
+    }
+    Ok(Item::Question(q))
+}
+
+// "qti-choice-interaction" and "choiceInteraction" -> "choiceinteraction"
+fn normalize(name: &str) -> String {
+    name.strip_prefix("qti-").unwrap_or(name).replace('-', "").to_ascii_lowercase()
+}
+
+fn local_name(node: Node) -> String {
+    normalize(node.tag_name().name())
+}
+
+fn is(node: Node, name: &str) -> bool {
+    node.is_element() && local_name(node) == name
+}
+
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|a| normalize(a.name()) == name).map(|a| a.value())
+}
+
+// Plain text of an element. Markup is flattened, block elements and <br>
+// start a new line, choices and inline feedback are left out and a
+// text-entry interaction shows as a blank.
+fn text_of(node: Node) -> String {
+    let mut raw = String::new();
+    collect_text(node, &mut raw);
+    raw.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collect_text(node: Node, out: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            out.push_str(&child.text().unwrap_or("").replace(['\n', '\r'], " "));
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+        match local_name(child).as_str() {
+            "simplechoice" | "feedbackinline" | "feedbackblock" | "rubricblock" | "responseprocessing" => {}
+            "textentryinteraction" => out.push_str(BLANK),
+            "br" => out.push('\n'),
+            "p" | "div" | "prompt" | "li" | "pre" | "blockquote" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                out.push('\n');
+                collect_text(child, out);
+                out.push('\n');
+            }
+            _ => collect_text(child, out),
+        }
+    }
+}
+
+// ----- Export -----
+
+pub fn render(bank: &ContentBank, version: Version) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut manifest = Xml::new(None);
+    manifest.raw("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let (cp_ns, schema, schema_version, suffix) = match version {
+        Version::V2p1 => ("http://www.imsglobal.org/xsd/imscp_v1p1", "QTIv2.1 Package", "1.0.0", "v2p1"),
+        Version::V3p0 => ("http://www.imsglobal.org/xsd/qti/qtiv3p0/imscp_v1p1", "QTI Package", "3.0.0", "v3p0"),
+    };
+    manifest.open(
+        "manifest",
+        &[
+            ("xmlns", cp_ns),
+            ("xmlns:imsmd", "http://ltsc.ieee.org/xsd/LOM"),
+            ("identifier", "hackademy-export"),
+        ],
+    );
+    manifest.open("metadata", &[]);
+    manifest.leaf("schema", &[], schema);
+    manifest.leaf("schemaversion", &[], schema_version);
+    manifest.close("metadata");
+    manifest.raw("<organizations/>");
+    manifest.open("resources", &[]);
+
+    for cat in &bank.categories {
+        let test_path = format!("tests/{}.xml", file_stem(&cat.id));
+        zip.start_file(test_path.as_str(), options)?;
+        zip.write_all(render_test(cat, version).as_bytes())?;
+
+        manifest.open(
+            "resource",
+            &[
+                ("identifier", &format!("test-{}", cat.id)),
+                ("type", &format!("imsqti_test_xml{}", suffix)),
+                ("href", &test_path),
+            ],
+        );
+        manifest.empty("file", &[("href", &test_path)]);
+        for q in cat.questions.iter().chain(cat.subcategories.iter().flat_map(|s| &s.questions)) {
+            manifest.empty("dependency", &[("identifierref", &format!("item-{}", q.id))]);
+        }
+        manifest.close("resource");
+    }
+
+    for (_, _, q) in bank.questions() {
+        let item_path = format!("items/{}.xml", file_stem(&q.id));
+        zip.start_file(item_path.as_str(), options)?;
+        zip.write_all(render_item(q, version).as_bytes())?;
+
+        manifest.open(
+            "resource",
+            &[
+                ("identifier", &format!("item-{}", q.id)),
+                ("type", &format!("imsqti_item_xml{}", suffix)),
+                ("href", &item_path),
+            ],
+        );
+        if !q.tags.is_empty() {
+            manifest.open("metadata", &[]);
+            manifest.open("imsmd:lom", &[]);
+            manifest.open("imsmd:general", &[]);
+            for tag in &q.tags {
+                manifest.open("imsmd:keyword", &[]);
+                manifest.leaf("imsmd:string", &[], tag);
+                manifest.close("imsmd:keyword");
+            }
+            manifest.close("imsmd:general");
+            manifest.close("imsmd:lom");
+            manifest.close("metadata");
+        }
+        manifest.empty("file", &[("href", &item_path)]);
+        manifest.close("resource");
+    }
+    manifest.close("resources");
+    manifest.close("manifest");
+
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(manifest.out.as_bytes())?;
+    Ok(zip.finish()?.into_inner())
+}
+
+// Ids end up in file names; keep those portable
+fn file_stem(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn render_test(cat: &CategoryDoc, version: Version) -> String {
+    let mut xml = Xml::new(Some(version));
+    xml.raw("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    xml.open(
+        "assessmentTest",
+        &[("xmlns", namespace(version)), ("identifier", &cat.id), ("title", &cat.title)],
+    );
+    xml.open(
+        "testPart",
+        &[("identifier", "part-1"), ("navigationMode", "nonlinear"), ("submissionMode", "simultaneous")],
+    );
+
+    // The category's own questions go in a section titled like the test
+    if !cat.questions.is_empty() {
+        render_section(&mut xml, &format!("{}-questions", cat.id), &cat.title, None, &cat.questions);
+    }
+    for sub in &cat.subcategories {
+        render_section(&mut xml, &sub.id, &sub.title, sub.description.as_deref(), &sub.questions);
+    }
+    xml.close("testPart");
+    xml.close("assessmentTest");
+    xml.out
+}
+
+fn render_section(xml: &mut Xml, id: &str, title: &str, description: Option<&str>, questions: &[QuestionDoc]) {
+    xml.open("assessmentSection", &[("identifier", id), ("title", title), ("visible", "true")]);
+    if let Some(description) = description {
+        xml.open("rubricBlock", &[("view", "candidate")]);
+        if xml.version == Some(Version::V3p0) {
+            xml.open("contentBody", &[]);
+        }
+        xml.paragraphs(description);
+        if xml.version == Some(Version::V3p0) {
+            xml.close("contentBody");
+        }
+        xml.close("rubricBlock");
+    }
+    for q in questions {
+        xml.empty(
+            "assessmentItemRef",
+            &[("identifier", &q.id), ("href", &format!("../items/{}.xml", file_stem(&q.id)))],
+        );
+    }
+    xml.close("assessmentSection");
+}
+
+fn render_item(q: &QuestionDoc, version: Version) -> String {
+    let mut xml = Xml::new(Some(version));
+    xml.raw("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    xml.open(
+        "assessmentItem",
+        &[
+            ("xmlns", namespace(version)),
+            ("identifier", &q.id),
+            ("title", &q.id),
+            ("adaptive", "false"),
+            ("timeDependent", "false"),
+        ],
+    );
+
+    let choice_id = |i: usize| format!("C{}", i);
+    let (cardinality, base_type, correct): (&str, &str, Vec<String>) = match q.kind.as_str() {
+        KIND_MULTIPLE_RESPONSE => (
+            "multiple",
+            "identifier",
+            q.correct_answers.iter().map(|i| choice_id(*i as usize)).collect(),
+        ),
+        KIND_ORDER => ("ordered", "identifier", (0..q.options.len()).map(choice_id).collect()),
+        KIND_TEXT_ENTRY => ("single", "string", q.options.iter().take(1).cloned().collect()),
+        _ => ("single", "identifier", vec![choice_id(q.correct_answer_idx as usize)]),
+    };
+    xml.open(
+        "responseDeclaration",
+        &[("identifier", "RESPONSE"), ("cardinality", cardinality), ("baseType", base_type)],
+    );
+    xml.open("correctResponse", &[]);
+    for value in &correct {
+        xml.leaf("value", &[], value);
+    }
+    xml.close("correctResponse");
+    if q.kind == KIND_TEXT_ENTRY {
+        xml.open("mapping", &[("defaultValue", "0")]);
+        for answer in &q.options {
+            xml.empty(
+                "mapEntry",
+                &[("mapKey", answer), ("mappedValue", "1"), ("caseSensitive", "false")],
+            );
+        }
+        xml.close("mapping");
+    }
+    xml.close("responseDeclaration");
+    xml.empty(
+        "outcomeDeclaration",
+        &[("identifier", "SCORE"), ("cardinality", "single"), ("baseType", "float")],
+    );
+    if q.explanation.is_some() {
+        xml.empty(
+            "outcomeDeclaration",
+            &[("identifier", "FEEDBACK"), ("cardinality", "single"), ("baseType", "identifier")],
+        );
+    }
+
+    xml.open("itemBody", &[]);
+    match q.kind.as_str() {
+        KIND_TEXT_ENTRY => {
+            let interaction = xml.empty_tag("textEntryInteraction", &[("responseIdentifier", "RESPONSE")]);
+            match q.question_text.split_once(BLANK) {
+                Some((before, after)) => {
+                    xml.raw(&format!("<p>{}{}{}</p>", with_breaks(before), interaction, with_breaks(after)))
+                }
+                None => {
+                    xml.paragraphs(&q.question_text);
+                    xml.raw(&format!("<p>{}</p>", interaction));
+                }
+            }
+        }
+        kind => {
+            let (name, max_choices) = match kind {
+                KIND_ORDER => ("orderInteraction", None),
+                KIND_MULTIPLE_RESPONSE => ("choiceInteraction", Some("0")),
+                _ => ("choiceInteraction", Some("1")),
+            };
+            let shuffle = if kind == KIND_ORDER { "true" } else { "false" };
+            let mut attrs = vec![("responseIdentifier", "RESPONSE"), ("shuffle", shuffle)];
+            attrs.extend(max_choices.map(|m| ("maxChoices", m)));
+            xml.open(name, &attrs);
+            let prompt = xml.name("prompt");
+            xml.raw(&format!("<{0}>{1}</{0}>", prompt, with_breaks(&q.question_text)));
+            for (i, option) in q.options.iter().enumerate() {
+                let choice = xml.name("simpleChoice");
+                xml.raw(&format!(
+                    "<{0} identifier=\"{1}\">{2}</{0}>",
+                    choice,
+                    choice_id(i),
+                    with_breaks(option)
+                ));
+            }
+            xml.close(name);
+        }
+    }
+    xml.close("itemBody");
+
+    let template = match (q.kind.as_str(), version) {
+        (KIND_TEXT_ENTRY, Version::V2p1) => "http://www.imsglobal.org/question/qti_v2p1/rptemplates/map_response",
+        (_, Version::V2p1) => "http://www.imsglobal.org/question/qti_v2p1/rptemplates/match_correct",
+        (KIND_TEXT_ENTRY, Version::V3p0) => "https://purl.imsglobal.org/spec/qti/v3p0/rptemplates/map_response.xml",
+        (_, Version::V3p0) => "https://purl.imsglobal.org/spec/qti/v3p0/rptemplates/match_correct.xml",
+    };
+    xml.empty("responseProcessing", &[("template", template)]);
+
+    // FEEDBACK is never set, so with showHide="hide" the explanation is
+    // always shown once the item has been answered
+    if let Some(explanation) = &q.explanation {
+        xml.open(
+            "modalFeedback",
+            &[("outcomeIdentifier", "FEEDBACK"), ("identifier", "EXPLANATION"), ("showHide", "hide")],
+        );
+        xml.paragraphs(explanation);
+        xml.close("modalFeedback");
+    }
+    xml.close("assessmentItem");
+    xml.out
+}
+
+fn namespace(version: Version) -> &'static str {
+    match version {
+        Version::V2p1 => "http://www.imsglobal.org/xsd/imsqti_v2p1",
+        Version::V3p0 => "http://www.imsglobal.org/xsd/imsqtiasi_v3p0",
+    }
+}
+
+fn with_breaks(text: &str) -> String {
+    text.lines().map(escape).collect::<Vec<_>>().join("<br/>")
+}
+
+// Minimal indented XML writer. QTI element and attribute names are given
+// in their 2.1 spelling and converted for 3.0 ("choiceInteraction" ->
+// "qti-choice-interaction", "maxChoices" -> "max-choices").
+struct Xml {
+    version: Option<Version>,
+    depth: usize,
+    out: String,
+}
+
+impl Xml {
+    fn new(version: Option<Version>) -> Self {
+        Xml { version, depth: 0, out: String::new() }
+    }
+
+    fn name(&self, name: &str) -> String {
+        match self.version {
+            Some(Version::V3p0) if name != "p" => format!("qti-{}", kebab(name)),
+            _ => name.to_string(),
+        }
+    }
+
+    fn attrs(&self, attrs: &[(&str, &str)]) -> String {
+        attrs
+            .iter()
+            .map(|(name, value)| {
+                let name = match self.version {
+                    Some(Version::V3p0) if !name.contains(':') => kebab(name),
+                    _ => name.to_string(),
+                };
+                format!(" {}=\"{}\"", name, escape(value))
+            })
+            .collect()
+    }
+
+    fn raw(&mut self, text: &str) {
+        self.out.push_str(&"  ".repeat(self.depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn open(&mut self, name: &str, attrs: &[(&str, &str)]) {
+        let tag = format!("<{}{}>", self.name(name), self.attrs(attrs));
+        self.raw(&tag);
+        self.depth += 1;
+    }
+
+    fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        let tag = format!("</{}>", self.name(name));
+        self.raw(&tag);
+    }
+
+    fn empty_tag(&self, name: &str, attrs: &[(&str, &str)]) -> String {
+        format!("<{}{}/>", self.name(name), self.attrs(attrs))
+    }
+
+    fn empty(&mut self, name: &str, attrs: &[(&str, &str)]) {
+        let tag = self.empty_tag(name, attrs);
+        self.raw(&tag);
+    }
+
+    fn leaf(&mut self, name: &str, attrs: &[(&str, &str)], text: &str) {
+        let name = self.name(name);
+        let tag = format!("<{0}{1}>{2}</{0}>", name, self.attrs(attrs), escape(text));
+        self.raw(&tag);
+    }
+
+    fn paragraphs(&mut self, text: &str) {
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            self.raw(&format!("<p>{}</p>", escape(line)));
+        }
+    }
+}
+
+fn kebab(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('-');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QTI21_SAMPLE: &[u8] = include_bytes!("../../tests/fixtures/qti/qti21-sample.zip");
+    const QTI30_SAMPLE: &[u8] = include_bytes!("../../tests/fixtures/qti/qti30-sample.zip");
+
+    // What a round trip must keep of each question: where it files, its
+    // kind, options and correct answers
+    type Row = (String, String, Option<(String, String)>, String, String, Vec<String>, i64, Vec<i64>);
+    type Snapshot = Vec<Row>;
+
+    fn snapshot(bank: &ContentBank) -> Snapshot {
+        let mut out = Snapshot::new();
+        for cat in &bank.categories {
+            let mut push = |sub: Option<(String, String)>, q: &QuestionDoc| {
+                out.push((
+                    cat.id.clone(),
+                    cat.title.clone(),
+                    sub,
+                    q.id.clone(),
+                    q.kind.clone(),
+                    q.options.clone(),
+                    q.correct_answer_idx,
+                    q.correct_answers.clone(),
+                ));
+            };
+            for q in &cat.questions {
+                push(None, q);
+            }
+            for sub in &cat.subcategories {
+                for q in &sub.questions {
+                    push(Some((sub.id.clone(), sub.title.clone())), q);
+                }
+            }
+        }
+        out
+    }
+
+    fn import(input: &[u8]) -> ContentBank {
+        let parsed = parse(input);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        parsed.bank
+    }
+
+    fn row(
+        cat: (&str, &str),
+        sub: Option<(&str, &str)>,
+        id: &str,
+        kind: &str,
+        options: &[&str],
+        correct_answer_idx: i64,
+        correct_answers: &[i64],
+    ) -> Row {
+        (
+            cat.0.to_string(),
+            cat.1.to_string(),
+            sub.map(|(id, title)| (id.to_string(), title.to_string())),
+            id.to_string(),
+            kind.to_string(),
+            options.iter().map(|o| o.to_string()).collect(),
+            correct_answer_idx,
+            correct_answers.to_vec(),
+        )
+    }
+
+    // Export in both versions and import again; nothing may change
+    fn assert_round_trips(bank: &ContentBank) {
+        for version in [Version::V2p1, Version::V3p0] {
+            let exported = render(bank, version).unwrap();
+            let reimported = import(&exported);
+            assert_eq!(snapshot(&reimported), snapshot(bank), "{:?}", version);
+            let again = import(&render(&reimported, version).unwrap());
+            assert_eq!(snapshot(&again), snapshot(bank), "{:?}, second round", version);
+        }
+    }
+
+    #[test]
+    fn imports_qti21_sample() {
+        let web = ("web", "Web Security");
+        let xss = Some(("xss", "Cross-Site Scripting"));
+        let net = Some(("net", "Network Basics"));
+        let parsed = parse(QTI21_SAMPLE);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.warnings, ["items/hotspot.xml: skipped hotspotInteraction item"]);
+        assert_eq!(
+            snapshot(&parsed.bank),
+            vec![
+                row(web, None, "csp", KIND_CHOICE, &["X-Frame-Options", "Content-Security-Policy", "Strict-Transport-Security"], 1, &[]),
+                row(web, xss, "xss-sinks", KIND_MULTIPLE_RESPONSE, &["innerHTML", "textContent", "document.write", "setAttribute('class')"], 0, &[0, 2]),
+                row(web, net, "port", KIND_TEXT_ENTRY, &["22", "tcp/22"], 0, &[]),
+                row(web, net, "tls-order", KIND_ORDER, &["ClientHello", "ServerHello", "ClientKeyExchange", "Finished"], 0, &[]),
+                row(("imported", "Imported"), None, "hash-salt", KIND_CHOICE, &["Yes", "No"], 0, &[]),
+            ]
+        );
+        assert_eq!(parsed.bank.categories[0].questions[0].tags, ["headers"]);
+        assert_eq!(parsed.bank.categories[0].subcategories[0].description.as_deref(), Some("Injecting script into pages."));
+    }
+
+    #[test]
+    fn imports_qti30_sample() {
+        let crypto = ("crypto", "Cryptography");
+        let sym = Some(("sym", "Symmetric Ciphers"));
+        let parsed = parse(QTI30_SAMPLE);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+        assert_eq!(
+            snapshot(&parsed.bank),
+            vec![
+                row(crypto, None, "hashes", KIND_MULTIPLE_RESPONSE, &["MD5", "SHA-1", "SHA-256"], 0, &[0, 1]),
+                row(crypto, None, "pki", KIND_ORDER, &["Root CA", "Intermediate CA", "Server certificate"], 0, &[]),
+                row(crypto, sym, "aes", KIND_CHOICE, &["64 bits", "128 bits", "256 bits"], 1, &[]),
+                row(crypto, sym, "cipher", KIND_TEXT_ENTRY, &["Caesar", "Caesar cipher"], 0, &[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn qti21_sample_round_trips() {
+        assert_round_trips(&import(QTI21_SAMPLE));
+    }
+
+    #[test]
+    fn qti30_sample_round_trips() {
+        assert_round_trips(&import(QTI30_SAMPLE));
+    }
+}
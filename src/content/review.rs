@@ -0,0 +1,182 @@
+// Spaced review, in the style of SM-2. Each rating of a question moves its
+// next review date for that user: flashcards are rated by the learner, quiz
+// answers count as "good" when right and "again" when wrong.
+
+use anyhow::{anyhow, Result};
+use sqlx::SqliteConnection;
+
+pub const RATING_AGAIN: &str = "again";
+pub const RATING_HARD: &str = "hard";
+pub const RATING_GOOD: &str = "good";
+pub const RATING_EASY: &str = "easy";
+
+// (value, label) of the ratings, worst first
+pub const RATINGS: &[(&str, &str)] =
+    &[(RATING_AGAIN, "Again"), (RATING_HARD, "Hard"), (RATING_GOOD, "Good"), (RATING_EASY, "Easy")];
+
+const MIN_EASE: f64 = 1.3;
+// A card rated "again" comes back within the same session
+const RELEARN_MINUTES: i64 = 10;
+
+// Where a question stands in a user's reviews
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ReviewState {
+    pub ease: f64,
+    pub interval_days: i64,
+    pub repetitions: i64,
+}
+
+impl Default for ReviewState {
+    fn default() -> Self {
+        ReviewState { ease: 2.5, interval_days: 0, repetitions: 0 }
+    }
+}
+
+// The state after a rating, and how many minutes until the next review
+pub fn schedule(state: &ReviewState, rating: &str) -> Result<(ReviewState, i64)> {
+    let good_interval = match state.repetitions {
+        0 => 1,
+        1 => 3,
+        _ => ((state.interval_days as f64 * state.ease).round() as i64).max(state.interval_days + 1),
+    };
+    let next = match rating {
+        RATING_AGAIN => ReviewState { ease: (state.ease - 0.2).max(MIN_EASE), interval_days: 0, repetitions: 0 },
+        RATING_HARD => ReviewState {
+            ease: (state.ease - 0.15).max(MIN_EASE),
+            interval_days: ((state.interval_days as f64 * 1.2).round() as i64).max(1),
+            repetitions: state.repetitions + 1,
+        },
+        RATING_GOOD => ReviewState { interval_days: good_interval, repetitions: state.repetitions + 1, ..state.clone() },
+        RATING_EASY => ReviewState {
+            ease: state.ease + 0.15,
+            interval_days: ((good_interval as f64 * 1.3).round() as i64).max(good_interval + 1),
+            repetitions: state.repetitions + 1,
+        },
+        other => return Err(anyhow!("Unknown rating: {}", other)),
+    };
+    let minutes = match next.interval_days {
+        0 => RELEARN_MINUTES,
+        days => days * 24 * 60,
+    };
+    Ok((next, minutes))
+}
+
+// Rate a question for a user and store when it is due next. Quiz answers
+// name their attempt, so the rating can be replayed as the answer is graded
+// later (see `replay_reviews`).
+pub async fn record_review(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    question_id: &str,
+    rating: &str,
+    attempt_id: Option<&str>,
+) -> Result<()> {
+    let state = sqlx::query_as::<_, ReviewState>(
+        "SELECT ease, interval_days, repetitions FROM review_schedule WHERE user_id = ? AND question_id = ?"
+    )
+    .bind(user_id)
+    .bind(question_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .unwrap_or_default();
+    let (next, minutes) = schedule(&state, rating)?;
+
+    sqlx::query("INSERT INTO review_log (user_id, question_id, rating, attempt_id) VALUES (?, ?, ?, ?)")
+        .bind(user_id)
+        .bind(question_id)
+        .bind(rating)
+        .bind(attempt_id)
+        .execute(&mut *conn)
+        .await?;
+    save_schedule(conn, user_id, question_id, &next, rating, None, minutes).await
+}
+
+// Store a user's state for a question, due `minutes` after it was reviewed
+// (now, if `reviewed_at` is None)
+async fn save_schedule(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    question_id: &str,
+    state: &ReviewState,
+    rating: &str,
+    reviewed_at: Option<&str>,
+    minutes: i64,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO review_schedule (user_id, question_id, ease, interval_days, repetitions, last_rating, due_at, reviewed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime(COALESCE(?7, 'now'), ?8), COALESCE(?7, CURRENT_TIMESTAMP))
+         ON CONFLICT (user_id, question_id) DO UPDATE SET
+             ease = excluded.ease,
+             interval_days = excluded.interval_days,
+             repetitions = excluded.repetitions,
+             last_rating = excluded.last_rating,
+             due_at = excluded.due_at,
+             reviewed_at = excluded.reviewed_at"
+    )
+    .bind(user_id)
+    .bind(question_id)
+    .bind(state.ease)
+    .bind(state.interval_days)
+    .bind(state.repetitions)
+    .bind(rating)
+    .bind(reviewed_at)
+    .bind(format!("+{} minutes", minutes))
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Rebuild every user's schedule for a question from their ratings, with quiz
+// answers rated as they are graded now: right is "good", wrong "again", and
+// answers taken out of scoring don't count. Call it in the transaction that
+// regrades the question. Returns the number of users rescheduled.
+pub async fn replay_reviews(conn: &mut SqliteConnection, question_id: &str) -> Result<u64> {
+    let users: Vec<String> = sqlx::query_scalar("SELECT DISTINCT user_id FROM review_log WHERE question_id = ?")
+        .bind(question_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    for user_id in &users {
+        let ratings: Vec<(String, String, bool, Option<bool>, bool)> = sqlx::query_as(
+            "SELECT l.rating, l.reviewed_at, l.attempt_id IS NOT NULL, a.correct, x.revision IS NOT NULL
+             FROM review_log l
+             LEFT JOIN attempt_answers a ON a.attempt_id = l.attempt_id AND a.question_id = l.question_id
+             LEFT JOIN scoring_exclusions x ON x.question_id = a.question_id AND x.revision = a.revision
+             WHERE l.user_id = ? AND l.question_id = ?
+             ORDER BY l.id"
+        )
+        .bind(user_id)
+        .bind(question_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut state = ReviewState::default();
+        let mut last = None;
+        for (rating, reviewed_at, from_quiz, correct, excluded) in ratings {
+            let rating = match (from_quiz, correct) {
+                (false, _) => rating,
+                (true, Some(_)) if excluded => continue,
+                (true, Some(true)) => RATING_GOOD.to_string(),
+                (true, Some(false)) => RATING_AGAIN.to_string(),
+                (true, None) => continue,
+            };
+            let (next, minutes) = schedule(&state, &rating)?;
+            state = next;
+            last = Some((rating, reviewed_at, minutes));
+        }
+
+        match last {
+            Some((rating, reviewed_at, minutes)) => {
+                save_schedule(conn, user_id, question_id, &state, &rating, Some(&reviewed_at), minutes).await?
+            }
+            // Nothing left counts, so the question was never reviewed
+            None => {
+                sqlx::query("DELETE FROM review_schedule WHERE user_id = ? AND question_id = ?")
+                    .bind(user_id)
+                    .bind(question_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+    }
+    Ok(users.len() as u64)
+}
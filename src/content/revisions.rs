@@ -0,0 +1,186 @@
+// Question history: every change to a question's content is kept as an
+// immutable revision, which is what quiz attempts point at.
+
+use anyhow::Result;
+use sqlx::SqliteConnection;
+
+use super::tags::set_question_tags;
+use crate::models::{QuestionEdit, QuestionRevision, KIND_CHOICE, KIND_MULTIPLE_RESPONSE};
+
+// Snapshot the question's current content and tags as its next revision.
+// Call it inside the transaction that made the change.
+pub async fn record_revision(
+    conn: &mut SqliteConnection,
+    question_id: &str,
+    author_id: Option<&str>,
+    note: &str,
+) -> Result<i64> {
+    let revision: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(revision), 0) + 1 FROM question_revisions WHERE question_id = ?"
+    )
+    .bind(question_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO question_revisions (question_id, revision, category_id, subcategory_id, topic_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation, tags, author_id, note)
+         SELECT id, ?, category_id, subcategory_id, topic_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation,
+             (SELECT json_group_array(tag) FROM (SELECT tag FROM question_tags WHERE question_id = questions.id ORDER BY tag)),
+             ?, ?
+         FROM questions WHERE id = ?"
+    )
+    .bind(revision)
+    .bind(author_id)
+    .bind(note)
+    .bind(question_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE questions SET revision = ? WHERE id = ?")
+        .bind(revision)
+        .bind(question_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(revision)
+}
+
+// The edit of a published question waiting for review, if any
+pub async fn pending_edit(conn: &mut SqliteConnection, question_id: &str) -> Result<Option<QuestionEdit>> {
+    Ok(sqlx::query_as::<_, QuestionEdit>("SELECT * FROM question_edits WHERE question_id = ?")
+        .bind(question_id)
+        .fetch_optional(&mut *conn)
+        .await?)
+}
+
+// Put an approved edit live as the question's next revision, crediting its
+// author. Call it inside the transaction that approves it.
+pub async fn apply_edit(conn: &mut SqliteConnection, edit: &QuestionEdit) -> Result<i64> {
+    sqlx::query(
+        "UPDATE questions SET category_id = ?, subcategory_id = ?, topic_id = ?, kind = ?, question_text = ?, options = ?, correct_answer_idx = ?, correct_answers = ?, explanation = ? WHERE id = ?"
+    )
+    .bind(&edit.category_id)
+    .bind(&edit.subcategory_id)
+    .bind(&edit.topic_id)
+    .bind(&edit.kind)
+    .bind(&edit.question_text)
+    .bind(&edit.options)
+    .bind(edit.correct_answer_idx)
+    .bind(&edit.correct_answers)
+    .bind(&edit.explanation)
+    .bind(&edit.question_id)
+    .execute(&mut *conn)
+    .await?;
+    set_question_tags(&mut *conn, &edit.question_id, &edit.get_tags()).await?;
+    sqlx::query("DELETE FROM question_edits WHERE question_id = ?")
+        .bind(&edit.question_id)
+        .execute(&mut *conn)
+        .await?;
+    record_revision(conn, &edit.question_id, edit.author_id.as_deref(), "edited").await
+}
+
+// A run of text or list items in a diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffPart {
+    // "same", "removed" or "added"
+    pub change: &'static str,
+    pub text: String,
+}
+
+// One field of two revisions side by side
+#[derive(Debug)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub parts: Vec<DiffPart>,
+    // Render parts as list items rather than running text
+    pub list: bool,
+}
+
+// Longest-common-subsequence diff of two lists
+pub fn diff_list(old: &[String], new: &[String]) -> Vec<DiffPart> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j]: common length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut parts = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut push = |change: &'static str, text: &str| parts.push(DiffPart { change, text: text.to_string() });
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            push("same", &old[i]);
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push("removed", &old[i]);
+            i += 1;
+        } else {
+            push("added", &new[j]);
+            j += 1;
+        }
+    }
+    parts
+}
+
+// Word diff of two texts, with neighbouring words of the same kind merged
+pub fn diff_text(old: &str, new: &str) -> Vec<DiffPart> {
+    let words = |s: &str| s.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+    let mut merged: Vec<DiffPart> = Vec::new();
+    for part in diff_list(&words(old), &words(new)) {
+        match merged.last_mut() {
+            Some(last) if last.change == part.change => {
+                last.text.push(' ');
+                last.text.push_str(&part.text);
+            }
+            _ => merged.push(part),
+        }
+    }
+    merged
+}
+
+// The texts of the correct options, for the kinds that mark options correct
+fn correct_options(rev: &QuestionRevision) -> Vec<String> {
+    let options = rev.get_options_vec();
+    let indices = match rev.kind.as_str() {
+        KIND_CHOICE => vec![rev.correct_answer_idx],
+        KIND_MULTIPLE_RESPONSE => rev.get_correct_answers(),
+        _ => Vec::new(),
+    };
+    indices
+        .iter()
+        .filter_map(|i| usize::try_from(*i).ok().and_then(|i| options.get(i)).cloned())
+        .collect()
+}
+
+// The fields that differ between two revisions
+pub fn diff_revisions(old: &QuestionRevision, new: &QuestionRevision) -> Vec<FieldDiff> {
+    let optional = |s: &Option<String>| s.clone().unwrap_or_default();
+    let mut fields = vec![
+        FieldDiff { field: "Category", parts: diff_text(&old.category_id, &new.category_id), list: false },
+        FieldDiff {
+            field: "Subcategory",
+            parts: diff_text(&optional(&old.subcategory_id), &optional(&new.subcategory_id)),
+            list: false,
+        },
+        FieldDiff { field: "Topic", parts: diff_text(&old.topic_id, &new.topic_id), list: false },
+        FieldDiff { field: "Kind", parts: diff_text(&old.kind, &new.kind), list: false },
+        FieldDiff { field: "Question", parts: diff_text(&old.question_text, &new.question_text), list: false },
+        FieldDiff { field: "Options", parts: diff_list(&old.get_options_vec(), &new.get_options_vec()), list: true },
+        FieldDiff { field: "Correct answers", parts: diff_list(&correct_options(old), &correct_options(new)), list: true },
+        FieldDiff {
+            field: "Explanation",
+            parts: diff_text(&optional(&old.explanation), &optional(&new.explanation)),
+            list: false,
+        },
+        FieldDiff { field: "Tags", parts: diff_list(&old.get_tags(), &new.get_tags()), list: true },
+    ];
+    fields.retain(|f| f.parts.iter().any(|p| p.change != "same"));
+    fields
+}
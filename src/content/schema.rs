@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use super::{check_question, slugify, stored_answer_idx};
+use crate::models::KIND_CHOICE;
+
+// A question bank as authored in JSON or YAML files.
+//
+// Categories nest their subcategories, and questions live either directly
+// under a category or under one of its subcategories. Every entry carries a
+// stable `id` that is used as the primary key in the database, so importing
+// the same file twice updates rows instead of duplicating them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ContentBank {
+    #[serde(default)]
+    pub categories: Vec<CategoryDoc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryDoc {
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subcategories: Vec<SubcategoryDoc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub questions: Vec<QuestionDoc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubcategoryDoc {
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub questions: Vec<QuestionDoc>,
+}
+
+// `kind` defaults to "choice". Multiple-response questions list their
+// answers in `correct_answers`; text-entry questions keep the accepted
+// answers in `options`, and order questions their items in the right order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuestionDoc {
+    pub id: String,
+    #[serde(default = "default_kind", skip_serializing_if = "is_default_kind")]
+    pub kind: String,
+    pub question_text: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub correct_answer_idx: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub correct_answers: Vec<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+fn default_kind() -> String {
+    KIND_CHOICE.to_string()
+}
+
+fn is_default_kind(kind: &str) -> bool {
+    kind == KIND_CHOICE
+}
+
+impl ContentBank {
+    // Add a question under the category/subcategory with these titles,
+    // creating them with slug ids if needed. Used by the flat formats.
+    pub fn push_question(&mut self, category: &str, subcategory: Option<&str>, question: QuestionDoc) {
+        let cat_id = match slugify(category) {
+            id if id.is_empty() => "uncategorized".to_string(),
+            id => id,
+        };
+        let cat = self.category_entry(&cat_id, category.trim());
+
+        let Some(sub_title) = subcategory.map(str::trim).filter(|s| !s.is_empty()) else {
+            cat.questions.push(question);
+            return;
+        };
+        let sub_id = format!("{}-{}", cat_id, slugify(sub_title));
+        cat.subcategory_entry(&sub_id, sub_title).questions.push(question);
+    }
+
+    // The category with this id, added with the given title if missing
+    pub fn category_entry(&mut self, id: &str, title: &str) -> &mut CategoryDoc {
+        let pos = match self.categories.iter().position(|c| c.id == id) {
+            Some(pos) => pos,
+            None => {
+                self.categories.push(CategoryDoc {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    subcategories: Vec::new(),
+                    questions: Vec::new(),
+                });
+                self.categories.len() - 1
+            }
+        };
+        &mut self.categories[pos]
+    }
+
+    // Every question with its parents, in document order
+    pub fn questions(&self) -> Vec<(&CategoryDoc, Option<&SubcategoryDoc>, &QuestionDoc)> {
+        let mut out = Vec::new();
+        for cat in &self.categories {
+            out.extend(cat.questions.iter().map(|q| (cat, None, q)));
+            for sub in &cat.subcategories {
+                out.extend(sub.questions.iter().map(|q| (cat, Some(sub), q)));
+            }
+        }
+        out
+    }
+
+    // Check the whole bank and return every problem found, each prefixed
+    // with the path of the offending entry.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut seen_ids = std::collections::HashSet::new();
+        let mut check_id = |path: &str, kind: &str, id: &str, errors: &mut Vec<String>| {
+            if id.trim().is_empty() {
+                errors.push(format!("{}: id is required", path));
+            } else if !seen_ids.insert((kind.to_string(), id.to_string())) {
+                errors.push(format!("{}: duplicate {} id '{}'", path, kind, id));
+            }
+        };
+
+        for (ci, cat) in self.categories.iter().enumerate() {
+            let cat_path = format!("categories[{}]", ci);
+            check_id(&cat_path, "category", &cat.id, &mut errors);
+            if cat.title.trim().is_empty() {
+                errors.push(format!("{}: title is required", cat_path));
+            }
+
+            for (qi, q) in cat.questions.iter().enumerate() {
+                let q_path = format!("{}.questions[{}]", cat_path, qi);
+                check_id(&q_path, "question", &q.id, &mut errors);
+                errors.extend(q.validate().into_iter().map(|e| format!("{}: {}", q_path, e)));
+            }
+
+            for (si, sub) in cat.subcategories.iter().enumerate() {
+                let sub_path = format!("{}.subcategories[{}]", cat_path, si);
+                check_id(&sub_path, "subcategory", &sub.id, &mut errors);
+                if sub.title.trim().is_empty() {
+                    errors.push(format!("{}: title is required", sub_path));
+                }
+
+                for (qi, q) in sub.questions.iter().enumerate() {
+                    let q_path = format!("{}.questions[{}]", sub_path, qi);
+                    check_id(&q_path, "question", &q.id, &mut errors);
+                    errors.extend(q.validate().into_iter().map(|e| format!("{}: {}", q_path, e)));
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl CategoryDoc {
+    // The subcategory with this id, added with the given title if missing
+    pub fn subcategory_entry(&mut self, id: &str, title: &str) -> &mut SubcategoryDoc {
+        let pos = match self.subcategories.iter().position(|s| s.id == id) {
+            Some(pos) => pos,
+            None => {
+                self.subcategories.push(SubcategoryDoc {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    description: None,
+                    questions: Vec::new(),
+                });
+                self.subcategories.len() - 1
+            }
+        };
+        &mut self.subcategories[pos]
+    }
+}
+
+impl QuestionDoc {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.question_text.trim().is_empty() {
+            errors.push("question_text is required".to_string());
+        }
+        errors.extend(check_question(&self.kind, &self.options, self.correct_answer_idx, &self.correct_answers));
+        if self.tags.iter().any(|t| t.trim().is_empty()) {
+            errors.push("tags must not be empty".to_string());
+        }
+        errors
+    }
+
+    // The value kept in the `correct_answer_idx` column
+    pub fn stored_answer_idx(&self) -> i64 {
+        stored_answer_idx(&self.kind, self.correct_answer_idx, &self.correct_answers)
+    }
+}
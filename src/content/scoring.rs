@@ -0,0 +1,272 @@
+// Grading and attempt scores. An attempt's score and total are kept on
+// quiz_attempts and derived from its stored answers, leaving out any
+// excluded revisions.
+
+use anyhow::{anyhow, Result};
+use nanoid::nanoid;
+use sqlx::SqliteConnection;
+
+use super::review::replay_reviews;
+use crate::models::{Question, KIND_CHOICE, KIND_MULTIPLE_RESPONSE, KIND_ORDER, KIND_TEXT_ENTRY};
+
+// Submitted values as option indices; unparseable values never match
+pub fn response_indices(response: &[String]) -> Vec<i64> {
+    response
+        .iter()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().unwrap_or(-1))
+        .collect()
+}
+
+// Text answers match ignoring case and extra whitespace
+pub fn same_text(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    normalize(a) == normalize(b)
+}
+
+// Whether a response answers the question correctly. `accepted` lists extra
+// options counted as correct for a choice question.
+pub fn is_correct(q: &Question, response: &[String], accepted: &[i64]) -> bool {
+    let options = q.get_options_vec();
+    let indices = response_indices(response);
+    match q.kind.as_str() {
+        KIND_MULTIPLE_RESPONSE => {
+            let mut selected = indices;
+            selected.sort();
+            selected.dedup();
+            let mut correct = q.get_correct_answers();
+            correct.sort();
+            selected == correct
+        }
+        KIND_TEXT_ENTRY => {
+            let answer = response.first().map(|s| s.trim()).unwrap_or("");
+            !answer.is_empty() && options.iter().any(|o| same_text(o, answer))
+        }
+        KIND_ORDER => indices.len() == options.len() && indices.iter().enumerate().all(|(pos, idx)| *idx == pos as i64),
+        _ => {
+            let selected = indices.first().copied().unwrap_or(-1);
+            selected == q.correct_answer_idx || accepted.contains(&selected)
+        }
+    }
+}
+
+pub async fn accepted_answers(conn: &mut SqliteConnection, question_id: &str, revision: i64) -> Result<Vec<i64>> {
+    let accepted = sqlx::query_scalar(
+        "SELECT option_idx FROM accepted_answers WHERE question_id = ? AND revision = ? ORDER BY option_idx"
+    )
+    .bind(question_id)
+    .bind(revision)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(accepted)
+}
+
+// Whether answers to this revision of the question still count
+pub async fn is_scored(conn: &mut SqliteConnection, question_id: &str, revision: i64) -> Result<bool> {
+    let excluded: Option<i64> = sqlx::query_scalar(
+        "SELECT revision FROM scoring_exclusions WHERE question_id = ? AND revision = ?"
+    )
+    .bind(question_id)
+    .bind(revision)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(excluded.is_none())
+}
+
+// Take a revision out of scoring, regrade the attempts that answered it and
+// rebuild the review schedules they fed. Returns the number of attempts
+// whose scores were recomputed.
+pub async fn exclude_revision(
+    conn: &mut SqliteConnection,
+    question_id: &str,
+    revision: i64,
+    reason: &str,
+    user_id: Option<&str>,
+) -> Result<u64> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO scoring_exclusions (question_id, revision, reason, created_by) VALUES (?, ?, ?, ?)"
+    )
+    .bind(question_id)
+    .bind(revision)
+    .bind(reason)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+    let rescored = rescore_question(conn, question_id).await?;
+    replay_reviews(conn, question_id).await?;
+    Ok(rescored)
+}
+
+// Recompute score and total for every attempt that answered the question,
+// and the path step scores taken from those attempts. A step stays complete
+// even if its attempt no longer passes.
+pub async fn rescore_question(conn: &mut SqliteConnection, question_id: &str) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE quiz_attempts SET
+             score = (SELECT COUNT(*) FROM attempt_answers a
+                      WHERE a.attempt_id = quiz_attempts.id AND a.correct = 1
+                        AND NOT EXISTS (SELECT 1 FROM scoring_exclusions x WHERE x.question_id = a.question_id AND x.revision = a.revision)),
+             total = (SELECT COUNT(*) FROM attempt_answers a
+                      WHERE a.attempt_id = quiz_attempts.id
+                        AND NOT EXISTS (SELECT 1 FROM scoring_exclusions x WHERE x.question_id = a.question_id AND x.revision = a.revision))
+         WHERE id IN (SELECT attempt_id FROM attempt_answers WHERE question_id = ?)"
+    )
+    .bind(question_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE path_step_completions SET
+             score_pct = (SELECT CASE WHEN t.total > 0 THEN t.score * 100 / t.total ELSE 0 END
+                          FROM quiz_attempts t WHERE t.id = path_step_completions.attempt_id)
+         WHERE attempt_id IN (SELECT attempt_id FROM attempt_answers WHERE question_id = ?)"
+    )
+    .bind(question_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// What an instructor can do about a question whose answers were graded wrong
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegradeAction {
+    // Grade stored answers again against the current answer key
+    Regrade,
+    // Also count these options as correct (choice questions only)
+    Accept(Vec<i64>),
+    // Stop counting the question at all
+    Void,
+}
+
+impl RegradeAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegradeAction::Regrade => "regrade",
+            RegradeAction::Accept(_) => "accept",
+            RegradeAction::Void => "void",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RegradeOutcome {
+    pub revisions: Vec<i64>,
+    pub answers_changed: u64,
+    pub attempts_rescored: u64,
+}
+
+// The revisions whose answers the current answer key can grade: those with
+// the same kind and options as the current one, so a stored option index
+// means the same thing in both
+pub async fn gradable_revisions(conn: &mut SqliteConnection, q: &Question) -> Result<Vec<i64>> {
+    let revisions = sqlx::query_scalar(
+        "SELECT revision FROM question_revisions WHERE question_id = ? AND kind = ? AND options = ? ORDER BY revision"
+    )
+    .bind(&q.id)
+    .bind(&q.kind)
+    .bind(&q.options)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(revisions)
+}
+
+// Apply a regrade to every answer given to the gradable revisions of the
+// question, recompute the affected attempts and review schedules and record
+// it in the audit trail. Call it inside a transaction.
+pub async fn regrade_question(
+    conn: &mut SqliteConnection,
+    q: &Question,
+    action: &RegradeAction,
+    user_id: &str,
+    note: Option<&str>,
+) -> Result<RegradeOutcome> {
+    let revisions = gradable_revisions(conn, q).await?;
+    let mut outcome = RegradeOutcome::default();
+
+    if let RegradeAction::Accept(options) = action {
+        if q.kind != KIND_CHOICE {
+            return Err(anyhow!("Only single-answer choice questions can accept more answers; edit the question instead"));
+        }
+        let count = q.get_options_vec().len() as i64;
+        if options.is_empty() {
+            return Err(anyhow!("Choose the options to accept"));
+        }
+        if let Some(bad) = options.iter().find(|i| **i < 0 || **i >= count) {
+            return Err(anyhow!("Option {} is out of range", bad));
+        }
+        for revision in &revisions {
+            for option in options {
+                sqlx::query("INSERT OR IGNORE INTO accepted_answers (question_id, revision, option_idx) VALUES (?, ?, ?)")
+                    .bind(&q.id)
+                    .bind(revision)
+                    .bind(option)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+    }
+
+    if *action == RegradeAction::Void {
+        let reason = format!("voided: {}", note.unwrap_or("regrade"));
+        for revision in &revisions {
+            sqlx::query(
+                "INSERT OR IGNORE INTO scoring_exclusions (question_id, revision, reason, created_by) VALUES (?, ?, ?, ?)"
+            )
+            .bind(&q.id)
+            .bind(revision)
+            .bind(&reason)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+    } else {
+        for revision in &revisions {
+            let accepted = accepted_answers(conn, &q.id, *revision).await?;
+            let answers: Vec<(String, String, bool)> = sqlx::query_as(
+                "SELECT attempt_id, response, correct FROM attempt_answers WHERE question_id = ? AND revision = ?"
+            )
+            .bind(&q.id)
+            .bind(revision)
+            .fetch_all(&mut *conn)
+            .await?;
+            for (attempt_id, response, correct) in answers {
+                let response: Vec<String> = serde_json::from_str(&response).unwrap_or_default();
+                let now_correct = is_correct(q, &response, &accepted);
+                if now_correct != correct {
+                    sqlx::query("UPDATE attempt_answers SET correct = ? WHERE attempt_id = ? AND question_id = ?")
+                        .bind(now_correct)
+                        .bind(&attempt_id)
+                        .bind(&q.id)
+                        .execute(&mut *conn)
+                        .await?;
+                    outcome.answers_changed += 1;
+                }
+            }
+        }
+    }
+    outcome.attempts_rescored = rescore_question(conn, &q.id).await?;
+    replay_reviews(conn, &q.id).await?;
+
+    let accepted = match action {
+        RegradeAction::Accept(options) => Some(serde_json::to_string(options)?),
+        _ => None,
+    };
+    sqlx::query(
+        "INSERT INTO regrades (id, question_id, action, revisions, accepted, note, user_id, answers_changed, attempts_rescored)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(nanoid!())
+    .bind(&q.id)
+    .bind(action.name())
+    .bind(serde_json::to_string(&revisions)?)
+    .bind(accepted)
+    .bind(note)
+    .bind(user_id)
+    .bind(outcome.answers_changed as i64)
+    .bind(outcome.attempts_rescored as i64)
+    .execute(&mut *conn)
+    .await?;
+
+    outcome.revisions = revisions;
+    Ok(outcome)
+}
@@ -0,0 +1,312 @@
+// Full-text search over the FTS5 indexes. Queries typed by users are turned
+// into FTS5 syntax here, so that their punctuation never reaches the query
+// parser; matches come back wrapped in marker characters that become <mark>
+// once the text around them is escaped.
+//
+// Before that, misspelt words are corrected against the words found in
+// content, and acronyms are widened to their synonyms (sqli also finds "SQL
+// injection").
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use sqlx::SqliteConnection;
+
+use super::lessons::plain_text;
+use super::xml_escape;
+
+// What highlight() and snippet() put around each match; pass them to SQL as
+// char(2) and char(3)
+pub const MARK_START: char = '\u{2}';
+pub const MARK_END: char = '\u{3}';
+
+// One term of a search box query: a word, or "a quoted phrase", either of
+// which may end in * to match as a prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTerm {
+    pub text: String,
+    pub phrase: bool,
+    pub prefix: bool,
+}
+
+impl QueryTerm {
+    fn words(&self) -> Vec<String> {
+        words(&self.text)
+    }
+
+    fn to_fts(&self) -> String {
+        let quoted = format!("\"{}\"", self.text.replace('"', "\"\""));
+        if self.prefix { format!("{}*", quoted) } else { quoted }
+    }
+}
+
+// The searchable terms of a query; terms without letters or digits are
+// dropped, as the index would never match them
+pub fn parse_query(input: &str) -> Vec<QueryTerm> {
+    let mut terms = Vec::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let (text, phrase, tail) = match rest.strip_prefix('"') {
+            Some(after) => match after.find('"') {
+                Some(end) => (&after[..end], true, &after[end + 1..]),
+                None => (after, true, ""),
+            },
+            None => {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '"').unwrap_or(rest.len());
+                (&rest[..end], false, &rest[end..])
+            }
+        };
+        // A * right after the term, or after the closing quote of a phrase
+        let (text, prefix) = match (phrase, tail.strip_prefix('*')) {
+            (true, Some(after)) => {
+                rest = after.trim_start();
+                (text, true)
+            }
+            _ => {
+                rest = tail.trim_start();
+                match text.strip_suffix('*') {
+                    Some(stem) if !phrase => (stem, true),
+                    _ => (text, false),
+                }
+            }
+        };
+        if text.chars().any(char::is_alphanumeric) {
+            terms.push(QueryTerm { text: text.trim().to_string(), phrase, prefix });
+        }
+    }
+    terms
+}
+
+// Terms as an FTS5 expression: every term must match, "a quoted phrase"
+// matches those words in order, and a trailing * makes a prefix query
+// (inject* finds injection). A run of terms spelling out an entry of a
+// synonym group matches any entry of the group. None if there are no terms.
+pub fn fts_expression(terms: &[QueryTerm], synonyms: &Synonyms) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < terms.len() {
+        match synonyms.longest_match(&terms[i..]) {
+            Some((len, group)) => {
+                let entries: Vec<String> = group.iter().map(|entry| format!("\"{}\"", entry.join(" "))).collect();
+                parts.push(format!("({})", entries.join(" OR ")));
+                i += len;
+            }
+            None => {
+                parts.push(terms[i].to_fts());
+                i += 1;
+            }
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+// Lowercase words, split the way the unicode61 tokenizer splits them
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Used when SEARCH_SYNONYMS names no file. One group of interchangeable
+// terms per line.
+pub const DEFAULT_SYNONYMS: &str = "\
+sqli, sql injection
+xss, cross-site scripting
+csrf, xsrf, cross-site request forgery
+ssrf, server-side request forgery
+rce, remote code execution
+lfi, local file inclusion
+rfi, remote file inclusion
+idor, insecure direct object reference
+privesc, priv esc, privilege escalation
+mitm, man-in-the-middle
+dos, denial of service
+";
+
+// Groups of terms a search treats as the same, such as an acronym and what
+// it stands for. Each entry is kept as its words.
+#[derive(Debug, Clone)]
+pub struct Synonyms {
+    groups: Arc<Vec<Vec<Vec<String>>>>,
+}
+
+impl Synonyms {
+    // Comma-separated entries, one group per line; blank lines and lines
+    // starting with # are skipped
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut groups = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let group: Vec<Vec<String>> = line.split(',').map(words).filter(|w| !w.is_empty()).collect();
+            if group.len() < 2 {
+                return Err(anyhow!("Line {}: a synonym group needs at least two terms", n + 1));
+            }
+            groups.push(group);
+        }
+        Ok(Synonyms { groups: Arc::new(groups) })
+    }
+
+    // The groups in `path`, or the built-in ones
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        match path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| anyhow!("Cannot read {}: {}", path, e))?;
+                Self::parse(&text).map_err(|e| anyhow!("{}: {}", path, e))
+            }
+            None => Self::parse(DEFAULT_SYNONYMS),
+        }
+    }
+
+    fn contains_word(&self, word: &str) -> bool {
+        self.groups.iter().flatten().flatten().any(|w| w == word)
+    }
+
+    // The most terms at the start of `terms` whose words are an entry of a
+    // group, and that group. Prefix terms never match, and a phrase only
+    // as an entry of its own.
+    fn longest_match(&self, terms: &[QueryTerm]) -> Option<(usize, &[Vec<String>])> {
+        let mut found = None;
+        let mut seen: Vec<String> = Vec::new();
+        for (n, term) in terms.iter().enumerate() {
+            if term.prefix || (term.phrase && n > 0) {
+                break;
+            }
+            seen.extend(term.words());
+            if let Some(group) = self.groups.iter().find(|g| g.contains(&seen)) {
+                found = Some((n + 1, group.as_slice()));
+            }
+            if term.phrase {
+                break;
+            }
+        }
+        found
+    }
+}
+
+// Typos allowed in a word of this many characters; short words are left
+// alone, as too many words are one edit apart
+fn max_typos(len: usize) -> usize {
+    match len {
+        0..=4 => 0,
+        5..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Edits (insertions, deletions, substitutions and swaps of neighbours)
+// turning `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+// How often each word occurs in live content: category and subcategory
+// titles and descriptions, published questions and lessons
+pub async fn term_dictionary(conn: &mut SqliteConnection) -> Result<HashMap<String, i64>> {
+    let texts: Vec<Option<String>> = sqlx::query_scalar(
+        "SELECT title FROM categories WHERE deleted_at IS NULL
+         UNION ALL SELECT title || ' ' || COALESCE(description, '') FROM subcategories WHERE deleted_at IS NULL
+         UNION ALL SELECT question_text || ' ' || COALESCE(explanation, '') FROM questions
+             WHERE deleted_at IS NULL AND status = 'published'
+         UNION ALL SELECT title FROM lessons WHERE deleted_at IS NULL"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let bodies: Vec<String> = sqlx::query_scalar("SELECT body FROM lessons WHERE deleted_at IS NULL")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut dictionary = HashMap::new();
+    let lessons = bodies.iter().map(|body| plain_text(body));
+    for text in texts.into_iter().flatten().chain(lessons) {
+        for word in words(&text) {
+            *dictionary.entry(word).or_insert(0) += 1;
+        }
+    }
+    Ok(dictionary)
+}
+
+// Whether any index holds the term, deleted rows included
+async fn indexed(conn: &mut SqliteConnection, term: &QueryTerm) -> Result<bool> {
+    let found: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM categories_fts WHERE categories_fts MATCH ?1)
+             OR EXISTS (SELECT 1 FROM subcategories_fts WHERE subcategories_fts MATCH ?1)
+             OR EXISTS (SELECT 1 FROM questions_fts WHERE questions_fts MATCH ?1)
+             OR EXISTS (SELECT 1 FROM lessons_fts WHERE lessons_fts MATCH ?1)"
+    )
+    .bind(term.to_fts())
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(found)
+}
+
+// The closest word in the dictionary within the typos allowed, the more
+// frequent one on a tie
+fn closest_word<'a>(word: &str, dictionary: &'a HashMap<String, i64>) -> Option<&'a str> {
+    let max = max_typos(word.chars().count());
+    dictionary
+        .iter()
+        .filter(|(candidate, _)| candidate.chars().count().abs_diff(word.chars().count()) <= max)
+        .map(|(candidate, count)| (edit_distance(word, candidate), -count, candidate.as_str()))
+        .filter(|(distance, _, _)| (1..=max).contains(distance))
+        .min()
+        .map(|(_, _, candidate)| candidate)
+}
+
+// Replace misspelt words by the closest word found in content, returning
+// (typed, used) for each. Only single plain words that no index holds are
+// corrected; phrases, prefixes and synonyms are taken as typed.
+pub async fn correct_spelling(
+    conn: &mut SqliteConnection,
+    terms: &mut [QueryTerm],
+    synonyms: &Synonyms,
+) -> Result<Vec<(String, String)>> {
+    let mut corrections = Vec::new();
+    let mut dictionary = None;
+    for term in terms.iter_mut() {
+        let words = term.words();
+        if term.phrase || term.prefix || words.len() != 1 || max_typos(words[0].chars().count()) == 0 {
+            continue;
+        }
+        if synonyms.contains_word(&words[0]) || indexed(conn, term).await? {
+            continue;
+        }
+        if dictionary.is_none() {
+            dictionary = Some(term_dictionary(conn).await?);
+        }
+        if let Some(word) = dictionary.as_ref().and_then(|d| closest_word(&words[0], d)) {
+            corrections.push((term.text.clone(), word.to_string()));
+            term.text = word.to_string();
+        }
+    }
+    Ok(corrections)
+}
+
+// HTML for text carrying match markers
+pub fn marked_html(text: &str) -> String {
+    xml_escape(text)
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
+
+pub fn has_match(text: &str) -> bool {
+    text.contains(MARK_START)
+}
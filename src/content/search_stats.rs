@@ -0,0 +1,126 @@
+// What learners search for, for instructors deciding what content to write
+// next. Searches are counted per day and query text; no user, session,
+// address or time of day is stored. Queries are lowercased and anything that
+// looks like an email address is blanked out before it is counted.
+
+use anyhow::Result;
+use sqlx::SqliteConnection;
+
+pub const MAX_QUERY_CHARS: usize = 200;
+// Rows in each list of the report
+pub const REPORT_ROWS: i64 = 50;
+
+// The query as counted: lowercase, single spaces, at most MAX_QUERY_CHARS.
+// None if nothing is left.
+pub fn normalize_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|w| if w.contains('@') { "[email]".to_string() } else { w.to_lowercase() })
+        .collect();
+    let query: String = words.join(" ").chars().take(MAX_QUERY_CHARS).collect();
+    (!query.is_empty()).then_some(query)
+}
+
+pub async fn record_search(conn: &mut SqliteConnection, query: &str, results: i64) -> Result<()> {
+    let Some(query) = normalize_query(query) else {
+        return Ok(());
+    };
+    sqlx::query(
+        "INSERT INTO search_stats (day, query, searches, zero_result_searches, results)
+         VALUES (date('now'), ?, 1, ?, ?)
+         ON CONFLICT (day, query) DO UPDATE SET
+             searches = searches + 1,
+             zero_result_searches = zero_result_searches + excluded.zero_result_searches,
+             results = excluded.results"
+    )
+    .bind(&query)
+    .bind(i64::from(results == 0))
+    .bind(results)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// A result opened from the results page of `query`
+pub async fn record_click(conn: &mut SqliteConnection, query: &str) -> Result<()> {
+    let Some(query) = normalize_query(query) else {
+        return Ok(());
+    };
+    sqlx::query(
+        "INSERT INTO search_stats (day, query, clicks) VALUES (date('now'), ?, 1)
+         ON CONFLICT (day, query) DO UPDATE SET clicks = clicks + 1"
+    )
+    .bind(&query)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// A query's counts over the period of a report
+#[derive(Debug, sqlx::FromRow)]
+pub struct QueryStats {
+    pub query: String,
+    pub searches: i64,
+    pub zero_result_searches: i64,
+    pub clicks: i64,
+    // Results of the latest search
+    pub results: i64,
+    pub last_day: String,
+}
+
+impl QueryStats {
+    // Clicks per search, as a percentage
+    pub fn click_through(&self) -> i64 {
+        percent(self.clicks, self.searches)
+    }
+}
+
+pub fn percent(part: i64, whole: i64) -> i64 {
+    if whole == 0 { 0 } else { part * 100 / whole }
+}
+
+// `order` and `having` are fixed SQL, never user input
+async fn query_stats(conn: &mut SqliteConnection, days: i64, having: &str, order: &str) -> Result<Vec<QueryStats>> {
+    let sql = format!(
+        "SELECT query, SUM(searches) AS searches, SUM(zero_result_searches) AS zero_result_searches,
+             SUM(clicks) AS clicks,
+             (SELECT s.results FROM search_stats s WHERE s.query = t.query AND s.searches > 0
+              ORDER BY s.day DESC LIMIT 1) AS results,
+             MAX(day) AS last_day
+         FROM search_stats t
+         WHERE day > date('now', ?)
+         GROUP BY query HAVING {}
+         ORDER BY {}
+         LIMIT ?",
+        having, order
+    );
+    let stats = sqlx::query_as::<_, QueryStats>(&sql)
+        .bind(format!("-{} days", days))
+        .bind(REPORT_ROWS)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(stats)
+}
+
+// The most searched queries over the last `days` days
+pub async fn top_queries(conn: &mut SqliteConnection, days: i64) -> Result<Vec<QueryStats>> {
+    query_stats(conn, days, "SUM(searches) > 0", "searches DESC, query").await
+}
+
+// Queries that found nothing at least once over the last `days` days, most
+// frequent first
+pub async fn zero_result_queries(conn: &mut SqliteConnection, days: i64) -> Result<Vec<QueryStats>> {
+    query_stats(conn, days, "SUM(zero_result_searches) > 0", "zero_result_searches DESC, last_day DESC, query").await
+}
+
+// (searches, searches without results, clicks) over the last `days` days
+pub async fn totals(conn: &mut SqliteConnection, days: i64) -> Result<(i64, i64, i64)> {
+    let totals = sqlx::query_as(
+        "SELECT COALESCE(SUM(searches), 0), COALESCE(SUM(zero_result_searches), 0), COALESCE(SUM(clicks), 0)
+         FROM search_stats WHERE day > date('now', ?)"
+    )
+    .bind(format!("-{} days", days))
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(totals)
+}
@@ -0,0 +1,292 @@
+// How alike questions and lessons are, from TF-IDF vectors of their words.
+// Words found in many documents count for little, so two questions are
+// similar when they share the words that are rare in the bank. Everything is
+// computed locally.
+//
+// The linter compares the questions of a bank with it to find
+// near-duplicates. In the database, each live question and lesson keeps its
+// closest matches in `related_content`. Triggers bump a content version
+// whenever questions or lessons change, and the table is rebuilt after
+// imports and after each request that writes, so reading it never has to.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use sqlx::SqliteConnection;
+
+use super::lessons::plain_text;
+use super::search::words;
+
+pub const KIND_QUESTION: &str = "question";
+pub const KIND_LESSON: &str = "lesson";
+
+// Matches kept for each question or lesson, and the least similarity worth
+// showing
+const RELATED_KEPT: usize = 5;
+const MIN_RELATED_SIMILARITY: f64 = 0.1;
+
+// Too common to say anything about what a text is about
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "can", "do", "does", "for", "from", "how", "if", "in", "is",
+    "it", "its", "of", "on", "or", "that", "the", "this", "to", "what", "when", "which", "who", "why", "with",
+];
+
+// The words of some texts, without stop words
+pub fn terms<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    texts
+        .into_iter()
+        .flat_map(words)
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+        .collect()
+}
+
+// What a question is compared on: its text, options and explanation
+pub fn question_terms(question_text: &str, options: &[String], explanation: Option<&str>) -> Vec<String> {
+    terms(std::iter::once(question_text).chain(options.iter().map(String::as_str)).chain(explanation))
+}
+
+pub fn lesson_terms(title: &str, body: &str) -> Vec<String> {
+    terms([title, plain_text(body).as_str()])
+}
+
+// Unit-length TF-IDF vectors of a set of documents
+pub struct TfIdf {
+    // (term, weight) of each document, by term
+    vectors: Vec<Vec<(usize, f64)>>,
+    // (document, weight) of each term
+    postings: Vec<Vec<(usize, f64)>>,
+}
+
+impl TfIdf {
+    pub fn new(docs: &[Vec<String>]) -> Self {
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        let counts: Vec<HashMap<usize, usize>> = docs
+            .iter()
+            .map(|doc| {
+                let mut counts = HashMap::new();
+                for word in doc {
+                    let next = ids.len();
+                    *counts.entry(*ids.entry(word.as_str()).or_insert(next)).or_insert(0) += 1;
+                }
+                counts
+            })
+            .collect();
+
+        let mut doc_freq = vec![0usize; ids.len()];
+        for term in counts.iter().flat_map(HashMap::keys) {
+            doc_freq[*term] += 1;
+        }
+        // Smoothed, so that a word found in every document still counts a little
+        let n = docs.len() as f64;
+        let idf: Vec<f64> = doc_freq.iter().map(|df| ((1.0 + n) / (1.0 + *df as f64)).ln() + 1.0).collect();
+
+        let mut postings = vec![Vec::new(); ids.len()];
+        let vectors = counts
+            .into_iter()
+            .enumerate()
+            .map(|(doc, counts)| {
+                let mut vector: Vec<(usize, f64)> =
+                    counts.into_iter().map(|(term, count)| (term, (1.0 + (count as f64).ln()) * idf[term])).collect();
+                vector.sort_by_key(|(term, _)| *term);
+                let norm = vector.iter().map(|(_, w)| w * w).sum::<f64>().sqrt();
+                for (term, weight) in &mut vector {
+                    *weight /= norm;
+                    postings[*term].push((doc, *weight));
+                }
+                vector
+            })
+            .collect();
+        TfIdf { vectors, postings }
+    }
+
+    // Cosine similarity, from 0 (no word in common) to 1
+    pub fn similarity(&self, a: usize, b: usize) -> f64 {
+        let (mut x, mut y) = (self.vectors[a].iter().peekable(), self.vectors[b].iter().peekable());
+        let mut dot = 0.0;
+        while let (Some((ta, wa)), Some((tb, wb))) = (x.peek(), y.peek()) {
+            match ta.cmp(tb) {
+                std::cmp::Ordering::Less => {
+                    x.next();
+                }
+                std::cmp::Ordering::Greater => {
+                    y.next();
+                }
+                std::cmp::Ordering::Equal => {
+                    dot += wa * wb;
+                    x.next();
+                    y.next();
+                }
+            }
+        }
+        dot
+    }
+
+    // The `limit` other documents most similar to `doc`, at least `min`
+    // similar, most similar first
+    pub fn most_similar(&self, doc: usize, limit: usize, min: f64) -> Vec<(usize, f64)> {
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for (term, weight) in &self.vectors[doc] {
+            for (other, other_weight) in &self.postings[*term] {
+                if *other != doc {
+                    *scores.entry(*other).or_insert(0.0) += weight * other_weight;
+                }
+            }
+        }
+        let mut scores: Vec<(usize, f64)> = scores.into_iter().filter(|(_, s)| *s >= min).collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(limit);
+        scores
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct QuestionText {
+    id: String,
+    question_text: String,
+    options: String,
+    explanation: Option<String>,
+}
+
+// Recompute `related_content` if questions or lessons changed since it was
+// last built. Returns whether it was rebuilt.
+pub async fn refresh_related(conn: &mut SqliteConnection) -> Result<bool> {
+    let (version, built): (i64, i64) =
+        sqlx::query_as("SELECT content_version, built_version FROM related_content_state WHERE id = 1")
+            .fetch_one(&mut *conn)
+            .await?;
+    if version == built {
+        return Ok(false);
+    }
+
+    let questions = sqlx::query_as::<_, QuestionText>(
+        "SELECT id, question_text, options, explanation FROM questions
+         WHERE deleted_at IS NULL AND status = 'published' ORDER BY id"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let lessons: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT l.id, l.title, l.body FROM lessons l
+         JOIN subcategories s ON s.id = l.subcategory_id JOIN categories c ON c.id = s.category_id
+         WHERE l.deleted_at IS NULL AND s.deleted_at IS NULL AND c.deleted_at IS NULL ORDER BY l.id"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut items: Vec<(&str, &str)> = Vec::new();
+    let mut docs = Vec::new();
+    for q in &questions {
+        let options: Vec<String> = serde_json::from_str(&q.options).unwrap_or_default();
+        items.push((KIND_QUESTION, &q.id));
+        docs.push(question_terms(&q.question_text, &options, q.explanation.as_deref()));
+    }
+    for (id, title, body) in &lessons {
+        items.push((KIND_LESSON, id));
+        docs.push(lesson_terms(title, body));
+    }
+    let model = TfIdf::new(&docs);
+
+    let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+    sqlx::query("DELETE FROM related_content").execute(&mut *tx).await?;
+    for (i, (kind, id)) in items.iter().enumerate() {
+        for (j, score) in model.most_similar(i, RELATED_KEPT, MIN_RELATED_SIMILARITY) {
+            sqlx::query(
+                "INSERT INTO related_content (kind, id, related_kind, related_id, score) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(kind)
+            .bind(id)
+            .bind(items[j].0)
+            .bind(items[j].1)
+            .bind(score)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    // Changes made while this ran leave the versions apart, so they are
+    // picked up by the next refresh
+    sqlx::query("UPDATE related_content_state SET built_version = ? WHERE id = 1")
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+// A question or lesson related to another, with where to find it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RelatedItem {
+    pub kind: String,
+    pub id: String,
+    // Question text or lesson title
+    pub title: String,
+    pub category_id: Option<String>,
+    pub subcategory_id: Option<String>,
+}
+
+impl RelatedItem {
+    pub fn is_lesson(&self) -> bool {
+        self.kind == KIND_LESSON
+    }
+
+    pub fn url(&self) -> String {
+        match (self.is_lesson(), &self.category_id, &self.subcategory_id) {
+            (true, _, _) => format!("/lessons/{}", self.id),
+            (false, Some(category_id), Some(subcategory_id)) => {
+                format!("/quiz?category_id={}&subcategory_id={}", category_id, subcategory_id)
+            }
+            (false, Some(category_id), None) => format!("/quiz?category_id={}", category_id),
+            (false, None, _) => "/quiz".to_string(),
+        }
+    }
+}
+
+// A match as read, with what it is a match for
+#[derive(sqlx::FromRow)]
+struct RelatedRow {
+    of_kind: String,
+    of_id: String,
+    #[sqlx(flatten)]
+    item: RelatedItem,
+}
+
+// The questions and lessons most like each of some questions and lessons,
+// most similar first, by (kind, id). Reads the precomputed matches only.
+pub async fn related_to_each(
+    conn: &mut SqliteConnection,
+    items: &[(&str, &str)],
+    limit: i64,
+) -> Result<HashMap<(String, String), Vec<RelatedItem>>> {
+    let mut related: HashMap<(String, String), Vec<RelatedItem>> = HashMap::new();
+    if items.is_empty() {
+        return Ok(related);
+    }
+    let sql = format!(
+        "SELECT of_kind, of_id, kind, id, title, category_id, subcategory_id FROM (
+             SELECT r.kind AS of_kind, r.id AS of_id, r.related_kind AS kind, r.related_id AS id,
+                 COALESCE(q.question_text, l.title) AS title, q.category_id, q.subcategory_id,
+                 ROW_NUMBER() OVER (PARTITION BY r.kind, r.id ORDER BY r.score DESC, r.related_id) AS n
+             FROM related_content r
+             LEFT JOIN questions q ON r.related_kind = 'question' AND q.id = r.related_id
+                 AND q.deleted_at IS NULL AND q.status = 'published'
+             LEFT JOIN lessons l ON r.related_kind = 'lesson' AND l.id = r.related_id AND l.deleted_at IS NULL
+             WHERE (r.kind, r.id) IN (VALUES {}) AND (q.id IS NOT NULL OR l.id IS NOT NULL)
+         )
+         WHERE n <= ?
+         ORDER BY of_kind, of_id, n",
+        vec!["(?, ?)"; items.len()].join(", ")
+    );
+    let mut query = sqlx::query_as::<_, RelatedRow>(&sql);
+    for (kind, id) in items {
+        query = query.bind(*kind).bind(*id);
+    }
+    for row in query.bind(limit).fetch_all(&mut *conn).await? {
+        related.entry((row.of_kind, row.of_id)).or_default().push(row.item);
+    }
+    Ok(related)
+}
+
+// The questions and lessons most like a question or lesson, most similar
+// first
+pub async fn related_to(conn: &mut SqliteConnection, kind: &str, id: &str, limit: i64) -> Result<Vec<RelatedItem>> {
+    let mut related = related_to_each(conn, &[(kind, id)], limit).await?;
+    Ok(related.remove(&(kind.to_string(), id.to_string())).unwrap_or_default())
+}
@@ -0,0 +1,221 @@
+// Suggestions for the header search box. Topic titles, published questions
+// and tags are kept in memory as a sorted list of their words, so that each
+// keystroke costs a binary search rather than a database query. The index is
+// rebuilt when it is older than a minute, so new content shows up in
+// suggestions shortly after it is saved.
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+
+use super::search::words;
+
+const MAX_AGE: Duration = Duration::from_secs(60);
+// Suggestions of each kind
+pub const PER_KIND: usize = 5;
+// Shorter queries match too much to be useful
+pub const MIN_QUERY_CHARS: usize = 2;
+const QUESTION_LABEL_CHARS: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Topic,
+    Question,
+    Tag,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub label: String,
+    // The parent topic, category or question count
+    pub detail: Option<String>,
+    pub url: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Suggestions {
+    pub topics: Vec<Suggestion>,
+    pub questions: Vec<Suggestion>,
+    pub tags: Vec<Suggestion>,
+}
+
+struct Entry {
+    kind: Kind,
+    suggestion: Suggestion,
+    words: Vec<String>,
+}
+
+pub struct PrefixIndex {
+    entries: Vec<Entry>,
+    // (word, entry) for every word of every entry, sorted
+    keys: Vec<(String, usize)>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TopicRow {
+    id: String,
+    title: String,
+    parent_title: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct QuestionRow {
+    category_id: String,
+    subcategory_id: Option<String>,
+    question_text: String,
+    category_title: String,
+}
+
+fn shorten(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}\u{2026}", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+impl PrefixIndex {
+    pub async fn build(conn: &mut SqliteConnection) -> Result<Self> {
+        let mut entries = Vec::new();
+        let topics = sqlx::query_as::<_, TopicRow>(
+            "SELECT t.id, t.title, p.title AS parent_title
+             FROM topics t LEFT JOIN topics p ON p.id = t.parent_id
+             WHERE t.deleted_at IS NULL"
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for t in topics {
+            entries.push(Entry {
+                kind: Kind::Topic,
+                words: words(&t.title),
+                suggestion: Suggestion { url: format!("/topics/{}", t.id), label: t.title, detail: t.parent_title },
+            });
+        }
+
+        let questions = sqlx::query_as::<_, QuestionRow>(
+            "SELECT q.category_id, q.subcategory_id, q.question_text, c.title AS category_title
+             FROM questions q JOIN categories c ON c.id = q.category_id
+             WHERE q.deleted_at IS NULL AND q.status = 'published'"
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for q in questions {
+            let mut url = format!("/quiz?category_id={}", q.category_id);
+            if let Some(subcategory_id) = &q.subcategory_id {
+                url.push_str(&format!("&subcategory_id={}", subcategory_id));
+            }
+            entries.push(Entry {
+                kind: Kind::Question,
+                words: words(&q.question_text),
+                suggestion: Suggestion {
+                    label: shorten(&q.question_text, QUESTION_LABEL_CHARS),
+                    detail: Some(q.category_title),
+                    url,
+                },
+            });
+        }
+
+        let tags: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT t.tag, COUNT(*) FROM question_tags t JOIN questions q ON q.id = t.question_id
+             WHERE q.deleted_at IS NULL AND q.status = 'published'
+             GROUP BY t.tag"
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for (tag, questions) in tags {
+            entries.push(Entry {
+                kind: Kind::Tag,
+                words: words(&tag),
+                suggestion: Suggestion {
+                    url: format!("/tags/{}", tag),
+                    detail: Some(format!("{} question{}", questions, if questions == 1 { "" } else { "s" })),
+                    label: tag,
+                },
+            });
+        }
+
+        let mut keys: Vec<(String, usize)> = entries
+            .iter()
+            .enumerate()
+            .flat_map(|(i, e)| e.words.iter().map(move |w| (w.clone(), i)))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(PrefixIndex { entries, keys })
+    }
+
+    // Entries with a word starting with each word of the query, the last
+    // word being taken as still being typed. Labels starting with the query
+    // come first, then shorter ones.
+    pub fn suggest(&self, query: &str) -> Suggestions {
+        let mut suggestions = Suggestions::default();
+        if query.trim().chars().count() < MIN_QUERY_CHARS {
+            return suggestions;
+        }
+        let query_words = words(query);
+        let Some((last, others)) = query_words.split_last() else {
+            return suggestions;
+        };
+
+        let start = self.keys.partition_point(|(word, _)| word < last);
+        let candidates: BTreeSet<usize> = self.keys[start..]
+            .iter()
+            .take_while(|(word, _)| word.starts_with(last.as_str()))
+            .map(|(_, i)| *i)
+            .collect();
+        let query = query.trim().to_lowercase();
+        let mut matches: Vec<&Entry> = candidates
+            .into_iter()
+            .map(|i| &self.entries[i])
+            .filter(|e| others.iter().all(|o| e.words.iter().any(|w| w.starts_with(o.as_str()))))
+            .collect();
+        matches.sort_by_cached_key(|e| {
+            let label = e.suggestion.label.to_lowercase();
+            (e.kind, !label.starts_with(&query), label.len(), label)
+        });
+
+        for e in matches {
+            let list = match e.kind {
+                Kind::Topic => &mut suggestions.topics,
+                Kind::Question => &mut suggestions.questions,
+                Kind::Tag => &mut suggestions.tags,
+            };
+            if list.len() < PER_KIND {
+                list.push(e.suggestion.clone());
+            }
+        }
+        suggestions
+    }
+}
+
+// An index and when it was built
+type Built = (Instant, Arc<PrefixIndex>);
+
+// The shared index, built on first use and again once it is stale
+#[derive(Clone, Default)]
+pub struct SuggestCache {
+    index: Arc<Mutex<Option<Built>>>,
+}
+
+impl SuggestCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn index(&self, db: &Pool<Sqlite>) -> Result<Arc<PrefixIndex>> {
+        if let Some((built, index)) = self.index.lock().unwrap().as_ref()
+            && built.elapsed() < MAX_AGE
+        {
+            return Ok(index.clone());
+        }
+        // Requests arriving during a rebuild may build it too; the last one
+        // to finish is kept
+        let mut conn = db.acquire().await?;
+        let index = Arc::new(PrefixIndex::build(&mut conn).await?);
+        *self.index.lock().unwrap() = Some((Instant::now(), index.clone()));
+        Ok(index)
+    }
+}
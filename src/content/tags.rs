@@ -0,0 +1,57 @@
+// Free-form question tags. Tags are stored lowercase in question_tags; the
+// difficulty tags form a facet of their own on tag pages and quizzes.
+
+use anyhow::Result;
+use sqlx::SqliteConnection;
+
+use super::normalize_tag;
+
+// Tags that say how hard a question is, easiest first
+pub const DIFFICULTY_TAGS: &[&str] = &["beginner", "intermediate", "advanced"];
+
+pub const MAX_TAG_LEN: usize = 50;
+
+pub fn is_difficulty(tag: &str) -> bool {
+    DIFFICULTY_TAGS.contains(&tag)
+}
+
+// Comma-separated tags as typed into a form or query string, normalized,
+// sorted and without duplicates
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = input.split(',').map(normalize_tag).filter(|t| !t.is_empty()).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+pub fn check_tags(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .filter(|t| t.len() > MAX_TAG_LEN)
+        .map(|t| format!("Tag is too long ({} characters at most): {}", MAX_TAG_LEN, t))
+        .collect()
+}
+
+pub async fn question_tags(conn: &mut SqliteConnection, question_id: &str) -> Result<Vec<String>> {
+    let tags = sqlx::query_scalar("SELECT tag FROM question_tags WHERE question_id = ? ORDER BY tag")
+        .bind(question_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(tags)
+}
+
+// Replace the question's tags. Record a revision afterwards, as the tags
+// are part of it.
+pub async fn set_question_tags(conn: &mut SqliteConnection, question_id: &str, tags: &[String]) -> Result<()> {
+    sqlx::query("DELETE FROM question_tags WHERE question_id = ?")
+        .bind(question_id)
+        .execute(&mut *conn)
+        .await?;
+    for tag in tags {
+        sqlx::query("INSERT INTO question_tags (question_id, tag) VALUES (?, ?)")
+            .bind(question_id)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,177 @@
+// The topic tree. Categories are its roots and subcategories their children
+// (triggers keep both mirrored in `topics`); deeper topics hang below
+// subcategories. A question is filed under its most specific topic, and its
+// category_id and subcategory_id are that topic's first two levels.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use sqlx::SqliteConnection;
+
+use crate::models::Topic;
+
+// Guards the recursive queries against a cycle slipping into the tree
+const MAX_DEPTH: i64 = 64;
+
+// Topic ids of a subtree, its root included; bind the root's id once
+pub const SUBTREE_SQL: &str = "WITH RECURSIVE subtree(id) AS (
+        SELECT ? UNION SELECT t.id FROM topics t JOIN subtree s ON t.parent_id = s.id WHERE t.deleted_at IS NULL
+    ) SELECT id FROM subtree";
+
+// A topic with how deep it sits; roots are at depth 0
+#[derive(Debug)]
+pub struct TopicNode {
+    pub topic: Topic,
+    pub depth: usize,
+}
+
+impl TopicNode {
+    pub fn indent(&self) -> String {
+        "\u{2014} ".repeat(self.depth)
+    }
+}
+
+// The topics from the root down to `id`, deleted ones included; empty if
+// there is no such topic
+pub async fn topic_path(conn: &mut SqliteConnection, id: &str) -> Result<Vec<Topic>> {
+    let path = sqlx::query_as::<_, Topic>(
+        "WITH RECURSIVE path(id, depth) AS (
+             SELECT ?, 0
+             UNION ALL SELECT t.parent_id, path.depth + 1 FROM topics t JOIN path ON t.id = path.id
+             WHERE t.parent_id IS NOT NULL AND path.depth < ?
+         )
+         SELECT t.* FROM path JOIN topics t ON t.id = path.id ORDER BY path.depth DESC"
+    )
+    .bind(id)
+    .bind(MAX_DEPTH)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(path)
+}
+
+// A topic is live when neither it nor any topic above it is deleted
+pub fn is_live(path: &[Topic]) -> bool {
+    !path.is_empty() && path.iter().all(|t| t.deleted_at.is_none())
+}
+
+// The category and subcategory a question filed under the end of `path`
+// belongs to
+pub fn placement(path: &[Topic]) -> (String, Option<String>) {
+    let category_id = path.first().map(|t| t.id.clone()).unwrap_or_default();
+    (category_id, path.get(1).map(|t| t.id.clone()))
+}
+
+// Where a question given by category goes: its current topic if that lies
+// within the subcategory (or category) given, otherwise that level itself.
+// Imports use this so re-importing a bank doesn't undo finer filing.
+pub async fn file_under(
+    conn: &mut SqliteConnection,
+    current: Option<&str>,
+    category_id: &str,
+    subcategory_id: Option<&str>,
+) -> Result<String> {
+    let level = match subcategory_id {
+        Some(sub) => {
+            let is_child: Option<String> = sqlx::query_scalar("SELECT id FROM topics WHERE id = ? AND parent_id = ?")
+                .bind(sub)
+                .bind(category_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+            is_child.unwrap_or_else(|| category_id.to_string())
+        }
+        None => category_id.to_string(),
+    };
+    if let Some(current) = current {
+        let path = topic_path(conn, current).await?;
+        if path.iter().any(|t| t.id == level) {
+            return Ok(current.to_string());
+        }
+    }
+    Ok(level)
+}
+
+// Every live topic whose ancestors are live too, depth first, siblings by
+// position then title
+pub async fn topic_tree(conn: &mut SqliteConnection) -> Result<Vec<TopicNode>> {
+    let topics = sqlx::query_as::<_, Topic>("SELECT * FROM topics WHERE deleted_at IS NULL ORDER BY position, title")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(flatten(topics))
+}
+
+fn flatten(topics: Vec<Topic>) -> Vec<TopicNode> {
+    let mut children: HashMap<Option<String>, Vec<Topic>> = HashMap::new();
+    for topic in topics {
+        children.entry(topic.parent_id.clone()).or_default().push(topic);
+    }
+    let mut nodes = Vec::new();
+    // Stack of (topic, depth), reversed so siblings come out in order
+    let mut stack: Vec<(Topic, usize)> = children.remove(&None).unwrap_or_default().into_iter().rev().map(|t| (t, 0)).collect();
+    while let Some((topic, depth)) = stack.pop() {
+        if let Some(kids) = children.remove(&Some(topic.id.clone())) {
+            stack.extend(kids.into_iter().rev().map(|t| (t, depth + 1)));
+        }
+        nodes.push(TopicNode { topic, depth });
+    }
+    nodes
+}
+
+// Published questions in each topic's subtree
+pub async fn question_counts(conn: &mut SqliteConnection, nodes: &[TopicNode]) -> Result<HashMap<String, i64>> {
+    let direct: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT topic_id, COUNT(*) FROM questions WHERE deleted_at IS NULL AND status = 'published' GROUP BY topic_id"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut counts: HashMap<String, i64> = direct.into_iter().collect();
+    // Nodes are depth first, so walking them backwards reaches every child
+    // before its parent
+    for node in nodes.iter().rev() {
+        let own = counts.get(&node.topic.id).copied().unwrap_or(0);
+        if let Some(parent) = &node.topic.parent_id {
+            *counts.entry(parent.clone()).or_default() += own;
+        }
+    }
+    Ok(counts)
+}
+
+pub async fn subtree_ids(conn: &mut SqliteConnection, id: &str) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar(SUBTREE_SQL).bind(id).fetch_all(&mut *conn).await?;
+    Ok(ids)
+}
+
+// After a topic moves, point the questions below it at their new category
+// and subcategory
+pub async fn refile_subtree(conn: &mut SqliteConnection, id: &str) -> Result<u64> {
+    let mut moved = 0;
+    for topic_id in subtree_ids(conn, id).await? {
+        let path = topic_path(conn, &topic_id).await?;
+        let (category_id, subcategory_id) = placement(&path);
+        moved += sqlx::query("UPDATE questions SET category_id = ?, subcategory_id = ? WHERE topic_id = ?")
+            .bind(&category_id)
+            .bind(&subcategory_id)
+            .bind(&topic_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+    }
+    Ok(moved)
+}
+
+// A new parent for a topic below the subcategory level: it must be live, a
+// subcategory or deeper, and outside the topic's own subtree
+pub async fn check_parent(conn: &mut SqliteConnection, topic_id: Option<&str>, parent_id: &str) -> Result<Vec<Topic>> {
+    let path = topic_path(conn, parent_id).await?;
+    if !is_live(&path) {
+        return Err(anyhow!("Unknown parent topic"));
+    }
+    if path.len() < 2 {
+        return Err(anyhow!("New topics go under a subcategory or one of its topics"));
+    }
+    if let Some(id) = topic_id
+        && path.iter().any(|t| t.id == id)
+    {
+        return Err(anyhow!("A topic can't be moved under itself"));
+    }
+    Ok(path)
+}
@@ -12,4 +12,18 @@ pub async fn init_db(database_url: &str) -> Result<Pool<Sqlite>> {
     sqlx::migrate!("./migrations").run(&pool).await?;
 
     Ok(pool)
-}
\ No newline at end of file
+}
+// A migrated in-memory database for tests. It lives as long as its one
+// connection, so the pool never lets it go.
+#[cfg(test)]
+pub async fn test_db() -> Pool<Sqlite> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
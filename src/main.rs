@@ -1,10 +1,15 @@
+mod api;
+mod auth;
+mod cli;
+mod content;
 mod db;
 mod models;
 mod routes;
 mod utils;
 
 use poem::{
-    get, post, handler, http::Method, listener::TcpListener, EndpointExt, Route,
+    get, post, handler, http::Method, listener::TcpListener, Endpoint, EndpointExt, IntoEndpoint, IntoResponse,
+    Request, Response, Route,
 };
 use poem::middleware::Tracing;
 use askama::Template;
@@ -12,6 +17,12 @@ use anyhow::Result;
 use sqlx::Pool;
 use sqlx::Sqlite;
 
+use crate::auth::oidc::{Oidc, OidcSettings};
+use crate::auth::providers::AuthProviders;
+use crate::content::pack::ContentPack;
+use crate::content::search::Synonyms;
+use crate::content::similarity::refresh_related;
+use crate::content::suggest::SuggestCache;
 use crate::db::init_db;
 use crate::utils::config::AppConfig;
 use routes::{
@@ -19,7 +30,29 @@ use routes::{
     category::get_categories,
     subcategory::get_subcategories,
     quiz::{get_quiz, submit_quiz},
-    search::search_handler,
+    search::{search_click, search_handler, suggest_handler},
+    search_stats::search_stats,
+    admin::*,
+    review::{question_review, review_question},
+    revisions::{question_revisions, rollback_question},
+    reports::{question_report, report_form, report_queue, submit_report, triage_report},
+    regrade::{regrade, regrade_form},
+    tags::{get_tag, get_tags},
+    paths::{
+        admin_paths, complete_lesson, create_path, create_step, delete_path, delete_step, edit_path_form, edit_step_form,
+        get_path, get_path_step, get_paths, new_path_form, new_step_form, submit_flag, update_path, update_step,
+    },
+    flashcards::{flashcard_decks, rate_flashcard, study_flashcards},
+    tokens::{create_token, revoke_token},
+    oidc::{oidc_callback, oidc_login, oidc_unlink},
+    lessons::{
+        admin_lessons, check_answer, create_lesson, delete_lesson, edit_lesson_form, get_lesson, lesson_progress,
+        new_lesson_form, update_lesson,
+    },
+    topics::{
+        admin_topics, create_topic, delete_topic, edit_topic_form, get_topic, get_topics, new_topic_form, restore_topic,
+        update_topic,
+    },
 };
 
 #[derive(Template)]
@@ -36,12 +69,48 @@ async fn home_page() -> String {
     tmpl.render().unwrap()
 }
 
+// Rebuild related content once a request that may have changed questions or
+// lessons is done, so pages only ever read it. Nothing happens unless the
+// content version moved.
+async fn refresh_related_after_writes<E: Endpoint>(next: E, req: Request) -> poem::Result<Response> {
+    let writes = req.method() != Method::GET && req.method() != Method::HEAD;
+    let db = req.data::<Pool<Sqlite>>().cloned();
+    let resp = next.call(req).await?.into_response();
+    if let (true, Some(db)) = (writes, db) {
+        refresh_related(&mut db.acquire().await.unwrap()).await.unwrap();
+    }
+    Ok(resp)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = AppConfig::from_env();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "lint") {
+        return cli::lint(&args);
+    }
+
     let db_pool: Pool<Sqlite> = init_db(&config.database_url).await?;
 
+    // Maintenance subcommands, e.g. `hackademy import bank.yaml --dry-run`
+    if !args.is_empty() {
+        return cli::run(&args, &db_pool).await;
+    }
+
     let session_store = SessionStore::new();
+    let synonyms = Synonyms::load(config.search_synonyms.as_deref())?;
+    let oidc = Oidc::new(OidcSettings::from_config(&config)?);
+    let auth_providers = AuthProviders::from_config(&config)?;
+
+    let content_pack = ContentPack::new(config.content_dir.as_ref().map(Into::into));
+    content_pack.sync(&db_pool).await;
+    refresh_related(&mut *db_pool.acquire().await?).await?;
+    if config.content_watch_secs > 0 {
+        content_pack.watch(db_pool.clone(), std::time::Duration::from_secs(config.content_watch_secs));
+    }
+
+    let api = api::service();
+    let api_spec = api.spec_endpoint();
 
     let app = Route::new()
         // Home
@@ -49,19 +118,92 @@ async fn main() -> Result<()> {
         // Categories
         .at("/categories", get(get_categories))
         .at("/category/:cat_id", get(get_subcategories))
+        .at("/tags", get(get_tags))
+        .at("/tags/:tag", get(get_tag))
+        .at("/topics", get(get_topics))
+        .at("/topics/:id", get(get_topic))
+        .at("/paths", get(get_paths))
+        .at("/paths/:id", get(get_path))
+        .at("/paths/:id/steps/:step", get(get_path_step))
+        .at("/paths/:id/steps/:step/complete", post(complete_lesson))
+        .at("/paths/:id/steps/:step/flag", post(submit_flag))
+        .at("/lessons/:id", get(get_lesson))
+        .at("/lessons/:id/check", post(check_answer))
+        .at("/lessons/:id/progress", post(lesson_progress))
+        .at("/flashcards", get(flashcard_decks))
+        .at("/flashcards/study", get(study_flashcards))
+        .at("/flashcards/rate", post(rate_flashcard))
         // Quiz
         .at("/quiz", get(get_quiz))
         .at("/quiz/submit", post(submit_quiz))
+        .at("/questions/:id/report", get(report_form).post(submit_report))
         // Search
         .at("/search", get(search_handler))
+        .at("/search/suggest", get(suggest_handler))
+        .at("/search/click", get(search_click))
         // Auth
         .at("/auth/register", get(register_form).post(register_user))
         .at("/auth/login", get(login_form).post(login_user))
         .at("/auth/profile", get(profile))
         .at("/auth/logout", get(logout))
+        .at("/auth/tokens", post(create_token))
+        .at("/auth/tokens/:id/revoke", post(revoke_token))
+        .at("/auth/oidc/login", get(oidc_login))
+        .at("/auth/oidc/callback", get(oidc_callback))
+        .at("/auth/oidc/unlink", post(oidc_unlink))
+        // Content admin
+        .at("/admin", get(admin_dashboard))
+        .at("/admin/categories/new", get(new_category_form).post(create_category))
+        .at("/admin/categories/:id/edit", get(edit_category_form).post(update_category))
+        .at("/admin/categories/:id/delete", post(delete_category))
+        .at("/admin/categories/:id/restore", post(restore_category))
+        .at("/admin/subcategories/new", get(new_subcategory_form).post(create_subcategory))
+        .at("/admin/subcategories/:id/edit", get(edit_subcategory_form).post(update_subcategory))
+        .at("/admin/subcategories/:id/delete", post(delete_subcategory))
+        .at("/admin/subcategories/:id/restore", post(restore_subcategory))
+        .at("/admin/topics", get(admin_topics))
+        .at("/admin/topics/new", get(new_topic_form).post(create_topic))
+        .at("/admin/topics/:id/edit", get(edit_topic_form).post(update_topic))
+        .at("/admin/topics/:id/delete", post(delete_topic))
+        .at("/admin/topics/:id/restore", post(restore_topic))
+        .at("/admin/paths", get(admin_paths))
+        .at("/admin/paths/new", get(new_path_form).post(create_path))
+        .at("/admin/paths/:id/edit", get(edit_path_form).post(update_path))
+        .at("/admin/paths/:id/delete", post(delete_path))
+        .at("/admin/paths/:id/steps/new", get(new_step_form).post(create_step))
+        .at("/admin/paths/:id/steps/:step/edit", get(edit_step_form).post(update_step))
+        .at("/admin/paths/:id/steps/:step/delete", post(delete_step))
+        .at("/admin/lessons", get(admin_lessons))
+        .at("/admin/lessons/new", get(new_lesson_form).post(create_lesson))
+        .at("/admin/lessons/:id/edit", get(edit_lesson_form).post(update_lesson))
+        .at("/admin/lessons/:id/delete", post(delete_lesson))
+        .at("/admin/questions/new", get(new_question_form).post(create_question))
+        .at("/admin/questions/:id/edit", get(edit_question_form).post(update_question))
+        .at("/admin/questions/:id/delete", post(delete_question))
+        .at("/admin/questions/:id/restore", post(restore_question))
+        .at("/admin/questions/:id/review", get(question_review).post(review_question))
+        .at("/admin/questions/:id/revisions", get(question_revisions))
+        .at("/admin/questions/:id/revisions/:revision/rollback", post(rollback_question))
+        .at("/admin/questions/:id/regrade", get(regrade_form).post(regrade))
+        .at("/admin/search-stats", get(search_stats))
+        .at("/admin/reports", get(report_queue))
+        .at("/admin/reports/:id", get(question_report).post(triage_report))
+        .at("/admin/import", get(import_form).post(import_content))
+        .at("/admin/export", get(export_content))
+        .at("/admin/content-pack", get(content_pack_status))
+        .at("/admin/content-pack/sync", post(sync_content_pack))
+        // JSON API
+        .at("/api/v1/openapi.json", api_spec)
+        .nest(api::PREFIX, api.into_endpoint().catch_all_error(api::error_body))
+        .around(refresh_related_after_writes)
 
         .data(db_pool)
         .data(session_store)
+        .data(content_pack)
+        .data(synonyms)
+        .data(oidc)
+        .data(auth_providers)
+        .data(SuggestCache::new())
         .with(Tracing);
 
     println!("Hackademy listening on {}", config.server_addr);
@@ -1,11 +1,46 @@
 use serde::{Deserialize, Serialize};
 
+// User roles
+pub const ROLE_STUDENT: &str = "student";
+pub const ROLE_INSTRUCTOR: &str = "instructor";
+pub const ROLE_ADMIN: &str = "admin";
+
+// Question kinds
+pub const KIND_CHOICE: &str = "choice";
+pub const KIND_MULTIPLE_RESPONSE: &str = "multiple_response";
+pub const KIND_TEXT_ENTRY: &str = "text_entry";
+pub const KIND_ORDER: &str = "order";
+
+// Question statuses; learners only ever see published questions
+pub const STATUS_DRAFT: &str = "draft";
+pub const STATUS_IN_REVIEW: &str = "in_review";
+pub const STATUS_PUBLISHED: &str = "published";
+pub const STATUS_RETIRED: &str = "retired";
+
+// Learning path step kinds
+pub const STEP_LESSON: &str = "lesson";
+pub const STEP_QUIZ: &str = "quiz";
+pub const STEP_CHALLENGE: &str = "challenge";
+
 // For user auth
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
     pub id: String,
     pub username: String,
     pub password_hash: String,
+    pub role: String,
+}
+
+impl User {
+    // Admins and instructors may manage content
+    pub fn is_staff(&self) -> bool {
+        self.role == ROLE_ADMIN || self.role == ROLE_INSTRUCTOR
+    }
+
+    // Users created by single sign-on have no password to log in with
+    pub fn has_password(&self) -> bool {
+        !self.password_hash.is_empty()
+    }
 }
 
 // For categories
@@ -13,6 +48,7 @@ pub struct User {
 pub struct Category {
     pub id: String,
     pub title: String,
+    pub deleted_at: Option<String>,
 }
 
 // For subcategories
@@ -22,6 +58,20 @@ pub struct Subcategory {
     pub category_id: String,
     pub title: String,
     pub description: Option<String>,
+    pub deleted_at: Option<String>,
+}
+
+// A node of the topic tree. Root topics are categories and their children
+// subcategories; deeper topics exist only in this table.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Topic {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub position: i64,
+    pub deleted_at: Option<String>,
+    pub created_at: String,
 }
 
 // For questions
@@ -33,6 +83,15 @@ pub struct Question {
     pub question_text: String,
     pub options: String, // stored as JSON string
     pub correct_answer_idx: i64,
+    pub deleted_at: Option<String>,
+    pub explanation: Option<String>,
+    pub kind: String,
+    pub correct_answers: Option<String>, // JSON array of indices, multiple_response only
+    pub status: String,
+    pub author_id: Option<String>,
+    pub reviewer_id: Option<String>,
+    pub revision: i64, // latest entry in question_revisions
+    pub topic_id: String, // the most specific topic; category and subcategory are its first levels
 }
 
 impl Question {
@@ -40,4 +99,142 @@ impl Question {
     pub fn get_options_vec(&self) -> Vec<String> {
         serde_json::from_str(&self.options).unwrap_or_default()
     }
-}
\ No newline at end of file
+
+    pub fn get_correct_answers(&self) -> Vec<i64> {
+        self.correct_answers
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default()
+    }
+}
+
+// An immutable snapshot of a question's content
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct QuestionRevision {
+    pub question_id: String,
+    pub revision: i64,
+    pub category_id: String,
+    pub subcategory_id: Option<String>,
+    pub kind: String,
+    pub question_text: String,
+    pub options: String, // stored as JSON string
+    pub correct_answer_idx: i64,
+    pub correct_answers: Option<String>,
+    pub explanation: Option<String>,
+    pub tags: String, // JSON array
+    pub author_id: Option<String>,
+    pub note: Option<String>,
+    pub created_at: String,
+    pub topic_id: String,
+}
+
+impl QuestionRevision {
+    pub fn get_options_vec(&self) -> Vec<String> {
+        serde_json::from_str(&self.options).unwrap_or_default()
+    }
+
+    pub fn get_correct_answers(&self) -> Vec<i64> {
+        self.correct_answers
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn get_tags(&self) -> Vec<String> {
+        serde_json::from_str(&self.tags).unwrap_or_default()
+    }
+
+    // The question as it was at this revision, e.g. for grading an answer
+    // to the revision that was served
+    pub fn apply_to(&self, question: &mut Question) {
+        question.category_id = self.category_id.clone();
+        question.subcategory_id = self.subcategory_id.clone();
+        question.topic_id = self.topic_id.clone();
+        question.kind = self.kind.clone();
+        question.question_text = self.question_text.clone();
+        question.options = self.options.clone();
+        question.correct_answer_idx = self.correct_answer_idx;
+        question.correct_answers = self.correct_answers.clone();
+        question.explanation = self.explanation.clone();
+        question.revision = self.revision;
+    }
+}
+
+// An edit of a published question waiting for review; the published content
+// stays live until it is approved
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct QuestionEdit {
+    pub question_id: String,
+    pub category_id: String,
+    pub subcategory_id: Option<String>,
+    pub topic_id: String,
+    pub kind: String,
+    pub question_text: String,
+    pub options: String, // stored as JSON string
+    pub correct_answer_idx: i64,
+    pub correct_answers: Option<String>,
+    pub explanation: Option<String>,
+    pub tags: String, // JSON array
+    pub status: String, // draft or in_review
+    pub author_id: Option<String>,
+    pub reviewer_id: Option<String>,
+    pub updated_at: String,
+}
+
+impl QuestionEdit {
+    pub fn get_options_vec(&self) -> Vec<String> {
+        serde_json::from_str(&self.options).unwrap_or_default()
+    }
+
+    pub fn get_correct_answers(&self) -> Vec<i64> {
+        self.correct_answers
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn get_tags(&self) -> Vec<String> {
+        serde_json::from_str(&self.tags).unwrap_or_default()
+    }
+}
+
+// A curated sequence of steps
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LearningPath {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub deleted_at: Option<String>,
+    pub created_at: String,
+}
+
+// One step of a learning path; which of the optional fields apply depends
+// on its kind
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PathStep {
+    pub id: String,
+    pub path_id: String,
+    pub position: i64,
+    pub kind: String,
+    pub title: String,
+    pub body: Option<String>,
+    pub url: Option<String>,
+    pub topic_id: Option<String>,
+    pub pass_pct: i64,
+    pub flag_hash: Option<String>,
+    pub deleted_at: Option<String>,
+    pub created_at: String,
+}
+
+// A markdown page attached to a subcategory
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Lesson {
+    pub id: String,
+    pub subcategory_id: String,
+    pub title: String,
+    pub body: String,
+    pub position: i64,
+    pub deleted_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
@@ -0,0 +1,1205 @@
+use poem::{
+    handler,
+    web::{Data, Form, Html, Multipart, Path, Query},
+    http::StatusCode,
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite};
+use nanoid::nanoid;
+
+use crate::content::{
+    export::export_bank,
+    import::{import_bank, ChangeKind, ImportReport},
+    pack::{ContentPack, SyncReport},
+    revisions::{pending_edit, record_revision},
+    tags::{check_tags, parse_tags, question_tags, set_question_tags},
+    topics::{is_live, placement, refile_subtree, topic_path, topic_tree, TopicNode},
+    correct_answers_json, parse_bank, render_bank, validate_answers, Format,
+};
+use crate::models::{
+    Category, Question, QuestionEdit, Subcategory, User, KIND_CHOICE, KIND_MULTIPLE_RESPONSE, STATUS_DRAFT,
+    STATUS_IN_REVIEW, STATUS_PUBLISHED,
+};
+use crate::routes::auth::{current_user, SessionStore};
+use crate::routes::review::note_edited_in_review;
+use crate::routes::tokens::{admin_token_user, bearer_token};
+
+#[derive(Template)]
+#[template(path = "admin_dashboard.html")]
+struct AdminDashboardTemplate<'a> {
+    title: &'a str,
+    username: &'a str,
+    categories: &'a [Category],
+    subcategories: &'a [Subcategory],
+    questions: &'a [Question],
+    // In review and assigned to the signed-in user
+    review_queue: &'a [Question],
+    open_reports: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin_category_form.html")]
+struct CategoryFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    form: &'a CategoryForm,
+    errors: &'a [String],
+}
+
+#[derive(Template)]
+#[template(path = "admin_subcategory_form.html")]
+struct SubcategoryFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    form: &'a SubcategoryForm,
+    categories: &'a [Category],
+    errors: &'a [String],
+}
+
+#[derive(Template)]
+#[template(path = "admin_question_form.html")]
+struct QuestionFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    form: &'a QuestionForm,
+    topics: &'a [TopicNode],
+    errors: &'a [String],
+    preview: Option<&'a ValidQuestion>,
+}
+
+impl QuestionFormTemplate<'_> {
+    fn is_chosen(&self, topic_id: &str) -> bool {
+        self.form.topic_id == topic_id
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin_import.html")]
+struct ImportTemplate<'a> {
+    title: &'a str,
+    form: &'a ImportForm,
+    report: Option<&'a ImportReport>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_content_pack.html")]
+struct ContentPackTemplate<'a> {
+    title: &'a str,
+    dir: Option<String>,
+    report: Option<&'a SyncReport>,
+}
+
+// Read from a multipart form so a package can be uploaded as a file
+#[derive(Debug, Default)]
+pub struct ImportForm {
+    // A format name, or "auto" to go by the uploaded file's extension
+    pub format: String,
+    pub content: String,
+    // Checkbox: present means "only show what would change"
+    pub dry_run: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExportParams {
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct CategoryForm {
+    pub title: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct SubcategoryForm {
+    pub category_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct QuestionForm {
+    // Any topic; the category and subcategory follow from where it sits
+    pub topic_id: String,
+    #[serde(default)]
+    pub kind: String,
+    pub question_text: String,
+    pub options: String, // JSON array of strings, as stored
+    #[serde(default)]
+    pub correct_answer_idx: String,
+    // Comma-separated indices, multiple_response only
+    #[serde(default)]
+    pub correct_answers: String,
+    #[serde(default)]
+    pub explanation: String,
+    // Comma-separated
+    #[serde(default)]
+    pub tags: String,
+    // "preview" re-renders the form instead of saving
+    #[serde(default)]
+    pub action: String,
+}
+
+// A question that passed validation, ready to preview or store
+#[derive(Debug, PartialEq)]
+pub struct ValidQuestion {
+    pub category_id: String,
+    pub subcategory_id: Option<String>,
+    pub topic_id: String,
+    pub kind: String,
+    pub question_text: String,
+    pub options: Vec<String>,
+    pub correct_answer_idx: i64,
+    pub correct_answers: Vec<i64>,
+    pub explanation: Option<String>,
+    pub tags: Vec<String>,
+}
+
+// Tags live in their own table; callers that need them fill them in
+impl From<&Question> for ValidQuestion {
+    fn from(q: &Question) -> Self {
+        Self {
+            category_id: q.category_id.clone(),
+            subcategory_id: q.subcategory_id.clone(),
+            topic_id: q.topic_id.clone(),
+            kind: q.kind.clone(),
+            question_text: q.question_text.clone(),
+            options: q.get_options_vec(),
+            correct_answer_idx: q.correct_answer_idx,
+            correct_answers: q.get_correct_answers(),
+            explanation: q.explanation.clone(),
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl From<&QuestionEdit> for ValidQuestion {
+    fn from(e: &QuestionEdit) -> Self {
+        Self {
+            category_id: e.category_id.clone(),
+            subcategory_id: e.subcategory_id.clone(),
+            topic_id: e.topic_id.clone(),
+            kind: e.kind.clone(),
+            question_text: e.question_text.clone(),
+            options: e.get_options_vec(),
+            correct_answer_idx: e.correct_answer_idx,
+            correct_answers: e.get_correct_answers(),
+            explanation: e.explanation.clone(),
+            tags: e.get_tags(),
+        }
+    }
+}
+
+impl ValidQuestion {
+    pub fn is_correct(&self, idx: &usize) -> bool {
+        if self.kind == KIND_MULTIPLE_RESPONSE {
+            self.correct_answers.contains(&(*idx as i64))
+        } else {
+            *idx as i64 == self.correct_answer_idx
+        }
+    }
+}
+
+// Only admins and instructors may manage content
+pub(crate) async fn require_staff(req: &Request, db: &Pool<Sqlite>, session_store: &SessionStore) -> Result<User, Response> {
+    // Scripts send an API token with the admin scope instead of a session
+    if bearer_token(req).is_some() {
+        return admin_token_user(req, db).await.ok_or_else(|| {
+            Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body("This API token can't manage content.")
+        });
+    }
+    match current_user(req, db, session_store).await {
+        Some(user) if user.is_staff() => Ok(user),
+        Some(_) => Err(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body("You do not have permission to manage content.")),
+        None => Err(redirect("/auth/login")),
+    }
+}
+
+pub(crate) fn redirect(location: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header("Location", location)
+        .body(())
+}
+
+pub(crate) fn not_found(what: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(format!("{} not found", what))
+}
+
+fn optional_text(s: &str) -> Option<&str> {
+    Some(s.trim()).filter(|s| !s.is_empty())
+}
+
+async fn all_categories(db: &Pool<Sqlite>) -> Vec<Category> {
+    sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE deleted_at IS NULL ORDER BY title")
+        .fetch_all(db)
+        .await
+        .unwrap()
+}
+
+async fn all_topics(db: &Pool<Sqlite>) -> Vec<TopicNode> {
+    let mut conn = db.acquire().await.unwrap();
+    topic_tree(&mut conn).await.unwrap()
+}
+
+async fn validate_question(db: &Pool<Sqlite>, form: &QuestionForm) -> Result<ValidQuestion, Vec<String>> {
+    let mut errors = Vec::new();
+
+    if form.question_text.trim().is_empty() {
+        errors.push("Question text is required".to_string());
+    }
+
+    let mut conn = db.acquire().await.unwrap();
+    let path = topic_path(&mut conn, form.topic_id.trim()).await.unwrap();
+    if !is_live(&path) {
+        errors.push("Unknown topic".to_string());
+    }
+    let (category_id, subcategory_id) = placement(&path);
+
+    let tags = parse_tags(&form.tags);
+    errors.extend(check_tags(&tags));
+
+    let kind = optional_text(&form.kind).unwrap_or(KIND_CHOICE);
+    let answers = match validate_answers(kind, &form.options, &form.correct_answer_idx, &form.correct_answers) {
+        Ok(parsed) => Some(parsed),
+        Err(mut errs) => {
+            errors.append(&mut errs);
+            None
+        }
+    };
+
+    match answers {
+        Some((options, correct_answer_idx, correct_answers)) if errors.is_empty() => Ok(ValidQuestion {
+            category_id,
+            subcategory_id,
+            topic_id: form.topic_id.trim().to_string(),
+            kind: kind.to_string(),
+            question_text: form.question_text.trim().to_string(),
+            options,
+            correct_answer_idx,
+            correct_answers,
+            explanation: optional_text(&form.explanation).map(|s| s.to_string()),
+            tags,
+        }),
+        _ => Err(errors),
+    }
+}
+
+// ----- Dashboard -----
+
+#[handler]
+pub async fn admin_dashboard(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+
+    // Soft-deleted rows are listed too so they can be restored
+    let categories = sqlx::query_as::<_, Category>("SELECT * FROM categories ORDER BY title")
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+    let subcategories = sqlx::query_as::<_, Subcategory>("SELECT * FROM subcategories ORDER BY category_id, title")
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+    let questions = sqlx::query_as::<_, Question>("SELECT * FROM questions ORDER BY category_id, subcategory_id, id")
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+    let review_queue = sqlx::query_as::<_, Question>(
+        "SELECT * FROM questions WHERE deleted_at IS NULL
+             AND ((status = 'in_review' AND reviewer_id = ?)
+                  OR id IN (SELECT question_id FROM question_edits WHERE status = 'in_review' AND reviewer_id = ?))
+         ORDER BY id"
+    )
+    .bind(&user.id)
+    .bind(&user.id)
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+    let open_reports: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM question_reports WHERE status = 'open'")
+        .fetch_one(&**db)
+        .await
+        .unwrap();
+
+    let tmpl = AdminDashboardTemplate {
+        title: "Hackademy - Admin",
+        username: &user.username,
+        categories: &categories,
+        subcategories: &subcategories,
+        questions: &questions,
+        review_queue: &review_queue,
+        open_reports,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// ----- Categories -----
+
+#[handler]
+pub async fn new_category_form(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let tmpl = CategoryFormTemplate {
+        title: "New Category",
+        action: "/admin/categories/new",
+        form: &CategoryForm::default(),
+        errors: &[],
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn create_category(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<CategoryForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    if form.title.trim().is_empty() {
+        let tmpl = CategoryFormTemplate {
+            title: "New Category",
+            action: "/admin/categories/new",
+            form: &form,
+            errors: &["Title is required".to_string()],
+        };
+        return Html(tmpl.render().unwrap()).into_response();
+    }
+
+    sqlx::query("INSERT INTO categories (id, title) VALUES (?, ?)")
+        .bind(nanoid!())
+        .bind(form.title.trim())
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin")
+}
+
+#[handler]
+pub async fn edit_category_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let category: Option<Category> = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&**db)
+        .await
+        .unwrap();
+    let Some(category) = category else {
+        return not_found("Category");
+    };
+
+    let action = format!("/admin/categories/{}/edit", id);
+    let tmpl = CategoryFormTemplate {
+        title: "Edit Category",
+        action: &action,
+        form: &CategoryForm { title: category.title },
+        errors: &[],
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn update_category(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<CategoryForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    if form.title.trim().is_empty() {
+        let action = format!("/admin/categories/{}/edit", id);
+        let tmpl = CategoryFormTemplate {
+            title: "Edit Category",
+            action: &action,
+            form: &form,
+            errors: &["Title is required".to_string()],
+        };
+        return Html(tmpl.render().unwrap()).into_response();
+    }
+
+    let result = sqlx::query("UPDATE categories SET title = ? WHERE id = ?")
+        .bind(form.title.trim())
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    if result.rows_affected() == 0 {
+        return not_found("Category");
+    }
+    redirect("/admin")
+}
+
+// Deleting a category also hides its subcategories and questions. They share
+// the same deleted_at stamp so a restore brings back exactly that set.
+#[handler]
+pub async fn delete_category(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let mut tx = db.begin().await.unwrap();
+    let now: String = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP")
+        .fetch_one(&mut tx)
+        .await
+        .unwrap();
+    for sql in [
+        "UPDATE categories SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+        "UPDATE subcategories SET deleted_at = ? WHERE category_id = ? AND deleted_at IS NULL",
+        "UPDATE questions SET deleted_at = ? WHERE category_id = ? AND deleted_at IS NULL",
+    ] {
+        sqlx::query(sql)
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    redirect("/admin")
+}
+
+#[handler]
+pub async fn restore_category(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let deleted_at: Option<Option<String>> = sqlx::query_scalar("SELECT deleted_at FROM categories WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&**db)
+        .await
+        .unwrap();
+    let Some(Some(deleted_at)) = deleted_at else {
+        return redirect("/admin");
+    };
+
+    let mut tx = db.begin().await.unwrap();
+    for sql in [
+        "UPDATE categories SET deleted_at = NULL WHERE id = ? AND deleted_at = ?",
+        "UPDATE subcategories SET deleted_at = NULL WHERE category_id = ? AND deleted_at = ?",
+        "UPDATE questions SET deleted_at = NULL WHERE category_id = ? AND deleted_at = ?",
+    ] {
+        sqlx::query(sql)
+            .bind(&id)
+            .bind(&deleted_at)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    redirect("/admin")
+}
+
+// ----- Subcategories -----
+
+#[handler]
+pub async fn new_subcategory_form(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let categories = all_categories(&db).await;
+    let tmpl = SubcategoryFormTemplate {
+        title: "New Subcategory",
+        action: "/admin/subcategories/new",
+        form: &SubcategoryForm::default(),
+        categories: &categories,
+        errors: &[],
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+async fn validate_subcategory(db: &Pool<Sqlite>, form: &SubcategoryForm) -> Vec<String> {
+    let mut errors = Vec::new();
+    if form.title.trim().is_empty() {
+        errors.push("Title is required".to_string());
+    }
+    let category: Option<Category> = sqlx::query_as::<_, Category>(
+        "SELECT * FROM categories WHERE id = ? AND deleted_at IS NULL"
+    )
+    .bind(&form.category_id)
+    .fetch_optional(db)
+    .await
+    .unwrap();
+    if category.is_none() {
+        errors.push("Unknown category".to_string());
+    }
+    errors
+}
+
+#[handler]
+pub async fn create_subcategory(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<SubcategoryForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let errors = validate_subcategory(&db, &form).await;
+    if !errors.is_empty() {
+        let categories = all_categories(&db).await;
+        let tmpl = SubcategoryFormTemplate {
+            title: "New Subcategory",
+            action: "/admin/subcategories/new",
+            form: &form,
+            categories: &categories,
+            errors: &errors,
+        };
+        return Html(tmpl.render().unwrap()).into_response();
+    }
+
+    sqlx::query("INSERT INTO subcategories (id, category_id, title, description) VALUES (?, ?, ?, ?)")
+        .bind(nanoid!())
+        .bind(&form.category_id)
+        .bind(form.title.trim())
+        .bind(optional_text(&form.description))
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin")
+}
+
+#[handler]
+pub async fn edit_subcategory_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let sub: Option<Subcategory> = sqlx::query_as::<_, Subcategory>("SELECT * FROM subcategories WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&**db)
+        .await
+        .unwrap();
+    let Some(sub) = sub else {
+        return not_found("Subcategory");
+    };
+
+    let categories = all_categories(&db).await;
+    let action = format!("/admin/subcategories/{}/edit", id);
+    let form = SubcategoryForm {
+        category_id: sub.category_id,
+        title: sub.title,
+        description: sub.description.unwrap_or_default(),
+    };
+    let tmpl = SubcategoryFormTemplate {
+        title: "Edit Subcategory",
+        action: &action,
+        form: &form,
+        categories: &categories,
+        errors: &[],
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn update_subcategory(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<SubcategoryForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let errors = validate_subcategory(&db, &form).await;
+    if !errors.is_empty() {
+        let categories = all_categories(&db).await;
+        let action = format!("/admin/subcategories/{}/edit", id);
+        let tmpl = SubcategoryFormTemplate {
+            title: "Edit Subcategory",
+            action: &action,
+            form: &form,
+            categories: &categories,
+            errors: &errors,
+        };
+        return Html(tmpl.render().unwrap()).into_response();
+    }
+
+    let mut tx = db.begin().await.unwrap();
+    let result = sqlx::query("UPDATE subcategories SET category_id = ?, title = ?, description = ? WHERE id = ?")
+        .bind(&form.category_id)
+        .bind(form.title.trim())
+        .bind(optional_text(&form.description))
+        .bind(&id)
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    if result.rows_affected() == 0 {
+        return not_found("Subcategory");
+    }
+    // Its questions, down to the deepest topics, follow it to the new category
+    refile_subtree(&mut tx, &id).await.unwrap();
+    tx.commit().await.unwrap();
+    redirect("/admin")
+}
+
+#[handler]
+pub async fn delete_subcategory(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let mut tx = db.begin().await.unwrap();
+    let now: String = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP")
+        .fetch_one(&mut tx)
+        .await
+        .unwrap();
+    for sql in [
+        "UPDATE subcategories SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+        "UPDATE questions SET deleted_at = ? WHERE subcategory_id = ? AND deleted_at IS NULL",
+    ] {
+        sqlx::query(sql)
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    redirect("/admin")
+}
+
+#[handler]
+pub async fn restore_subcategory(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let subcategory: Option<(Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT s.deleted_at, c.deleted_at FROM subcategories s LEFT JOIN categories c ON c.id = s.category_id
+         WHERE s.id = ?"
+    )
+    .bind(&id)
+    .fetch_optional(&**db)
+    .await
+    .unwrap();
+    let Some((Some(deleted_at), category_deleted_at)) = subcategory else {
+        return redirect("/admin");
+    };
+    if category_deleted_at.is_some() {
+        return Response::builder()
+            .status(StatusCode::CONFLICT)
+            .body("Restore the subcategory's category first");
+    }
+
+    let mut tx = db.begin().await.unwrap();
+    for sql in [
+        "UPDATE subcategories SET deleted_at = NULL WHERE id = ? AND deleted_at = ?",
+        "UPDATE questions SET deleted_at = NULL WHERE subcategory_id = ? AND deleted_at = ?",
+    ] {
+        sqlx::query(sql)
+            .bind(&id)
+            .bind(&deleted_at)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    redirect("/admin")
+}
+
+// ----- Questions -----
+
+#[handler]
+pub async fn new_question_form(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let topics = all_topics(&db).await;
+    let form = QuestionForm {
+        kind: KIND_CHOICE.to_string(),
+        options: "[\"\", \"\"]".to_string(),
+        correct_answer_idx: "0".to_string(),
+        ..Default::default()
+    };
+    let tmpl = QuestionFormTemplate {
+        title: "New Question",
+        action: "/admin/questions/new",
+        form: &form,
+        topics: &topics,
+        errors: &[],
+        preview: None,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// Shared by create and update: validate, then either preview or hand back
+// the validated question for saving.
+async fn question_form_outcome(
+    db: &Pool<Sqlite>,
+    title: &str,
+    action: &str,
+    form: &QuestionForm,
+) -> Result<ValidQuestion, Response> {
+    let validated = validate_question(db, form).await;
+    if form.action != "preview"
+        && let Ok(question) = validated
+    {
+        return Ok(question);
+    }
+
+    let topics = all_topics(db).await;
+    let (errors, preview) = match &validated {
+        Ok(question) => (Vec::new(), Some(question)),
+        Err(errors) => (errors.clone(), None),
+    };
+    let tmpl = QuestionFormTemplate {
+        title,
+        action,
+        form,
+        topics: &topics,
+        errors: &errors,
+        preview,
+    };
+    Err(Html(tmpl.render().unwrap()).into_response())
+}
+
+#[handler]
+pub async fn create_question(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<QuestionForm>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let question = match question_form_outcome(&db, "New Question", "/admin/questions/new", &form).await {
+        Ok(q) => q,
+        Err(resp) => return resp,
+    };
+
+    // New questions start as drafts and go live once a reviewer approves them
+    let id = nanoid!();
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query(
+        "INSERT INTO questions (id, category_id, subcategory_id, topic_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation, status, author_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(&question.category_id)
+    .bind(&question.subcategory_id)
+    .bind(&question.topic_id)
+    .bind(&question.kind)
+    .bind(&question.question_text)
+    .bind(serde_json::to_string(&question.options).unwrap())
+    .bind(question.correct_answer_idx)
+    .bind(correct_answers_json(&question.correct_answers))
+    .bind(&question.explanation)
+    .bind(STATUS_DRAFT)
+    .bind(&user.id)
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    set_question_tags(&mut tx, &id, &question.tags).await.unwrap();
+    record_revision(&mut tx, &id, Some(&user.id), "created").await.unwrap();
+    tx.commit().await.unwrap();
+    redirect(&format!("/admin/questions/{}/review", id))
+}
+
+#[handler]
+pub async fn edit_question_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let question: Option<Question> = sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&**db)
+        .await
+        .unwrap();
+    let Some(q) = question else {
+        return not_found("Question");
+    };
+    // A published question's pending edit is what gets edited further
+    let mut conn = db.acquire().await.unwrap();
+    let q = match pending_edit(&mut conn, &id).await.unwrap() {
+        Some(edit) => ValidQuestion::from(&edit),
+        None => {
+            let mut current = ValidQuestion::from(&q);
+            current.tags = question_tags(&mut conn, &id).await.unwrap();
+            current
+        }
+    };
+
+    let topics = all_topics(&db).await;
+    let action = format!("/admin/questions/{}/edit", id);
+    let form = QuestionForm {
+        correct_answers: q.correct_answers.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", "),
+        topic_id: q.topic_id,
+        kind: q.kind,
+        question_text: q.question_text,
+        options: serde_json::to_string(&q.options).unwrap(),
+        correct_answer_idx: q.correct_answer_idx.to_string(),
+        explanation: q.explanation.unwrap_or_default(),
+        tags: q.tags.join(", "),
+        action: String::new(),
+    };
+    let tmpl = QuestionFormTemplate {
+        title: "Edit Question",
+        action: &action,
+        form: &form,
+        topics: &topics,
+        errors: &[],
+        preview: None,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn update_question(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<QuestionForm>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let action = format!("/admin/questions/{}/edit", id);
+    let question = match question_form_outcome(&db, "Edit Question", &action, &form).await {
+        Ok(q) => q,
+        Err(resp) => return resp,
+    };
+
+    let mut tx = db.begin().await.unwrap();
+    let existing: Option<Question> = sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&mut tx)
+        .await
+        .unwrap();
+    let Some(existing) = existing else {
+        return not_found("Question");
+    };
+    // Saving without changes doesn't make a new revision
+    let mut current = ValidQuestion::from(&existing);
+    current.tags = question_tags(&mut tx, &id).await.unwrap();
+    if existing.status == STATUS_PUBLISHED {
+        save_edit(&mut tx, &id, &user, &current, &question).await;
+        tx.commit().await.unwrap();
+        return redirect(&format!("/admin/questions/{}/review", id));
+    }
+    if current == question {
+        return redirect("/admin");
+    }
+
+    // The editor is the author now; see `note_edited_in_review`
+    sqlx::query(
+        "UPDATE questions SET category_id = ?, subcategory_id = ?, topic_id = ?, kind = ?, question_text = ?, options = ?, correct_answer_idx = ?, correct_answers = ?, explanation = ?,
+             author_id = ?, status = CASE WHEN status = 'in_review' THEN 'draft' ELSE status END
+         WHERE id = ?"
+    )
+    .bind(&question.category_id)
+    .bind(&question.subcategory_id)
+    .bind(&question.topic_id)
+    .bind(&question.kind)
+    .bind(&question.question_text)
+    .bind(serde_json::to_string(&question.options).unwrap())
+    .bind(question.correct_answer_idx)
+    .bind(correct_answers_json(&question.correct_answers))
+    .bind(&question.explanation)
+    .bind(&user.id)
+    .bind(&id)
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    if existing.status == STATUS_IN_REVIEW {
+        note_edited_in_review(&mut tx, &id, &user.id).await.unwrap();
+    }
+    set_question_tags(&mut tx, &id, &question.tags).await.unwrap();
+    record_revision(&mut tx, &id, Some(&user.id), "edited").await.unwrap();
+    tx.commit().await.unwrap();
+    redirect("/admin")
+}
+
+// Published questions stay as they are while an edit is reviewed; the edit
+// waits in question_edits as a draft, authored by whoever changed it last.
+// Editing it back to the published content drops it.
+async fn save_edit(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    id: &str,
+    user: &User,
+    published: &ValidQuestion,
+    question: &ValidQuestion,
+) {
+    let pending = pending_edit(&mut *tx, id).await.unwrap();
+    if pending.as_ref().is_some_and(|e| ValidQuestion::from(e) == *question) {
+        return;
+    }
+    if question == published {
+        sqlx::query("DELETE FROM question_edits WHERE question_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        return;
+    }
+    sqlx::query(
+        "INSERT INTO question_edits (question_id, category_id, subcategory_id, topic_id, kind, question_text, options, correct_answer_idx, correct_answers, explanation, tags, author_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (question_id) DO UPDATE SET category_id = excluded.category_id, subcategory_id = excluded.subcategory_id,
+             topic_id = excluded.topic_id, kind = excluded.kind, question_text = excluded.question_text, options = excluded.options,
+             correct_answer_idx = excluded.correct_answer_idx, correct_answers = excluded.correct_answers,
+             explanation = excluded.explanation, tags = excluded.tags, author_id = excluded.author_id, status = 'draft',
+             updated_at = CURRENT_TIMESTAMP"
+    )
+    .bind(id)
+    .bind(&question.category_id)
+    .bind(&question.subcategory_id)
+    .bind(&question.topic_id)
+    .bind(&question.kind)
+    .bind(&question.question_text)
+    .bind(serde_json::to_string(&question.options).unwrap())
+    .bind(question.correct_answer_idx)
+    .bind(correct_answers_json(&question.correct_answers))
+    .bind(&question.explanation)
+    .bind(serde_json::to_string(&question.tags).unwrap())
+    .bind(&user.id)
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    if pending.is_some_and(|e| e.status == STATUS_IN_REVIEW) {
+        note_edited_in_review(&mut *tx, id, &user.id).await.unwrap();
+    }
+}
+
+#[handler]
+pub async fn delete_question(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    sqlx::query("UPDATE questions SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL")
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin")
+}
+
+#[handler]
+pub async fn restore_question(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let topic_id: Option<Option<String>> =
+        sqlx::query_scalar("SELECT COALESCE(topic_id, subcategory_id, category_id) FROM questions WHERE id = ?")
+            .bind(&id)
+            .fetch_optional(&**db)
+            .await
+            .unwrap();
+    let Some(topic_id) = topic_id else {
+        return not_found("Question");
+    };
+    // The topic path covers the category and subcategory too
+    let mut conn = db.acquire().await.unwrap();
+    let parent = topic_path(&mut conn, topic_id.as_deref().unwrap_or("")).await.unwrap();
+    drop(conn);
+    if !is_live(&parent) {
+        return Response::builder()
+            .status(StatusCode::CONFLICT)
+            .body("Restore the question's category, subcategory or topic first");
+    }
+    sqlx::query("UPDATE questions SET deleted_at = NULL WHERE id = ?")
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin")
+}
+
+// ----- Import / export -----
+
+#[handler]
+pub async fn import_form(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let form = ImportForm {
+        format: "auto".to_string(),
+        dry_run: Some("on".to_string()),
+        ..Default::default()
+    };
+    let tmpl = ImportTemplate {
+        title: "Import Content",
+        form: &form,
+        report: None,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn import_content(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    mut multipart: Multipart,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let mut form = ImportForm::default();
+    let mut upload: Option<(String, Vec<u8>)> = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name() {
+            Some("format") => form.format = field.text().await.unwrap_or_default(),
+            Some("content") => form.content = field.text().await.unwrap_or_default(),
+            Some("dry_run") => form.dry_run = Some("on".to_string()),
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or_default().to_string();
+                let data = field.bytes().await.unwrap_or_default();
+                if !data.is_empty() {
+                    upload = Some((file_name, data));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // An uploaded file takes precedence over pasted content
+    let (input, format) = match (&upload, form.format.as_str()) {
+        (Some((file_name, data)), "auto") => (data.as_slice(), Format::from_path(file_name)),
+        (Some((_, data)), name) => (data.as_slice(), Format::from_name(name)),
+        (None, "auto") => (
+            form.content.as_bytes(),
+            Err(anyhow::anyhow!("Choose a format for pasted content, or upload a file")),
+        ),
+        (None, name) => (form.content.as_bytes(), Format::from_name(name)),
+    };
+    let report = match format {
+        Ok(format) => {
+            let parsed = parse_bank(input, format);
+            import_bank(&db, &parsed, form.dry_run.is_some()).await.unwrap()
+        }
+        Err(e) => ImportReport {
+            errors: vec![e.to_string()],
+            ..Default::default()
+        },
+    };
+
+    let tmpl = ImportTemplate {
+        title: "Import Content",
+        form: &form,
+        report: Some(&report),
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn export_content(
+    req: &Request,
+    Query(params): Query<ExportParams>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let format = match Format::from_name(params.format.as_deref().unwrap_or("yaml")) {
+        Ok(f) => f,
+        Err(e) => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(e.to_string())
+        }
+    };
+    let bank = export_bank(&db).await.unwrap();
+    let file_name = format!("hackademy-content.{}", format.extension());
+    Response::builder()
+        .content_type(format.content_type())
+        .header("Content-Disposition", format!("attachment; filename=\"{}\"", file_name))
+        .body(render_bank(&bank, format).unwrap())
+}
+
+// ----- Content pack -----
+
+#[handler]
+pub async fn content_pack_status(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    content_pack: Data<&ContentPack>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let report = content_pack.last_report();
+    let tmpl = ContentPackTemplate {
+        title: "Content Pack",
+        dir: content_pack.dir.as_ref().map(|d| d.display().to_string()),
+        report: report.as_ref(),
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn sync_content_pack(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    content_pack: Data<&ContentPack>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    content_pack.sync(&db).await;
+    redirect("/admin/content-pack")
+}
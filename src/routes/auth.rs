@@ -2,15 +2,19 @@ use poem::{
     handler,
     web::{Form, Data},
     http::{header::SET_COOKIE, StatusCode},
-    IntoResponse, Request,
+    IntoResponse, Request, Response,
 };
 use askama::Template;
 use sqlx::{Pool, Sqlite};
 use nanoid::nanoid;
 use std::sync::{Arc, Mutex};
 
-use crate::models::User;
-use crate::utils::security::{hash_password, verify_password};
+use crate::auth::oidc::Oidc;
+use crate::auth::providers::AuthProviders;
+use crate::models::{User, ROLE_STUDENT};
+use crate::routes::oidc::{user_identities, UserIdentity};
+use crate::routes::tokens::{user_tokens, ApiToken, LIFETIMES, SCOPES, SCOPE_ADMIN};
+use crate::utils::security::hash_password;
 
 #[derive(Debug, Clone)]
 pub struct SessionStore {
@@ -48,14 +52,34 @@ struct RegisterTemplate {
 
 #[derive(Template)]
 #[template(path = "auth_login.html")]
-struct LoginTemplate {
+struct LoginTemplate<'a> {
     error: Option<String>,
+    // Name of the single sign-on provider, if enabled
+    sso: Option<&'a str>,
 }
 
 #[derive(Template)]
 #[template(path = "auth_profile.html")]
 struct ProfileTemplate<'a> {
     username: &'a str,
+    is_staff: bool,
+    tokens: &'a [ApiToken],
+    identities: &'a [UserIdentity],
+    sso: Option<&'a str>,
+    // Whether an identity may be unlinked without locking the user out
+    can_unlink: bool,
+    scopes: &'a [(&'a str, &'a str)],
+    lifetimes: &'a [(&'a str, &'a str)],
+    // A token just created, shown this once
+    new_token: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+impl ProfileTemplate<'_> {
+    // Only staff may create admin tokens
+    fn offers(&self, scope: &str) -> bool {
+        scope != SCOPE_ADMIN || self.is_staff
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -104,11 +128,12 @@ pub async fn register_user(db: Data<&Pool<Sqlite>>, Form(form): Form<RegisterFor
     // Create user record
     let user_id = nanoid!();
     sqlx::query(
-        "INSERT INTO users (id, username, password_hash) VALUES (?, ?, ?)"
+        "INSERT INTO users (id, username, password_hash, role) VALUES (?, ?, ?, ?)"
     )
     .bind(&user_id)
     .bind(&form.username)
     .bind(&hashed)
+    .bind(ROLE_STUDENT)
     .execute(&**db)
     .await
     .unwrap();
@@ -121,9 +146,24 @@ pub async fn register_user(db: Data<&Pool<Sqlite>>, Form(form): Form<RegisterFor
 }
 
 #[handler]
-pub async fn login_form() -> impl IntoResponse {
-    let tmpl = LoginTemplate { error: None };
-    tmpl.render().unwrap()
+pub async fn login_form(oidc: Data<&Oidc>) -> impl IntoResponse {
+    login_page(&oidc, None)
+}
+
+pub(crate) fn login_page(oidc: &Oidc, error: Option<String>) -> Response {
+    let tmpl = LoginTemplate { error, sso: oidc.name() };
+    tmpl.render().unwrap().into_response()
+}
+
+// Sign the user in with a new session and send them to their profile
+pub(crate) fn start_session(session_store: &SessionStore, user_id: &str) -> Response {
+    let session_id = nanoid!();
+    session_store.set_session(&session_id, user_id);
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header("Location", "/auth/profile")
+        .header(SET_COOKIE, format!("hackademy_session_id={}; Path=/; HttpOnly", session_id))
+        .body("Login successful!")
 }
 
 #[handler]
@@ -131,39 +171,22 @@ pub async fn login_user(
     db: Data<&Pool<Sqlite>>,
     Form(form): Form<LoginForm>,
     session_store: Data<&SessionStore>,
+    oidc: Data<&Oidc>,
+    providers: Data<&AuthProviders>,
 ) -> impl IntoResponse {
-    let user: Option<User> = sqlx::query_as::<_, User>(
-        "SELECT * FROM users WHERE username = ?"
-    )
-    .bind(&form.username)
-    .fetch_optional(&**db)
-    .await
-    .unwrap();
-
-    if let Some(u) = user {
-        if verify_password(&form.password, &u.password_hash) {
-            let session_id = nanoid!();
-            session_store.set_session(&session_id, &u.id);
-
-            let cookie_value = format!("hackademy_session_id={}; Path=/; HttpOnly", session_id);
-
-            return (
-                StatusCode::FOUND,
-                [("Location", "/auth/profile"), (SET_COOKIE, cookie_value)],
-                "Login successful!",
-            );
-        }
+    match providers.login(&db, &form.username, &form.password).await {
+        Ok(user_id) => start_session(&session_store, &user_id),
+        Err(e) => login_page(&oidc, Some(e.message().into())),
     }
-
-    // Invalid user/pass
-    let tmpl = LoginTemplate {
-        error: Some("Invalid username or password".into()),
-    };
-    tmpl.render().unwrap()
 }
 
 #[handler]
-pub async fn profile(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> impl IntoResponse {
+pub async fn profile(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    oidc: Data<&Oidc>,
+) -> impl IntoResponse {
     let sid = get_session_id_from_cookie(req);
     if let Some(session_id) = sid {
         if let Some(user_id) = session_store.get_user_id(&session_id) {
@@ -177,8 +200,7 @@ pub async fn profile(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data
             .unwrap();
 
             if let Some(u) = user {
-                let tmpl = ProfileTemplate { username: &u.username };
-                return tmpl.render().unwrap();
+                return render_profile(&db, &oidc, &u, None, None).await;
             }
         }
     }
@@ -188,6 +210,30 @@ pub async fn profile(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data
         .body("Redirecting to login...")
 }
 
+pub(crate) async fn render_profile(
+    db: &Pool<Sqlite>,
+    oidc: &Oidc,
+    user: &User,
+    new_token: Option<&str>,
+    error: Option<&str>,
+) -> Response {
+    let tokens = user_tokens(db, &user.id).await;
+    let identities = user_identities(db, &user.id).await;
+    let tmpl = ProfileTemplate {
+        username: &user.username,
+        is_staff: user.is_staff(),
+        tokens: &tokens,
+        identities: &identities,
+        sso: oidc.name(),
+        can_unlink: user.has_password() || identities.len() > 1,
+        scopes: SCOPES,
+        lifetimes: LIFETIMES,
+        new_token,
+        error,
+    };
+    tmpl.render().unwrap().into_response()
+}
+
 #[handler]
 pub async fn logout(req: &Request, session_store: Data<&SessionStore>) -> impl IntoResponse {
     if let Some(sid) = get_session_id_from_cookie(req) {
@@ -201,6 +247,17 @@ pub async fn logout(req: &Request, session_store: Data<&SessionStore>) -> impl I
     )
 }
 
+// Resolve the logged-in user from the session cookie, if any
+pub async fn current_user(req: &Request, db: &Pool<Sqlite>, session_store: &SessionStore) -> Option<User> {
+    let session_id = get_session_id_from_cookie(req)?;
+    let user_id = session_store.get_user_id(&session_id)?;
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(&user_id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
 fn get_session_id_from_cookie(req: &Request) -> Option<String> {
     if let Some(cookie) = req.cookie() {
         cookie.get("hackademy_session_id").map(|s| s.to_string())
@@ -12,10 +12,16 @@ pub struct CategoryListTemplate<'a> {
 
 #[handler]
 pub async fn get_categories(db: Data<&Pool<Sqlite>>) -> impl IntoResponse {
-    let categories = sqlx::query_as::<_, Category>("SELECT * FROM categories ORDER BY title")
-        .fetch_all(&**db)
-        .await
-        .unwrap();
+    // Only categories with something to quiz on
+    let categories = sqlx::query_as::<_, Category>(
+        "SELECT * FROM categories WHERE deleted_at IS NULL AND EXISTS (
+            SELECT 1 FROM questions q
+            WHERE q.category_id = categories.id AND q.deleted_at IS NULL AND q.status = 'published'
+        ) ORDER BY title"
+    )
+    .fetch_all(&**db)
+    .await
+    .unwrap();
 
     let tmpl = CategoryListTemplate {
         title: "Hackademy - Categories",
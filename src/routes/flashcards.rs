@@ -0,0 +1,350 @@
+use poem::{
+    handler,
+    http::StatusCode,
+    web::{Data, Form, Html, Query},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+
+use crate::content::review::{record_review, RATINGS};
+use crate::content::similarity::{related_to, RelatedItem, KIND_QUESTION};
+use crate::content::scoring::accepted_answers;
+use crate::content::topics::{topic_tree, TopicNode};
+use crate::models::Question;
+use crate::routes::admin::{not_found, redirect};
+use crate::routes::auth::{current_user, SessionStore};
+use crate::routes::quiz::correct_answer;
+use crate::routes::tags::TagCount;
+
+// Related questions and lessons shown with the answer
+const RELATED: i64 = 3;
+
+// A deck is the published questions of a category, a subcategory or a tag,
+// or every question due for the signed-in user
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct DeckParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subcategory_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    // "1" for the cards due for review, whatever their topic
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    // Without a user to schedule for, cards come in id order from here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+impl DeckParams {
+    pub fn category(&self) -> Option<&str> {
+        self.category_id.as_deref().filter(|c| !c.is_empty())
+    }
+
+    pub fn subcategory(&self) -> Option<&str> {
+        self.subcategory_id.as_deref().filter(|s| !s.is_empty())
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref().filter(|t| !t.is_empty())
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.due.as_deref() == Some("1")
+    }
+
+    // SQL conditions on questions `q` picking the deck, with their binds
+    fn filter(&self) -> (String, Vec<String>) {
+        let mut sql = String::new();
+        let mut binds = Vec::new();
+        if let Some(category_id) = self.category() {
+            sql.push_str(" AND q.category_id = ?");
+            binds.push(category_id.to_string());
+        }
+        if let Some(subcategory_id) = self.subcategory() {
+            sql.push_str(" AND q.subcategory_id = ?");
+            binds.push(subcategory_id.to_string());
+        }
+        if let Some(tag) = self.tag() {
+            sql.push_str(" AND q.id IN (SELECT question_id FROM question_tags WHERE tag = ?)");
+            binds.push(tag.to_string());
+        }
+        (sql, binds)
+    }
+
+    // The study URL for this deck, starting after `after`
+    fn study_url(&self, after: Option<&str>) -> String {
+        let params = DeckParams {
+            category_id: self.category().map(String::from),
+            subcategory_id: self.subcategory().map(String::from),
+            tag: self.tag().map(String::from),
+            due: self.is_due().then(|| "1".to_string()),
+            after: after.map(String::from),
+        };
+        format!("/flashcards/study?{}", serde_urlencoded::to_string(&params).unwrap())
+    }
+}
+
+// The front and back of a card
+pub struct Flashcard {
+    pub question_id: String,
+    pub front: String,
+    pub answer: String,
+    pub explanation: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "flashcard_decks.html")]
+struct DecksTemplate<'a> {
+    title: &'a str,
+    // Categories and subcategories with published questions
+    topics: &'a [(TopicNode, i64)],
+    tags: &'a [TagCount],
+    signed_in: bool,
+    // Cards due now
+    due: i64,
+}
+
+#[derive(Template)]
+#[template(path = "flashcard.html")]
+struct FlashcardTemplate<'a> {
+    title: &'a str,
+    deck_title: &'a str,
+    deck: &'a DeckParams,
+    card: Option<&'a Flashcard>,
+    ratings: &'a [(&'a str, &'a str)],
+    signed_in: bool,
+    // Cards due now and not yet seen, for a signed-in user
+    due: i64,
+    new: i64,
+    // Where "next" goes when there is no one to schedule for
+    next_url: &'a str,
+    related: &'a [RelatedItem],
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RatingForm {
+    pub question_id: String,
+    pub rating: String,
+    pub category_id: Option<String>,
+    pub subcategory_id: Option<String>,
+    pub tag: Option<String>,
+    pub due: Option<String>,
+}
+
+#[handler]
+pub async fn flashcard_decks(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> impl IntoResponse {
+    let user = current_user(req, &db, &session_store).await;
+    let mut conn = db.acquire().await.unwrap();
+    let counts: std::collections::HashMap<String, i64> = sqlx::query_as::<_, (String, i64)>(
+        "SELECT category_id, COUNT(*) FROM questions WHERE deleted_at IS NULL AND status = 'published' GROUP BY category_id
+         UNION ALL
+         SELECT subcategory_id, COUNT(*) FROM questions
+         WHERE deleted_at IS NULL AND status = 'published' AND subcategory_id IS NOT NULL GROUP BY subcategory_id"
+    )
+    .fetch_all(&mut conn)
+    .await
+    .unwrap()
+    .into_iter()
+    .collect();
+    let topics: Vec<(TopicNode, i64)> = topic_tree(&mut conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|n| n.depth <= 1)
+        .filter_map(|n| counts.get(&n.topic.id).map(|c| (n, *c)))
+        .collect();
+    let tags = sqlx::query_as::<_, TagCount>(
+        "SELECT t.tag, COUNT(*) AS questions
+         FROM question_tags t JOIN questions q ON q.id = t.question_id
+         WHERE q.deleted_at IS NULL AND q.status = 'published'
+         GROUP BY t.tag ORDER BY t.tag"
+    )
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+    let due = match &user {
+        Some(user) => deck_counts(&mut conn, &user.id, &DeckParams::default()).await.0,
+        None => 0,
+    };
+
+    let tmpl = DecksTemplate {
+        title: "Hackademy - Flashcards",
+        topics: &topics,
+        tags: &tags,
+        signed_in: user.is_some(),
+        due,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// (cards due now, cards never reviewed) in the deck
+async fn deck_counts(conn: &mut SqliteConnection, user_id: &str, deck: &DeckParams) -> (i64, i64) {
+    let (filter, binds) = deck.filter();
+    let sql = format!(
+        "SELECT
+             COALESCE(SUM(r.due_at <= datetime('now')), 0),
+             COALESCE(SUM(r.question_id IS NULL), 0)
+         FROM questions q LEFT JOIN review_schedule r ON r.question_id = q.id AND r.user_id = ?
+         WHERE q.deleted_at IS NULL AND q.status = 'published'{}",
+        filter
+    );
+    let mut query = sqlx::query_as::<_, (i64, i64)>(&sql).bind(user_id);
+    for value in &binds {
+        query = query.bind(value);
+    }
+    query.fetch_one(&mut *conn).await.unwrap()
+}
+
+// The next card to study: for a user, the most overdue card and then cards
+// they have never reviewed; otherwise the deck in id order
+async fn next_card(conn: &mut SqliteConnection, user_id: Option<&str>, deck: &DeckParams) -> Option<Question> {
+    let (filter, binds) = deck.filter();
+    let published = "q.deleted_at IS NULL AND q.status = 'published'";
+    let queries = match user_id {
+        Some(_) => {
+            let mut queries = vec![format!(
+                "SELECT q.* FROM questions q JOIN review_schedule r ON r.question_id = q.id AND r.user_id = ?
+                 WHERE {} AND r.due_at <= datetime('now'){} ORDER BY r.due_at, q.id LIMIT 1",
+                published, filter
+            )];
+            if !deck.is_due() {
+                queries.push(format!(
+                    "SELECT q.* FROM questions q
+                     WHERE q.id NOT IN (SELECT question_id FROM review_schedule WHERE user_id = ?) AND {}{}
+                     ORDER BY q.id LIMIT 1",
+                    published, filter
+                ));
+            }
+            queries
+        }
+        None => vec![format!(
+            "SELECT q.* FROM questions q WHERE q.id > ? AND {}{} ORDER BY q.id LIMIT 1",
+            published, filter
+        )],
+    };
+
+    for sql in queries {
+        let first = user_id.map(String::from).unwrap_or_else(|| deck.after.clone().unwrap_or_default());
+        let mut query = sqlx::query_as::<_, Question>(&sql).bind(first);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        if let Some(q) = query.fetch_optional(&mut *conn).await.unwrap() {
+            return Some(q);
+        }
+    }
+    None
+}
+
+async fn deck_title(conn: &mut SqliteConnection, deck: &DeckParams) -> Option<String> {
+    if deck.is_due() {
+        return Some("Due for review".to_string());
+    }
+    if let Some(tag) = deck.tag() {
+        return Some(format!("Tag: {}", tag));
+    }
+    let topic_id = deck.subcategory().or(deck.category())?;
+    sqlx::query_scalar("SELECT title FROM topics WHERE id = ? AND deleted_at IS NULL")
+        .bind(topic_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .unwrap()
+}
+
+#[handler]
+pub async fn study_flashcards(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Query(deck): Query<DeckParams>,
+) -> Response {
+    let user = current_user(req, &db, &session_store).await;
+    if deck.is_due() && user.is_none() {
+        return redirect("/auth/login");
+    }
+    if deck.category().is_none() && deck.subcategory().is_none() && deck.tag().is_none() && !deck.is_due() {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Choose a category, subcategory or tag for the deck");
+    }
+    let mut conn = db.acquire().await.unwrap();
+    let Some(deck_title) = deck_title(&mut conn, &deck).await else {
+        return not_found("Deck");
+    };
+
+    let user_id = user.as_ref().map(|u| u.id.as_str());
+    let card = match next_card(&mut conn, user_id, &deck).await {
+        Some(q) => {
+            let accepted = accepted_answers(&mut conn, &q.id, q.revision).await.unwrap();
+            Some(Flashcard {
+                answer: correct_answer(&q, &accepted),
+                question_id: q.id,
+                front: q.question_text,
+                explanation: q.explanation,
+            })
+        }
+        None => None,
+    };
+    let (due, new) = match user_id {
+        Some(user_id) => deck_counts(&mut conn, user_id, &deck).await,
+        None => (0, 0),
+    };
+    let next_url = deck.study_url(card.as_ref().map(|c| c.question_id.as_str()));
+    let related = match &card {
+        Some(card) => related_to(&mut conn, KIND_QUESTION, &card.question_id, RELATED).await.unwrap(),
+        None => Vec::new(),
+    };
+
+    let tmpl = FlashcardTemplate {
+        title: &format!("Hackademy - {}", deck_title),
+        deck_title: &deck_title,
+        deck: &deck,
+        card: card.as_ref(),
+        ratings: RATINGS,
+        signed_in: user.is_some(),
+        due,
+        new,
+        next_url: &next_url,
+        related: &related,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn rate_flashcard(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<RatingForm>,
+) -> Response {
+    let Some(user) = current_user(req, &db, &session_store).await else {
+        return redirect("/auth/login");
+    };
+    if !RATINGS.iter().any(|(value, _)| *value == form.rating) {
+        return Response::builder().status(StatusCode::BAD_REQUEST).body("Unknown rating");
+    }
+    let exists: Option<String> = sqlx::query_scalar(
+        "SELECT id FROM questions WHERE id = ? AND deleted_at IS NULL AND status = 'published'"
+    )
+    .bind(&form.question_id)
+    .fetch_optional(&**db)
+    .await
+    .unwrap();
+    if exists.is_none() {
+        return not_found("Question");
+    }
+    let mut conn = db.acquire().await.unwrap();
+    record_review(&mut conn, &user.id, &form.question_id, &form.rating, None).await.unwrap();
+
+    let deck = DeckParams {
+        category_id: form.category_id,
+        subcategory_id: form.subcategory_id,
+        tag: form.tag,
+        due: form.due,
+        after: None,
+    };
+    redirect(&deck.study_url(None))
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+
+use poem::{
+    handler,
+    http::StatusCode,
+    web::{Data, Form, Html, Path, Query},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use nanoid::nanoid;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+
+use crate::content::lessons::{embedded_questions, record_progress, render_lesson, LessonPart};
+use crate::content::similarity::{related_to, RelatedItem, KIND_LESSON};
+use crate::content::topics::{is_live, topic_path};
+use crate::models::{Lesson, Question, Topic, User};
+use crate::routes::admin::{not_found, redirect, require_staff};
+use crate::routes::auth::{current_user, SessionStore};
+use crate::routes::quiz::{
+    grade_served, quiz_question, record_served, served_revision, take_served, QuestionFeedback, QuizQuestion,
+    SubmittedAnswer, SERVED_EXPIRED,
+};
+
+// Related questions and lessons listed below a lesson
+const RELATED: i64 = 5;
+
+// A lesson as laid out on its page
+pub enum LessonBlock {
+    Html(String),
+    // An embedded question, with feedback once it has been checked
+    Question(QuizQuestion, Option<QuestionFeedback>),
+}
+
+// Render a lesson's markdown, loading the questions it embeds. Embeds of
+// questions that are gone (or, for learners, not published) are dropped.
+async fn lesson_blocks(
+    conn: &mut SqliteConnection,
+    body: &str,
+    published_only: bool,
+    feedback: &HashMap<String, QuestionFeedback>,
+) -> Vec<LessonBlock> {
+    let mut blocks = Vec::new();
+    for part in render_lesson(body) {
+        match part {
+            LessonPart::Html(html) => blocks.push(LessonBlock::Html(html)),
+            LessonPart::Question(id) => {
+                let question: Option<Question> = sqlx::query_as::<_, Question>(
+                    "SELECT * FROM questions WHERE id = ? AND deleted_at IS NULL AND (status = 'published' OR NOT ?)"
+                )
+                .bind(&id)
+                .bind(published_only)
+                .fetch_optional(&mut *conn)
+                .await
+                .unwrap();
+                if let Some(q) = question {
+                    blocks.push(LessonBlock::Question(quiz_question(q), feedback.get(&id).cloned()));
+                }
+            }
+        }
+    }
+    blocks
+}
+
+// ----- Learners -----
+
+#[derive(Template)]
+#[template(path = "lesson_page.html")]
+struct LessonPageTemplate<'a> {
+    title: &'a str,
+    lesson: &'a Lesson,
+    // Root first, ending with the lesson's subcategory
+    breadcrumbs: &'a [Topic],
+    blocks: &'a [LessonBlock],
+    // Sent back with an answer; see `record_served`
+    served_id: &'a str,
+    // How far the signed-in user has read, in percent
+    progress: Option<i64>,
+    related: &'a [RelatedItem],
+}
+
+impl LessonPageTemplate<'_> {
+    fn is_read(&self) -> bool {
+        self.progress.is_some_and(|pct| pct >= 100)
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ProgressForm {
+    pub pct: i64,
+}
+
+async fn find_lesson(db: &Pool<Sqlite>, id: &str) -> Option<Lesson> {
+    sqlx::query_as::<_, Lesson>("SELECT * FROM lessons WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn render_lesson_page(
+    db: &Pool<Sqlite>,
+    lesson: &Lesson,
+    user: Option<&User>,
+    feedback: &HashMap<String, QuestionFeedback>,
+) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let breadcrumbs = topic_path(&mut conn, &lesson.subcategory_id).await.unwrap();
+    if !is_live(&breadcrumbs) {
+        return not_found("Lesson");
+    }
+    let blocks = lesson_blocks(&mut conn, &lesson.body, true, feedback).await;
+    let mut questions = Vec::new();
+    for block in &blocks {
+        if let LessonBlock::Question(q, _) = block {
+            questions.push(q);
+        }
+    }
+    let served_id = record_served(&mut conn, &questions, None).await.unwrap();
+    let progress = match user {
+        Some(user) => Some(
+            sqlx::query_scalar("SELECT progress_pct FROM lesson_progress WHERE user_id = ? AND lesson_id = ?")
+                .bind(&user.id)
+                .bind(&lesson.id)
+                .fetch_optional(&mut conn)
+                .await
+                .unwrap()
+                .unwrap_or(0),
+        ),
+        None => None,
+    };
+
+    let related = related_to(&mut conn, KIND_LESSON, &lesson.id, RELATED).await.unwrap();
+
+    let tmpl = LessonPageTemplate {
+        title: &format!("Hackademy - {}", lesson.title),
+        lesson,
+        breadcrumbs: &breadcrumbs,
+        blocks: &blocks,
+        served_id: &served_id,
+        progress,
+        related: &related,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn get_lesson(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let Some(lesson) = find_lesson(&db, &id).await else {
+        return not_found("Lesson");
+    };
+    let user = current_user(req, &db, &session_store).await;
+    render_lesson_page(&db, &lesson, user.as_ref(), &HashMap::new()).await
+}
+
+// Grade an embedded question and show the lesson again with the feedback.
+// The fields are those of the quiz form, for the one question checked.
+// Knowledge checks aren't recorded as quiz attempts.
+#[handler]
+pub async fn check_answer(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    let Some(lesson) = find_lesson(&db, &id).await else {
+        return not_found("Lesson");
+    };
+    let embedded = embedded_questions(&lesson.body);
+    let mut served_id = String::new();
+    let mut asked: Option<String> = None;
+    let mut claimed = None;
+    let mut response = Vec::new();
+    for (key, value) in fields {
+        if key == "served" {
+            served_id = value;
+        } else if key == "asked" {
+            asked = Some(value);
+        } else if key.starts_with("revision_") {
+            claimed = value.parse().ok();
+        } else if key.starts_with("question_") {
+            response.push(value);
+        }
+    }
+
+    let mut feedback = HashMap::new();
+    if let Some(question_id) = asked.filter(|q| embedded.contains(q)) {
+        let mut conn = db.acquire().await.unwrap();
+        let served = take_served(&mut conn, &served_id).await.unwrap();
+        if served.is_empty() {
+            return Response::builder().status(StatusCode::BAD_REQUEST).body(SERVED_EXPIRED);
+        }
+        let answer = SubmittedAnswer { question_id, revision: claimed, response };
+        let revision = match served_revision(&served, &answer) {
+            Ok(revision) => revision,
+            Err(e) => return Response::builder().status(StatusCode::BAD_REQUEST).body(e),
+        };
+        if let Some(f) = grade_served(&mut conn, &answer.question_id, revision, &answer.response).await.unwrap() {
+            feedback.insert(answer.question_id, f);
+        }
+    }
+    let user = current_user(req, &db, &session_store).await;
+    render_lesson_page(&db, &lesson, user.as_ref(), &feedback).await
+}
+
+// Sent as the learner scrolls (and by the "Mark as read" button)
+#[handler]
+pub async fn lesson_progress(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<ProgressForm>,
+) -> Response {
+    let Some(user) = current_user(req, &db, &session_store).await else {
+        return redirect("/auth/login");
+    };
+    if find_lesson(&db, &id).await.is_none() {
+        return not_found("Lesson");
+    }
+    let mut tx = db.begin().await.unwrap();
+    record_progress(&mut tx, &user.id, &id, form.pct).await.unwrap();
+    tx.commit().await.unwrap();
+    redirect(&format!("/lessons/{}", id))
+}
+
+// ----- Administration -----
+
+#[derive(Template)]
+#[template(path = "admin_lessons.html")]
+struct AdminLessonsTemplate<'a> {
+    title: &'a str,
+    lessons: &'a [LessonRow],
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct LessonRow {
+    pub id: String,
+    pub title: String,
+    pub position: i64,
+    pub category_title: String,
+    pub subcategory_title: String,
+    // Learners who have read it to the end
+    pub completed: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin_lesson_form.html")]
+struct LessonFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    form: &'a LessonForm,
+    // (id, "Category / Subcategory")
+    subcategories: &'a [(String, String)],
+    // The rendered lesson, after "Preview"
+    preview: Option<&'a [LessonBlock]>,
+    errors: &'a [String],
+}
+
+impl LessonFormTemplate<'_> {
+    fn is_chosen(&self, id: &str) -> bool {
+        self.form.subcategory_id == id
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct LessonForm {
+    pub subcategory_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub position: String,
+    // "preview" shows the rendered lesson instead of saving
+    #[serde(default)]
+    pub action: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct NewLessonParams {
+    pub subcategory_id: Option<String>,
+}
+
+async fn validate_lesson(db: &Pool<Sqlite>, existing: Option<&Lesson>, form: &LessonForm) -> Result<i64, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut conn = db.acquire().await.unwrap();
+    if form.title.trim().is_empty() {
+        errors.push("Title is required".to_string());
+    }
+    if form.body.trim().is_empty() {
+        errors.push("The lesson has no text".to_string());
+    }
+    let path = topic_path(&mut conn, &form.subcategory_id).await.unwrap();
+    if path.len() != 2 || !is_live(&path) {
+        errors.push("Unknown subcategory".to_string());
+    }
+    for id in embedded_questions(&form.body) {
+        let found: Option<String> = sqlx::query_scalar("SELECT id FROM questions WHERE id = ? AND deleted_at IS NULL")
+            .bind(&id)
+            .fetch_optional(&mut conn)
+            .await
+            .unwrap();
+        if found.is_none() {
+            errors.push(format!("Embedded question {} doesn't exist", id));
+        }
+    }
+
+    let position = match form.position.trim() {
+        "" => match existing.filter(|l| l.subcategory_id == form.subcategory_id) {
+            Some(lesson) => lesson.position,
+            None => sqlx::query_scalar(
+                "SELECT COALESCE(MAX(position), 0) + 1 FROM lessons WHERE subcategory_id = ? AND deleted_at IS NULL"
+            )
+            .bind(&form.subcategory_id)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap(),
+        },
+        p => p.parse().unwrap_or_else(|_| {
+            errors.push("Position must be a whole number".to_string());
+            0
+        }),
+    };
+
+    if errors.is_empty() { Ok(position) } else { Err(errors) }
+}
+
+async fn render_lesson_form(db: &Pool<Sqlite>, title: &str, action: &str, form: &LessonForm, errors: &[String]) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let subcategories: Vec<(String, String)> = sqlx::query_as(
+        "SELECT s.id, c.title || ' / ' || s.title FROM subcategories s JOIN categories c ON c.id = s.category_id
+         WHERE s.deleted_at IS NULL AND c.deleted_at IS NULL ORDER BY c.title, s.title"
+    )
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+    let preview = match form.action.as_str() {
+        "preview" => Some(lesson_blocks(&mut conn, &form.body, false, &HashMap::new()).await),
+        _ => None,
+    };
+    let tmpl = LessonFormTemplate {
+        title,
+        action,
+        form,
+        subcategories: &subcategories,
+        preview: preview.as_deref(),
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn admin_lessons(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let lessons = sqlx::query_as::<_, LessonRow>(
+        "SELECT l.id, l.title, l.position, c.title AS category_title, s.title AS subcategory_title,
+             (SELECT COUNT(*) FROM lesson_progress p WHERE p.lesson_id = l.id AND p.completed_at IS NOT NULL) AS completed
+         FROM lessons l JOIN subcategories s ON s.id = l.subcategory_id JOIN categories c ON c.id = s.category_id
+         WHERE l.deleted_at IS NULL ORDER BY c.title, s.title, l.position, l.title"
+    )
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+    let tmpl = AdminLessonsTemplate {
+        title: "Lessons",
+        lessons: &lessons,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn new_lesson_form(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Query(params): Query<NewLessonParams>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let form = LessonForm {
+        subcategory_id: params.subcategory_id.unwrap_or_default(),
+        ..Default::default()
+    };
+    render_lesson_form(&db, "New Lesson", "/admin/lessons/new", &form, &[]).await
+}
+
+#[handler]
+pub async fn create_lesson(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<LessonForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let position = match validate_lesson(&db, None, &form).await {
+        Ok(_) if form.action == "preview" => {
+            return render_lesson_form(&db, "New Lesson", "/admin/lessons/new", &form, &[]).await;
+        }
+        Ok(position) => position,
+        Err(errors) => return render_lesson_form(&db, "New Lesson", "/admin/lessons/new", &form, &errors).await,
+    };
+    let id = nanoid!();
+    sqlx::query("INSERT INTO lessons (id, subcategory_id, title, body, position) VALUES (?, ?, ?, ?, ?)")
+        .bind(&id)
+        .bind(&form.subcategory_id)
+        .bind(form.title.trim())
+        .bind(&form.body)
+        .bind(position)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin/lessons")
+}
+
+#[handler]
+pub async fn edit_lesson_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(lesson) = find_lesson(&db, &id).await else {
+        return not_found("Lesson");
+    };
+    let form = LessonForm {
+        subcategory_id: lesson.subcategory_id,
+        title: lesson.title,
+        body: lesson.body,
+        position: lesson.position.to_string(),
+        action: String::new(),
+    };
+    let action = format!("/admin/lessons/{}/edit", id);
+    render_lesson_form(&db, "Edit Lesson", &action, &form, &[]).await
+}
+
+// Reading progress is kept when a lesson is edited
+#[handler]
+pub async fn update_lesson(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<LessonForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(lesson) = find_lesson(&db, &id).await else {
+        return not_found("Lesson");
+    };
+    let action = format!("/admin/lessons/{}/edit", id);
+    let position = match validate_lesson(&db, Some(&lesson), &form).await {
+        Ok(_) if form.action == "preview" => {
+            return render_lesson_form(&db, "Edit Lesson", &action, &form, &[]).await;
+        }
+        Ok(position) => position,
+        Err(errors) => return render_lesson_form(&db, "Edit Lesson", &action, &form, &errors).await,
+    };
+    sqlx::query(
+        "UPDATE lessons SET subcategory_id = ?, title = ?, body = ?, position = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+    )
+    .bind(&form.subcategory_id)
+    .bind(form.title.trim())
+    .bind(&form.body)
+    .bind(position)
+    .bind(&id)
+    .execute(&**db)
+    .await
+    .unwrap();
+    redirect("/admin/lessons")
+}
+
+#[handler]
+pub async fn delete_lesson(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    sqlx::query("UPDATE lessons SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL")
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin/lessons")
+}
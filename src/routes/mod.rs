@@ -1,4 +1,18 @@
 pub mod quiz;
 pub mod category;
 pub mod subcategory;
-pub mod search; // <-- add
\ No newline at end of file
+pub mod search; // <-- add
+pub mod auth;
+pub mod admin;
+pub mod review;
+pub mod revisions;
+pub mod reports;
+pub mod regrade;
+pub mod tags;
+pub mod topics;
+pub mod paths;
+pub mod lessons;
+pub mod flashcards;
+pub mod search_stats;
+pub mod tokens;
+pub mod oidc;
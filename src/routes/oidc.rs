@@ -0,0 +1,224 @@
+// Single sign-on through an OpenID Connect provider. A provider account is
+// linked to one local user:
+//  - signing in with a linked account signs in as its user
+//  - a signed-in user can link an account from their profile page
+//  - an account whose verified email is verified on one user's other
+//    accounts joins that user
+//  - otherwise signing in creates a user, named after the account, with no
+//    password
+// With a role mapping configured, each sign-in sets the user's role from the
+// provider's claims.
+
+use poem::{
+    handler,
+    http::{header::SET_COOKIE, StatusCode},
+    web::{Data, Form, Query},
+    Request, Response,
+};
+use sqlx::{Pool, Sqlite};
+
+use crate::auth::oidc::Oidc;
+use crate::auth::sign_in_identity;
+use crate::routes::admin::{not_found, redirect};
+use crate::routes::auth::{current_user, login_page, start_session, SessionStore};
+
+// Ties the callback to the browser that started the sign-in
+const STATE_COOKIE: &str = "hackademy_oidc_state";
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: String,
+    pub last_login_at: Option<String>,
+}
+
+impl UserIdentity {
+    // LDAP accounts are linked again at their next login, so they can't be
+    // unlinked
+    pub fn is_directory(&self) -> bool {
+        self.issuer.starts_with("ldap://") || self.issuer.starts_with("ldaps://")
+    }
+}
+
+pub async fn user_identities(db: &Pool<Sqlite>, user_id: &str) -> Vec<UserIdentity> {
+    sqlx::query_as::<_, UserIdentity>(
+        "SELECT issuer, subject, email, created_at, last_login_at FROM user_identities
+         WHERE user_id = ? ORDER BY created_at, rowid"
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+    .unwrap()
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LoginParams {
+    // Set to link the account to the signed-in user
+    pub link: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UnlinkForm {
+    pub issuer: String,
+    pub subject: String,
+}
+
+#[handler]
+pub async fn oidc_login(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    oidc: Data<&Oidc>,
+    Query(params): Query<LoginParams>,
+) -> Response {
+    if oidc.name().is_none() {
+        return not_found("Single sign-on");
+    }
+    let link_user_id = if params.link.is_some() {
+        let Some(user) = current_user(req, &db, &session_store).await else {
+            return redirect("/auth/login");
+        };
+        Some(user.id)
+    } else {
+        None
+    };
+    match oidc.authorization_url(link_user_id).await {
+        Ok((url, state)) => Response::builder()
+            .status(StatusCode::FOUND)
+            .header("Location", url)
+            .header(SET_COOKIE, format!("{}={}; Path=/auth/oidc; HttpOnly; SameSite=Lax; Max-Age=600", STATE_COOKIE, state))
+            .body(()),
+        Err(e) => login_page(&oidc, Some(format!("Single sign-on is unavailable: {}", e))),
+    }
+}
+
+#[handler]
+pub async fn oidc_callback(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    oidc: Data<&Oidc>,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    if oidc.name().is_none() {
+        return not_found("Single sign-on");
+    }
+    if let Some(error) = params.error {
+        let detail = params.error_description.unwrap_or(error);
+        return login_page(&oidc, Some(format!("Single sign-on failed: {}", detail)));
+    }
+    let (Some(code), Some(state)) = (params.code, params.state) else {
+        return login_page(&oidc, Some("Single sign-on failed: the provider sent no code".into()));
+    };
+    let cookie_state = req.cookie().get(STATE_COOKIE).map(|c| c.value_str().to_string());
+    if cookie_state.as_deref() != Some(state.as_str()) {
+        return login_page(&oidc, Some("Single sign-on failed: this sign-in wasn't started here; please try again".into()));
+    }
+    let identity = match oidc.finish(&code, &state).await {
+        Ok(identity) => identity,
+        Err(e) => return login_page(&oidc, Some(format!("Single sign-on failed: {}", e))),
+    };
+
+    let user_id = match sign_in_identity(&db, &identity).await {
+        Ok(user_id) => user_id,
+        Err(e) => return login_page(&oidc, Some(e.to_string())),
+    };
+    let mut response = start_session(&session_store, &user_id);
+    response.headers_mut().append(
+        SET_COOKIE,
+        format!("{}=; Path=/auth/oidc; HttpOnly; Max-Age=0", STATE_COOKIE).parse().unwrap(),
+    );
+    response
+}
+
+#[handler]
+pub async fn oidc_unlink(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<UnlinkForm>,
+) -> Response {
+    let Some(user) = current_user(req, &db, &session_store).await else {
+        return redirect("/auth/login");
+    };
+    // Users without a password would have no way left to sign in
+    if !user.has_password() && user_identities(&db, &user.id).await.len() <= 1 {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("This is your only way to sign in, so it can't be unlinked");
+    }
+    let unlinked = sqlx::query(
+        "DELETE FROM user_identities WHERE issuer = ? AND subject = ? AND user_id = ?
+             AND issuer NOT LIKE 'ldap://%' AND issuer NOT LIKE 'ldaps://%'"
+    )
+        .bind(&form.issuer)
+        .bind(&form.subject)
+        .bind(&user.id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    if unlinked.rows_affected() == 0 {
+        return not_found("Linked account");
+    }
+    redirect("/auth/profile")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::oidc::OidcSettings;
+    use crate::db::test_db;
+    use poem::middleware::CookieJarManager;
+    use poem::{get, Endpoint, EndpointExt, Route};
+
+    // The callback refuses a state its cookie doesn't match before it asks
+    // the provider anything, so no provider is needed
+    async fn callback(state: &str, cookie: Option<&str>) -> String {
+        let oidc = Oidc::new(Some(OidcSettings {
+            issuer: "https://127.0.0.1:9".to_string(),
+            client_id: "hackademy".to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost:3000/auth/oidc/callback".to_string(),
+            scopes: "openid".to_string(),
+            name: "University login".to_string(),
+            role_claim: "groups".to_string(),
+            role_map: Vec::new(),
+        }));
+        let app = Route::new()
+            .at("/auth/oidc/callback", get(oidc_callback))
+            .data(test_db().await)
+            .data(SessionStore::new())
+            .data(oidc)
+            // The server adds this itself
+            .with(CookieJarManager::new());
+        let mut request = Request::builder().uri_str(format!("/auth/oidc/callback?code=the-code&state={}", state));
+        if let Some(cookie) = cookie {
+            request = request.header("Cookie", format!("{}={}", STATE_COOKIE, cookie));
+        }
+        let response = app.call(request.finish()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.into_body().into_string().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn refuses_a_state_that_does_not_match_the_cookie() {
+        let page = callback("abc", Some("xyz")).await;
+        assert!(page.contains("wasn&#x27;t started here"), "{}", page);
+        let page = callback("abc", None).await;
+        assert!(page.contains("wasn&#x27;t started here"), "{}", page);
+
+        // A matching cookie gets as far as the pending sign-ins
+        let page = callback("abc", Some("abc")).await;
+        assert!(page.contains("expired or was already used"), "{}", page);
+    }
+}
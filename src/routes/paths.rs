@@ -0,0 +1,802 @@
+use poem::{
+    handler,
+    http::StatusCode,
+    web::{Data, Form, Html, Path},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use nanoid::nanoid;
+use sqlx::{Pool, Sqlite};
+
+use crate::content::paths::{
+    complete_step, hash_flag, is_unlocked, path_progress, path_steps, step_prereqs, StepProgress, STEP_KINDS,
+};
+use crate::content::topics::{is_live, topic_path, topic_tree, TopicNode};
+use crate::models::{LearningPath, PathStep, User, STEP_CHALLENGE, STEP_LESSON, STEP_QUIZ};
+use crate::routes::admin::{not_found, redirect, require_staff};
+use crate::routes::auth::{current_user, SessionStore};
+
+// ----- Learners -----
+
+#[derive(Template)]
+#[template(path = "path_list.html")]
+struct PathListTemplate<'a> {
+    title: &'a str,
+    paths: &'a [PathSummary],
+}
+
+// A path with how far the signed-in user has got
+#[derive(Debug, sqlx::FromRow)]
+pub struct PathSummary {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub steps: i64,
+    pub completed: i64,
+}
+
+#[derive(Template)]
+#[template(path = "path_page.html")]
+struct PathPageTemplate<'a> {
+    title: &'a str,
+    path: &'a LearningPath,
+    steps: &'a [StepProgress],
+    signed_in: bool,
+}
+
+impl PathPageTemplate<'_> {
+    fn completed(&self) -> usize {
+        self.steps.iter().filter(|s| s.is_completed()).count()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "path_step.html")]
+struct PathStepTemplate<'a> {
+    title: &'a str,
+    path: &'a LearningPath,
+    progress: &'a StepProgress,
+    // Title of the quiz step's topic
+    topic_title: Option<&'a str>,
+    signed_in: bool,
+    errors: &'a [String],
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct FlagForm {
+    pub flag: String,
+}
+
+pub(crate) async fn find_path(db: &Pool<Sqlite>, id: &str) -> Option<LearningPath> {
+    sqlx::query_as::<_, LearningPath>("SELECT * FROM learning_paths WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn find_step(db: &Pool<Sqlite>, path_id: &str, step_id: &str) -> Option<PathStep> {
+    sqlx::query_as::<_, PathStep>("SELECT * FROM path_steps WHERE id = ? AND path_id = ? AND deleted_at IS NULL")
+        .bind(step_id)
+        .bind(path_id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+#[handler]
+pub async fn get_paths(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> impl IntoResponse {
+    let user = current_user(req, &db, &session_store).await;
+    let paths = sqlx::query_as::<_, PathSummary>(
+        "SELECT p.id, p.title, p.description,
+             (SELECT COUNT(*) FROM path_steps s WHERE s.path_id = p.id AND s.deleted_at IS NULL) AS steps,
+             (SELECT COUNT(*) FROM path_step_completions c JOIN path_steps s ON s.id = c.step_id
+              WHERE s.path_id = p.id AND s.deleted_at IS NULL AND c.user_id = ?) AS completed
+         FROM learning_paths p WHERE p.deleted_at IS NULL AND steps > 0 ORDER BY p.title"
+    )
+    .bind(user.map(|u| u.id))
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+
+    let tmpl = PathListTemplate {
+        title: "Hackademy - Learning Paths",
+        paths: &paths,
+    };
+    tmpl.render().unwrap()
+}
+
+#[handler]
+pub async fn get_path(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let Some(path) = find_path(&db, &id).await else {
+        return not_found("Learning path");
+    };
+    let user = current_user(req, &db, &session_store).await;
+    let mut conn = db.acquire().await.unwrap();
+    let steps = path_progress(&mut conn, &id, user.as_ref().map(|u| u.id.as_str())).await.unwrap();
+
+    let tmpl = PathPageTemplate {
+        title: &format!("Hackademy - {}", path.title),
+        path: &path,
+        steps: &steps,
+        signed_in: user.is_some(),
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+async fn render_step(db: &Pool<Sqlite>, path: &LearningPath, step_id: &str, user: Option<&User>, errors: &[String]) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let steps = path_progress(&mut conn, &path.id, user.map(|u| u.id.as_str())).await.unwrap();
+    let Some(progress) = steps.iter().find(|s| s.step.id == step_id) else {
+        return not_found("Step");
+    };
+    let topic = match &progress.step.topic_id {
+        Some(topic_id) => topic_path(&mut conn, topic_id).await.unwrap().pop(),
+        None => None,
+    };
+
+    let tmpl = PathStepTemplate {
+        title: &progress.step.title,
+        path,
+        progress,
+        topic_title: topic.as_ref().map(|t| t.title.as_str()),
+        signed_in: user.is_some(),
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn get_path_step(
+    req: &Request,
+    Path((id, step_id)): Path<(String, String)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let Some(path) = find_path(&db, &id).await else {
+        return not_found("Learning path");
+    };
+    let user = current_user(req, &db, &session_store).await;
+    render_step(&db, &path, &step_id, user.as_ref(), &[]).await
+}
+
+// Why a step can't be completed now
+pub(crate) enum StepUnavailable {
+    NotFound,
+    // The kind it is
+    WrongKind(String),
+    Locked,
+}
+
+impl StepUnavailable {
+    pub fn message(&self) -> String {
+        match self {
+            StepUnavailable::NotFound => "Step not found".to_string(),
+            StepUnavailable::WrongKind(kind) => format!("This step is a {}", kind),
+            StepUnavailable::Locked => "Complete the steps this one requires first".to_string(),
+        }
+    }
+}
+
+// The step and its path, if the step is a `kind` step the user can complete
+// now
+pub(crate) async fn step_to_complete(
+    db: &Pool<Sqlite>,
+    user: &User,
+    path_id: &str,
+    step_id: &str,
+    kind: &str,
+) -> Result<(LearningPath, PathStep), StepUnavailable> {
+    let (Some(path), Some(step)) = (find_path(db, path_id).await, find_step(db, path_id, step_id).await) else {
+        return Err(StepUnavailable::NotFound);
+    };
+    if step.kind != kind {
+        return Err(StepUnavailable::WrongKind(step.kind));
+    }
+    let mut conn = db.acquire().await.unwrap();
+    if !is_unlocked(&mut conn, &user.id, &step.id).await.unwrap() {
+        return Err(StepUnavailable::Locked);
+    }
+    Ok((path, step))
+}
+
+// Complete a challenge step if the flag is its flag; false if it isn't
+pub(crate) async fn capture_flag(db: &Pool<Sqlite>, user: &User, step: &PathStep, flag: &str) -> bool {
+    if step.flag_hash.as_deref() != Some(hash_flag(flag).as_str()) {
+        return false;
+    }
+    let mut conn = db.acquire().await.unwrap();
+    complete_step(&mut conn, &user.id, &step.id, None, None).await.unwrap();
+    true
+}
+
+// The signed-in user and the step, if the step is theirs to complete now
+async fn completable_step(
+    req: &Request,
+    db: &Pool<Sqlite>,
+    session_store: &SessionStore,
+    path_id: &str,
+    step_id: &str,
+    kind: &str,
+) -> Result<(User, LearningPath, PathStep), Response> {
+    let Some(user) = current_user(req, db, session_store).await else {
+        return Err(redirect("/auth/login"));
+    };
+    match step_to_complete(db, &user, path_id, step_id, kind).await {
+        Ok((path, step)) => Ok((user, path, step)),
+        Err(StepUnavailable::NotFound) => Err(not_found("Step")),
+        Err(e @ StepUnavailable::WrongKind(_)) => Err(Response::builder().status(StatusCode::BAD_REQUEST).body(e.message())),
+        Err(e @ StepUnavailable::Locked) => Err(Response::builder().status(StatusCode::CONFLICT).body(e.message())),
+    }
+}
+
+// Lessons are marked done by the learner
+#[handler]
+pub async fn complete_lesson(
+    req: &Request,
+    Path((id, step_id)): Path<(String, String)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let (user, _, step) = match completable_step(req, &db, &session_store, &id, &step_id, STEP_LESSON).await {
+        Ok(found) => found,
+        Err(resp) => return resp,
+    };
+    let mut conn = db.acquire().await.unwrap();
+    complete_step(&mut conn, &user.id, &step.id, None, None).await.unwrap();
+    redirect(&format!("/paths/{}", id))
+}
+
+// Challenges are completed by submitting their flag
+#[handler]
+pub async fn submit_flag(
+    req: &Request,
+    Path((id, step_id)): Path<(String, String)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<FlagForm>,
+) -> Response {
+    let (user, path, step) = match completable_step(req, &db, &session_store, &id, &step_id, STEP_CHALLENGE).await {
+        Ok(found) => found,
+        Err(resp) => return resp,
+    };
+    if !capture_flag(&db, &user, &step, &form.flag).await {
+        return render_step(&db, &path, &step.id, Some(&user), &["That is not the flag".to_string()]).await;
+    }
+    redirect(&format!("/paths/{}", id))
+}
+
+// ----- Administration -----
+
+#[derive(Template)]
+#[template(path = "admin_paths.html")]
+struct AdminPathsTemplate<'a> {
+    title: &'a str,
+    paths: &'a [LearningPath],
+}
+
+#[derive(Template)]
+#[template(path = "admin_path_form.html")]
+struct PathFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    form: &'a PathForm,
+    // The path being edited, with its steps; None for a new path
+    path_id: Option<&'a str>,
+    steps: &'a [StepProgress],
+    errors: &'a [String],
+}
+
+#[derive(Template)]
+#[template(path = "admin_path_step_form.html")]
+struct StepFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    path: &'a LearningPath,
+    form: &'a StepForm,
+    kinds: &'a [(&'a str, &'a str)],
+    topics: &'a [TopicNode],
+    // Steps that may be required: the ones before this step
+    earlier: &'a [PathStep],
+    // A challenge being edited already has a flag
+    has_flag: bool,
+    errors: &'a [String],
+}
+
+impl StepFormTemplate<'_> {
+    fn is_kind(&self, kind: &str) -> bool {
+        self.form.kind == kind
+    }
+
+    fn is_topic(&self, id: &str) -> bool {
+        self.form.topic_id == id
+    }
+
+    fn is_required(&self, id: &str) -> bool {
+        self.form.requires.iter().any(|r| r == id)
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct PathForm {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+// Read from a list of fields, as "requires" repeats
+#[derive(Debug, Default)]
+pub struct StepForm {
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub url: String,
+    pub topic_id: String,
+    pub pass_pct: String,
+    // Blank when editing keeps the current flag
+    pub flag: String,
+    pub position: String,
+    pub requires: Vec<String>,
+}
+
+impl StepForm {
+    fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let mut form = StepForm::default();
+        for (key, value) in fields {
+            match key.as_str() {
+                "kind" => form.kind = value,
+                "title" => form.title = value,
+                "body" => form.body = value,
+                "url" => form.url = value,
+                "topic_id" => form.topic_id = value,
+                "pass_pct" => form.pass_pct = value,
+                "flag" => form.flag = value,
+                "position" => form.position = value,
+                "requires" => form.requires.push(value),
+                _ => {}
+            }
+        }
+        form
+    }
+}
+
+// A step that passed validation
+struct ValidStep {
+    position: i64,
+    pass_pct: i64,
+    flag_hash: Option<String>,
+}
+
+fn optional_text(s: &str) -> Option<&str> {
+    Some(s.trim()).filter(|s| !s.is_empty())
+}
+
+async fn validate_step(
+    db: &Pool<Sqlite>,
+    path_id: &str,
+    existing: Option<&PathStep>,
+    form: &StepForm,
+) -> Result<ValidStep, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut conn = db.acquire().await.unwrap();
+    if form.title.trim().is_empty() {
+        errors.push("Title is required".to_string());
+    }
+
+    let steps = path_steps(&mut conn, path_id).await.unwrap();
+    let position = match form.position.trim() {
+        "" => existing
+            .map(|s| s.position)
+            .unwrap_or_else(|| steps.iter().map(|s| s.position + 1).max().unwrap_or(1)),
+        p => p.parse().unwrap_or_else(|_| {
+            errors.push("Position must be a whole number".to_string());
+            0
+        }),
+    };
+    for required in &form.requires {
+        match steps.iter().find(|s| &s.id == required) {
+            Some(s) if s.position < position => {}
+            Some(s) => errors.push(format!("\"{}\" doesn't come before this step, so it can't be required", s.title)),
+            None => errors.push("Unknown required step".to_string()),
+        }
+    }
+
+    let mut pass_pct = 80;
+    let mut flag_hash = existing.and_then(|s| s.flag_hash.clone());
+    match form.kind.as_str() {
+        STEP_LESSON => {
+            if let Some(url) = optional_text(&form.url)
+                && !(url.starts_with('/') || url.starts_with("https://") || url.starts_with("http://"))
+            {
+                errors.push("Link must be a site path (/...) or an http(s) URL".to_string());
+            }
+            if optional_text(&form.url).is_none() && optional_text(&form.body).is_none() {
+                errors.push("A lesson needs a link or some text".to_string());
+            }
+        }
+        STEP_QUIZ => {
+            if !is_live(&topic_path(&mut conn, &form.topic_id).await.unwrap()) {
+                errors.push("Unknown topic".to_string());
+            }
+            match form.pass_pct.trim().parse::<i64>() {
+                Ok(pct) if (1..=100).contains(&pct) => pass_pct = pct,
+                _ => errors.push("Pass mark must be a percentage from 1 to 100".to_string()),
+            }
+        }
+        STEP_CHALLENGE => match optional_text(&form.flag) {
+            Some(flag) => flag_hash = Some(hash_flag(flag)),
+            None if flag_hash.is_none() => errors.push("A challenge needs a flag".to_string()),
+            None => {}
+        },
+        _ => errors.push("Unknown step kind".to_string()),
+    }
+
+    if errors.is_empty() {
+        Ok(ValidStep { position, pass_pct, flag_hash })
+    } else {
+        Err(errors)
+    }
+}
+
+async fn render_path_form(
+    db: &Pool<Sqlite>,
+    title: &str,
+    action: &str,
+    form: &PathForm,
+    path_id: Option<&str>,
+    errors: &[String],
+) -> Response {
+    let steps = match path_id {
+        Some(id) => {
+            let mut conn = db.acquire().await.unwrap();
+            path_progress(&mut conn, id, None).await.unwrap()
+        }
+        None => Vec::new(),
+    };
+    let tmpl = PathFormTemplate {
+        title,
+        action,
+        form,
+        path_id,
+        steps: &steps,
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+async fn render_step_form(
+    db: &Pool<Sqlite>,
+    title: &str,
+    action: &str,
+    path: &LearningPath,
+    existing: Option<&PathStep>,
+    form: &StepForm,
+    errors: &[String],
+) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let topics = topic_tree(&mut conn).await.unwrap();
+    let mut earlier = path_steps(&mut conn, &path.id).await.unwrap();
+    if let Some(step) = existing {
+        earlier.retain(|s| s.position < step.position);
+    }
+    let tmpl = StepFormTemplate {
+        title,
+        action,
+        path,
+        form,
+        kinds: STEP_KINDS,
+        topics: &topics,
+        earlier: &earlier,
+        has_flag: existing.is_some_and(|s| s.flag_hash.is_some()),
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+async fn set_prereqs(conn: &mut sqlx::SqliteConnection, step_id: &str, requires: &[String]) {
+    sqlx::query("DELETE FROM path_step_prereqs WHERE step_id = ?")
+        .bind(step_id)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    for required in requires {
+        sqlx::query("INSERT OR IGNORE INTO path_step_prereqs (step_id, requires_id) VALUES (?, ?)")
+            .bind(step_id)
+            .bind(required)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+}
+
+#[handler]
+pub async fn admin_paths(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let paths = sqlx::query_as::<_, LearningPath>("SELECT * FROM learning_paths WHERE deleted_at IS NULL ORDER BY title")
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+    let tmpl = AdminPathsTemplate {
+        title: "Learning Paths",
+        paths: &paths,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn new_path_form(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    render_path_form(&db, "New Learning Path", "/admin/paths/new", &PathForm::default(), None, &[]).await
+}
+
+#[handler]
+pub async fn create_path(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<PathForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    if form.title.trim().is_empty() {
+        let errors = ["Title is required".to_string()];
+        return render_path_form(&db, "New Learning Path", "/admin/paths/new", &form, None, &errors).await;
+    }
+    let id = nanoid!();
+    sqlx::query("INSERT INTO learning_paths (id, title, description) VALUES (?, ?, ?)")
+        .bind(&id)
+        .bind(form.title.trim())
+        .bind(optional_text(&form.description))
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect(&format!("/admin/paths/{}/edit", id))
+}
+
+#[handler]
+pub async fn edit_path_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(path) = find_path(&db, &id).await else {
+        return not_found("Learning path");
+    };
+    let form = PathForm {
+        title: path.title,
+        description: path.description.unwrap_or_default(),
+    };
+    let action = format!("/admin/paths/{}/edit", id);
+    render_path_form(&db, "Edit Learning Path", &action, &form, Some(&id), &[]).await
+}
+
+#[handler]
+pub async fn update_path(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<PathForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    if form.title.trim().is_empty() {
+        let action = format!("/admin/paths/{}/edit", id);
+        let errors = ["Title is required".to_string()];
+        return render_path_form(&db, "Edit Learning Path", &action, &form, Some(&id), &errors).await;
+    }
+    let result = sqlx::query("UPDATE learning_paths SET title = ?, description = ? WHERE id = ? AND deleted_at IS NULL")
+        .bind(form.title.trim())
+        .bind(optional_text(&form.description))
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    if result.rows_affected() == 0 {
+        return not_found("Learning path");
+    }
+    redirect(&format!("/admin/paths/{}/edit", id))
+}
+
+// Learners' completions are kept, so restoring a path by hand loses nothing
+#[handler]
+pub async fn delete_path(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    sqlx::query("UPDATE learning_paths SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL")
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin/paths")
+}
+
+#[handler]
+pub async fn new_step_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(path) = find_path(&db, &id).await else {
+        return not_found("Learning path");
+    };
+    // By default a new step requires the one before it
+    let mut conn = db.acquire().await.unwrap();
+    let last = path_steps(&mut conn, &id).await.unwrap().pop();
+    let form = StepForm {
+        kind: STEP_LESSON.to_string(),
+        pass_pct: "80".to_string(),
+        requires: last.into_iter().map(|s| s.id).collect(),
+        ..Default::default()
+    };
+    let action = format!("/admin/paths/{}/steps/new", id);
+    render_step_form(&db, "New Step", &action, &path, None, &form, &[]).await
+}
+
+#[handler]
+pub async fn create_step(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(path) = find_path(&db, &id).await else {
+        return not_found("Learning path");
+    };
+    let form = StepForm::from_fields(fields);
+    let valid = match validate_step(&db, &id, None, &form).await {
+        Ok(v) => v,
+        Err(errors) => {
+            let action = format!("/admin/paths/{}/steps/new", id);
+            return render_step_form(&db, "New Step", &action, &path, None, &form, &errors).await;
+        }
+    };
+
+    let step_id = nanoid!();
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query(
+        "INSERT INTO path_steps (id, path_id, position, kind, title, body, url, topic_id, pass_pct, flag_hash)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&step_id)
+    .bind(&id)
+    .bind(valid.position)
+    .bind(&form.kind)
+    .bind(form.title.trim())
+    .bind(optional_text(&form.body))
+    .bind(optional_text(&form.url).filter(|_| form.kind == STEP_LESSON))
+    .bind(optional_text(&form.topic_id).filter(|_| form.kind == STEP_QUIZ))
+    .bind(valid.pass_pct)
+    .bind(valid.flag_hash.filter(|_| form.kind == STEP_CHALLENGE))
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    set_prereqs(&mut tx, &step_id, &form.requires).await;
+    tx.commit().await.unwrap();
+    redirect(&format!("/admin/paths/{}/edit", id))
+}
+
+#[handler]
+pub async fn edit_step_form(
+    req: &Request,
+    Path((id, step_id)): Path<(String, String)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let (Some(path), Some(step)) = (find_path(&db, &id).await, find_step(&db, &id, &step_id).await) else {
+        return not_found("Step");
+    };
+    let mut conn = db.acquire().await.unwrap();
+    let form = StepForm {
+        kind: step.kind.clone(),
+        title: step.title.clone(),
+        body: step.body.clone().unwrap_or_default(),
+        url: step.url.clone().unwrap_or_default(),
+        topic_id: step.topic_id.clone().unwrap_or_default(),
+        pass_pct: step.pass_pct.to_string(),
+        flag: String::new(),
+        position: step.position.to_string(),
+        requires: step_prereqs(&mut conn, &step.id).await.unwrap(),
+    };
+    let action = format!("/admin/paths/{}/steps/{}/edit", id, step_id);
+    render_step_form(&db, "Edit Step", &action, &path, Some(&step), &form, &[]).await
+}
+
+// Completions made before an edit stand, even if the pass mark went up
+#[handler]
+pub async fn update_step(
+    req: &Request,
+    Path((id, step_id)): Path<(String, String)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let (Some(path), Some(step)) = (find_path(&db, &id).await, find_step(&db, &id, &step_id).await) else {
+        return not_found("Step");
+    };
+    let form = StepForm::from_fields(fields);
+    let valid = match validate_step(&db, &id, Some(&step), &form).await {
+        Ok(v) => v,
+        Err(errors) => {
+            let action = format!("/admin/paths/{}/steps/{}/edit", id, step_id);
+            return render_step_form(&db, "Edit Step", &action, &path, Some(&step), &form, &errors).await;
+        }
+    };
+
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query(
+        "UPDATE path_steps SET position = ?, kind = ?, title = ?, body = ?, url = ?, topic_id = ?, pass_pct = ?, flag_hash = ?
+         WHERE id = ?"
+    )
+    .bind(valid.position)
+    .bind(&form.kind)
+    .bind(form.title.trim())
+    .bind(optional_text(&form.body))
+    .bind(optional_text(&form.url).filter(|_| form.kind == STEP_LESSON))
+    .bind(optional_text(&form.topic_id).filter(|_| form.kind == STEP_QUIZ))
+    .bind(valid.pass_pct)
+    .bind(valid.flag_hash.filter(|_| form.kind == STEP_CHALLENGE))
+    .bind(&step_id)
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    set_prereqs(&mut tx, &step_id, &form.requires).await;
+    tx.commit().await.unwrap();
+    redirect(&format!("/admin/paths/{}/edit", id))
+}
+
+// Steps that required a deleted step no longer wait for it
+#[handler]
+pub async fn delete_step(
+    req: &Request,
+    Path((id, step_id)): Path<(String, String)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    sqlx::query("UPDATE path_steps SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND path_id = ? AND deleted_at IS NULL")
+        .bind(&step_id)
+        .bind(&id)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect(&format!("/admin/paths/{}/edit", id))
+}
+
@@ -1,13 +1,37 @@
-use poem::{handler, web::{Data, Query, Form}, IntoResponse, Request, post};
+use std::collections::HashMap;
+
+use poem::{handler, http::StatusCode, web::{Data, Query, Form}, IntoResponse, Request, Response, post};
 use askama::Template;
-use sqlx::{Pool, Sqlite};
-use crate::models::{Question};
+use nanoid::nanoid;
+use rand_core::{OsRng, RngCore};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use crate::content::tags::parse_tags;
+use crate::content::paths::record_quiz_pass;
+use crate::content::review::{record_review, RATING_AGAIN, RATING_GOOD};
+use crate::content::scoring::{accepted_answers, is_correct, is_scored, response_indices};
+use crate::content::topics::{is_live, topic_path, SUBTREE_SQL};
+use crate::models::{Question, QuestionRevision, Topic, User, KIND_MULTIPLE_RESPONSE, KIND_ORDER, KIND_TEXT_ENTRY};
+use crate::routes::auth::{current_user, SessionStore};
 
 #[derive(Template)]
 #[template(path = "quiz.html")]
 struct QuizTemplate<'a> {
     title: &'a str,
-    questions: &'a [Question],
+    // Root first, down to the topic the quiz draws from
+    breadcrumbs: &'a [Topic],
+    questions: &'a [QuizQuestion],
+    // Sent back on submit; see `record_served`
+    served_id: &'a str,
+}
+
+// A question as shown on the quiz page
+pub struct QuizQuestion {
+    pub id: String,
+    pub revision: i64,
+    pub kind: String,
+    pub question_text: String,
+    // (index into the stored options, text) in display order
+    pub choices: Vec<(usize, String)>,
 }
 
 #[derive(Template)]
@@ -17,50 +41,163 @@ struct QuizResultsTemplate<'a> {
     total_questions: usize,
     correct_count: usize,
     feedback: &'a [QuestionFeedback],
+    // For reports about a question, so staff can see the answer given
+    attempt_id: Option<&'a str>,
+    // Learning path steps this attempt completed
+    completed_steps: &'a [String],
 }
 
 #[derive(Clone, Debug)]
 pub struct QuestionFeedback {
+    pub question_id: String,
+    pub revision: i64,
     pub question_text: String,
     pub selected_option: String,
     pub correct_option: String,
     pub is_correct: bool,
+    // False once the revision has been taken out of scoring
+    pub scored: bool,
 }
 
-#[derive(Debug, serde::Deserialize)]
+// A quiz draws from a category (optionally narrowed to a subcategory), from
+// a topic and everything below it, from tags across all categories, or from
+// a mix of these
+#[derive(Debug, Default, serde::Deserialize)]
 pub struct QuizParams {
-    pub category_id: String,
+    pub category_id: Option<String>,
     pub subcategory_id: Option<String>,
+    pub topic_id: Option<String>,
+    // Comma-separated, e.g. "nmap,beginner"
+    pub tags: Option<String>,
+    // "all" (the default) asks for questions with every tag, "any" for
+    // questions with at least one
+    pub tag_match: Option<String>,
 }
 
-#[handler]
-pub async fn get_quiz(db: Data<&Pool<Sqlite>>, Query(params): Query<QuizParams>) -> impl IntoResponse {
-    let mut sql = r#"SELECT * FROM questions WHERE category_id = ?"#.to_string();
-    if params.subcategory_id.is_some() {
+impl QuizParams {
+    pub fn category(&self) -> Option<&str> {
+        self.category_id.as_deref().filter(|c| !c.is_empty())
+    }
+
+    pub fn subcategory(&self) -> Option<&str> {
+        self.subcategory_id.as_deref().filter(|s| !s.is_empty())
+    }
+
+    pub fn topic(&self) -> Option<&str> {
+        self.topic_id.as_deref().filter(|t| !t.is_empty())
+    }
+
+    pub fn tag_list(&self) -> Vec<String> {
+        parse_tags(self.tags.as_deref().unwrap_or(""))
+    }
+
+    pub fn match_any(&self) -> bool {
+        self.tag_match.as_deref() == Some("any")
+    }
+}
+
+// Why a quiz can't be served
+pub(crate) enum QuizUnavailable {
+    NothingChosen,
+    TopicNotFound,
+}
+
+// How long a page's answers are accepted, as an SQLite datetime modifier
+const SERVED_MAX_AGE: &str = "-1 day";
+
+// Why answers can't be graded against what was served
+pub(crate) const SERVED_EXPIRED: &str = "This quiz has expired or was already submitted; reload it to try again";
+const NOT_SERVED: &str = "An answer is to a question this quiz didn't show; reload it to try again";
+const WRONG_REVISION: &str = "An answer names a revision of its question that this quiz didn't show";
+const ANSWERED_TWICE: &str = "A question is answered more than once";
+
+impl QuizUnavailable {
+    pub fn message(&self) -> &'static str {
+        match self {
+            QuizUnavailable::NothingChosen => "Choose a topic or some tags for the quiz",
+            QuizUnavailable::TopicNotFound => "Topic not found",
+        }
+    }
+}
+
+// The published questions a quiz draws from, with the breadcrumbs of its
+// topic
+pub(crate) async fn load_quiz(
+    conn: &mut SqliteConnection,
+    params: &QuizParams,
+) -> Result<(Vec<Topic>, Vec<Question>), QuizUnavailable> {
+    let tags = params.tag_list();
+    if params.category().is_none() && params.topic().is_none() && tags.is_empty() {
+        return Err(QuizUnavailable::NothingChosen);
+    }
+
+    let breadcrumbs = match params.topic().or(params.subcategory()).or(params.category()) {
+        Some(id) => topic_path(&mut *conn, id).await.unwrap(),
+        None => Vec::new(),
+    };
+    if params.topic().is_some() && !is_live(&breadcrumbs) {
+        return Err(QuizUnavailable::TopicNotFound);
+    }
+
+    let mut sql = r#"SELECT * FROM questions WHERE deleted_at IS NULL AND status = 'published'"#.to_string();
+    let mut binds: Vec<String> = Vec::new();
+    if let Some(category_id) = params.category() {
+        sql.push_str(" AND category_id = ?");
+        binds.push(category_id.to_string());
+    }
+    if let Some(subcat_id) = params.subcategory() {
         sql.push_str(" AND subcategory_id = ?");
+        binds.push(subcat_id.to_string());
+    }
+    if let Some(topic_id) = params.topic() {
+        sql.push_str(&format!(" AND topic_id IN ({})", SUBTREE_SQL));
+        binds.push(topic_id.to_string());
+    }
+    if !tags.is_empty() {
+        let placeholders = vec!["?"; tags.len()].join(", ");
+        if params.match_any() {
+            sql.push_str(&format!(" AND id IN (SELECT question_id FROM question_tags WHERE tag IN ({}))", placeholders));
+        } else {
+            sql.push_str(&format!(
+                " AND id IN (SELECT question_id FROM question_tags WHERE tag IN ({}) GROUP BY question_id HAVING COUNT(*) = {})",
+                placeholders,
+                tags.len()
+            ));
+        }
+        binds.extend(tags);
     }
     sql.push_str(" ORDER BY id");
 
-    let questions = if let Some(subcat_id) = &params.subcategory_id {
-        sqlx::query_as::<_, Question>(&sql)
-            .bind(&params.category_id)
-            .bind(subcat_id)
-            .fetch_all(&**db)
-            .await
-            .unwrap()
-    } else {
-        sqlx::query_as::<_, Question>(&sql)
-            .bind(&params.category_id)
-            .fetch_all(&**db)
-            .await
-            .unwrap()
+    let mut query = sqlx::query_as::<_, Question>(&sql);
+    for value in &binds {
+        query = query.bind(value);
+    }
+    let questions = query.fetch_all(&mut *conn).await.unwrap();
+    Ok((breadcrumbs, questions))
+}
+
+#[handler]
+pub async fn get_quiz(db: Data<&Pool<Sqlite>>, Query(params): Query<QuizParams>) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let (breadcrumbs, questions) = match load_quiz(&mut conn, &params).await {
+        Ok(quiz) => quiz,
+        Err(e @ QuizUnavailable::NothingChosen) => {
+            return Response::builder().status(StatusCode::BAD_REQUEST).body(e.message());
+        }
+        Err(e @ QuizUnavailable::TopicNotFound) => {
+            return Response::builder().status(StatusCode::NOT_FOUND).body(e.message());
+        }
     };
 
+    let questions: Vec<QuizQuestion> = questions.into_iter().map(quiz_question).collect();
+    let served_id = record_served(&mut conn, &questions.iter().collect::<Vec<_>>(), Some(&params)).await.unwrap();
     let tmpl = QuizTemplate {
         title: "Hackademy - Quiz",
+        breadcrumbs: &breadcrumbs,
         questions: &questions,
+        served_id: &served_id,
     };
-    tmpl.render().unwrap()
+    tmpl.render().unwrap().into_response()
 }
 
 // We store form data as question_<qid> -> selected_option_index
@@ -69,58 +206,513 @@ pub struct QuizSubmission {
     // We'll parse with a generic approach, or direct from the Request
 }
 
+pub(crate) fn quiz_question(q: Question) -> QuizQuestion {
+    let mut choices: Vec<(usize, String)> = q.get_options_vec().into_iter().enumerate().collect();
+    if q.kind == KIND_TEXT_ENTRY {
+        // The options are the accepted answers; never send them
+        choices.clear();
+    } else if q.kind == KIND_ORDER {
+        shuffle(&mut choices);
+    }
+    QuizQuestion {
+        id: q.id,
+        revision: q.revision,
+        kind: q.kind,
+        question_text: q.question_text,
+        choices,
+    }
+}
+
+// Remember which revision of each question a page shows, and for a quiz page
+// what the quiz drew from, so its answers are graded and credited against
+// that whatever the form says. Returns the id the page sends back with its
+// answers.
+pub(crate) async fn record_served(
+    conn: &mut SqliteConnection,
+    questions: &[&QuizQuestion],
+    quiz: Option<&QuizParams>,
+) -> anyhow::Result<String> {
+    sqlx::query("DELETE FROM served_questions WHERE created_at < datetime('now', ?)")
+        .bind(SERVED_MAX_AGE)
+        .execute(&mut *conn)
+        .await?;
+    let served_id = nanoid!();
+    let tags = quiz.map(|q| q.tag_list().join(",")).filter(|t| !t.is_empty());
+    for q in questions {
+        sqlx::query(
+            "INSERT OR IGNORE INTO served_questions (served_id, question_id, revision, category_id, subcategory_id, topic_id, tags)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&served_id)
+        .bind(&q.id)
+        .bind(q.revision)
+        .bind(quiz.and_then(|q| q.category()))
+        .bind(quiz.and_then(|q| q.subcategory()))
+        .bind(quiz.and_then(|q| q.topic()))
+        .bind(&tags)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(served_id)
+}
+
+// What a page served
+#[derive(Default)]
+pub(crate) struct Served {
+    // The revision of each question, by id
+    pub revisions: HashMap<String, i64>,
+    // What a quiz page drew from; nothing for a lesson page
+    pub quiz: QuizParams,
+}
+
+impl Served {
+    pub fn is_empty(&self) -> bool {
+        self.revisions.is_empty()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ServedRow {
+    question_id: String,
+    revision: i64,
+    category_id: Option<String>,
+    subcategory_id: Option<String>,
+    topic_id: Option<String>,
+    tags: Option<String>,
+}
+
+// What a page served. Answers are taken once, so this is empty for a page
+// already answered, and for an expired or unknown one.
+pub(crate) async fn take_served(conn: &mut SqliteConnection, served_id: &str) -> anyhow::Result<Served> {
+    let rows = sqlx::query_as::<_, ServedRow>(
+        "DELETE FROM served_questions WHERE served_id = ? AND created_at >= datetime('now', ?)
+         RETURNING question_id, revision, category_id, subcategory_id, topic_id, tags"
+    )
+    .bind(served_id)
+    .bind(SERVED_MAX_AGE)
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut served = Served::default();
+    for row in rows {
+        served.revisions.insert(row.question_id, row.revision);
+        served.quiz = QuizParams {
+            category_id: row.category_id,
+            subcategory_id: row.subcategory_id,
+            topic_id: row.topic_id,
+            tags: row.tags,
+            tag_match: None,
+        };
+    }
+    Ok(served)
+}
+
+// The revision to grade an answer against: the one its page showed
+pub(crate) fn served_revision(served: &Served, answer: &SubmittedAnswer) -> Result<i64, &'static str> {
+    let Some(&revision) = served.revisions.get(&answer.question_id) else {
+        return Err(NOT_SERVED);
+    };
+    if answer.revision.is_some_and(|r| r != revision) {
+        return Err(WRONG_REVISION);
+    }
+    Ok(revision)
+}
+
+// Fisher-Yates; an order question must not start out solved
+fn shuffle(choices: &mut [(usize, String)]) {
+    for i in (1..choices.len()).rev() {
+        let j = OsRng.next_u32() as usize % (i + 1);
+        choices.swap(i, j);
+    }
+    if choices.iter().enumerate().all(|(pos, (idx, _))| pos == *idx) {
+        choices.rotate_left(1);
+    }
+}
+
+// Form fields: "served" from the quiz page, "asked" and "revision_<id>" once
+// per question shown, then "question_<id>" once per answer given (several for
+// multiple response and order questions, in form order)
 #[handler]
-pub async fn submit_quiz(req: &Request, db: Data<&Pool<Sqlite>>) -> impl IntoResponse {
-    let form = req.form::<std::collections::HashMap<String, String>>().await.unwrap();
+pub async fn submit_quiz(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    let mut asked: Vec<String> = Vec::new();
+    let mut responses: HashMap<String, Vec<String>> = HashMap::new();
+    let mut revisions: HashMap<String, i64> = HashMap::new();
+    let mut served_id = String::new();
+    for (key, value) in fields {
+        if key == "served" {
+            served_id = value;
+        } else if let Some(question_id) = key.strip_prefix("revision_") {
+            if let Ok(revision) = value.parse() {
+                revisions.insert(question_id.to_string(), revision);
+            }
+        } else if key == "asked" {
+            if !asked.contains(&value) {
+                asked.push(value);
+            }
+        } else if let Some(question_id) = key.strip_prefix("question_") {
+            if !asked.iter().any(|id| id == question_id) {
+                asked.push(question_id.to_string());
+            }
+            responses.entry(question_id.to_string()).or_default().push(value);
+        }
+    }
+
+    let answers: Vec<SubmittedAnswer> = asked
+        .into_iter()
+        .map(|question_id| SubmittedAnswer {
+            revision: revisions.get(&question_id).copied(),
+            response: responses.remove(&question_id).unwrap_or_default(),
+            question_id,
+        })
+        .collect();
+    let user = current_user(req, &db, &session_store).await;
+    let result = match grade_quiz(&db, user.as_ref(), &served_id, &answers).await {
+        Ok(result) => result,
+        Err(e) => return Response::builder().status(StatusCode::BAD_REQUEST).body(e),
+    };
+
+    let tmpl = QuizResultsTemplate {
+        title: "Hackademy - Results",
+        total_questions: result.total_questions,
+        correct_count: result.correct_count,
+        feedback: &result.feedback,
+        attempt_id: result.attempt_id.as_deref(),
+        completed_steps: &result.completed_steps,
+    };
+    tmpl.render().unwrap().into_response()
+}
+
+// One question of a submitted quiz
+pub(crate) struct SubmittedAnswer {
+    pub question_id: String,
+    // The revision the answer says was served, if any; it must be the one
+    // that was
+    pub revision: Option<i64>,
+    // Option indices, or the typed answer for text entry
+    pub response: Vec<String>,
+}
+
+pub(crate) struct QuizResult {
+    pub feedback: Vec<QuestionFeedback>,
+    // Scored questions only
+    pub total_questions: usize,
+    pub correct_count: usize,
+    // None if no question could be graded, so nothing was recorded
+    pub attempt_id: Option<String>,
+    // Learning path steps this attempt completed
+    pub completed_steps: Vec<String>,
+}
+
+// Grade a quiz against the revisions its page showed and record the
+// attempt, scheduling reviews and completing path steps for a signed-in
+// user. Every question shown counts; one left unanswered is wrong. Err says
+// why the answers can't be taken.
+pub(crate) async fn grade_quiz(
+    db: &Pool<Sqlite>,
+    user: Option<&User>,
+    served_id: &str,
+    submitted: &[SubmittedAnswer],
+) -> Result<QuizResult, &'static str> {
     let mut feedback_list = Vec::new();
+    let mut answers = Vec::new();
+    let mut conn = db.acquire().await.unwrap();
+    let served = take_served(&mut conn, served_id).await.unwrap();
+    if served.is_empty() {
+        return Err(SERVED_EXPIRED);
+    }
+    let mut graded = Vec::new();
+    for answer in submitted {
+        if graded.iter().any(|(id, _, _)| *id == answer.question_id.as_str()) {
+            return Err(ANSWERED_TWICE);
+        }
+        graded.push((answer.question_id.as_str(), served_revision(&served, answer)?, answer.response.as_slice()));
+    }
+    let mut unanswered: Vec<(&String, &i64)> =
+        served.revisions.iter().filter(|(id, _)| !graded.iter().any(|(g, _, _)| g == id)).collect();
+    unanswered.sort();
+    graded.extend(unanswered.into_iter().map(|(id, revision)| (id.as_str(), *revision, &[] as &[String])));
+    for (question_id, revision, response) in graded {
+        let Some(feedback) = grade_served(&mut conn, question_id, revision, response).await.unwrap() else {
+            continue;
+        };
+        answers.push(serde_json::to_string(response).unwrap());
+        feedback_list.push(feedback);
+    }
 
-    for (key, value) in &form {
-        if let Some(question_id) = key.strip_prefix("question_") {
-            let selected_idx: i64 = value.parse().unwrap_or(-1);
+    drop(conn);
 
-            let question: Option<Question> = sqlx::query_as::<_, Question>(
-                "SELECT * FROM questions WHERE id = ?"
+    let scored = feedback_list.iter().filter(|f| f.scored);
+    let total_questions = scored.clone().count();
+    let correct_count = scored.filter(|f| f.is_correct).count();
+
+    let mut attempt_id = None;
+    let mut completed_steps = Vec::new();
+    if !feedback_list.is_empty() {
+        let id = attempt_id.insert(nanoid!());
+        let mut tx = db.begin().await.unwrap();
+        sqlx::query(
+            "INSERT INTO quiz_attempts (id, user_id, category_id, subcategory_id, topic_id, score, total) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&*id)
+        .bind(user.map(|u| &u.id))
+        .bind(served.quiz.category())
+        .bind(served.quiz.subcategory())
+        .bind(served.quiz.topic())
+        .bind(correct_count as i64)
+        .bind(total_questions as i64)
+        .execute(&mut tx)
+        .await
+        .unwrap();
+        for (response, feedback) in answers.iter().zip(&feedback_list) {
+            sqlx::query(
+                "INSERT INTO attempt_answers (attempt_id, question_id, revision, response, correct) VALUES (?, ?, ?, ?, ?)"
             )
-            .bind(question_id)
-            .fetch_optional(&**db)
+            .bind(&*id)
+            .bind(&feedback.question_id)
+            .bind(feedback.revision)
+            .bind(response)
+            .bind(feedback.is_correct)
+            .execute(&mut tx)
             .await
             .unwrap();
-
-            if let Some(q) = question {
-                let correct_answer = q.correct_answer_idx;
-                let options_vec = q.get_options_vec();
-                let is_correct = correct_answer == selected_idx;
-
-                let correct_option = if (correct_answer as usize) < options_vec.len() {
-                    options_vec[correct_answer as usize].clone()
-                } else {
-                    "Unknown".to_string()
-                };
-
-                let selected_option = if (selected_idx as usize) < options_vec.len() && selected_idx >= 0 {
-                    options_vec[selected_idx as usize].clone()
-                } else {
-                    "No Answer".to_string()
-                };
-
-                feedback_list.push(QuestionFeedback {
-                    question_text: q.question_text,
-                    selected_option,
-                    correct_option,
-                    is_correct,
-                });
+        }
+        if let Some(user) = user {
+            for feedback in feedback_list.iter().filter(|f| f.scored) {
+                let rating = if feedback.is_correct { RATING_GOOD } else { RATING_AGAIN };
+                record_review(&mut tx, &user.id, &feedback.question_id, rating, Some(id)).await.unwrap();
             }
         }
+        // A quiz narrowed by tags isn't the topic's quiz, so it counts for
+        // no path step
+        let quiz = &served.quiz;
+        let topic = quiz.topic().or(quiz.subcategory()).or(quiz.category());
+        if let (Some(user), Some(topic)) = (user, topic)
+            && quiz.tag_list().is_empty()
+        {
+            completed_steps = record_quiz_pass(&mut tx, &user.id, topic, correct_count as i64, total_questions as i64, id)
+                .await
+                .unwrap();
+        }
+        tx.commit().await.unwrap();
     }
 
-    let total_questions = feedback_list.len();
-    let correct_count = feedback_list.iter().filter(|f| f.is_correct).count();
+    Ok(QuizResult { feedback: feedback_list, total_questions, correct_count, attempt_id, completed_steps })
+}
 
-    let tmpl = QuizResultsTemplate {
-        title: "Hackademy - Results",
-        total_questions,
-        correct_count,
-        feedback: &feedback_list,
+// Grade a response to a published question against the revision that was
+// served; None if the question is gone
+pub(crate) async fn grade_served(
+    conn: &mut SqliteConnection,
+    question_id: &str,
+    served: i64,
+    response: &[String],
+) -> anyhow::Result<Option<QuestionFeedback>> {
+    let question: Option<Question> = sqlx::query_as::<_, Question>(
+        "SELECT * FROM questions WHERE id = ? AND deleted_at IS NULL AND status = 'published'"
+    )
+    .bind(question_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(mut q) = question else {
+        return Ok(None);
     };
-    tmpl.render().unwrap()
-}
\ No newline at end of file
+
+    // The question may have been edited since it was served
+    if served != q.revision {
+        let revision: Option<QuestionRevision> = sqlx::query_as::<_, QuestionRevision>(
+            "SELECT * FROM question_revisions WHERE question_id = ? AND revision = ?"
+        )
+        .bind(question_id)
+        .bind(served)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(revision) = revision {
+            revision.apply_to(&mut q);
+        }
+    }
+
+    let accepted = accepted_answers(conn, &q.id, q.revision).await?;
+    let mut feedback = grade(q, response, &accepted);
+    feedback.scored = is_scored(conn, &feedback.question_id, feedback.revision).await?;
+    Ok(Some(feedback))
+}
+
+// `accepted` lists extra options a regrade has accepted for a choice question
+fn grade(q: Question, response: &[String], accepted: &[i64]) -> QuestionFeedback {
+    let options = q.get_options_vec();
+    let indices = response_indices(response);
+    let is_correct = is_correct(&q, response, accepted);
+
+    let correct_option = correct_answer(&q, accepted);
+    let selected_option = match q.kind.as_str() {
+        KIND_MULTIPLE_RESPONSE => {
+            let mut selected = indices;
+            selected.sort();
+            selected.dedup();
+            option_list(&options, &selected, ", ")
+        }
+        KIND_TEXT_ENTRY => {
+            let answer = response.first().map(|s| s.trim()).unwrap_or("");
+            if answer.is_empty() { "No Answer".to_string() } else { answer.to_string() }
+        }
+        KIND_ORDER => option_list(&options, &indices, " → "),
+        _ => option_list(&options, &indices[..indices.len().min(1)], ""),
+    };
+
+    QuestionFeedback {
+        question_id: q.id,
+        revision: q.revision,
+        question_text: q.question_text,
+        selected_option,
+        correct_option,
+        is_correct,
+        scored: true,
+    }
+}
+
+// The right answer as shown to learners, e.g. on results and flashcards
+pub(crate) fn correct_answer(q: &Question, accepted: &[i64]) -> String {
+    let options = q.get_options_vec();
+    match q.kind.as_str() {
+        KIND_MULTIPLE_RESPONSE => {
+            let mut correct = q.get_correct_answers();
+            correct.sort();
+            option_list(&options, &correct, ", ")
+        }
+        KIND_TEXT_ENTRY => options.join(" / "),
+        KIND_ORDER => options.join(" → "),
+        _ => {
+            let mut correct = vec![q.correct_answer_idx];
+            correct.extend(accepted.iter().filter(|i| **i != q.correct_answer_idx));
+            option_list(&options, &correct, " / ")
+        }
+    }
+}
+
+fn option_list(options: &[String], indices: &[i64], separator: &str) -> String {
+    if indices.is_empty() {
+        return "No Answer".to_string();
+    }
+    indices
+        .iter()
+        .map(|i| usize::try_from(*i).ok().and_then(|i| options.get(i)).map(String::as_str).unwrap_or("Unknown"))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+    use poem::middleware::CookieJarManager;
+    use poem::{Endpoint, EndpointExt, Route};
+
+    // Three web questions and a crypto one, all answered by option 0, and a
+    // path with an 80% quiz step on each topic
+    async fn lab() -> Pool<Sqlite> {
+        let db = test_db().await;
+        for sql in [
+            "INSERT INTO users (id, username, password_hash) VALUES ('u-alice', 'alice', '')",
+            "INSERT INTO categories (id, title) VALUES ('web', 'Web'), ('crypto', 'Crypto')",
+            "INSERT INTO questions (id, category_id, question_text, options, correct_answer_idx, status) VALUES
+                ('web-1', 'web', 'One?', '[\"yes\",\"no\"]', 0, 'published'),
+                ('web-2', 'web', 'Two?', '[\"yes\",\"no\"]', 0, 'published'),
+                ('web-3', 'web', 'Three?', '[\"yes\",\"no\"]', 0, 'published'),
+                ('crypto-1', 'crypto', 'Four?', '[\"yes\",\"no\"]', 0, 'published')",
+            "INSERT INTO learning_paths (id, title) VALUES ('p', 'Basics')",
+            "INSERT INTO path_steps (id, path_id, position, kind, title, topic_id) VALUES
+                ('web-step', 'p', 1, 'quiz', 'Web quiz', 'web'),
+                ('crypto-step', 'p', 2, 'quiz', 'Crypto quiz', 'crypto')",
+        ] {
+            sqlx::query(sql).execute(&db).await.unwrap();
+        }
+        db
+    }
+
+    async fn serve(db: &Pool<Sqlite>, params: &QuizParams) -> String {
+        let mut conn = db.acquire().await.unwrap();
+        let (_, questions) = load_quiz(&mut conn, params).await.ok().unwrap();
+        let questions: Vec<QuizQuestion> = questions.into_iter().map(quiz_question).collect();
+        record_served(&mut conn, &questions.iter().collect::<Vec<_>>(), Some(params)).await.unwrap()
+    }
+
+    // Submit the form as alice
+    async fn submit(db: &Pool<Sqlite>, form: &str) -> Response {
+        let sessions = SessionStore::new();
+        sessions.set_session("alice-session", "u-alice");
+        let app = Route::new()
+            .at("/quiz/submit", post(submit_quiz))
+            .data(db.clone())
+            .data(sessions)
+            .with(CookieJarManager::new());
+        let request = Request::builder()
+            .method(poem::http::Method::POST)
+            .uri_str("/quiz/submit")
+            .header("Cookie", "hackademy_session_id=alice-session")
+            .content_type("application/x-www-form-urlencoded")
+            .body(form.to_string());
+        app.call(request).await.unwrap()
+    }
+
+    async fn completed(db: &Pool<Sqlite>, step_id: &str) -> bool {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM path_step_completions WHERE user_id = 'u-alice' AND step_id = ?")
+            .bind(step_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+            > 0
+    }
+
+    #[tokio::test]
+    async fn credits_only_the_quiz_that_was_served() {
+        let db = lab().await;
+        let web = QuizParams { category_id: Some("web".to_string()), ..Default::default() };
+
+        // Leaving out an answer doesn't leave it out of the score, and naming
+        // another topic doesn't move the credit there
+        let served = serve(&db, &web).await;
+        let form = format!(
+            "served={}&topic_id=crypto&category_id=crypto&asked=web-1&question_web-1=0&asked=web-2&question_web-2=0",
+            served
+        );
+        assert_eq!(submit(&db, &form).await.status(), StatusCode::OK);
+        let (category_id, topic_id, score, total): (Option<String>, Option<String>, i64, i64) =
+            sqlx::query_as("SELECT category_id, topic_id, score, total FROM quiz_attempts")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!((category_id.as_deref(), topic_id, score, total), (Some("web"), None, 2, 3));
+        let unanswered: (String, bool) =
+            sqlx::query_as("SELECT response, correct FROM attempt_answers WHERE question_id = 'web-3'")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(unanswered, ("[]".to_string(), false));
+        assert!(!completed(&db, "web-step").await);
+        assert!(!completed(&db, "crypto-step").await);
+
+        // Answering everything passes the quiz that was served, and only it
+        let served = serve(&db, &web).await;
+        let form = format!(
+            "served={}&topic_id=crypto&asked=web-1&question_web-1=0&asked=web-2&question_web-2=0&asked=web-3&question_web-3=0",
+            served
+        );
+        assert_eq!(submit(&db, &form).await.status(), StatusCode::OK);
+        assert!(completed(&db, "web-step").await);
+        assert!(!completed(&db, "crypto-step").await);
+    }
+
+    #[tokio::test]
+    async fn refuses_an_answer_given_twice() {
+        let db = lab().await;
+        let served = serve(&db, &QuizParams { category_id: Some("web".to_string()), ..Default::default() }).await;
+        let answer = SubmittedAnswer { question_id: "web-1".to_string(), revision: None, response: vec!["0".to_string()] };
+        let twice = [answer, SubmittedAnswer { question_id: "web-1".to_string(), revision: None, response: vec!["0".to_string()] }];
+        assert_eq!(grade_quiz(&db, None, &served, &twice).await.err(), Some(ANSWERED_TWICE));
+    }
+}
@@ -0,0 +1,191 @@
+use poem::{
+    handler,
+    web::{Data, Form, Html, Path},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite};
+
+use crate::content::scoring::{gradable_revisions, regrade_question, response_indices, RegradeAction, RegradeOutcome};
+use crate::content::tags::question_tags;
+use crate::models::{Question, KIND_CHOICE};
+use crate::routes::admin::{not_found, require_staff, ValidQuestion};
+use crate::routes::auth::SessionStore;
+
+#[derive(Template)]
+#[template(path = "admin_question_regrade.html")]
+struct RegradeTemplate<'a> {
+    title: &'a str,
+    question: &'a Question,
+    preview: &'a ValidQuestion,
+    // Revisions the current answer key can grade
+    revisions: &'a [i64],
+    // Revisions taken out of scoring
+    excluded: &'a [i64],
+    // (option, times chosen, counted correct) over the gradable revisions,
+    // for choice questions
+    distribution: &'a [(String, i64, bool)],
+    answers: i64,
+    correct: i64,
+    history: &'a [RegradeEntry],
+    outcome: Option<&'a RegradeOutcome>,
+    errors: &'a [String],
+}
+
+// A row of regrades with the name of who ran it
+#[derive(Debug, sqlx::FromRow)]
+pub struct RegradeEntry {
+    pub action: String,
+    pub revisions: String,
+    pub accepted: Option<String>,
+    pub note: Option<String>,
+    pub answers_changed: i64,
+    pub attempts_rescored: i64,
+    pub created_at: String,
+    pub username: String,
+}
+
+async fn find_question(db: &Pool<Sqlite>, id: &str) -> Option<Question> {
+    sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn render_regrade(
+    db: &Pool<Sqlite>,
+    question: &Question,
+    outcome: Option<&RegradeOutcome>,
+    errors: &[String],
+) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let revisions = gradable_revisions(&mut conn, question).await.unwrap();
+    let excluded: Vec<i64> = sqlx::query_scalar("SELECT revision FROM scoring_exclusions WHERE question_id = ? ORDER BY revision")
+        .bind(&question.id)
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+
+    let answers: Vec<(i64, String, bool)> = sqlx::query_as(
+        "SELECT revision, response, correct FROM attempt_answers WHERE question_id = ?"
+    )
+    .bind(&question.id)
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+    let answers: Vec<_> = answers.into_iter().filter(|(revision, _, _)| revisions.contains(revision)).collect();
+
+    let mut distribution = Vec::new();
+    if question.kind == KIND_CHOICE {
+        let accepted: Vec<i64> = sqlx::query_scalar("SELECT option_idx FROM accepted_answers WHERE question_id = ? AND revision = ?")
+            .bind(&question.id)
+            .bind(question.revision)
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        for (idx, option) in question.get_options_vec().into_iter().enumerate() {
+            let idx = idx as i64;
+            let chosen = answers
+                .iter()
+                .filter(|(_, response, _)| {
+                    let response: Vec<String> = serde_json::from_str(response).unwrap_or_default();
+                    response_indices(&response).first() == Some(&idx)
+                })
+                .count();
+            distribution.push((option, chosen as i64, idx == question.correct_answer_idx || accepted.contains(&idx)));
+        }
+    }
+
+    let history = sqlx::query_as::<_, RegradeEntry>(
+        "SELECT r.action, r.revisions, r.accepted, r.note, r.answers_changed, r.attempts_rescored, r.created_at, u.username
+         FROM regrades r JOIN users u ON u.id = r.user_id
+         WHERE r.question_id = ? ORDER BY r.created_at DESC, r.rowid DESC"
+    )
+    .bind(&question.id)
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+
+    let mut preview = ValidQuestion::from(question);
+    preview.tags = question_tags(&mut conn, &question.id).await.unwrap();
+    let tmpl = RegradeTemplate {
+        title: "Regrade Question",
+        question,
+        preview: &preview,
+        revisions: &revisions,
+        excluded: &excluded,
+        distribution: &distribution,
+        answers: answers.len() as i64,
+        correct: answers.iter().filter(|(_, _, correct)| *correct).count() as i64,
+        history: &history,
+        outcome,
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn regrade_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(question) = find_question(&db, &id).await else {
+        return not_found("Question");
+    };
+    render_regrade(&db, &question, None, &[]).await
+}
+
+// Form fields: "action" (regrade, accept or void), "note", and "accept" once
+// per option to accept
+#[handler]
+pub async fn regrade(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let Some(question) = find_question(&db, &id).await else {
+        return not_found("Question");
+    };
+
+    let field = |name: &str| fields.iter().find(|(k, _)| k == name).map(|(_, v)| v.trim()).unwrap_or("");
+    let note = Some(field("note")).filter(|n| !n.is_empty());
+    let action = match field("action") {
+        "regrade" => RegradeAction::Regrade,
+        "accept" => RegradeAction::Accept(
+            fields
+                .iter()
+                .filter(|(k, _)| k == "accept")
+                .filter_map(|(_, v)| v.parse().ok())
+                .collect(),
+        ),
+        "void" => RegradeAction::Void,
+        other => return render_regrade(&db, &question, None, &[format!("Unknown action: {}", other)]).await,
+    };
+    if action == RegradeAction::Void && note.is_none() {
+        return render_regrade(&db, &question, None, &["Say why the question is being voided".to_string()]).await;
+    }
+
+    let mut tx = db.begin().await.unwrap();
+    match regrade_question(&mut tx, &question, &action, &user.id, note).await {
+        Ok(outcome) => {
+            tx.commit().await.unwrap();
+            render_regrade(&db, &question, Some(&outcome), &[]).await
+        }
+        Err(e) => {
+            tx.rollback().await.unwrap();
+            render_regrade(&db, &question, None, &[e.to_string()]).await
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use poem::{
+    handler,
+    web::{Data, Form, Html, Path, Query},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite};
+use nanoid::nanoid;
+
+use crate::content::scoring::{exclude_revision, is_scored};
+use crate::models::{Question, QuestionRevision, KIND_TEXT_ENTRY};
+use crate::routes::admin::{not_found, redirect, require_staff, ValidQuestion};
+use crate::routes::auth::{current_user, SessionStore};
+
+// (value, label) of the reasons a learner can pick
+pub const REASONS: &[(&str, &str)] = &[
+    ("wrong_answer", "The marked answer is wrong"),
+    ("unclear", "The question is unclear or ambiguous"),
+    ("typo", "Typo or formatting problem"),
+    ("other", "Something else"),
+];
+
+pub const REPORT_OPEN: &str = "open";
+pub const REPORT_CONFIRMED: &str = "confirmed";
+pub const REPORT_DISMISSED: &str = "dismissed";
+pub const REPORT_RESOLVED: &str = "resolved";
+
+fn reason_label(reason: &str) -> &str {
+    REASONS.iter().find(|(value, _)| *value == reason).map(|(_, label)| *label).unwrap_or(reason)
+}
+
+#[derive(Template)]
+#[template(path = "question_report.html")]
+struct ReportFormTemplate<'a> {
+    title: &'a str,
+    question: &'a Question,
+    reasons: &'a [(&'a str, &'a str)],
+    form: &'a ReportForm,
+    errors: &'a [String],
+    sent: bool,
+}
+
+impl ReportFormTemplate<'_> {
+    fn is_chosen(&self, reason: &str) -> bool {
+        reason == self.form.reason
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ReportParams {
+    pub revision: Option<i64>,
+    pub attempt_id: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ReportForm {
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub comment: String,
+}
+
+async fn published_question(db: &Pool<Sqlite>, id: &str) -> Option<Question> {
+    sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ? AND deleted_at IS NULL AND status = 'published'")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn find_revision(db: &Pool<Sqlite>, question_id: &str, revision: i64) -> Option<QuestionRevision> {
+    sqlx::query_as::<_, QuestionRevision>("SELECT * FROM question_revisions WHERE question_id = ? AND revision = ?")
+        .bind(question_id)
+        .bind(revision)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+#[handler]
+pub async fn report_form(
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+) -> Response {
+    let Some(question) = published_question(&db, &id).await else {
+        return not_found("Question");
+    };
+    let tmpl = ReportFormTemplate {
+        title: "Report a Problem",
+        question: &question,
+        reasons: REASONS,
+        form: &ReportForm::default(),
+        errors: &[],
+        sent: false,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// Anyone who can take the quiz can report; the attempt is only kept when it
+// really answered this question
+#[handler]
+pub async fn submit_report(
+    req: &Request,
+    Path(id): Path<String>,
+    Query(params): Query<ReportParams>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<ReportForm>,
+) -> Response {
+    let Some(question) = published_question(&db, &id).await else {
+        return not_found("Question");
+    };
+
+    let mut errors = Vec::new();
+    if !REASONS.iter().any(|(value, _)| *value == form.reason) {
+        errors.push("Choose what is wrong with the question".to_string());
+    }
+    if form.comment.len() > 2000 {
+        errors.push("Comment is too long (2000 characters at most)".to_string());
+    }
+    if !errors.is_empty() {
+        let tmpl = ReportFormTemplate {
+            title: "Report a Problem",
+            question: &question,
+                reasons: REASONS,
+            form: &form,
+            errors: &errors,
+            sent: false,
+        };
+        return Html(tmpl.render().unwrap()).into_response();
+    }
+
+    // Reported from a results page: the attempt says which revision was seen
+    let answered: Option<(String, i64)> = match &params.attempt_id {
+        Some(attempt_id) => sqlx::query_as("SELECT attempt_id, revision FROM attempt_answers WHERE attempt_id = ? AND question_id = ?")
+            .bind(attempt_id)
+            .bind(&id)
+            .fetch_optional(&**db)
+            .await
+            .unwrap(),
+        None => None,
+    };
+    let revision = match (&answered, params.revision) {
+        (Some((_, revision)), _) => *revision,
+        (None, Some(revision)) if find_revision(&db, &id, revision).await.is_some() => revision,
+        _ => question.revision,
+    };
+
+    let user = current_user(req, &db, &session_store).await;
+    sqlx::query(
+        "INSERT INTO question_reports (id, question_id, revision, attempt_id, user_id, reason, comment) VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(nanoid!())
+    .bind(&id)
+    .bind(revision)
+    .bind(answered.map(|(attempt_id, _)| attempt_id))
+    .bind(user.map(|u| u.id))
+    .bind(&form.reason)
+    .bind(Some(form.comment.trim()).filter(|c| !c.is_empty()))
+    .execute(&**db)
+    .await
+    .unwrap();
+
+    let tmpl = ReportFormTemplate {
+        title: "Report a Problem",
+        question: &question,
+        reasons: REASONS,
+        form: &form,
+        errors: &[],
+        sent: true,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// ----- Triage -----
+
+// A report with the question it is about, for the queue
+#[derive(Debug, sqlx::FromRow)]
+pub struct ReportEntry {
+    pub id: String,
+    pub question_id: String,
+    pub revision: i64,
+    pub attempt_id: Option<String>,
+    pub reason: String,
+    pub comment: Option<String>,
+    pub status: String,
+    pub resolution: Option<String>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+    pub question_text: String,
+    pub reporter: Option<String>,
+    pub triager: Option<String>,
+}
+
+impl ReportEntry {
+    pub fn reason_label(&self) -> &str {
+        reason_label(&self.reason)
+    }
+}
+
+const REPORT_SELECT: &str = "SELECT r.id, r.question_id, r.revision, r.attempt_id, r.reason, r.comment, r.status,
+        r.resolution, r.created_at, r.updated_at, q.question_text, u.username AS reporter, t.username AS triager
+     FROM question_reports r
+     JOIN questions q ON q.id = r.question_id
+     LEFT JOIN users u ON u.id = r.user_id
+     LEFT JOIN users t ON t.id = r.triaged_by";
+
+#[derive(Template)]
+#[template(path = "admin_reports.html")]
+struct ReportsTemplate<'a> {
+    title: &'a str,
+    status: &'a str,
+    // (status, number of reports) for every status
+    counts: &'a [(&'a str, i64)],
+    reports: &'a [ReportEntry],
+}
+
+impl ReportsTemplate<'_> {
+    fn is_current(&self, status: &str) -> bool {
+        status == self.status
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct QueueParams {
+    pub status: Option<String>,
+}
+
+#[handler]
+pub async fn report_queue(
+    req: &Request,
+    Query(params): Query<QueueParams>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let status = params.status.as_deref().unwrap_or(REPORT_OPEN);
+    let stored: Vec<(String, i64)> = sqlx::query_as("SELECT status, COUNT(*) FROM question_reports GROUP BY status")
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+    let counts: Vec<(&str, i64)> = [REPORT_OPEN, REPORT_CONFIRMED, REPORT_DISMISSED, REPORT_RESOLVED]
+        .into_iter()
+        .map(|s| (s, stored.iter().find(|(t, _)| t == s).map(|(_, n)| *n).unwrap_or(0)))
+        .collect();
+    let reports = sqlx::query_as::<_, ReportEntry>(&format!("{} WHERE r.status = ? ORDER BY r.created_at, r.rowid", REPORT_SELECT))
+        .bind(status)
+        .fetch_all(&**db)
+        .await
+        .unwrap();
+
+    let tmpl = ReportsTemplate {
+        title: "Question Reports",
+        status,
+        counts: &counts,
+        reports: &reports,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[derive(Template)]
+#[template(path = "admin_report.html")]
+struct ReportTemplate<'a> {
+    title: &'a str,
+    report: &'a ReportEntry,
+    // The question as the learner saw it
+    preview: Option<&'a ValidQuestion>,
+    // The learner's answer, when reported from a results page
+    response: Option<&'a str>,
+    // Whether answers to the reported revision still count
+    scored: bool,
+    // Other reports about the same question
+    related: &'a [ReportEntry],
+    form: &'a TriageForm,
+    errors: &'a [String],
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct TriageForm {
+    // confirm, dismiss, resolve or reopen
+    pub action: String,
+    #[serde(default)]
+    pub resolution: String,
+    // "on" to take the reported revision out of scoring when confirming
+    #[serde(default)]
+    pub exclude: Option<String>,
+}
+
+async fn find_report(db: &Pool<Sqlite>, id: &str) -> Option<ReportEntry> {
+    sqlx::query_as::<_, ReportEntry>(&format!("{} WHERE r.id = ?", REPORT_SELECT))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+// Stored responses are option indices, except for text entry
+fn describe_response(revision: &QuestionRevision, response: &str) -> String {
+    let values: Vec<String> = serde_json::from_str(response).unwrap_or_default();
+    if values.is_empty() {
+        return "No Answer".to_string();
+    }
+    if revision.kind == KIND_TEXT_ENTRY {
+        return values.join(", ");
+    }
+    let options = revision.get_options_vec();
+    values
+        .iter()
+        .map(|v| v.parse::<usize>().ok().and_then(|i| options.get(i)).cloned().unwrap_or_else(|| v.clone()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn render_report(db: &Pool<Sqlite>, report: &ReportEntry, form: &TriageForm, errors: &[String]) -> Response {
+    let question: Option<Question> = sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ?")
+        .bind(&report.question_id)
+        .fetch_optional(db)
+        .await
+        .unwrap();
+    let revision = find_revision(db, &report.question_id, report.revision).await;
+    let preview = match (question, &revision) {
+        (Some(mut question), Some(revision)) => {
+            revision.apply_to(&mut question);
+            let mut preview = ValidQuestion::from(&question);
+            preview.tags = revision.get_tags();
+            Some(preview)
+        }
+        _ => None,
+    };
+    let response = match (&report.attempt_id, &revision) {
+        (Some(attempt_id), Some(revision)) => {
+            let stored: Option<String> = sqlx::query_scalar("SELECT response FROM attempt_answers WHERE attempt_id = ? AND question_id = ?")
+                .bind(attempt_id)
+                .bind(&report.question_id)
+                .fetch_optional(db)
+                .await
+                .unwrap();
+            stored.map(|r| describe_response(revision, &r))
+        }
+        _ => None,
+    };
+    let mut conn = db.acquire().await.unwrap();
+    let scored = is_scored(&mut conn, &report.question_id, report.revision).await.unwrap();
+    let related = sqlx::query_as::<_, ReportEntry>(&format!(
+        "{} WHERE r.question_id = ? AND r.id != ? ORDER BY r.created_at DESC, r.rowid DESC",
+        REPORT_SELECT
+    ))
+    .bind(&report.question_id)
+    .bind(&report.id)
+    .fetch_all(db)
+    .await
+    .unwrap();
+
+    let tmpl = ReportTemplate {
+        title: "Question Report",
+        report,
+        preview: preview.as_ref(),
+        response: response.as_deref(),
+        scored,
+        related: &related,
+        form,
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn question_report(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(report) = find_report(&db, &id).await else {
+        return not_found("Report");
+    };
+    render_report(&db, &report, &TriageForm::default(), &[]).await
+}
+
+// Check a triage step against the report's status; returns the new status
+fn next_status(report: &ReportEntry, form: &TriageForm) -> Result<&'static str, String> {
+    let expect = |statuses: &[&str], what: &str| {
+        if statuses.contains(&report.status.as_str()) {
+            Ok(())
+        } else {
+            Err(format!("Only {} reports can be {}", statuses.join(" or "), what))
+        }
+    };
+    match form.action.as_str() {
+        "confirm" => expect(&[REPORT_OPEN], "confirmed").map(|_| REPORT_CONFIRMED),
+        "dismiss" => expect(&[REPORT_OPEN], "dismissed").map(|_| REPORT_DISMISSED),
+        "resolve" => expect(&[REPORT_CONFIRMED], "resolved").map(|_| REPORT_RESOLVED),
+        "reopen" => expect(&[REPORT_DISMISSED, REPORT_RESOLVED], "reopened").map(|_| REPORT_OPEN),
+        other => Err(format!("Unknown action: {}", other)),
+    }
+}
+
+#[handler]
+pub async fn triage_report(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<TriageForm>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let Some(report) = find_report(&db, &id).await else {
+        return not_found("Report");
+    };
+    let status = match next_status(&report, &form) {
+        Ok(status) => status,
+        Err(e) => return render_report(&db, &report, &form, &[e]).await,
+    };
+
+    let mut tx = db.begin().await.unwrap();
+    // Guard on the old status so two people triaging can't both move it
+    let result = sqlx::query(
+        "UPDATE question_reports SET status = ?, triaged_by = ?, resolution = ?, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND status = ?"
+    )
+    .bind(status)
+    .bind(&user.id)
+    .bind(Some(form.resolution.trim()).filter(|r| !r.is_empty()))
+    .bind(&id)
+    .bind(&report.status)
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    if result.rows_affected() == 0 {
+        tx.rollback().await.unwrap();
+        return redirect(&format!("/admin/reports/{}", id));
+    }
+    if status == REPORT_CONFIRMED && form.exclude.is_some() {
+        let reason = format!("report {}: {}", id, reason_label(&report.reason));
+        exclude_revision(&mut tx, &report.question_id, report.revision, &reason, Some(&user.id))
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    redirect(&format!("/admin/reports/{}", id))
+}
@@ -0,0 +1,474 @@
+use poem::{
+    handler,
+    web::{Data, Form, Html, Path},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use nanoid::nanoid;
+
+use crate::content::revisions::{apply_edit, pending_edit};
+use crate::content::tags::question_tags;
+use crate::models::{
+    Question, QuestionEdit, User, ROLE_ADMIN, STATUS_DRAFT, STATUS_IN_REVIEW, STATUS_PUBLISHED, STATUS_RETIRED,
+};
+use crate::routes::admin::{not_found, redirect, require_staff, ValidQuestion};
+use crate::routes::auth::SessionStore;
+
+#[derive(Template)]
+#[template(path = "admin_question_review.html")]
+struct ReviewTemplate<'a> {
+    title: &'a str,
+    question: &'a Question,
+    // The status of the pending edit, for a published question being edited
+    edit_status: Option<&'a str>,
+    preview: &'a ValidQuestion,
+    author: Option<&'a str>,
+    reviewer: Option<&'a str>,
+    // Staff who can be asked to review
+    reviewers: &'a [User],
+    history: &'a [ReviewEntry],
+    // Whether the signed-in user may approve or send back the question
+    can_decide: bool,
+    form: &'a ReviewForm,
+    errors: &'a [String],
+}
+
+// A row of question_reviews with the name of who acted
+#[derive(Debug, sqlx::FromRow)]
+pub struct ReviewEntry {
+    pub action: String,
+    pub comment: Option<String>,
+    pub created_at: String,
+    pub username: String,
+}
+
+impl ReviewEntry {
+    pub fn label(&self) -> &str {
+        match self.action.as_str() {
+            "submitted" => "submitted for review",
+            "approved" => "approved and published",
+            "changes_requested" => "requested changes",
+            "commented" => "commented",
+            "retired" => "retired",
+            "reopened" => "reopened as a draft",
+            "edit_approved" => "approved and published an edit",
+            "edit_discarded" => "discarded the pending edit",
+            "edited_in_review" => "edited it during review, sending it back to draft",
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ReviewForm {
+    // submit, approve, request_changes, comment, retire, reopen or discard
+    pub action: String,
+    #[serde(default)]
+    pub reviewer_id: String,
+    #[serde(default)]
+    pub comment: String,
+}
+
+// What the review steps act on: a published question's pending edit if it
+// has one, otherwise the question
+struct Subject<'a> {
+    status: &'a str,
+    author_id: Option<&'a str>,
+    reviewer_id: Option<&'a str>,
+    edit: bool,
+}
+
+impl<'a> Subject<'a> {
+    fn of(q: &'a Question, edit: Option<&'a QuestionEdit>) -> Self {
+        match edit {
+            Some(e) => Subject {
+                status: &e.status,
+                author_id: e.author_id.as_deref(),
+                reviewer_id: e.reviewer_id.as_deref(),
+                edit: true,
+            },
+            None => Subject {
+                status: &q.status,
+                author_id: q.author_id.as_deref(),
+                reviewer_id: q.reviewer_id.as_deref(),
+                edit: false,
+            },
+        }
+    }
+}
+
+// The assigned reviewer decides, unless they wrote the question or edit;
+// admins can always decide.
+fn can_decide(subject: &Subject, user: &User) -> bool {
+    user.role == ROLE_ADMIN
+        || (subject.reviewer_id == Some(user.id.as_str()) && subject.author_id != Some(user.id.as_str()))
+}
+
+// Check a workflow step against the status and the user. Returns the status
+// to move to (None for a plain comment) and the history action.
+fn next_status(
+    subject: &Subject,
+    user: &User,
+    form: &ReviewForm,
+) -> Result<(Option<&'static str>, &'static str), String> {
+    let comment = form.comment.trim();
+    let expect = |status: &str, what: &str| {
+        if subject.status == status {
+            Ok(())
+        } else if subject.edit {
+            Err(format!("Only {} edits can be {}", status.replace('_', " "), what))
+        } else {
+            Err(format!("Only {} questions can be {}", status.replace('_', " "), what))
+        }
+    };
+    match form.action.as_str() {
+        "submit" => {
+            expect(STATUS_DRAFT, "submitted for review")?;
+            Ok((Some(STATUS_IN_REVIEW), "submitted"))
+        }
+        "approve" | "request_changes" => {
+            expect(STATUS_IN_REVIEW, "approved or sent back")?;
+            if !can_decide(subject, user) {
+                return Err("Only the assigned reviewer or an admin can decide on this question".to_string());
+            }
+            if form.action == "approve" {
+                Ok((Some(STATUS_PUBLISHED), if subject.edit { "edit_approved" } else { "approved" }))
+            } else if comment.is_empty() {
+                Err("Say what needs to change".to_string())
+            } else {
+                Ok((Some(STATUS_DRAFT), "changes_requested"))
+            }
+        }
+        "comment" if comment.is_empty() => Err("Comment is empty".to_string()),
+        "comment" => Ok((None, "commented")),
+        "retire" if subject.edit => Err("Approve or discard the pending edit before retiring the question".to_string()),
+        "retire" => {
+            expect(STATUS_PUBLISHED, "retired")?;
+            Ok((Some(STATUS_RETIRED), "retired"))
+        }
+        "discard" if subject.edit => Ok((None, "edit_discarded")),
+        "discard" => Err("There is no pending edit to discard".to_string()),
+        "reopen" => {
+            expect(STATUS_RETIRED, "reopened")?;
+            Ok((Some(STATUS_DRAFT), "reopened"))
+        }
+        other => Err(format!("Unknown action: {}", other)),
+    }
+}
+
+// Editing a question or pending edit in review sends it back to draft with
+// the editor as its author, so it is reviewed afresh and never by whoever
+// changed it last. Call this in the transaction that saves the edit.
+pub(crate) async fn note_edited_in_review(conn: &mut SqliteConnection, question_id: &str, user_id: &str) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO question_reviews (id, question_id, user_id, action) VALUES (?, ?, ?, 'edited_in_review')")
+        .bind(nanoid!())
+        .bind(question_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn staff_users(db: &Pool<Sqlite>) -> Vec<User> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE role IN ('admin', 'instructor') ORDER BY username")
+        .fetch_all(db)
+        .await
+        .unwrap()
+}
+
+async fn find_question(db: &Pool<Sqlite>, id: &str) -> Option<Question> {
+    sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn render_review(
+    db: &Pool<Sqlite>,
+    user: &User,
+    question: &Question,
+    edit: Option<&QuestionEdit>,
+    form: &ReviewForm,
+    errors: &[String],
+) -> Response {
+    let subject = Subject::of(question, edit);
+    let reviewers = staff_users(db).await;
+    let name_of = |id: &Option<String>| {
+        id.as_deref()
+            .and_then(|id| reviewers.iter().find(|u| u.id == id))
+            .map(|u| u.username.as_str())
+    };
+    let history = sqlx::query_as::<_, ReviewEntry>(
+        "SELECT r.action, r.comment, r.created_at, u.username
+         FROM question_reviews r JOIN users u ON u.id = r.user_id
+         WHERE r.question_id = ? ORDER BY r.created_at, r.rowid"
+    )
+    .bind(&question.id)
+    .fetch_all(db)
+    .await
+    .unwrap();
+
+    let preview = match edit {
+        Some(edit) => ValidQuestion::from(edit),
+        None => {
+            let mut preview = ValidQuestion::from(question);
+            let mut conn = db.acquire().await.unwrap();
+            preview.tags = question_tags(&mut conn, &question.id).await.unwrap();
+            preview
+        }
+    };
+    let tmpl = ReviewTemplate {
+        title: "Review Question",
+        question,
+        edit_status: edit.map(|e| e.status.as_str()),
+        preview: &preview,
+        author: name_of(&subject.author_id.map(String::from)),
+        reviewer: name_of(&subject.reviewer_id.map(String::from)),
+        reviewers: &reviewers,
+        history: &history,
+        can_decide: can_decide(&subject, user),
+        form,
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// Shows the question as it will appear once published (or once its pending
+// edit is), its status and review history, and the workflow steps open to
+// the signed-in user
+#[handler]
+pub async fn question_review(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let Some(question) = find_question(&db, &id).await else {
+        return not_found("Question");
+    };
+    let mut conn = db.acquire().await.unwrap();
+    let edit = pending_edit(&mut conn, &id).await.unwrap();
+    drop(conn);
+    render_review(&db, &user, &question, edit.as_ref(), &ReviewForm::default(), &[]).await
+}
+
+#[handler]
+pub async fn review_question(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<ReviewForm>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let Some(question) = find_question(&db, &id).await else {
+        return not_found("Question");
+    };
+    let mut conn = db.acquire().await.unwrap();
+    let edit = pending_edit(&mut conn, &id).await.unwrap();
+    drop(conn);
+    let subject = Subject::of(&question, edit.as_ref());
+
+    let mut errors = Vec::new();
+    let step = next_status(&subject, &user, &form);
+    let mut reviewer_id = subject.reviewer_id.map(String::from);
+    if form.action == "submit" {
+        let staff = staff_users(&db).await;
+        match staff.iter().find(|u| u.id == form.reviewer_id) {
+            None => errors.push("Choose a reviewer".to_string()),
+            Some(r) if subject.author_id == Some(r.id.as_str()) => {
+                errors.push("Authors cannot review their own questions".to_string())
+            }
+            Some(r) => reviewer_id = Some(r.id.clone()),
+        }
+    }
+    let (status, action) = match step {
+        Ok(step) if errors.is_empty() => step,
+        Ok(_) => return render_review(&db, &user, &question, edit.as_ref(), &form, &errors).await,
+        Err(e) => {
+            errors.insert(0, e);
+            return render_review(&db, &user, &question, edit.as_ref(), &form, &errors).await;
+        }
+    };
+
+    let mut tx = db.begin().await.unwrap();
+    if let Some(edit) = &edit {
+        // Guard on the old status, and for approvals on the edit the reviewer
+        // saw, so two reviewers can't both move it
+        let moved = match (status, action) {
+            (Some(STATUS_PUBLISHED), _) => sqlx::query(
+                "DELETE FROM question_edits WHERE question_id = ? AND status = ? AND updated_at = ?"
+            )
+            .bind(&id)
+            .bind(&edit.status)
+            .bind(&edit.updated_at)
+            .execute(&mut tx)
+            .await
+            .unwrap(),
+            (Some(status), _) => sqlx::query(
+                "UPDATE question_edits SET status = ?, reviewer_id = ? WHERE question_id = ? AND status = ?"
+            )
+            .bind(status)
+            .bind(&reviewer_id)
+            .bind(&id)
+            .bind(&edit.status)
+            .execute(&mut tx)
+            .await
+            .unwrap(),
+            (None, "edit_discarded") => sqlx::query("DELETE FROM question_edits WHERE question_id = ? AND updated_at = ?")
+                .bind(&id)
+                .bind(&edit.updated_at)
+                .execute(&mut tx)
+                .await
+                .unwrap(),
+            (None, _) => Default::default(),
+        };
+        if action != "commented" && moved.rows_affected() == 0 {
+            tx.rollback().await.unwrap();
+            return redirect(&format!("/admin/questions/{}/review", id));
+        }
+        if status == Some(STATUS_PUBLISHED) {
+            apply_edit(&mut tx, edit).await.unwrap();
+        }
+    } else if let Some(status) = status {
+        // Guard on the old status so two reviewers can't both move it
+        let result = sqlx::query("UPDATE questions SET status = ?, reviewer_id = ? WHERE id = ? AND status = ?")
+            .bind(status)
+            .bind(&reviewer_id)
+            .bind(&id)
+            .bind(&question.status)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+        if result.rows_affected() == 0 {
+            tx.rollback().await.unwrap();
+            return redirect(&format!("/admin/questions/{}/review", id));
+        }
+    }
+    sqlx::query("INSERT INTO question_reviews (id, question_id, user_id, action, comment) VALUES (?, ?, ?, ?, ?)")
+        .bind(nanoid!())
+        .bind(&id)
+        .bind(&user.id)
+        .bind(action)
+        .bind(Some(form.comment.trim()).filter(|c| !c.is_empty()))
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    redirect(&format!("/admin/questions/{}/review", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+    use crate::routes::admin::update_question;
+    use poem::middleware::CookieJarManager;
+    use poem::{post, Endpoint, EndpointExt, Route};
+
+    // Ann wrote a question and asked Rob to review it
+    async fn lab(status: &str) -> Pool<Sqlite> {
+        let db = test_db().await;
+        for sql in [
+            "INSERT INTO users (id, username, password_hash, role) VALUES
+                ('u-ann', 'ann', '', 'instructor'), ('u-rob', 'rob', '', 'instructor')",
+            "INSERT INTO categories (id, title) VALUES ('web', 'Web')",
+        ] {
+            sqlx::query(sql).execute(&db).await.unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO questions (id, category_id, question_text, options, correct_answer_idx, status, author_id, reviewer_id)
+             VALUES ('q', 'web', 'What does XSS stand for?', '[\"Cross-site scripting\",\"XML schema\"]', 0, ?, 'u-ann', 'u-rob')"
+        )
+        .bind(status)
+        .execute(&db)
+        .await
+        .unwrap();
+        db
+    }
+
+    // Post a form as Rob
+    async fn post_as_rob(db: &Pool<Sqlite>, uri: &str, form: &str) {
+        let sessions = SessionStore::new();
+        sessions.set_session("rob-session", "u-rob");
+        let app = Route::new()
+            .at("/admin/questions/:id/edit", post(update_question))
+            .at("/admin/questions/:id/review", post(review_question))
+            .data(db.clone())
+            .data(sessions)
+            .with(CookieJarManager::new());
+        let request = Request::builder()
+            .method(poem::http::Method::POST)
+            .uri_str(uri)
+            .header("Cookie", "hackademy_session_id=rob-session")
+            .content_type("application/x-www-form-urlencoded")
+            .body(form.to_string());
+        app.call(request).await.unwrap();
+    }
+
+    const EDIT: &str = "topic_id=web&kind=choice&question_text=Is+this+right%3F&options=%5B%22Yes%22%2C%22No%22%5D&correct_answer_idx=1";
+
+    #[tokio::test]
+    async fn a_reviewer_cannot_approve_their_own_edit() {
+        let db = lab(STATUS_IN_REVIEW).await;
+        post_as_rob(&db, "/admin/questions/q/edit", EDIT).await;
+        let (status, author_id, text): (String, Option<String>, String) =
+            sqlx::query_as("SELECT status, author_id, question_text FROM questions WHERE id = 'q'")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!((status.as_str(), author_id.as_deref(), text.as_str()), (STATUS_DRAFT, Some("u-rob"), "Is this right?"));
+
+        post_as_rob(&db, "/admin/questions/q/review", "action=approve").await;
+        // Nor can they send it back to themselves
+        post_as_rob(&db, "/admin/questions/q/review", "action=submit&reviewer_id=u-rob").await;
+        post_as_rob(&db, "/admin/questions/q/review", "action=approve").await;
+        let status: String = sqlx::query_scalar("SELECT status FROM questions WHERE id = 'q'").fetch_one(&db).await.unwrap();
+        assert_eq!(status, STATUS_DRAFT);
+        let actions: Vec<String> = sqlx::query_scalar("SELECT action FROM question_reviews ORDER BY rowid").fetch_all(&db).await.unwrap();
+        assert_eq!(actions, ["edited_in_review"]);
+    }
+
+    #[tokio::test]
+    async fn a_reviewer_cannot_approve_their_own_change_to_a_pending_edit() {
+        let db = lab(STATUS_PUBLISHED).await;
+        sqlx::query(
+            "INSERT INTO question_edits (question_id, category_id, topic_id, kind, question_text, options, correct_answer_idx, status, author_id, reviewer_id)
+             VALUES ('q', 'web', 'web', 'choice', 'What is XSS?', '[\"Cross-site scripting\",\"XML schema\"]', 0, 'in_review', 'u-ann', 'u-rob')"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        post_as_rob(&db, "/admin/questions/q/edit", EDIT).await;
+        let (status, author_id, text): (String, Option<String>, String) =
+            sqlx::query_as("SELECT status, author_id, question_text FROM question_edits WHERE question_id = 'q'")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!((status.as_str(), author_id.as_deref(), text.as_str()), (STATUS_DRAFT, Some("u-rob"), "Is this right?"));
+
+        post_as_rob(&db, "/admin/questions/q/review", "action=approve").await;
+        let text: String = sqlx::query_scalar("SELECT question_text FROM questions WHERE id = 'q'").fetch_one(&db).await.unwrap();
+        assert_eq!(text, "What does XSS stand for?");
+        let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM question_edits").fetch_one(&db).await.unwrap();
+        assert_eq!(pending, 1);
+    }
+
+    #[tokio::test]
+    async fn the_reviewer_still_approves_what_they_were_asked_to() {
+        let db = lab(STATUS_IN_REVIEW).await;
+        post_as_rob(&db, "/admin/questions/q/review", "action=approve").await;
+        let status: String = sqlx::query_scalar("SELECT status FROM questions WHERE id = 'q'").fetch_one(&db).await.unwrap();
+        assert_eq!(status, STATUS_PUBLISHED);
+    }
+}
@@ -0,0 +1,173 @@
+use poem::{
+    handler,
+    web::{Data, Html, Path, Query},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite};
+
+use crate::content::revisions::{diff_revisions, record_revision, FieldDiff};
+use crate::content::tags::set_question_tags;
+use crate::content::topics::{is_live, placement, topic_path};
+use crate::models::{Question, QuestionRevision};
+use crate::routes::admin::{not_found, redirect, require_staff};
+use crate::routes::auth::SessionStore;
+use crate::routes::review::note_edited_in_review;
+
+#[derive(Template)]
+#[template(path = "admin_question_revisions.html")]
+struct RevisionsTemplate<'a> {
+    title: &'a str,
+    question: &'a Question,
+    revisions: &'a [RevisionEntry],
+    from: i64,
+    to: i64,
+    diff: &'a [FieldDiff],
+}
+
+// A row of question_revisions with the name of who made it
+#[derive(Debug, sqlx::FromRow)]
+pub struct RevisionEntry {
+    pub revision: i64,
+    pub note: Option<String>,
+    pub created_at: String,
+    pub username: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CompareParams {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+async fn find_revision(db: &Pool<Sqlite>, id: &str, revision: i64) -> Option<QuestionRevision> {
+    sqlx::query_as::<_, QuestionRevision>("SELECT * FROM question_revisions WHERE question_id = ? AND revision = ?")
+        .bind(id)
+        .bind(revision)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+// Lists a question's revisions and diffs two of them, by default the
+// latest against the one before
+#[handler]
+pub async fn question_revisions(
+    req: &Request,
+    Path(id): Path<String>,
+    Query(params): Query<CompareParams>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let question: Option<Question> = sqlx::query_as::<_, Question>("SELECT * FROM questions WHERE id = ? AND deleted_at IS NULL")
+        .bind(&id)
+        .fetch_optional(&**db)
+        .await
+        .unwrap();
+    let Some(question) = question else {
+        return not_found("Question");
+    };
+
+    let revisions = sqlx::query_as::<_, RevisionEntry>(
+        "SELECT r.revision, r.note, r.created_at, u.username
+         FROM question_revisions r LEFT JOIN users u ON u.id = r.author_id
+         WHERE r.question_id = ? ORDER BY r.revision DESC"
+    )
+    .bind(&id)
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+
+    let to = params.to.unwrap_or(question.revision);
+    let from = params.from.unwrap_or((to - 1).max(1));
+    let diff = match (find_revision(&db, &id, from).await, find_revision(&db, &id, to).await) {
+        (Some(old), Some(new)) => diff_revisions(&old, &new),
+        _ => return not_found("Revision"),
+    };
+
+    let tmpl = RevisionsTemplate {
+        title: "Question Revisions",
+        question: &question,
+        revisions: &revisions,
+        from,
+        to,
+        diff: &diff,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// Restores an earlier revision's content as a new revision; the history
+// itself never changes. The question keeps its workflow status, except that
+// one in review goes back to draft like any other edit.
+#[handler]
+pub async fn rollback_question(
+    req: &Request,
+    Path((id, revision)): Path<(String, i64)>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let user = match require_staff(req, &db, &session_store).await {
+        Ok(u) => u,
+        Err(resp) => return resp,
+    };
+    let Some(rev) = find_revision(&db, &id, revision).await else {
+        return not_found("Revision");
+    };
+    let mut tx = db.begin().await.unwrap();
+    // The topic may have moved since; file the question where it is now
+    let path = topic_path(&mut tx, &rev.topic_id).await.unwrap();
+    if !is_live(&path) {
+        tx.rollback().await.unwrap();
+        return Response::builder()
+            .status(poem::http::StatusCode::CONFLICT)
+            .body(format!("Revision {} belongs to a topic that no longer exists", revision));
+    }
+    let (category_id, subcategory_id) = placement(&path);
+    let in_review: Option<String> =
+        sqlx::query_scalar("SELECT id FROM questions WHERE id = ? AND status = 'in_review' AND deleted_at IS NULL")
+            .bind(&id)
+            .fetch_optional(&mut tx)
+            .await
+            .unwrap();
+
+    let result = sqlx::query(
+        "UPDATE questions SET category_id = ?, subcategory_id = ?, topic_id = ?, kind = ?, question_text = ?, options = ?,
+             correct_answer_idx = ?, correct_answers = ?, explanation = ?
+         WHERE id = ? AND deleted_at IS NULL"
+    )
+    .bind(&category_id)
+    .bind(&subcategory_id)
+    .bind(&rev.topic_id)
+    .bind(&rev.kind)
+    .bind(&rev.question_text)
+    .bind(&rev.options)
+    .bind(rev.correct_answer_idx)
+    .bind(&rev.correct_answers)
+    .bind(&rev.explanation)
+    .bind(&id)
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    if result.rows_affected() == 0 {
+        tx.rollback().await.unwrap();
+        return not_found("Question");
+    }
+    if in_review.is_some() {
+        sqlx::query("UPDATE questions SET status = 'draft', author_id = ? WHERE id = ?")
+            .bind(&user.id)
+            .bind(&id)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+        note_edited_in_review(&mut tx, &id, &user.id).await.unwrap();
+    }
+    set_question_tags(&mut tx, &id, &rev.get_tags()).await.unwrap();
+    record_revision(&mut tx, &id, Some(&user.id), &format!("rolled back to revision {}", revision))
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    redirect(&format!("/admin/questions/{}/revisions", id))
+}
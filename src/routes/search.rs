@@ -1,143 +1,594 @@
+use std::collections::HashMap;
+
 use poem::{
     handler,
-    web::{Data, Query},
-    IntoResponse,
+    http::StatusCode,
+    web::{Data, Json, Query},
+    IntoResponse, Response,
 };
 use askama::Template;
-use sqlx::{Pool, Sqlite};
-use crate::models::{Category, Subcategory, Question};
+use sqlx::{sqlite::SqliteRow, FromRow, Pool, Sqlite};
+use crate::content::lessons::plain_text;
+use crate::content::normalize_tag;
+use crate::content::search::{correct_spelling, fts_expression, has_match, marked_html, parse_query, Synonyms};
+use crate::content::search_stats::{record_click, record_search};
+use crate::content::similarity::{related_to_each, RelatedItem, KIND_LESSON, KIND_QUESTION};
+use crate::content::suggest::{SuggestCache, Suggestions};
+use crate::content::tags::{is_difficulty, DIFFICULTY_TAGS};
+use crate::routes::admin::redirect;
 
-#[derive(Debug, serde::Deserialize)]
+// Results per page of one type, and per section on the "all" tab
+pub const PER_PAGE: i64 = 20;
+pub const PREVIEW: i64 = 5;
+// Related questions and lessons under each question or lesson result
+const RELATED: i64 = 3;
+
+// (value, label) of the result types; "all" shows a few of each
+pub const RESULT_TYPES: &[(&str, &str)] = &[
+    ("all", "All"),
+    ("categories", "Categories"),
+    ("subcategories", "Subcategories"),
+    ("questions", "Questions"),
+    ("lessons", "Lessons"),
+];
+
+pub const SORTS: &[(&str, &str)] = &[("relevance", "Relevance"), ("title", "Title"), ("recent", "Recently updated")];
+
+// Every filtered view has one URL: `url()` writes the parameters back in a
+// fixed order, leaving out those at their defaults
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct SearchParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub q: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub result_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<i64>,
 }
 
-#[derive(Clone, Debug)]
+impl SearchParams {
+    pub fn query(&self) -> &str {
+        self.q.as_deref().map(str::trim).unwrap_or("")
+    }
+
+    pub fn result_type(&self) -> &str {
+        let t = self.result_type.as_deref().unwrap_or("all");
+        if RESULT_TYPES.iter().any(|(value, _)| *value == t) { t } else { "all" }
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category_id.as_deref().filter(|c| !c.is_empty())
+    }
+
+    pub fn tag(&self) -> Option<String> {
+        self.tag.as_deref().map(normalize_tag).filter(|t| !t.is_empty())
+    }
+
+    pub fn difficulty(&self) -> Option<&str> {
+        self.difficulty.as_deref().filter(|d| is_difficulty(d))
+    }
+
+    pub fn sort(&self) -> &str {
+        let s = self.sort.as_deref().unwrap_or("relevance");
+        if SORTS.iter().any(|(value, _)| *value == s) { s } else { "relevance" }
+    }
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    // The tags every question must carry
+    fn required_tags(&self) -> Vec<String> {
+        self.tag().into_iter().chain(self.difficulty().map(String::from)).collect()
+    }
+
+    // The canonical form of these parameters
+    fn canonical(&self) -> SearchParams {
+        SearchParams {
+            q: Some(self.query().to_string()).filter(|q| !q.is_empty()),
+            result_type: Some(self.result_type().to_string()).filter(|t| t != "all"),
+            category_id: self.category().map(String::from),
+            tag: self.tag(),
+            difficulty: self.difficulty().map(String::from),
+            sort: Some(self.sort().to_string()).filter(|s| s != "relevance"),
+            page: Some(self.page()).filter(|p| *p > 1),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("/search?{}", serde_urlencoded::to_string(self.canonical()).unwrap())
+    }
+
+    // The same search showing another type of result, from its first page
+    pub fn type_url(&self, result_type: &str) -> String {
+        SearchParams { result_type: Some(result_type.to_string()), page: None, ..self.clone() }.url()
+    }
+
+    pub fn page_url(&self, page: i64) -> String {
+        SearchParams { page: Some(page), ..self.clone() }.url()
+    }
+}
+
+// Fields ending in _html hold escaped text with the matches in <mark>
+
+#[derive(Clone, Debug, sqlx::FromRow)]
 pub struct CategoryResult {
     pub id: String,
-    pub title: String,
+    pub title_html: String,
 }
 
-#[derive(Clone, Debug)]
+impl CategoryResult {
+    pub fn url(&self) -> String {
+        format!("/category/{}", self.id)
+    }
+}
+
+#[derive(Clone, Debug, sqlx::FromRow)]
 pub struct SubCategoryResult {
     pub id: String,
     pub category_id: String,
-    pub title: String,
-    pub description: Option<String>,
+    pub title_html: String,
+    pub description_html: Option<String>,
     pub parent_category_title: String,
 }
 
-#[derive(Clone, Debug)]
+impl SubCategoryResult {
+    pub fn url(&self) -> String {
+        format!("/quiz?category_id={}&subcategory_id={}", self.category_id, self.id)
+    }
+}
+
+#[derive(Clone, Debug, sqlx::FromRow)]
 pub struct QuestionResult {
     pub id: String,
     pub category_id: String,
     pub subcategory_id: Option<String>,
-    pub question_text: String,
+    pub question_html: String,
+    // Only when the explanation matched
+    pub explanation_html: Option<String>,
+}
+
+impl QuestionResult {
+    pub fn url(&self) -> String {
+        match &self.subcategory_id {
+            Some(subcategory_id) => format!("/quiz?category_id={}&subcategory_id={}", self.category_id, subcategory_id),
+            None => format!("/quiz?category_id={}", self.category_id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct LessonResult {
+    pub id: String,
+    pub title_html: String,
+    pub subcategory_title: String,
+    pub snippet_html: String,
+}
+
+impl LessonResult {
+    pub fn url(&self) -> String {
+        format!("/lessons/{}", self.id)
+    }
+}
+
+// One page of a type of result, and how many there are in all
+#[derive(Debug)]
+pub struct Section<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+}
+
+impl<T> Default for Section<T> {
+    fn default() -> Self {
+        Section { items: Vec::new(), total: 0 }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "search_results.html")]
 pub struct SearchResultsTemplate<'a> {
     title: &'a str,
-    query: &'a str,
-    categories: &'a [CategoryResult],
-    subcategories: &'a [SubCategoryResult],
-    questions: &'a [QuestionResult],
+    params: &'a SearchParams,
+    types: &'a [(&'a str, &'a str)],
+    sorts: &'a [(&'a str, &'a str)],
+    // (id, title) of the categories to filter by
+    category_choices: &'a [(String, String)],
+    // Tags in use, other than the difficulty tags
+    tag_choices: &'a [String],
+    difficulties: &'a [&'a str],
+    categories: &'a Section<CategoryResult>,
+    subcategories: &'a Section<SubCategoryResult>,
+    questions: &'a Section<QuestionResult>,
+    lessons: &'a Section<LessonResult>,
+    // (typed, searched for) of the words taken as misspelt
+    corrections: &'a [(String, String)],
+    // By "kind:id" of a question or lesson result
+    related: &'a HashMap<(String, String), Vec<RelatedItem>>,
+}
+
+impl SearchResultsTemplate<'_> {
+    fn total(&self) -> i64 {
+        self.categories.total + self.subcategories.total + self.questions.total + self.lessons.total
+    }
+
+    fn count(&self, result_type: &str) -> i64 {
+        match result_type {
+            "categories" => self.categories.total,
+            "subcategories" => self.subcategories.total,
+            "questions" => self.questions.total,
+            "lessons" => self.lessons.total,
+            _ => self.total(),
+        }
+    }
+
+    fn is_type(&self, result_type: &str) -> bool {
+        self.params.result_type() == result_type
+    }
+
+    // Whether a section is listed: all of them on the "all" tab
+    fn shows(&self, result_type: &str) -> bool {
+        self.is_all() || self.is_type(result_type)
+    }
+
+    fn is_all(&self) -> bool {
+        self.is_type("all")
+    }
+
+    fn is_category(&self, id: &str) -> bool {
+        self.params.category() == Some(id)
+    }
+
+    fn is_tag(&self, tag: &str) -> bool {
+        self.params.tag().as_deref() == Some(tag)
+    }
+
+    fn is_difficulty(&self, d: &str) -> bool {
+        self.params.difficulty() == Some(d)
+    }
+
+    fn is_sort(&self, s: &str) -> bool {
+        self.params.sort() == s
+    }
+
+    // Whether the "all" tab lists only the first few of a type
+    fn has_more(&self, result_type: &str) -> bool {
+        self.is_all() && self.count(result_type) > PREVIEW
+    }
+
+    fn pages(&self) -> i64 {
+        (self.count(self.params.result_type()) + PER_PAGE - 1) / PER_PAGE
+    }
+
+    // A result link that counts the click before going on to `url`
+    fn click_url(&self, url: String) -> String {
+        let params = [("q", self.params.query()), ("to", url.as_str())];
+        format!("/search/click?{}", serde_urlencoded::to_string(params).unwrap())
+    }
+
+    fn related(&self, kind: &str, id: &str) -> &[RelatedItem] {
+        self.related.get(&(kind.to_string(), id.to_string())).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn has_prev(&self) -> bool {
+        !self.is_all() && self.params.page() > 1
+    }
+
+    fn has_next(&self) -> bool {
+        !self.is_all() && self.params.page() < self.pages()
+    }
+}
+
+// The SQL for one type of result. `from` and `filter` are shared by the
+// count and the page; binds go in the order of the placeholders in `filter`.
+struct SectionQuery {
+    columns: &'static str,
+    from: &'static str,
+    filter: String,
+    binds: Vec<String>,
+    by_relevance: &'static str,
+    by_title: &'static str,
+    by_recent: &'static str,
+}
+
+impl SectionQuery {
+    async fn run<T>(self, db: &Pool<Sqlite>, sort: &str, (limit, offset): (i64, i64)) -> Section<T>
+    where
+        T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+    {
+        let count_sql = format!("SELECT COUNT(*) FROM {} WHERE {}", self.from, self.filter);
+        let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+        for value in &self.binds {
+            count = count.bind(value);
+        }
+        let total = count.fetch_one(db).await.unwrap();
+
+        let order = match sort {
+            "title" => self.by_title,
+            "recent" => self.by_recent,
+            _ => self.by_relevance,
+        };
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} ORDER BY {} LIMIT {} OFFSET {}",
+            self.columns, self.from, self.filter, order, limit, offset
+        );
+        let mut page = sqlx::query_as::<_, T>(&sql);
+        for value in &self.binds {
+            page = page.bind(value);
+        }
+        Section { items: page.fetch_all(db).await.unwrap(), total }
+    }
+}
+
+// A published question under `outer`.category_id (or subcategory_id)
+// carrying every required tag, as an EXISTS condition with its binds
+fn has_questions(column: &str, tags: &[String]) -> (String, Vec<String>) {
+    let mut sql = format!(
+        "EXISTS (SELECT 1 FROM questions q WHERE q.{} AND q.deleted_at IS NULL AND q.status = 'published'",
+        column
+    );
+    for _ in tags {
+        sql.push_str(" AND q.id IN (SELECT question_id FROM question_tags WHERE tag = ?)");
+    }
+    sql.push(')');
+    (sql, tags.to_vec())
+}
+
+fn category_query(fts: &str, params: &SearchParams) -> SectionQuery {
+    let mut filter = "categories_fts MATCH ? AND c.deleted_at IS NULL".to_string();
+    let mut binds = vec![fts.to_string()];
+    if let Some(category_id) = params.category() {
+        filter.push_str(" AND c.id = ?");
+        binds.push(category_id.to_string());
+    }
+    let tags = params.required_tags();
+    if !tags.is_empty() {
+        let (exists, tags) = has_questions("category_id = c.id", &tags);
+        filter.push_str(&format!(" AND {}", exists));
+        binds.extend(tags);
+    }
+    SectionQuery {
+        columns: "c.id, highlight(categories_fts, 0, char(2), char(3)) AS title_html",
+        from: "categories_fts JOIN categories_fts_ids cm ON cm.fts_rowid = categories_fts.rowid JOIN categories c ON c.id = cm.id",
+        filter,
+        binds,
+        by_relevance: "bm25(categories_fts)",
+        by_title: "c.title",
+        // Categories keep no dates
+        by_recent: "c.title",
+    }
+}
+
+fn subcategory_query(fts: &str, params: &SearchParams) -> SectionQuery {
+    let mut filter = "subcategories_fts MATCH ? AND s.deleted_at IS NULL AND c.deleted_at IS NULL".to_string();
+    let mut binds = vec![fts.to_string()];
+    if let Some(category_id) = params.category() {
+        filter.push_str(" AND s.category_id = ?");
+        binds.push(category_id.to_string());
+    }
+    let tags = params.required_tags();
+    if !tags.is_empty() {
+        let (exists, tags) = has_questions("subcategory_id = s.id", &tags);
+        filter.push_str(&format!(" AND {}", exists));
+        binds.extend(tags);
+    }
+    SectionQuery {
+        columns: "s.id, s.category_id,
+             highlight(subcategories_fts, 0, char(2), char(3)) AS title_html,
+             snippet(subcategories_fts, 1, char(2), char(3), '…', 24) AS description_html,
+             c.title AS parent_category_title",
+        from: "subcategories_fts
+             JOIN subcategories_fts_ids sm ON sm.fts_rowid = subcategories_fts.rowid JOIN subcategories s ON s.id = sm.id
+             JOIN categories c ON c.id = s.category_id",
+        filter,
+        binds,
+        by_relevance: "bm25(subcategories_fts, 2.0, 1.0)",
+        by_title: "s.title",
+        by_recent: "s.title",
+    }
+}
+
+fn question_query(fts: &str, params: &SearchParams) -> SectionQuery {
+    let mut filter = "questions_fts MATCH ? AND q.deleted_at IS NULL AND q.status = 'published'".to_string();
+    let mut binds = vec![fts.to_string()];
+    if let Some(category_id) = params.category() {
+        filter.push_str(" AND q.category_id = ?");
+        binds.push(category_id.to_string());
+    }
+    for tag in params.required_tags() {
+        filter.push_str(" AND q.id IN (SELECT question_id FROM question_tags WHERE tag = ?)");
+        binds.push(tag);
+    }
+    SectionQuery {
+        columns: "q.id, q.category_id, q.subcategory_id,
+             highlight(questions_fts, 0, char(2), char(3)) AS question_html,
+             snippet(questions_fts, 1, char(2), char(3), '…', 24) AS explanation_html",
+        from: "questions_fts JOIN questions_fts_ids qm ON qm.fts_rowid = questions_fts.rowid JOIN questions q ON q.id = qm.id",
+        filter,
+        binds,
+        by_relevance: "bm25(questions_fts, 2.0, 1.0)",
+        by_title: "q.question_text",
+        // When the current revision was made
+        by_recent: "(SELECT r.created_at FROM question_revisions r WHERE r.question_id = q.id AND r.revision = q.revision) DESC",
+    }
+}
+
+fn lesson_query(fts: &str, params: &SearchParams) -> SectionQuery {
+    let mut filter = "lessons_fts MATCH ? AND l.deleted_at IS NULL AND s.deleted_at IS NULL AND c.deleted_at IS NULL".to_string();
+    let mut binds = vec![fts.to_string()];
+    if let Some(category_id) = params.category() {
+        filter.push_str(" AND s.category_id = ?");
+        binds.push(category_id.to_string());
+    }
+    // Lessons carry no tags
+    if !params.required_tags().is_empty() {
+        filter.push_str(" AND 0");
+    }
+    SectionQuery {
+        columns: "l.id, highlight(lessons_fts, 0, char(2), char(3)) AS title_html, s.title AS subcategory_title,
+             snippet(lessons_fts, 1, char(2), char(3), '…', 32) AS snippet_html",
+        from: "lessons_fts
+             JOIN lessons_fts_ids lm ON lm.fts_rowid = lessons_fts.rowid JOIN lessons l ON l.id = lm.id
+             JOIN subcategories s ON s.id = l.subcategory_id JOIN categories c ON c.id = s.category_id",
+        filter,
+        binds,
+        by_relevance: "bm25(lessons_fts, 2.0, 1.0)",
+        by_title: "l.title",
+        by_recent: "l.updated_at DESC",
+    }
+}
+
+// The results of a search, with the matches marked
+pub(crate) struct SearchResults {
+    pub categories: Section<CategoryResult>,
+    pub subcategories: Section<SubCategoryResult>,
+    pub questions: Section<QuestionResult>,
+    pub lessons: Section<LessonResult>,
+    // (typed, searched for) of the words taken as misspelt
+    pub corrections: Vec<(String, String)>,
+}
+
+// Each type of result is counted in full; the "all" type lists the first few
+// of each, otherwise a page of the chosen type
+pub(crate) async fn run_search(db: &Pool<Sqlite>, params: &SearchParams, synonyms: &Synonyms) -> SearchResults {
+    let mut categories = Section::<CategoryResult>::default();
+    let mut subcategories = Section::<SubCategoryResult>::default();
+    let mut questions = Section::<QuestionResult>::default();
+    let mut lessons = Section::<LessonResult>::default();
+    let mut terms = parse_query(params.query());
+    let corrections = {
+        let mut conn = db.acquire().await.unwrap();
+        correct_spelling(&mut conn, &mut terms, synonyms).await.unwrap()
+    };
+    if let Some(fts) = fts_expression(&terms, synonyms) {
+        // (limit, offset) for a type of result
+        let window = |result_type: &str| match params.result_type() == result_type {
+            true => (PER_PAGE, (params.page() - 1) * PER_PAGE),
+            false => (PREVIEW, 0),
+        };
+        let sort = params.sort();
+        categories = category_query(&fts, params).run(db, sort, window("categories")).await;
+        subcategories = subcategory_query(&fts, params).run(db, sort, window("subcategories")).await;
+        questions = question_query(&fts, params).run(db, sort, window("questions")).await;
+        lessons = lesson_query(&fts, params).run(db, sort, window("lessons")).await;
+    }
+
+    for c in &mut categories.items {
+        c.title_html = marked_html(&c.title_html);
+    }
+    for s in &mut subcategories.items {
+        s.title_html = marked_html(&s.title_html);
+        s.description_html = s.description_html.as_deref().map(marked_html);
+    }
+    for q in &mut questions.items {
+        q.question_html = marked_html(&q.question_html);
+        q.explanation_html = q.explanation_html.as_deref().filter(|e| has_match(e)).map(marked_html);
+    }
+    // Lessons match on their markdown but are shown as plain text
+    for l in &mut lessons.items {
+        l.title_html = marked_html(&l.title_html);
+        l.snippet_html = marked_html(&plain_text(&l.snippet_html));
+    }
+    SearchResults { categories, subcategories, questions, lessons, corrections }
 }
 
 #[handler]
 pub async fn search_handler(
     Query(params): Query<SearchParams>,
     db: Data<&Pool<Sqlite>>,
+    synonyms: Data<&Synonyms>,
 ) -> impl IntoResponse {
-    let q = match params.q {
-        Some(ref s) if !s.trim().is_empty() => s.trim(),
-        _ => {
-            let tmpl = SearchResultsTemplate {
-                title: "Hackademy - Search",
-                query: "",
-                categories: &[],
-                subcategories: &[],
-                questions: &[],
-            };
-            return tmpl.render().unwrap();
-        }
-    };
-
-    // 1. Categories
-    let categories = sqlx::query_as::<_, Category>(
-        "SELECT * FROM categories WHERE title LIKE ?"
+    let category_choices: Vec<(String, String)> =
+        sqlx::query_as("SELECT id, title FROM categories WHERE deleted_at IS NULL ORDER BY title")
+            .fetch_all(&**db)
+            .await
+            .unwrap();
+    let tag_choices: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT t.tag FROM question_tags t JOIN questions q ON q.id = t.question_id
+         WHERE q.deleted_at IS NULL AND q.status = 'published' ORDER BY t.tag"
     )
-    .bind(format!("%{}%", q))
     .fetch_all(&**db)
     .await
-    .unwrap();
-    let category_results: Vec<CategoryResult> = categories
-        .iter()
-        .map(|c| CategoryResult {
-            id: c.id.clone(),
-            title: c.title.clone(),
-        })
-        .collect();
+    .unwrap()
+    .into_iter()
+    .filter(|t: &String| !is_difficulty(t))
+    .collect();
 
-    // 2. Subcategories
-    // We'll join categories to get the parent title
-    let subcategories = sqlx::query!(
-        r#"
-        SELECT 
-            subcategories.id as id,
-            subcategories.category_id as category_id,
-            subcategories.title as title,
-            subcategories.description as description,
-            categories.title as category_title
-        FROM subcategories
-        JOIN categories ON subcategories.category_id = categories.id
-        WHERE subcategories.title LIKE ?
-           OR subcategories.description LIKE ?
-        "#,
-        format!("%{}%", q),
-        format!("%{}%", q)
-    )
-    .fetch_all(&**db)
-    .await
-    .unwrap();
+    let SearchResults { categories, subcategories, questions, lessons, corrections } =
+        run_search(&db, &params, &synonyms).await;
 
-    let subcategory_results: Vec<SubCategoryResult> = subcategories
-        .iter()
-        .map(|row| SubCategoryResult {
-            id: row.id.clone(),
-            category_id: row.category_id.clone(),
-            title: row.title.clone(),
-            description: row.description.clone(),
-            parent_category_title: row.category_title.clone(),
-        })
-        .collect();
+    // A search is counted when its first page of results is shown; moving
+    // between tabs and pages is not counted again
+    if params.result_type() == "all" && params.page() == 1 {
+        let total = categories.total + subcategories.total + questions.total + lessons.total;
+        let mut conn = db.acquire().await.unwrap();
+        record_search(&mut conn, params.query(), total).await.unwrap();
+    }
 
-    // 3. Questions
-    let questions = sqlx::query_as::<_, Question>(
-        "SELECT * FROM questions WHERE question_text LIKE ?"
-    )
-    .bind(format!("%{}%", q))
-    .fetch_all(&**db)
-    .await
-    .unwrap();
-
-    let question_results: Vec<QuestionResult> = questions
+    let shown: Vec<(&str, &str)> = questions
+        .items
         .iter()
-        .map(|qt| QuestionResult {
-            id: qt.id.clone(),
-            category_id: qt.category_id.clone(),
-            subcategory_id: qt.subcategory_id.clone(),
-            question_text: qt.question_text.clone(),
-        })
+        .map(|q| (KIND_QUESTION, q.id.as_str()))
+        .chain(lessons.items.iter().map(|l| (KIND_LESSON, l.id.as_str())))
         .collect();
+    let mut conn = db.acquire().await.unwrap();
+    let related = related_to_each(&mut conn, &shown, RELATED).await.unwrap();
 
     let tmpl = SearchResultsTemplate {
         title: "Hackademy - Search",
-        query: q,
-        categories: &category_results,
-        subcategories: &subcategory_results,
-        questions: &question_results,
+        params: &params,
+        types: RESULT_TYPES,
+        sorts: SORTS,
+        category_choices: &category_choices,
+        tag_choices: &tag_choices,
+        difficulties: DIFFICULTY_TAGS,
+        categories: &categories,
+        subcategories: &subcategories,
+        questions: &questions,
+        lessons: &lessons,
+        corrections: &corrections,
+        related: &related,
     };
     tmpl.render().unwrap()
-}
\ No newline at end of file
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ClickParams {
+    pub q: Option<String>,
+    pub to: String,
+}
+
+// Count a click on a result of the search `q`, then go to the result.
+// Only paths on this site are followed.
+#[handler]
+pub async fn search_click(Query(params): Query<ClickParams>, db: Data<&Pool<Sqlite>>) -> Response {
+    if !params.to.starts_with('/') || params.to.starts_with("//") || params.to.contains('\\') {
+        return Response::builder().status(StatusCode::BAD_REQUEST).body("Not a link on this site");
+    }
+    let mut conn = db.acquire().await.unwrap();
+    record_click(&mut conn, params.q.as_deref().unwrap_or("")).await.unwrap();
+    redirect(&params.to)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SuggestParams {
+    pub q: Option<String>,
+}
+
+// Suggestions for the header search box, as JSON
+#[handler]
+pub async fn suggest_handler(
+    Query(params): Query<SuggestParams>,
+    db: Data<&Pool<Sqlite>>,
+    cache: Data<&SuggestCache>,
+) -> Json<Suggestions> {
+    let index = cache.index(&db).await.unwrap();
+    Json(index.suggest(params.q.as_deref().unwrap_or("")))
+}
@@ -0,0 +1,76 @@
+use poem::{
+    handler,
+    web::{Data, Html, Query},
+    IntoResponse, Request,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite};
+
+use crate::content::search_stats::{percent, top_queries, totals, zero_result_queries, QueryStats};
+use crate::routes::admin::require_staff;
+use crate::routes::auth::SessionStore;
+
+// Periods the report can cover, in days
+pub const PERIODS: &[i64] = &[7, 30, 90, 365];
+const DEFAULT_DAYS: i64 = 30;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StatsParams {
+    pub days: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_search_stats.html")]
+struct SearchStatsTemplate<'a> {
+    title: &'a str,
+    days: i64,
+    periods: &'a [i64],
+    searches: i64,
+    zero_result_searches: i64,
+    clicks: i64,
+    top: &'a [QueryStats],
+    zero_results: &'a [QueryStats],
+}
+
+impl SearchStatsTemplate<'_> {
+    fn is_period(&self, days: &i64) -> bool {
+        *days == self.days
+    }
+
+    fn zero_result_pct(&self) -> i64 {
+        percent(self.zero_result_searches, self.searches)
+    }
+
+    fn click_through(&self) -> i64 {
+        percent(self.clicks, self.searches)
+    }
+}
+
+#[handler]
+pub async fn search_stats(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Query(params): Query<StatsParams>,
+) -> impl IntoResponse {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let days = params.days.filter(|d| PERIODS.contains(d)).unwrap_or(DEFAULT_DAYS);
+    let mut conn = db.acquire().await.unwrap();
+    let (searches, zero_result_searches, clicks) = totals(&mut conn, days).await.unwrap();
+    let top = top_queries(&mut conn, days).await.unwrap();
+    let zero_results = zero_result_queries(&mut conn, days).await.unwrap();
+
+    let tmpl = SearchStatsTemplate {
+        title: "Hackademy - Search Statistics",
+        days,
+        periods: PERIODS,
+        searches,
+        zero_result_searches,
+        clicks,
+        top: &top,
+        zero_results: &zero_results,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
@@ -15,7 +15,7 @@ pub struct SubcategoryListTemplate<'a> {
 #[handler]
 pub async fn get_subcategories(Path(cat_id): Path<String>, db: Data<&Pool<Sqlite>>) -> impl IntoResponse {
     // Fetch category
-    let cat: Option<Category> = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id = ?")
+    let cat: Option<Category> = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id = ? AND deleted_at IS NULL")
         .bind(&cat_id)
         .fetch_optional(&**db)
         .await
@@ -24,7 +24,10 @@ pub async fn get_subcategories(Path(cat_id): Path<String>, db: Data<&Pool<Sqlite
     if let Some(category) = cat {
         // Get subcategories
         let subs = sqlx::query_as::<_, Subcategory>(
-            "SELECT * FROM subcategories WHERE category_id = ? ORDER BY title"
+            "SELECT * FROM subcategories WHERE category_id = ? AND deleted_at IS NULL AND EXISTS (
+                SELECT 1 FROM questions q
+                WHERE q.subcategory_id = subcategories.id AND q.deleted_at IS NULL AND q.status = 'published'
+            ) ORDER BY title"
         )
         .bind(&cat_id)
         .fetch_all(&**db)
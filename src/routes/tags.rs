@@ -0,0 +1,127 @@
+use poem::{
+    handler,
+    web::{Data, Path},
+    IntoResponse, Response,
+};
+use askama::Template;
+use sqlx::{Pool, Sqlite};
+
+use crate::content::normalize_tag;
+use crate::content::tags::{is_difficulty, DIFFICULTY_TAGS};
+use crate::routes::admin::not_found;
+
+// A tag and how many published questions carry it
+#[derive(Debug, sqlx::FromRow)]
+pub struct TagCount {
+    pub tag: String,
+    pub questions: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct TaggedQuestion {
+    pub question_text: String,
+    pub category_id: String,
+    pub category_title: String,
+}
+
+// Difficulty tags in their fixed order, then the rest as they came
+fn split_facets(counts: Vec<TagCount>) -> (Vec<TagCount>, Vec<TagCount>) {
+    let (mut difficulty, topics): (Vec<_>, Vec<_>) = counts.into_iter().partition(|c| is_difficulty(&c.tag));
+    difficulty.sort_by_key(|c| DIFFICULTY_TAGS.iter().position(|d| *d == c.tag));
+    (difficulty, topics)
+}
+
+#[derive(Template)]
+#[template(path = "tag_list.html")]
+struct TagListTemplate<'a> {
+    title: &'a str,
+    difficulty: &'a [TagCount],
+    topics: &'a [TagCount],
+}
+
+#[handler]
+pub async fn get_tags(db: Data<&Pool<Sqlite>>) -> impl IntoResponse {
+    let counts = sqlx::query_as::<_, TagCount>(
+        "SELECT t.tag, COUNT(*) AS questions
+         FROM question_tags t JOIN questions q ON q.id = t.question_id
+         WHERE q.deleted_at IS NULL AND q.status = 'published'
+         GROUP BY t.tag ORDER BY questions DESC, t.tag"
+    )
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+    let (difficulty, topics) = split_facets(counts);
+
+    let tmpl = TagListTemplate {
+        title: "Hackademy - Tags",
+        difficulty: &difficulty,
+        topics: &topics,
+    };
+    tmpl.render().unwrap()
+}
+
+#[derive(Template)]
+#[template(path = "tag_page.html")]
+struct TagPageTemplate<'a> {
+    title: &'a str,
+    tag: &'a str,
+    questions: &'a [TaggedQuestion],
+    // (category id, title, number of tagged questions)
+    categories: &'a [(String, String, i64)],
+    // Tags that also appear on these questions, to narrow down or widen by
+    difficulty: &'a [TagCount],
+    topics: &'a [TagCount],
+}
+
+#[handler]
+pub async fn get_tag(Path(tag): Path<String>, db: Data<&Pool<Sqlite>>) -> Response {
+    let tag = normalize_tag(&tag);
+    let questions = sqlx::query_as::<_, TaggedQuestion>(
+        "SELECT q.question_text, q.category_id, c.title AS category_title
+         FROM questions q
+         JOIN question_tags t ON t.question_id = q.id
+         JOIN categories c ON c.id = q.category_id
+         WHERE t.tag = ? AND q.deleted_at IS NULL AND q.status = 'published'
+         ORDER BY c.title, q.question_text"
+    )
+    .bind(&tag)
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+    if questions.is_empty() {
+        return not_found("Tag");
+    }
+
+    let mut categories: Vec<(String, String, i64)> = Vec::new();
+    for q in &questions {
+        match categories.last_mut() {
+            Some((id, _, n)) if *id == q.category_id => *n += 1,
+            _ => categories.push((q.category_id.clone(), q.category_title.clone(), 1)),
+        }
+    }
+
+    let related = sqlx::query_as::<_, TagCount>(
+        "SELECT other.tag, COUNT(*) AS questions
+         FROM question_tags t
+         JOIN question_tags other ON other.question_id = t.question_id AND other.tag != t.tag
+         JOIN questions q ON q.id = t.question_id
+         WHERE t.tag = ? AND q.deleted_at IS NULL AND q.status = 'published'
+         GROUP BY other.tag ORDER BY questions DESC, other.tag"
+    )
+    .bind(&tag)
+    .fetch_all(&**db)
+    .await
+    .unwrap();
+    let (difficulty, topics) = split_facets(related);
+
+    let title = format!("Hackademy - Tag: {}", tag);
+    let tmpl = TagPageTemplate {
+        title: &title,
+        tag: &tag,
+        questions: &questions,
+        categories: &categories,
+        difficulty: &difficulty,
+        topics: &topics,
+    };
+    tmpl.render().unwrap().into_response()
+}
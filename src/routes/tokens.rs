@@ -0,0 +1,239 @@
+// Personal API tokens. Users create them on their profile page for scripts
+// and apps, which send them as `Authorization: Bearer <token>`. A token is
+// shown once, when it is created; only its hash is stored. Each token carries
+// scopes limiting what it may do:
+//  - read: GET requests to the JSON API, including grades for staff
+//  - submit: submitting quiz attempts and challenge flags through the JSON
+//    API
+//  - admin: the staff pages under /admin, for staff only
+// Revoked and expired tokens are refused.
+
+use poem::{
+    handler,
+    http::{header::AUTHORIZATION, StatusCode},
+    web::{Data, Form, Path},
+    Request, Response,
+};
+use nanoid::nanoid;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite};
+
+use crate::auth::oidc::Oidc;
+use crate::models::User;
+use crate::routes::admin::{not_found, redirect};
+use crate::routes::auth::{current_user, render_profile, SessionStore};
+
+pub const SCOPE_READ: &str = "read";
+pub const SCOPE_SUBMIT: &str = "submit";
+pub const SCOPE_ADMIN: &str = "admin";
+
+// (scope, what it allows) as offered on the profile page
+pub const SCOPES: &[(&str, &str)] = &[
+    (SCOPE_READ, "Read categories, quizzes, paths, search results, your attempts and profile, and learners' grades (staff only)"),
+    (SCOPE_SUBMIT, "Submit quiz attempts and challenge flags"),
+    (SCOPE_ADMIN, "Use the content admin pages (staff only)"),
+];
+
+// (days, label) of the lifetimes offered; an empty value never expires
+pub const LIFETIMES: &[(&str, &str)] = &[("30", "30 days"), ("90", "90 days"), ("365", "1 year"), ("", "No expiry")];
+
+// Tokens are this prefix then random characters, so they are easy to spot
+// in logs and secret scanners
+const TOKEN_PREFIX: &str = "hk_";
+const TOKEN_RANDOM_CHARS: usize = 40;
+// Characters of a token kept in the clear to tell tokens apart
+const SHOWN_CHARS: usize = 10;
+const MAX_NAME_CHARS: usize = 100;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ApiToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    // Only filled in by `user_tokens`
+    #[sqlx(default)]
+    pub expired: bool,
+}
+
+impl ApiToken {
+    pub fn scope_list(&self) -> Vec<&str> {
+        self.scopes.split_whitespace().collect()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope_list().contains(&scope)
+    }
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The token of an `Authorization: Bearer` header, if there is one
+pub fn bearer_token(req: &Request) -> Option<&str> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    Some(token.trim()).filter(|t| scheme.eq_ignore_ascii_case("bearer") && !t.is_empty())
+}
+
+// The user and token a bearer token belongs to, if it is live. Using a token
+// records when it was last used.
+pub async fn authenticate(db: &Pool<Sqlite>, token: &str) -> Option<(User, ApiToken)> {
+    let api_token = sqlx::query_as::<_, ApiToken>(
+        "SELECT * FROM api_tokens WHERE token_hash = ? AND revoked_at IS NULL
+             AND (expires_at IS NULL OR expires_at > datetime('now'))"
+    )
+    .bind(hash_token(token))
+    .fetch_optional(db)
+    .await
+    .unwrap()?;
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(&api_token.user_id)
+        .fetch_optional(db)
+        .await
+        .unwrap()?;
+    sqlx::query("UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(&api_token.id)
+        .execute(db)
+        .await
+        .unwrap();
+    Some((user, api_token))
+}
+
+// The staff user of a bearer token with the admin scope. Staff who lose
+// their role lose the scope with it.
+pub async fn admin_token_user(req: &Request, db: &Pool<Sqlite>) -> Option<User> {
+    let (user, token) = authenticate(db, bearer_token(req)?).await?;
+    Some(user).filter(|u| u.is_staff() && token.has_scope(SCOPE_ADMIN))
+}
+
+// A user's tokens that haven't been revoked, newest first
+pub async fn user_tokens(db: &Pool<Sqlite>, user_id: &str) -> Vec<ApiToken> {
+    sqlx::query_as::<_, ApiToken>(
+        "SELECT *, expires_at IS NOT NULL AND expires_at <= datetime('now') AS expired FROM api_tokens
+         WHERE user_id = ? AND revoked_at IS NULL ORDER BY created_at DESC, rowid DESC"
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+    .unwrap()
+}
+
+// Form fields: "name", "scope" once per scope ticked and "lifetime", one of
+// the values in LIFETIMES
+#[handler]
+pub async fn create_token(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    oidc: Data<&Oidc>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    let Some(user) = current_user(req, &db, &session_store).await else {
+        return redirect("/auth/login");
+    };
+    let mut name = String::new();
+    let mut scopes: Vec<&str> = Vec::new();
+    let mut lifetime: Option<&str> = None;
+    for (key, value) in &fields {
+        match key.as_str() {
+            "name" => name = value.trim().to_string(),
+            "scope" => {
+                if let Some((scope, _)) = SCOPES.iter().find(|(s, _)| s == value)
+                    && !scopes.contains(scope)
+                {
+                    scopes.push(scope);
+                }
+            }
+            "lifetime" => lifetime = Some(value),
+            _ => {}
+        }
+    }
+
+    // The form only offers LIFETIMES, so anything else is a bad request
+    // rather than something to correct on the page
+    let Some((days, _)) = LIFETIMES.iter().find(|(days, _)| Some(*days) == lifetime) else {
+        return Response::builder().status(StatusCode::BAD_REQUEST).body("Choose one of the offered token lifetimes");
+    };
+
+    let error = if name.is_empty() {
+        Some("Give the token a name")
+    } else if name.chars().count() > MAX_NAME_CHARS {
+        Some("Token names are at most 100 characters")
+    } else if scopes.is_empty() {
+        Some("Choose at least one scope")
+    } else if scopes.contains(&SCOPE_ADMIN) && !user.is_staff() {
+        Some("Only staff can create admin tokens")
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return render_profile(&db, &oidc, &user, None, Some(error)).await;
+    }
+
+    let expires_at: Option<String> = if days.is_empty() {
+        None
+    } else {
+        let expires_at: Option<String> = sqlx::query_scalar("SELECT datetime('now', ?)")
+            .bind(format!("+{} days", days))
+            .fetch_one(&**db)
+            .await
+            .unwrap();
+        // datetime() gives NULL for a modifier it can't read, which would
+        // otherwise store a token that never expires
+        if expires_at.is_none() {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body("Couldn't work out when the token expires");
+        }
+        expires_at
+    };
+
+    let token = format!("{}{}", TOKEN_PREFIX, nanoid!(TOKEN_RANDOM_CHARS));
+    sqlx::query(
+        "INSERT INTO api_tokens (id, user_id, name, token_hash, token_prefix, scopes, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(nanoid!())
+    .bind(&user.id)
+    .bind(&name)
+    .bind(hash_token(&token))
+    .bind(&token[..SHOWN_CHARS])
+    .bind(scopes.join(" "))
+    .bind(expires_at)
+    .execute(&**db)
+    .await
+    .unwrap();
+
+    // Shown on this page only; it can't be recovered from the hash
+    render_profile(&db, &oidc, &user, Some(&token), None).await
+}
+
+#[handler]
+pub async fn revoke_token(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    let Some(user) = current_user(req, &db, &session_store).await else {
+        return redirect("/auth/login");
+    };
+    let revoked = sqlx::query(
+        "UPDATE api_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE id = ? AND user_id = ? AND revoked_at IS NULL"
+    )
+    .bind(&id)
+    .bind(&user.id)
+    .execute(&**db)
+    .await
+    .unwrap();
+    if revoked.rows_affected() == 0 {
+        return not_found("Token");
+    }
+    redirect("/auth/profile")
+}
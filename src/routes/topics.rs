@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use poem::{
+    handler,
+    web::{Data, Form, Html, Path, Query},
+    IntoResponse, Request, Response,
+};
+use askama::Template;
+use nanoid::nanoid;
+use sqlx::{Pool, Sqlite};
+
+use crate::content::topics::{
+    check_parent, is_live, question_counts, refile_subtree, topic_path, topic_tree, TopicNode, SUBTREE_SQL,
+};
+use crate::models::{Lesson, Topic};
+use crate::routes::admin::{not_found, redirect, require_staff};
+use crate::routes::auth::SessionStore;
+
+// ----- Browsing -----
+
+#[derive(Template)]
+#[template(path = "topic_list.html")]
+struct TopicListTemplate<'a> {
+    title: &'a str,
+    // Root topics with published questions somewhere below them
+    topics: &'a [(Topic, i64)],
+}
+
+#[derive(Template)]
+#[template(path = "topic_page.html")]
+struct TopicPageTemplate<'a> {
+    title: &'a str,
+    topic: &'a Topic,
+    // Root first, ending with the topic itself
+    breadcrumbs: &'a [Topic],
+    subtopics: &'a [(Topic, i64)],
+    // Published questions in the whole subtree
+    questions: i64,
+    // Lessons, when the topic is a subcategory
+    lessons: &'a [Lesson],
+}
+
+// The children of `parent` (roots for None) that have published questions,
+// with how many
+fn with_questions(nodes: &[TopicNode], counts: &HashMap<String, i64>, parent: Option<&str>) -> Vec<(Topic, i64)> {
+    nodes
+        .iter()
+        .filter(|n| n.topic.parent_id.as_deref() == parent)
+        .filter_map(|n| {
+            let count = counts.get(&n.topic.id).copied().unwrap_or(0);
+            (count > 0).then(|| (n.topic.clone(), count))
+        })
+        .collect()
+}
+
+#[handler]
+pub async fn get_topics(db: Data<&Pool<Sqlite>>) -> impl IntoResponse {
+    let mut conn = db.acquire().await.unwrap();
+    let nodes = topic_tree(&mut conn).await.unwrap();
+    let counts = question_counts(&mut conn, &nodes).await.unwrap();
+    let topics = with_questions(&nodes, &counts, None);
+
+    let tmpl = TopicListTemplate {
+        title: "Hackademy - Topics",
+        topics: &topics,
+    };
+    tmpl.render().unwrap()
+}
+
+#[handler]
+pub async fn get_topic(Path(id): Path<String>, db: Data<&Pool<Sqlite>>) -> Response {
+    let mut conn = db.acquire().await.unwrap();
+    let breadcrumbs = topic_path(&mut conn, &id).await.unwrap();
+    if !is_live(&breadcrumbs) {
+        return not_found("Topic");
+    }
+    let nodes = topic_tree(&mut conn).await.unwrap();
+    let counts = question_counts(&mut conn, &nodes).await.unwrap();
+    let subtopics = with_questions(&nodes, &counts, Some(&id));
+
+    let lessons = sqlx::query_as::<_, Lesson>(
+        "SELECT * FROM lessons WHERE subcategory_id = ? AND deleted_at IS NULL ORDER BY position, title"
+    )
+    .bind(&id)
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+
+    let topic = breadcrumbs.last().unwrap();
+    let tmpl = TopicPageTemplate {
+        title: &format!("Hackademy - {}", topic.title),
+        topic,
+        breadcrumbs: &breadcrumbs,
+        subtopics: &subtopics,
+        questions: counts.get(&id).copied().unwrap_or(0),
+        lessons: &lessons,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// ----- Administration -----
+//
+// The first two levels are the categories and subcategories and are edited
+// as such; topics below them are managed here.
+
+#[derive(Template)]
+#[template(path = "admin_topics.html")]
+struct AdminTopicsTemplate<'a> {
+    title: &'a str,
+    nodes: &'a [TopicNode],
+    counts: &'a HashMap<String, i64>,
+    // Deleted topics below the subcategory level, for restoring
+    deleted: &'a [Topic],
+}
+
+impl AdminTopicsTemplate<'_> {
+    fn count(&self, id: &str) -> i64 {
+        self.counts.get(id).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin_topic_form.html")]
+struct TopicFormTemplate<'a> {
+    title: &'a str,
+    action: &'a str,
+    form: &'a TopicForm,
+    // Where the topic may go: subcategories and the topics below them
+    parents: &'a [TopicNode],
+    errors: &'a [String],
+}
+
+impl TopicFormTemplate<'_> {
+    fn is_parent(&self, id: &str) -> bool {
+        self.form.parent_id == id
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct TopicForm {
+    pub parent_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub position: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct NewTopicParams {
+    pub parent_id: Option<String>,
+}
+
+async fn parent_choices(db: &Pool<Sqlite>) -> Vec<TopicNode> {
+    let mut conn = db.acquire().await.unwrap();
+    let mut nodes = topic_tree(&mut conn).await.unwrap();
+    nodes.retain(|n| n.depth >= 1);
+    nodes
+}
+
+async fn render_topic_form(db: &Pool<Sqlite>, title: &str, action: &str, form: &TopicForm, errors: &[String]) -> Response {
+    let parents = parent_choices(db).await;
+    let tmpl = TopicFormTemplate {
+        title,
+        action,
+        form,
+        parents: &parents,
+        errors,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+// Errors in the form, or the position to store
+async fn validate_topic(db: &Pool<Sqlite>, id: Option<&str>, form: &TopicForm) -> Result<i64, Vec<String>> {
+    let mut errors = Vec::new();
+    if form.title.trim().is_empty() {
+        errors.push("Title is required".to_string());
+    }
+    let mut conn = db.acquire().await.unwrap();
+    if let Err(e) = check_parent(&mut conn, id, &form.parent_id).await {
+        errors.push(e.to_string());
+    }
+    let position = match form.position.trim() {
+        "" => 0,
+        p => p.parse().unwrap_or_else(|_| {
+            errors.push("Position must be a whole number".to_string());
+            0
+        }),
+    };
+    if errors.is_empty() { Ok(position) } else { Err(errors) }
+}
+
+// A topic below the subcategory level, or None for anything else
+async fn find_own_topic(db: &Pool<Sqlite>, id: &str) -> Option<Topic> {
+    let mut conn = db.acquire().await.unwrap();
+    let mut path = topic_path(&mut conn, id).await.unwrap();
+    if path.len() < 3 {
+        return None;
+    }
+    path.pop()
+}
+
+#[handler]
+pub async fn admin_topics(req: &Request, db: Data<&Pool<Sqlite>>, session_store: Data<&SessionStore>) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let mut conn = db.acquire().await.unwrap();
+    let nodes = topic_tree(&mut conn).await.unwrap();
+    let counts = question_counts(&mut conn, &nodes).await.unwrap();
+    let deleted = sqlx::query_as::<_, Topic>(
+        "SELECT * FROM topics WHERE deleted_at IS NOT NULL
+             AND id NOT IN (SELECT id FROM categories) AND id NOT IN (SELECT id FROM subcategories)
+         ORDER BY title"
+    )
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+
+    let tmpl = AdminTopicsTemplate {
+        title: "Topics",
+        nodes: &nodes,
+        counts: &counts,
+        deleted: &deleted,
+    };
+    Html(tmpl.render().unwrap()).into_response()
+}
+
+#[handler]
+pub async fn new_topic_form(
+    req: &Request,
+    Query(params): Query<NewTopicParams>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let form = TopicForm {
+        parent_id: params.parent_id.unwrap_or_default(),
+        ..Default::default()
+    };
+    render_topic_form(&db, "New Topic", "/admin/topics/new", &form, &[]).await
+}
+
+#[handler]
+pub async fn create_topic(
+    req: &Request,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<TopicForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let position = match validate_topic(&db, None, &form).await {
+        Ok(p) => p,
+        Err(errors) => return render_topic_form(&db, "New Topic", "/admin/topics/new", &form, &errors).await,
+    };
+
+    sqlx::query("INSERT INTO topics (id, parent_id, title, description, position) VALUES (?, ?, ?, ?, ?)")
+        .bind(nanoid!())
+        .bind(&form.parent_id)
+        .bind(form.title.trim())
+        .bind(Some(form.description.trim()).filter(|d| !d.is_empty()))
+        .bind(position)
+        .execute(&**db)
+        .await
+        .unwrap();
+    redirect("/admin/topics")
+}
+
+#[handler]
+pub async fn edit_topic_form(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(topic) = find_own_topic(&db, &id).await else {
+        return not_found("Topic");
+    };
+    let form = TopicForm {
+        parent_id: topic.parent_id.unwrap_or_default(),
+        title: topic.title,
+        description: topic.description.unwrap_or_default(),
+        position: topic.position.to_string(),
+    };
+    let action = format!("/admin/topics/{}/edit", id);
+    render_topic_form(&db, "Edit Topic", &action, &form, &[]).await
+}
+
+// Moving a topic takes its questions along, to whatever category and
+// subcategory the new parent is in
+#[handler]
+pub async fn update_topic(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+    Form(form): Form<TopicForm>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    if find_own_topic(&db, &id).await.is_none() {
+        return not_found("Topic");
+    }
+    let position = match validate_topic(&db, Some(&id), &form).await {
+        Ok(p) => p,
+        Err(errors) => {
+            let action = format!("/admin/topics/{}/edit", id);
+            return render_topic_form(&db, "Edit Topic", &action, &form, &errors).await;
+        }
+    };
+
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query("UPDATE topics SET parent_id = ?, title = ?, description = ?, position = ? WHERE id = ? AND deleted_at IS NULL")
+        .bind(&form.parent_id)
+        .bind(form.title.trim())
+        .bind(Some(form.description.trim()).filter(|d| !d.is_empty()))
+        .bind(position)
+        .bind(&id)
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    refile_subtree(&mut tx, &id).await.unwrap();
+    tx.commit().await.unwrap();
+    redirect("/admin/topics")
+}
+
+// Deleting a topic hides the topics and questions below it; they share the
+// timestamp so restoring brings back just what went with it
+#[handler]
+pub async fn delete_topic(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    if find_own_topic(&db, &id).await.is_none() {
+        return not_found("Topic");
+    }
+    let mut tx = db.begin().await.unwrap();
+    let now: String = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP")
+        .fetch_one(&mut tx)
+        .await
+        .unwrap();
+    for sql in [
+        format!("UPDATE questions SET deleted_at = ? WHERE topic_id IN ({}) AND deleted_at IS NULL", SUBTREE_SQL),
+        format!("UPDATE topics SET deleted_at = ? WHERE id IN ({}) AND deleted_at IS NULL", SUBTREE_SQL),
+    ] {
+        sqlx::query(&sql)
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    redirect("/admin/topics")
+}
+
+#[handler]
+pub async fn restore_topic(
+    req: &Request,
+    Path(id): Path<String>,
+    db: Data<&Pool<Sqlite>>,
+    session_store: Data<&SessionStore>,
+) -> Response {
+    if let Err(resp) = require_staff(req, &db, &session_store).await {
+        return resp;
+    }
+    let Some(topic) = find_own_topic(&db, &id).await else {
+        return not_found("Topic");
+    };
+    let Some(deleted_at) = topic.deleted_at else {
+        return redirect("/admin/topics");
+    };
+    let mut conn = db.acquire().await.unwrap();
+    let parent = topic_path(&mut conn, topic.parent_id.as_deref().unwrap_or("")).await.unwrap();
+    drop(conn);
+    if !is_live(&parent) {
+        return Response::builder()
+            .status(poem::http::StatusCode::CONFLICT)
+            .body("Restore the topic's parent first");
+    }
+
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query(
+        "WITH RECURSIVE subtree(id) AS (
+             SELECT ? UNION SELECT t.id FROM topics t JOIN subtree s ON t.parent_id = s.id WHERE t.deleted_at = ?
+         )
+         UPDATE topics SET deleted_at = NULL WHERE id IN (SELECT id FROM subtree) AND deleted_at = ?"
+    )
+    .bind(&id)
+    .bind(&deleted_at)
+    .bind(&deleted_at)
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    sqlx::query(&format!("UPDATE questions SET deleted_at = NULL WHERE topic_id IN ({}) AND deleted_at = ?", SUBTREE_SQL))
+        .bind(&id)
+        .bind(&deleted_at)
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    redirect("/admin/topics")
+}
@@ -4,6 +4,45 @@ pub struct AppConfig {
     pub database_url: String,
     pub server_addr: String,
     pub session_secret: String,
+    // Markdown content pack synced into the database at startup
+    pub content_dir: Option<String>,
+    // Seconds between checks of content_dir for changes; 0 disables watching
+    pub content_watch_secs: u64,
+    // File of search synonym groups replacing the built-in ones
+    pub search_synonyms: Option<String>,
+    // OpenID Connect sign-in, enabled by setting an issuer and client ID
+    pub oidc_issuer: Option<String>,
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_redirect_url: String,
+    pub oidc_scopes: String,
+    // Label of the sign-in button
+    pub oidc_name: String,
+    // Claim whose values map to roles, and the mapping, e.g. "staff=instructor"
+    pub oidc_role_claim: String,
+    pub oidc_role_map: Option<String>,
+    // Providers checking login form passwords, in order, e.g. "local,ldap"
+    pub auth_providers: Option<String>,
+    // LDAP bind authentication, enabled by setting a URL
+    pub ldap_url: Option<String>,
+    pub ldap_starttls: bool,
+    pub ldap_timeout_secs: u64,
+    // DN template such as "uid={username},ou=people,dc=lab,dc=local", or a
+    // base DN and filter to search for users with
+    pub ldap_user_dn: Option<String>,
+    pub ldap_base_dn: Option<String>,
+    pub ldap_user_filter: String,
+    // Service account for the user search
+    pub ldap_bind_dn: Option<String>,
+    pub ldap_bind_password: Option<String>,
+    pub ldap_username_attr: String,
+    pub ldap_group_base_dn: Option<String>,
+    pub ldap_group_filter: String,
+    // Group cn to role, e.g. "lab-admins=admin"
+    pub ldap_role_map: Option<String>,
+    // Whether entries' mail values may link accounts by email; only safe
+    // where users can't change their own
+    pub ldap_trust_mail: bool,
 }
 
 impl AppConfig {
@@ -13,6 +52,33 @@ impl AppConfig {
             database_url: env::var("DATABASE_URL").unwrap_or("sqlite://hackademy.db".to_string()),
             server_addr: env::var("HACKADEMY_ADDR").unwrap_or("0.0.0.0:3000".to_string()),
             session_secret: env::var("SESSION_SECRET").unwrap_or("random_secret".to_string()),
+            content_dir: env::var("CONTENT_DIR").ok().filter(|d| !d.is_empty()),
+            content_watch_secs: env::var("CONTENT_WATCH_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(0),
+            search_synonyms: env::var("SEARCH_SYNONYMS").ok().filter(|f| !f.is_empty()),
+            oidc_issuer: env::var("OIDC_ISSUER").ok().filter(|s| !s.is_empty()),
+            oidc_client_id: env::var("OIDC_CLIENT_ID").ok().filter(|s| !s.is_empty()),
+            oidc_client_secret: env::var("OIDC_CLIENT_SECRET").ok().filter(|s| !s.is_empty()),
+            oidc_redirect_url: env::var("OIDC_REDIRECT_URL")
+                .unwrap_or("http://localhost:3000/auth/oidc/callback".to_string()),
+            oidc_scopes: env::var("OIDC_SCOPES").unwrap_or("openid profile email".to_string()),
+            oidc_name: env::var("OIDC_NAME").unwrap_or("single sign-on".to_string()),
+            oidc_role_claim: env::var("OIDC_ROLE_CLAIM").unwrap_or("groups".to_string()),
+            oidc_role_map: env::var("OIDC_ROLE_MAP").ok().filter(|s| !s.is_empty()),
+            auth_providers: env::var("AUTH_PROVIDERS").ok().filter(|s| !s.is_empty()),
+            ldap_url: env::var("LDAP_URL").ok().filter(|s| !s.is_empty()),
+            ldap_starttls: env::var("LDAP_STARTTLS").is_ok_and(|s| s == "1" || s == "true"),
+            ldap_timeout_secs: env::var("LDAP_TIMEOUT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(5),
+            ldap_user_dn: env::var("LDAP_USER_DN").ok().filter(|s| !s.is_empty()),
+            ldap_base_dn: env::var("LDAP_BASE_DN").ok().filter(|s| !s.is_empty()),
+            ldap_user_filter: env::var("LDAP_USER_FILTER").unwrap_or("(uid={username})".to_string()),
+            ldap_bind_dn: env::var("LDAP_BIND_DN").ok().filter(|s| !s.is_empty()),
+            ldap_bind_password: env::var("LDAP_BIND_PASSWORD").ok(),
+            ldap_username_attr: env::var("LDAP_USERNAME_ATTR").unwrap_or("uid".to_string()),
+            ldap_group_base_dn: env::var("LDAP_GROUP_BASE_DN").ok().filter(|s| !s.is_empty()),
+            ldap_group_filter: env::var("LDAP_GROUP_FILTER")
+                .unwrap_or("(|(member={dn})(uniqueMember={dn})(memberUid={username}))".to_string()),
+            ldap_role_map: env::var("LDAP_ROLE_MAP").ok().filter(|s| !s.is_empty()),
+            ldap_trust_mail: env::var("LDAP_TRUST_MAIL").is_ok_and(|s| s == "1" || s == "true"),
         }
     }
 }
\ No newline at end of file